@@ -0,0 +1,841 @@
+use crate::commands::{execute_cmd, queue_in_transaction};
+use crate::aof_manifest::{manifest_path, AofFileInfo, Manifest};
+use crate::config::ServerConfig;
+use crate::context::Context;
+use crate::persistence::snapshot_databases;
+use crate::rdb::decode::RdbDecoder;
+use crate::rdb::encode::write_rdb_snapshot;
+use crate::rdb::{decode_databases, Db, Value};
+use crate::resp::{read_multibulk_command, write_resp_array, write_resp_bytes_array, RespParser};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use std::{process, thread};
+
+/// `appendfsync`: when appended writes are forced to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// fsync after every write, before the client gets its reply.
+    Always,
+    /// fsync once a second from a background thread.
+    EverySec,
+    /// Leave flushing to the OS.
+    No,
+}
+
+impl AppendFsync {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.to_ascii_lowercase().as_str() {
+            "always" => Some(AppendFsync::Always),
+            "everysec" => Some(AppendFsync::EverySec),
+            "no" => Some(AppendFsync::No),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+    }
+}
+
+/// Single-file AOF written before the multi-part layout; migrated on startup.
+pub fn legacy_aof_path(cfg: &ServerConfig) -> PathBuf {
+    PathBuf::from(&cfg.dir).join(&cfg.appendfilename)
+}
+
+/// Directory holding the base file, the incremental files and the manifest.
+pub fn aof_dir(cfg: &ServerConfig) -> PathBuf {
+    PathBuf::from(&cfg.dir).join(&cfg.appenddirname)
+}
+
+/// Is there any AOF (multi-part or legacy) to load at startup?
+pub fn aof_exists(cfg: &ServerConfig) -> bool {
+    manifest_path(&aof_dir(cfg), &cfg.appendfilename).exists() || legacy_aof_path(cfg).exists()
+}
+
+/// Rewrite progress and sizes, for INFO.
+pub struct AofStatus {
+    pub rewrite_in_progress: bool,
+    pub last_rewrite_ok: bool,
+    pub current_size: u64,
+    pub base_size: u64,
+}
+
+struct AofState {
+    manifest: Manifest,
+    /// The incremental file new writes are appended to.
+    incr: File,
+    /// DB the incremental file last SELECTed; `None` forces a SELECT first.
+    selected_db: Option<usize>,
+    base_size: u64,
+    incr_size: u64,
+    rewrite_in_progress: bool,
+    last_rewrite_ok: bool,
+}
+
+/// The open multi-part append-only file. Every effective write is appended to
+/// the newest incremental file as the same RESP frame replicas receive.
+pub struct Aof {
+    dir: PathBuf,
+    filename: String,
+    use_rdb_preamble: bool,
+    fsync: AppendFsync,
+    /// Appends not yet fsynced (`everysec` only).
+    unsynced: AtomicBool,
+    state: Mutex<AofState>,
+}
+
+impl Aof {
+    /// Opens the AOF directory for appending. Without a manifest, a legacy
+    /// single-file AOF is adopted as the base, or else a base is written from
+    /// the current dataset, so the AOF alone can always rebuild everything.
+    pub fn open(ctx: &Context) -> io::Result<Arc<Aof>> {
+        let cfg = &ctx.cfg;
+        let dir = aof_dir(cfg);
+        let filename = cfg.appendfilename.clone();
+        fs::create_dir_all(&dir)?;
+
+        let mut manifest = match Manifest::load(&dir, &filename)? {
+            Some(m) => m,
+            None => {
+                let legacy = legacy_aof_path(cfg);
+                let base = if legacy.exists() {
+                    let base = AofFileInfo::base(&filename, 1, false);
+                    fs::rename(&legacy, dir.join(&base.name))?;
+                    println!("[aof::open] Moved legacy {:?} into {:?} as {}", legacy, dir, base.name);
+                    base
+                } else {
+                    println!("[aof::open] Creating base AOF from the current dataset");
+                    write_base_file(&dir, &filename, 1, cfg.aof_use_rdb_preamble, &snapshot_databases(ctx))?
+                };
+                Manifest { base: Some(base), incrs: Vec::new() }
+            }
+        };
+
+        let incr = match manifest.incrs.last() {
+            Some(last) => OpenOptions::new().create(true).append(true).open(dir.join(&last.name))?,
+            None => {
+                let info = AofFileInfo::incr(&filename, manifest.next_incr_seq());
+                let file = OpenOptions::new().create(true).append(true).open(dir.join(&info.name))?;
+                manifest.incrs.push(info);
+                file
+            }
+        };
+        manifest.persist(&dir, &filename)?;
+
+        let base_size = manifest.base.iter().map(|f| file_size(&dir.join(&f.name))).sum();
+        let incr_size = manifest.incrs.iter().map(|f| file_size(&dir.join(&f.name))).sum();
+        println!(
+            "[aof::open] Appending to {:?} (appendfsync {})",
+            dir.join(&manifest.incrs.last().unwrap().name),
+            cfg.appendfsync.as_str()
+        );
+
+        let aof = Arc::new(Aof {
+            dir,
+            filename,
+            use_rdb_preamble: cfg.aof_use_rdb_preamble,
+            fsync: cfg.appendfsync,
+            unsynced: AtomicBool::new(false),
+            state: Mutex::new(AofState {
+                manifest,
+                incr,
+                selected_db: None,
+                base_size,
+                incr_size,
+                rewrite_in_progress: false,
+                last_rewrite_ok: true,
+            }),
+        });
+
+        if aof.fsync == AppendFsync::EverySec {
+            let aof = aof.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(1));
+                if aof.unsynced.swap(false, Ordering::AcqRel) {
+                    if let Err(e) = aof.state.lock().unwrap().incr.sync_data() {
+                        eprintln!("[aof::fsync] Background fsync failed: {}", e);
+                        aof.unsynced.store(true, Ordering::Release);
+                    }
+                }
+            });
+        }
+
+        Ok(aof)
+    }
+
+    /// Appends one command frame executed against `db`, preceded by a SELECT
+    /// when the file was last on another DB; under `always` it is on disk
+    /// before this returns.
+    pub fn append(&self, db: usize, frame: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.selected_db != Some(db) {
+            let mut select = Vec::new();
+            write_resp_array(&mut select, &["SELECT", &db.to_string()])?;
+            state.incr.write_all(&select)?;
+            state.incr_size += select.len() as u64;
+            state.selected_db = Some(db);
+        }
+        state.incr.write_all(frame)?;
+        state.incr_size += frame.len() as u64;
+        match self.fsync {
+            AppendFsync::Always => state.incr.sync_data()?,
+            AppendFsync::EverySec => self.unsynced.store(true, Ordering::Release),
+            AppendFsync::No => {}
+        }
+        Ok(())
+    }
+
+    /// Forces every pending append to disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.state.lock().unwrap().incr.sync_data()?;
+        self.unsynced.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn status(&self) -> AofStatus {
+        let state = self.state.lock().unwrap();
+        AofStatus {
+            rewrite_in_progress: state.rewrite_in_progress,
+            last_rewrite_ok: state.last_rewrite_ok,
+            current_size: state.base_size + state.incr_size,
+            base_size: state.base_size,
+        }
+    }
+
+    /// Starts the AOF over from `dbs`, e.g. after a full resync replaced the dataset.
+    pub fn reset(&self, dbs: &[Db]) -> io::Result<()> {
+        // never race a background rewrite for the manifest
+        loop {
+            let mut state = self.state.lock().unwrap();
+            if !state.rewrite_in_progress {
+                state.rewrite_in_progress = true;
+                break;
+            }
+            drop(state);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let result = self.switch_incr().and_then(|seq| self.install_base(dbs, seq));
+        self.finish_rewrite(&result);
+        if result.is_ok() {
+            println!("[aof::reset] Rebuilt the AOF from the current dataset");
+        }
+        result
+    }
+
+    /// Sends new writes to a fresh incremental file and returns its seq.
+    /// Everything before it is covered by the base the rewrite will produce.
+    fn switch_incr(&self) -> io::Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let info = AofFileInfo::incr(&self.filename, state.manifest.next_incr_seq());
+        let path = self.dir.join(&info.name);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        state.manifest.incrs.push(info.clone());
+        if let Err(e) = state.manifest.persist(&self.dir, &self.filename) {
+            state.manifest.incrs.pop();
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+
+        state.incr.sync_data()?;
+        state.incr = file;
+        state.selected_db = None;
+        println!("[aof::rewrite] New writes now go to {}", info.name);
+        Ok(info.seq)
+    }
+
+    /// Writes a base file for `dbs` and points the manifest at it plus the
+    /// incremental files from `first_incr_seq` on; older files are deleted.
+    fn install_base(&self, dbs: &[Db], first_incr_seq: u64) -> io::Result<()> {
+        let base_seq = self.state.lock().unwrap().manifest.next_base_seq();
+        let base = write_base_file(&self.dir, &self.filename, base_seq, self.use_rdb_preamble, dbs)?;
+
+        let mut state = self.state.lock().unwrap();
+        let mut manifest = state.manifest.clone();
+        let obsolete: Vec<AofFileInfo> = manifest
+            .base
+            .iter()
+            .chain(manifest.incrs.iter().filter(|f| f.seq < first_incr_seq))
+            .cloned()
+            .collect();
+        manifest.base = Some(base);
+        manifest.incrs.retain(|f| f.seq >= first_incr_seq);
+
+        if let Err(e) = manifest.persist(&self.dir, &self.filename) {
+            let _ = fs::remove_file(self.dir.join(&manifest.base.as_ref().unwrap().name));
+            return Err(e);
+        }
+        for file in &obsolete {
+            if let Err(e) = fs::remove_file(self.dir.join(&file.name)) {
+                eprintln!("[aof::rewrite] Failed removing obsolete {}: {}", file.name, e);
+            }
+        }
+
+        state.base_size = manifest.base.iter().map(|f| file_size(&self.dir.join(&f.name))).sum();
+        state.incr_size = manifest.incrs.iter().map(|f| file_size(&self.dir.join(&f.name))).sum();
+        state.manifest = manifest;
+        Ok(())
+    }
+
+    fn finish_rewrite(&self, result: &io::Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        state.rewrite_in_progress = false;
+        state.last_rewrite_ok = result.is_ok();
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// BGREWRITEAOF: switch to a new incremental file, capture the dataset at that
+/// same instant, and write the new base on a separate thread. Returns `false`
+/// if a rewrite is already running.
+pub fn start_rewrite(ctx: &Context) -> bool {
+    let Some(aof) = ctx.aof.clone() else {
+        return false;
+    };
+    {
+        let mut state = aof.state.lock().unwrap();
+        if state.rewrite_in_progress {
+            println!("[aof::rewrite] Background append only file rewriting already in progress");
+            return false;
+        }
+        state.rewrite_in_progress = true;
+    }
+
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let result = (|| {
+            // writers hold the barrier across append + execute, so each write
+            // is either in this snapshot or in the new incremental file
+            let (seq, snapshot) = {
+                let _barrier = ctx.sync_lock.write().unwrap();
+                (aof.switch_incr()?, snapshot_databases(&ctx))
+            };
+            aof.install_base(&snapshot, seq)
+        })();
+
+        match &result {
+            Ok(()) => println!("[aof::rewrite] Background AOF rewrite finished successfully"),
+            Err(e) => eprintln!("[aof::rewrite] Background AOF rewrite failed: {}", e),
+        }
+        aof.finish_rewrite(&result);
+    });
+
+    true
+}
+
+/// Triggers BGREWRITEAOF once the AOF has grown `auto-aof-rewrite-percentage`
+/// past its size after the last rewrite, and is at least `auto-aof-rewrite-min-size`.
+pub fn spawn_aof_rewrite_policy_thread(ctx: Context) {
+    let Some(aof) = ctx.aof.clone() else {
+        return;
+    };
+    if ctx.cfg.auto_aof_rewrite_percentage == 0 {
+        println!("[aof::policy] auto-aof-rewrite-percentage is 0; automatic rewrites disabled.");
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(100));
+
+        let status = aof.status();
+        if status.rewrite_in_progress || status.current_size < ctx.cfg.auto_aof_rewrite_min_size {
+            continue;
+        }
+        let base = status.base_size.max(1);
+        let growth = (status.current_size * 100 / base).saturating_sub(100);
+        if growth >= ctx.cfg.auto_aof_rewrite_percentage {
+            println!(
+                "[aof::policy] Starting automatic rewriting of AOF on {}% growth",
+                growth
+            );
+            start_rewrite(&ctx);
+        }
+    });
+}
+
+/// Writes a base file to a temp name, fsyncs it, and renames it into place.
+/// Command form is used unless `rdb_preamble` is set or the dataset holds
+/// something no supported command can recreate.
+fn write_base_file(dir: &Path, filename: &str, seq: u64, rdb_preamble: bool, dbs: &[Db]) -> io::Result<AofFileInfo> {
+    let commands = if rdb_preamble { None } else { command_form(dbs)? };
+    let info = AofFileInfo::base(filename, seq, commands.is_none());
+    let tmp_path = dir.join(format!("temp-rewriteaof-bg-{}.aof", process::id()));
+
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        match &commands {
+            Some(bytes) => out.write_all(bytes)?,
+            None => write_rdb_snapshot(&mut out, dbs)?,
+        }
+        out.flush()?;
+        out.get_ref().sync_all()?;
+        fs::rename(&tmp_path, dir.join(&info.name))
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    println!("[aof::rewrite] Wrote base file {}", info.name);
+    Ok(info)
+}
+
+/// The dataset as a sequence of commands, or `None` if some value has no
+/// command that could recreate it here.
+fn command_form(dbs: &[Db]) -> io::Result<Option<Vec<u8>>> {
+    let mut out = Vec::new();
+    for (index, db) in dbs.iter().enumerate() {
+        if db.is_empty() {
+            continue;
+        }
+        write_resp_array(&mut out, &["SELECT", &index.to_string()])?;
+
+        for (key, (value, expiry)) in db {
+            match (value, expiry) {
+                (Value::String(s), None) => write_resp_bytes_array(&mut out, &[b"SET", key, s])?,
+                (Value::String(s), Some(t)) => {
+                    let ms = t.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0).to_string();
+                    write_resp_bytes_array(&mut out, &[b"SET", key, s, b"PXAT", ms.as_bytes()])?
+                }
+                (Value::List(items), None) => {
+                    let mut args: Vec<&[u8]> = vec![b"RPUSH", key];
+                    args.extend(items.iter().map(Vec::as_slice));
+                    write_resp_bytes_array(&mut out, &args)?
+                }
+                (Value::Stream(entries), None) => {
+                    for entry in entries {
+                        let mut args: Vec<&[u8]> = vec![b"XADD", key, entry.id.as_bytes()];
+                        for (field, val) in &entry.fields {
+                            args.push(field);
+                            args.push(val);
+                        }
+                        write_resp_bytes_array(&mut out, &args)?
+                    }
+                }
+                _ => {
+                    println!(
+                        "[aof::rewrite] Key '{}' ({}) has no command form; falling back to an RDB base",
+                        String::from_utf8_lossy(key),
+                        value.type_name()
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+    }
+    Ok(Some(out))
+}
+
+/// Replays the AOF into `ctx`'s databases. Returns `Ok(false)` if there is none.
+pub fn load_append_only_file(ctx: &mut Context) -> io::Result<bool> {
+    let dir = aof_dir(&ctx.cfg);
+    let files: Vec<PathBuf> = match Manifest::load(&dir, &ctx.cfg.appendfilename)? {
+        Some(manifest) => manifest.files().map(|f| dir.join(&f.name)).collect(),
+        None if legacy_aof_path(&ctx.cfg).exists() => vec![legacy_aof_path(&ctx.cfg)],
+        None => {
+            println!("[aof::load] No append only file in {:?}", dir);
+            return Ok(false);
+        }
+    };
+
+    // what is replayed is already on disk: it isn't counted or propagated
+    ctx.loading = true;
+    let mut commands = 0;
+    let mut loaded = Ok(());
+    for (i, path) in files.iter().enumerate() {
+        // only the newest file can have been cut short by a crash
+        let last = i + 1 == files.len();
+        match load_aof_file(ctx, path, last) {
+            Ok(n) => commands += n,
+            Err(e) => {
+                loaded = Err(e);
+                break;
+            }
+        }
+    }
+    ctx.loading = false;
+    loaded?;
+
+    // replayed SELECTs moved the loading context; clients start on DB 0
+    ctx.db_index = 0;
+    ctx.store = ctx.dbs[0].clone();
+
+    println!("[aof::load] Replayed {} command(s) from {} AOF file(s)", commands, files.len());
+    Ok(true)
+}
+
+/// Loads one AOF file: an optional RDB preamble, then command frames.
+fn load_aof_file(ctx: &mut Context, path: &Path, may_be_truncated: bool) -> io::Result<usize> {
+    println!("[aof::load] Loading {:?}", path);
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if reader.fill_buf()?.starts_with(b"REDIS") {
+        let mut decoder = RdbDecoder::new(&mut reader)?;
+        let dbs = decode_databases(&mut decoder, ctx.dbs.len())?;
+        for (store, db) in ctx.dbs.iter().zip(dbs) {
+            store.replace(db);
+        }
+        println!("[aof::load] Loaded RDB preamble");
+        replay_commands(ctx, &mut decoder.into_inner(), path, may_be_truncated)
+    } else {
+        replay_commands(ctx, &mut reader, path, may_be_truncated)
+    }
+}
+
+/// Executes every command frame in `reader`. A frame cut short by the end of
+/// the file is dropped (and trimmed from the file) if `aof-load-truncated` is
+/// on, and so is a MULTI the file ends before the EXEC of.
+fn replay_commands<R: BufRead + Seek>(
+    ctx: &mut Context,
+    reader: &mut R,
+    path: &Path,
+    may_be_truncated: bool,
+) -> io::Result<usize> {
+    let mut replayed = 0;
+    let mut parser = RespParser::default();
+    // where the open MULTI starts, if any
+    let mut multi_offset = None;
+
+    loop {
+        let good_offset = reader.stream_position()?;
+        let parsed = match read_multibulk_command(reader, &mut parser) {
+            Ok(Some(args)) if args.is_empty() => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Expected a command array"))
+            }
+            other => other,
+        };
+
+        let parsed = match parsed {
+            Ok(None) if multi_offset.is_some() => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "MULTI without EXEC"))
+            }
+            other => other,
+        };
+
+        let args = match parsed {
+            Ok(Some(args)) => args,
+            Ok(None) => break,
+            Err(e) => {
+                let at_eof = reader.fill_buf().map(|b| b.is_empty()).unwrap_or(false);
+                if !at_eof {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad file format reading the append only file {:?} at offset {}: {}", path, good_offset, e),
+                    ));
+                }
+                if !may_be_truncated || !ctx.cfg.aof_load_truncated {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("Unexpected end of file reading the append only file {:?} at offset {}", path, good_offset),
+                    ));
+                }
+
+                // an unfinished transaction is dropped whole: none of it was applied
+                let valid_up_to = multi_offset.unwrap_or(good_offset);
+                ctx.in_transaction = false;
+                ctx.queued.clear();
+                eprintln!("[aof::load] !!! Warning: short read while loading the AOF file {:?} !!!", path);
+                eprintln!("[aof::load] AOF loaded anyway because aof-load-truncated is enabled; truncating to {} bytes", valid_up_to);
+                OpenOptions::new().write(true).open(path)?.set_len(valid_up_to)?;
+                break;
+            }
+        };
+
+        let name = args[0].to_uppercase();
+        match name.as_str() {
+            "MULTI" => multi_offset = Some(good_offset),
+            "EXEC" | "DISCARD" => multi_offset = None,
+            _ => {}
+        }
+        if queue_in_transaction(&name, &args, ctx) {
+            replayed += 1;
+            continue;
+        }
+        match execute_cmd(&name, &args, ctx) {
+            Some(result) => {
+                result?;
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown command '{}' reading the append only file", args[0]),
+                ));
+            }
+        }
+        replayed += 1;
+    }
+
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdb::Value;
+    use crate::resp::write_resp_array;
+
+    /// A scratch directory, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("aof-test-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TestDir(path)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(dir: &Path, aof_load_truncated: bool, aof_use_rdb_preamble: bool) -> ServerConfig {
+        ServerConfig {
+            appendonly: true,
+            aof_load_truncated,
+            aof_use_rdb_preamble,
+            ..ServerConfig::for_tests(dir)
+        }
+    }
+
+    fn context(cfg: ServerConfig) -> Context {
+        let databases = cfg.databases;
+        Context::new(Arc::new(cfg), vec![Db::new(); databases])
+    }
+
+    fn frames(commands: &[&[&str]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for command in commands {
+            write_resp_array(&mut out, command).unwrap();
+        }
+        out
+    }
+
+    fn string_at(ctx: &Context, db: usize, key: &str) -> Option<String> {
+        match ctx.dbs[db].lock(key.as_bytes()).get(key.as_bytes()) {
+            Some((Value::String(s), _)) => Some(String::from_utf8_lossy(s).into_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn appendfsync_names_round_trip() {
+        for fsync in [AppendFsync::Always, AppendFsync::EverySec, AppendFsync::No] {
+            assert_eq!(AppendFsync::parse(fsync.as_str()), Some(fsync));
+        }
+        assert_eq!(AppendFsync::parse("EVERYSEC"), Some(AppendFsync::EverySec));
+        assert_eq!(AppendFsync::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn nothing_to_load_without_an_aof() {
+        let dir = TestDir::new("none");
+        let mut ctx = context(config(&dir.0, true, true));
+        assert!(!load_append_only_file(&mut ctx).unwrap());
+    }
+
+    #[test]
+    fn legacy_file_replays_into_the_selected_databases() {
+        let dir = TestDir::new("plain");
+        let cfg = config(&dir.0, true, true);
+        let aof = frames(&[&["SET", "a", "1"], &["SELECT", "2"], &["SET", "b", "2"], &["INCR", "b"]]);
+        fs::write(legacy_aof_path(&cfg), aof).unwrap();
+
+        let mut ctx = context(cfg);
+        assert!(load_append_only_file(&mut ctx).unwrap());
+        assert_eq!(string_at(&ctx, 0, "a").as_deref(), Some("1"));
+        assert_eq!(string_at(&ctx, 2, "b").as_deref(), Some("3"));
+        assert_eq!(ctx.db_index, 0);
+    }
+
+    #[test]
+    fn truncated_last_command_is_dropped_and_trimmed_from_the_file() {
+        let dir = TestDir::new("truncated");
+        let cfg = config(&dir.0, true, true);
+        let good = frames(&[&["SET", "a", "1"], &["SET", "b", "2"]]);
+        let mut aof = good.clone();
+        aof.extend_from_slice(&frames(&[&["SET", "c", "3"]])[..10]);
+        fs::write(legacy_aof_path(&cfg), &aof).unwrap();
+
+        let path = legacy_aof_path(&cfg);
+        let mut ctx = context(cfg);
+        assert!(load_append_only_file(&mut ctx).unwrap());
+        assert_eq!(string_at(&ctx, 0, "b").as_deref(), Some("2"));
+        assert_eq!(string_at(&ctx, 0, "c"), None);
+        assert_eq!(fs::read(path).unwrap(), good);
+    }
+
+    #[test]
+    fn transactions_replay_whole_and_an_unfinished_one_is_dropped() {
+        let dir = TestDir::new("multi");
+        let cfg = config(&dir.0, true, true);
+        let good = frames(&[&["MULTI"], &["SET", "a", "1"], &["INCR", "a"], &["EXEC"], &["SET", "b", "2"]]);
+        let mut aof = good.clone();
+        aof.extend_from_slice(&frames(&[&["MULTI"], &["SET", "c", "3"], &["SET", "b", "4"]]));
+        fs::write(legacy_aof_path(&cfg), &aof).unwrap();
+
+        let path = legacy_aof_path(&cfg);
+        let mut ctx = context(cfg);
+        assert!(load_append_only_file(&mut ctx).unwrap());
+        assert_eq!(string_at(&ctx, 0, "a").as_deref(), Some("2"));
+        assert_eq!(string_at(&ctx, 0, "b").as_deref(), Some("2"));
+        assert_eq!(string_at(&ctx, 0, "c"), None);
+        assert!(!ctx.in_transaction && !ctx.loading);
+        assert_eq!(ctx.save_state.lock().unwrap().dirty, 0);
+        assert_eq!(fs::read(path).unwrap(), good);
+    }
+
+    #[test]
+    fn truncated_last_command_is_an_error_unless_allowed() {
+        let dir = TestDir::new("strict");
+        let cfg = config(&dir.0, false, true);
+        let mut aof = frames(&[&["SET", "a", "1"]]);
+        aof.extend_from_slice(b"*3\r\n$3\r\nSET\r\n");
+        fs::write(legacy_aof_path(&cfg), &aof).unwrap();
+
+        let path = legacy_aof_path(&cfg);
+        let err = load_append_only_file(&mut context(cfg)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(fs::read(path).unwrap(), aof);
+    }
+
+    #[test]
+    fn malformed_command_mid_file_is_never_trimmed() {
+        let dir = TestDir::new("corrupt");
+        let cfg = config(&dir.0, true, true);
+        let mut aof = frames(&[&["SET", "a", "1"]]);
+        aof.extend_from_slice(b"*1\r\n$x\r\n");
+        aof.extend_from_slice(&frames(&[&["SET", "b", "2"]]));
+        fs::write(legacy_aof_path(&cfg), &aof).unwrap();
+
+        let path = legacy_aof_path(&cfg);
+        let err = load_append_only_file(&mut context(cfg)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(path).unwrap(), aof);
+    }
+
+    #[test]
+    fn only_the_last_file_of_a_manifest_may_be_truncated() {
+        let dir = TestDir::new("manifest-truncated");
+        let cfg = config(&dir.0, true, true);
+        let aof_dir = aof_dir(&cfg);
+        fs::create_dir_all(&aof_dir).unwrap();
+
+        let base = AofFileInfo::base(&cfg.appendfilename, 1, false);
+        let incr = AofFileInfo::incr(&cfg.appendfilename, 1);
+        let mut cut_short = frames(&[&["SET", "a", "1"]]);
+        cut_short.extend_from_slice(b"*2\r\n$3\r\nDEL");
+        fs::write(aof_dir.join(&base.name), &cut_short).unwrap();
+        fs::write(aof_dir.join(&incr.name), frames(&[&["SET", "b", "2"]])).unwrap();
+        Manifest { base: Some(base), incrs: vec![incr] }.persist(&aof_dir, &cfg.appendfilename).unwrap();
+
+        let err = load_append_only_file(&mut context(cfg)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Writes through an opened AOF, rewrites it, writes some more, and
+    /// checks a fresh server loads it all back.
+    fn write_rewrite_and_reload(name: &str, rdb_preamble: bool) {
+        let dir = TestDir::new(name);
+        let mut ctx = context(config(&dir.0, true, rdb_preamble));
+        execute_cmd("SET", &["SET".into(), "before".into(), "open".into()], &mut ctx).unwrap().unwrap();
+
+        let aof = Aof::open(&ctx).unwrap();
+        aof.append(0, &frames(&[&["SET", "k0", "v0"]])).unwrap();
+        aof.append(3, &frames(&[&["SET", "k3", "v3"]])).unwrap();
+        aof.append(3, &frames(&[&["RPUSH", "list", "x", "y"]])).unwrap();
+
+        let mut dbs = vec![Db::new(); 4];
+        dbs[1].insert(b"rewritten".to_vec(), (Value::String(b"r".to_vec()), None));
+        aof.reset(&dbs).unwrap();
+        aof.append(1, &frames(&[&["SET", "after", "reset"]])).unwrap();
+        assert!(!aof.status().rewrite_in_progress);
+        assert!(aof.status().last_rewrite_ok);
+
+        let manifest = Manifest::load(&aof_dir(&ctx.cfg), &ctx.cfg.appendfilename).unwrap().unwrap();
+        assert_eq!(manifest.base.as_ref().unwrap().seq, 2);
+        assert!(manifest.base.as_ref().unwrap().name.ends_with(if rdb_preamble { ".rdb" } else { ".aof" }));
+        assert_eq!(manifest.incrs.iter().map(|f| f.seq).collect::<Vec<_>>(), [2]);
+
+        let mut reloaded = context(config(&dir.0, true, rdb_preamble));
+        assert!(load_append_only_file(&mut reloaded).unwrap());
+        assert_eq!(string_at(&reloaded, 1, "rewritten").as_deref(), Some("r"));
+        assert_eq!(string_at(&reloaded, 1, "after").as_deref(), Some("reset"));
+        // the reset replaced everything written before it
+        assert_eq!(string_at(&reloaded, 0, "before"), None);
+        assert_eq!(string_at(&reloaded, 3, "k3"), None);
+    }
+
+    #[test]
+    fn rewrite_with_rdb_preamble_reloads() {
+        write_rewrite_and_reload("rdb-base", true);
+    }
+
+    #[test]
+    fn rewrite_with_command_base_reloads() {
+        write_rewrite_and_reload("command-base", false);
+    }
+
+    #[test]
+    fn binary_keys_survive_a_rewrite() {
+        for rdb_preamble in [false, true] {
+            let dir = TestDir::new(if rdb_preamble { "binary-keys-rdb" } else { "binary-keys-aof" });
+            let ctx = context(config(&dir.0, true, rdb_preamble));
+            let aof = Aof::open(&ctx).unwrap();
+
+            let mut dbs = vec![Db::new(); 1];
+            dbs[0].insert(b"\xe9".to_vec(), (Value::String(b"\xff\x00".to_vec()), None));
+            dbs[0].insert("é".as_bytes().to_vec(), (Value::String(b"text".to_vec()), None));
+            aof.reset(&dbs).unwrap();
+
+            let mut reloaded = context(config(&dir.0, true, rdb_preamble));
+            assert!(load_append_only_file(&mut reloaded).unwrap());
+            let db = reloaded.dbs[0].lock(b"\xe9");
+            assert!(matches!(db.get(&b"\xe9"[..]), Some((Value::String(v), _)) if v == b"\xff\x00"));
+            drop(db);
+            assert_eq!(string_at(&reloaded, 0, "é").as_deref(), Some("text"));
+        }
+    }
+
+    #[test]
+    fn open_writes_a_base_of_the_current_dataset_and_adopts_a_legacy_file() {
+        let dir = TestDir::new("open");
+        let mut ctx = context(config(&dir.0, true, true));
+        execute_cmd("SET", &["SET".into(), "k".into(), "v".into()], &mut ctx).unwrap().unwrap();
+        let aof = Aof::open(&ctx).unwrap();
+        aof.append(0, &frames(&[&["SET", "n", "1"]])).unwrap();
+        drop(aof);
+
+        let mut reloaded = context(config(&dir.0, true, true));
+        assert!(load_append_only_file(&mut reloaded).unwrap());
+        assert_eq!(string_at(&reloaded, 0, "k").as_deref(), Some("v"));
+        assert_eq!(string_at(&reloaded, 0, "n").as_deref(), Some("1"));
+
+        let legacy_dir = TestDir::new("open-legacy");
+        let cfg = config(&legacy_dir.0, true, true);
+        fs::write(legacy_aof_path(&cfg), frames(&[&["SET", "old", "yes"]])).unwrap();
+        let ctx = context(cfg);
+        Aof::open(&ctx).unwrap();
+        assert!(!legacy_aof_path(&ctx.cfg).exists());
+
+        let mut reloaded = context(config(&legacy_dir.0, true, true));
+        assert!(load_append_only_file(&mut reloaded).unwrap());
+        assert_eq!(string_at(&reloaded, 0, "old").as_deref(), Some("yes"));
+    }
+}
@@ -0,0 +1,226 @@
+//! The multi-part AOF manifest, shared by the server and `aof-check`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// `<dir>/<appendfilename>.manifest`
+pub fn manifest_path(dir: &Path, filename: &str) -> PathBuf {
+    dir.join(format!("{}.manifest", filename))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    Base,
+    Incr,
+}
+
+/// One file listed in the manifest.
+#[derive(Debug, Clone)]
+pub struct AofFileInfo {
+    pub name: String,
+    pub seq: u64,
+    pub file_type: AofFileType,
+}
+
+impl AofFileInfo {
+    pub fn base(filename: &str, seq: u64, rdb: bool) -> Self {
+        let ext = if rdb { "rdb" } else { "aof" };
+        Self { name: format!("{}.{}.base.{}", filename, seq, ext), seq, file_type: AofFileType::Base }
+    }
+
+    pub fn incr(filename: &str, seq: u64) -> Self {
+        Self { name: format!("{}.{}.incr.aof", filename, seq), seq, file_type: AofFileType::Incr }
+    }
+}
+
+/// The multi-part AOF manifest: one base file followed by the incremental
+/// files that apply on top of it, in order. Lines look like
+/// `file appendonly.aof.1.base.rdb seq 1 type b`.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub base: Option<AofFileInfo>,
+    pub incrs: Vec<AofFileInfo>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> io::Result<Manifest> {
+        let bad = |line: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid AOF manifest line: '{}'", line))
+        };
+
+        let mut manifest = Manifest::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if !words.len().is_multiple_of(2) {
+                return Err(bad(line));
+            }
+            let (mut name, mut seq, mut file_type) = (None, None, None);
+            for pair in words.chunks(2) {
+                match pair[0] {
+                    "file" => name = Some(pair[1].to_string()),
+                    "seq" => seq = pair[1].parse::<u64>().ok(),
+                    "type" => file_type = Some(pair[1]),
+                    _ => {} // unknown keys are ignored, like Redis does
+                }
+            }
+            let (Some(name), Some(seq), Some(file_type)) = (name, seq, file_type) else {
+                return Err(bad(line));
+            };
+
+            match file_type {
+                "b" => {
+                    if manifest.base.is_some() {
+                        return Err(bad(line));
+                    }
+                    manifest.base = Some(AofFileInfo { name, seq, file_type: AofFileType::Base });
+                }
+                "i" => manifest.incrs.push(AofFileInfo { name, seq, file_type: AofFileType::Incr }),
+                "h" => {} // history: left over from a rewrite, no longer part of the dataset
+                _ => return Err(bad(line)),
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn load(dir: &Path, filename: &str) -> io::Result<Option<Manifest>> {
+        match fs::read_to_string(manifest_path(dir, filename)) {
+            Ok(text) => Manifest::parse(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for file in self.files() {
+            let t = match file.file_type {
+                AofFileType::Base => "b",
+                AofFileType::Incr => "i",
+            };
+            out.push_str(&format!("file {} seq {} type {}\n", file.name, file.seq, t));
+        }
+        out
+    }
+
+    /// Base first, then the incremental files in replay order.
+    pub fn files(&self) -> impl Iterator<Item = &AofFileInfo> {
+        self.base.iter().chain(self.incrs.iter())
+    }
+
+    /// Atomically replaces the manifest on disk.
+    pub fn persist(&self, dir: &Path, filename: &str) -> io::Result<()> {
+        let tmp_path = dir.join(format!("temp-{}.manifest", filename));
+        let mut out = File::create(&tmp_path)?;
+        out.write_all(self.render().as_bytes())?;
+        out.sync_all()?;
+        fs::rename(&tmp_path, manifest_path(dir, filename))
+    }
+
+    pub fn next_incr_seq(&self) -> u64 {
+        self.incrs.last().map_or(1, |f| f.seq + 1)
+    }
+
+    pub fn next_base_seq(&self) -> u64 {
+        self.base.as_ref().map_or(1, |f| f.seq + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(manifest: &Manifest) -> Vec<&str> {
+        manifest.files().map(|f| f.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_base_and_incremental_files_in_replay_order() {
+        let text = "# written by a test\n\
+                    \n\
+                    file appendonly.aof.2.base.rdb seq 2 type b\n\
+                    file appendonly.aof.1.incr.aof seq 1 type h\n\
+                    file appendonly.aof.2.incr.aof seq 2 type i startoffset 0\n\
+                    file appendonly.aof.3.incr.aof seq 3 type i\n";
+        let manifest = Manifest::parse(text).unwrap();
+
+        assert_eq!(
+            names(&manifest),
+            ["appendonly.aof.2.base.rdb", "appendonly.aof.2.incr.aof", "appendonly.aof.3.incr.aof"]
+        );
+        assert_eq!(manifest.base.as_ref().unwrap().file_type, AofFileType::Base);
+        assert!(manifest.incrs.iter().all(|f| f.file_type == AofFileType::Incr));
+        assert_eq!(manifest.next_base_seq(), 3);
+        assert_eq!(manifest.next_incr_seq(), 4);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for text in [
+            "file a.aof seq 1 type b\nfile b.aof seq 2 type b\n",
+            "file a.aof seq 1 type\n",
+            "file a.aof seq one type i\n",
+            "file a.aof type i\n",
+            "seq 1 type i\n",
+            "file a.aof seq 1 type x\n",
+        ] {
+            let err = Manifest::parse(text).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn render_parses_back_to_the_same_files() {
+        let manifest = Manifest {
+            base: Some(AofFileInfo::base("appendonly.aof", 4, false)),
+            incrs: vec![AofFileInfo::incr("appendonly.aof", 7), AofFileInfo::incr("appendonly.aof", 8)],
+        };
+        assert_eq!(
+            manifest.render(),
+            "file appendonly.aof.4.base.aof seq 4 type b\n\
+             file appendonly.aof.7.incr.aof seq 7 type i\n\
+             file appendonly.aof.8.incr.aof seq 8 type i\n"
+        );
+
+        let parsed = Manifest::parse(&manifest.render()).unwrap();
+        assert_eq!(names(&parsed), names(&manifest));
+        assert_eq!(parsed.files().map(|f| f.seq).collect::<Vec<_>>(), [4, 7, 8]);
+    }
+
+    #[test]
+    fn empty_manifest_starts_sequences_at_one() {
+        let manifest = Manifest::parse("").unwrap();
+        assert!(manifest.base.is_none());
+        assert_eq!(manifest.files().count(), 0);
+        assert_eq!(manifest.next_base_seq(), 1);
+        assert_eq!(manifest.next_incr_seq(), 1);
+    }
+
+    #[test]
+    fn persist_and_load() {
+        let dir = std::env::temp_dir().join(format!("aof-manifest-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(Manifest::load(&dir, "appendonly.aof").unwrap().is_none());
+
+        let manifest = Manifest {
+            base: Some(AofFileInfo::base("appendonly.aof", 1, true)),
+            incrs: vec![AofFileInfo::incr("appendonly.aof", 1)],
+        };
+        manifest.persist(&dir, "appendonly.aof").unwrap();
+        assert!(manifest_path(&dir, "appendonly.aof").ends_with("appendonly.aof.manifest"));
+        assert!(!dir.join("temp-appendonly.aof.manifest").exists());
+
+        let loaded = Manifest::load(&dir, "appendonly.aof").unwrap().unwrap();
+        assert_eq!(names(&loaded), ["appendonly.aof.1.base.rdb", "appendonly.aof.1.incr.aof"]);
+
+        fs::write(manifest_path(&dir, "appendonly.aof"), "garbage\n").unwrap();
+        assert!(Manifest::load(&dir, "appendonly.aof").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,427 @@
+//! Checks, and optionally repairs, an append-only file the way the server
+//! loads it: an optional RDB preamble followed by RESP command frames.
+//!
+//! ```text
+//! aof-check [--fix] <file.aof | file.manifest | appendonlydir>
+//! ```
+//!
+//! With a manifest (or the directory holding one) every listed file is
+//! checked in replay order. Only the last file can be repaired, since a crash
+//! can only cut short the file being appended to.
+//!
+//! `--fix` truncates a command cut short at the end of the file right away:
+//! that is what a crash mid-append leaves. A malformed command anywhere
+//! else means truncating would throw away what follows it, so the byte
+//! range and the commands that would go are shown and confirmation asked
+//! for first.
+
+use codecrafters_redis::aof_manifest::Manifest;
+use codecrafters_redis::rdb::decode::RdbDecoder;
+use codecrafters_redis::resp::parse_resp_array;
+use memchr::memchr_iter;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::env;
+
+const USAGE: &str = "usage: aof-check [--fix] <file.aof | file.manifest | appendonlydir>";
+
+/// Per-command totals: (count, bytes).
+type CommandStats = HashMap<String, (usize, u64)>;
+
+/// Result of walking one file.
+struct FileReport {
+    size: u64,
+    commands: usize,
+    /// Offset just past the last well-formed command.
+    ok_up_to: u64,
+    /// First malformed record: offset and reason.
+    error: Option<(u64, String)>,
+    /// The malformed record is a command cut short by the end of the file.
+    partial_tail: bool,
+}
+
+impl FileReport {
+    /// A file whose RDB preamble is unreadable: nothing in it can be kept.
+    fn preamble_error(size: u64, reason: String) -> Self {
+        Self { size, commands: 0, ok_up_to: 0, error: Some((0, reason)), partial_tail: false }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (fix, target) = match args.as_slice() {
+        [path] => (false, path),
+        [flag, path] if flag == "--fix" => (true, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(Path::new(target), fix) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("aof-check: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Every file to check, in replay order.
+fn resolve_files(target: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let manifest_path = if target.is_dir() {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(target)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "manifest") {
+                found.push(path);
+            }
+        }
+        match found.len() {
+            1 => found.pop().unwrap(),
+            0 => return Err(format!("no manifest found in {:?}", target).into()),
+            _ => return Err(format!("more than one manifest in {:?}; name the one to check", target).into()),
+        }
+    } else if target.extension().is_some_and(|ext| ext == "manifest") {
+        target.to_path_buf()
+    } else {
+        return Ok(vec![target.to_path_buf()]);
+    };
+
+    let text = fs::read_to_string(&manifest_path).map_err(|e| format!("{:?}: {}", manifest_path, e))?;
+    let manifest = Manifest::parse(&text)?;
+    let dir = manifest_path.parent().unwrap_or(Path::new("."));
+    println!("Manifest {:?}: {} file(s)", manifest_path, manifest.files().count());
+    Ok(manifest.files().map(|f| dir.join(&f.name)).collect())
+}
+
+/// Checks every file; returns whether the AOF is (now) loadable.
+fn run(target: &Path, fix: bool) -> Result<bool, Box<dyn Error>> {
+    let files = resolve_files(target)?;
+    let mut stats = CommandStats::new();
+    let mut valid = true;
+
+    for (i, path) in files.iter().enumerate() {
+        let last = i + 1 == files.len();
+        let report = check_file(path, &mut stats).map_err(|e| format!("{:?}: {}", path, e))?;
+
+        let Some((offset, reason)) = &report.error else {
+            println!("{:?}: OK ({} command(s), {} bytes)", path, report.commands, report.size);
+            continue;
+        };
+
+        println!(
+            "{:?}: Bad format at offset {}: {} ({} command(s) before it, {} of {} bytes invalid)",
+            path,
+            offset,
+            reason,
+            report.commands,
+            report.size - report.ok_up_to,
+            report.size
+        );
+        if !last {
+            println!("{:?} is not the last file of the AOF; it can't be fixed by truncation.", path);
+            valid = false;
+            continue;
+        }
+        if !fix {
+            println!("Run with --fix to truncate it to the last valid command ({} bytes).", report.ok_up_to);
+            valid = false;
+            continue;
+        }
+        if !report.partial_tail {
+            let after = count_commands_after(path, report.ok_up_to)?;
+            println!(
+                "This is not a partial command at the end of the file: truncating discards bytes {}..{} \
+                 ({} bytes), which hold {} well-formed command(s) after the bad one.",
+                report.ok_up_to,
+                report.size,
+                report.size - report.ok_up_to,
+                after
+            );
+            if !confirm("Continue? [y/N]: ")? {
+                println!("{:?} left as it is.", path);
+                valid = false;
+                continue;
+            }
+        }
+
+        OpenOptions::new().write(true).open(path)?.set_len(report.ok_up_to)?;
+        println!("Successfully truncated {:?} to {} bytes.", path, report.ok_up_to);
+    }
+
+    print_stats(&stats);
+    println!("{}", if valid { "AOF is valid" } else { "AOF is not valid" });
+    Ok(valid)
+}
+
+/// Walks one file, validating the RDB preamble if there is one and then
+/// every command frame, stopping at the first malformed record.
+fn check_file(path: &Path, stats: &mut CommandStats) -> io::Result<FileReport> {
+    let size = fs::metadata(path)?.len();
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if reader.fill_buf()?.starts_with(b"REDIS") {
+        let mut decoder = match RdbDecoder::new(&mut reader) {
+            Ok(decoder) => decoder,
+            Err(e) => return Ok(FileReport::preamble_error(size, e.to_string())),
+        };
+        loop {
+            match decoder.next_event() {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => {
+                    return Ok(FileReport::preamble_error(size, format!("invalid RDB preamble: {}", e)));
+                }
+            }
+        }
+        println!("{:?}: RDB preamble OK (version {})", path, decoder.version());
+        let mut rest = decoder.into_inner();
+        check_commands(&mut rest, size, stats)
+    } else {
+        check_commands(&mut reader, size, stats)
+    }
+}
+
+fn check_commands<R: BufRead + Seek>(reader: &mut R, size: u64, stats: &mut CommandStats) -> io::Result<FileReport> {
+    let mut commands = 0;
+
+    loop {
+        let ok_up_to = reader.stream_position()?;
+        let mut partial_tail = false;
+        let error = match parse_resp_array(reader) {
+            Ok(None) => None,
+            Ok(Some(args)) if args.is_empty() => Some("expected a command array".to_string()),
+            Ok(Some(args)) => {
+                let end = reader.stream_position()?;
+                let entry = stats.entry(args[0].to_uppercase()).or_default();
+                entry.0 += 1;
+                entry.1 += end - ok_up_to;
+                commands += 1;
+                continue;
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                partial_tail = true;
+                Some("unexpected end of file".to_string())
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Some(e.to_string()),
+            Err(e) => return Err(e),
+        };
+
+        let error = error.map(|reason| (ok_up_to, reason));
+        return Ok(FileReport { size, commands, ok_up_to, error, partial_tail });
+    }
+}
+
+/// Well-formed commands in `path` past `offset`, where parsing failed:
+/// each line starting with `*` is tried as the start of a command, and
+/// counting goes on after every one that parses.
+fn count_commands_after(path: &Path, offset: u64) -> io::Result<usize> {
+    let data = fs::read(path)?;
+    let rest = &data[offset as usize..];
+    let mut count = 0;
+    let mut next = 0;
+    for newline in memchr_iter(b'\n', rest) {
+        let start = newline + 1;
+        if start < next || rest.get(start) != Some(&b'*') {
+            continue;
+        }
+        let mut cursor = Cursor::new(&rest[start..]);
+        if let Ok(Some(args)) = parse_resp_array(&mut cursor) {
+            if !args.is_empty() {
+                count += 1;
+                next = start + cursor.position() as usize;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Asks `question` on the terminal; only "y" or "yes" is a yes.
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_stats(stats: &CommandStats) {
+    let mut rows: Vec<_> = stats.iter().collect();
+    rows.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(b.0)));
+
+    let total: usize = rows.iter().map(|(_, (count, _))| count).sum();
+    println!("Commands: {}", total);
+    for (name, (count, bytes)) in rows {
+        println!("  {:<16} {:>10} {:>12} bytes", name, count, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codecrafters_redis::aof_manifest::AofFileInfo;
+    use codecrafters_redis::rdb::encode::encode_rdb_snapshot;
+    use codecrafters_redis::rdb::{Db, Value};
+    use std::io::Cursor;
+
+    /// A scratch directory, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("aof-check-test-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TestDir(path)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const SET_A: &[u8] = b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n";
+    const DEL_A: &[u8] = b"*2\r\n$3\r\nDEL\r\n$1\r\na\r\n";
+
+    fn check(bytes: &[u8]) -> (FileReport, CommandStats) {
+        let mut stats = CommandStats::new();
+        let report = check_commands(&mut Cursor::new(bytes), bytes.len() as u64, &mut stats).unwrap();
+        (report, stats)
+    }
+
+    #[test]
+    fn valid_commands_are_counted_per_name() {
+        let aof = [SET_A, DEL_A, SET_A].concat();
+        let (report, stats) = check(&aof);
+
+        assert!(report.error.is_none());
+        assert_eq!(report.commands, 3);
+        assert_eq!(report.ok_up_to, aof.len() as u64);
+        assert_eq!(stats["SET"], (2, 2 * SET_A.len() as u64));
+        assert_eq!(stats["DEL"], (1, DEL_A.len() as u64));
+    }
+
+    #[test]
+    fn a_command_cut_short_at_the_end_is_a_partial_tail() {
+        let aof = [SET_A, &DEL_A[..10]].concat();
+        let (report, _) = check(&aof);
+
+        assert!(report.partial_tail);
+        assert_eq!(report.commands, 1);
+        assert_eq!(report.ok_up_to, SET_A.len() as u64);
+        assert_eq!(report.error, Some((SET_A.len() as u64, "unexpected end of file".to_string())));
+    }
+
+    #[test]
+    fn a_malformed_command_mid_file_is_not_a_partial_tail() {
+        let aof = [SET_A, b"*2\r\n$x\r\n", DEL_A, SET_A].concat();
+        let (report, _) = check(&aof);
+
+        assert_eq!(report.commands, 1);
+        assert_eq!(report.ok_up_to, SET_A.len() as u64);
+        assert!(report.error.is_some());
+        assert!(!report.partial_tail);
+
+        let (report, _) = check(b"+OK\r\n");
+        assert_eq!(report.ok_up_to, 0);
+        assert!(report.error.is_some());
+        assert!(!report.partial_tail);
+    }
+
+    #[test]
+    fn counts_the_well_formed_commands_past_a_bad_one() {
+        let dir = TestDir::new("after");
+        let path = dir.0.join("appendonly.aof");
+        fs::write(&path, [SET_A, b"*2\r\n$x\r\n", DEL_A, SET_A, &DEL_A[..7]].concat()).unwrap();
+
+        assert_eq!(count_commands_after(&path, SET_A.len() as u64).unwrap(), 2);
+        fs::write(&path, [SET_A, b"*2\r\n$x\r\n"].concat()).unwrap();
+        assert_eq!(count_commands_after(&path, SET_A.len() as u64).unwrap(), 0);
+    }
+
+    #[test]
+    fn fix_truncates_a_partial_tail_without_asking() {
+        let dir = TestDir::new("fix");
+        let path = dir.0.join("appendonly.aof");
+        fs::write(&path, [SET_A, DEL_A, &SET_A[..5]].concat()).unwrap();
+
+        assert!(!run(&path, false).unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().len(), (SET_A.len() + DEL_A.len() + 5) as u64);
+
+        assert!(run(&path, true).unwrap());
+        assert_eq!(fs::read(&path).unwrap(), [SET_A, DEL_A].concat());
+        assert!(run(&path, false).unwrap());
+    }
+
+    #[test]
+    fn checks_the_rdb_preamble_before_the_commands() {
+        let dir = TestDir::new("preamble");
+        let mut db = Db::new();
+        db.insert(b"k".to_vec(), (Value::String(b"v".to_vec()), None));
+        let rdb = encode_rdb_snapshot(&[db]).unwrap();
+
+        let path = dir.0.join("appendonly.aof");
+        fs::write(&path, [&rdb[..], SET_A].concat()).unwrap();
+        let report = check_file(&path, &mut CommandStats::new()).unwrap();
+        assert!(report.error.is_none());
+        assert_eq!(report.commands, 1);
+
+        fs::write(&path, [&rdb[..rdb.len() - 3], SET_A].concat()).unwrap();
+        let report = check_file(&path, &mut CommandStats::new()).unwrap();
+        assert_eq!(report.ok_up_to, 0);
+        assert!(!report.partial_tail);
+        assert!(report.error.unwrap().1.starts_with("invalid RDB preamble"));
+    }
+
+    #[test]
+    fn only_the_last_file_of_a_manifest_is_repaired() {
+        let dir = TestDir::new("repair");
+        let manifest = Manifest {
+            base: Some(AofFileInfo::base("appendonly.aof", 1, false)),
+            incrs: vec![AofFileInfo::incr("appendonly.aof", 1)],
+        };
+        manifest.persist(&dir.0, "appendonly.aof").unwrap();
+        let base = dir.0.join(&manifest.base.as_ref().unwrap().name);
+        let incr = dir.0.join(&manifest.incrs[0].name);
+
+        fs::write(&base, [SET_A, &DEL_A[..4]].concat()).unwrap();
+        fs::write(&incr, SET_A).unwrap();
+        assert!(!run(&dir.0, true).unwrap());
+        assert_eq!(fs::metadata(&base).unwrap().len(), (SET_A.len() + 4) as u64);
+
+        fs::write(&base, SET_A).unwrap();
+        fs::write(&incr, [SET_A, &DEL_A[..4]].concat()).unwrap();
+        assert!(run(&dir.0, true).unwrap());
+        assert_eq!(fs::read(&incr).unwrap(), SET_A);
+    }
+
+    #[test]
+    fn manifests_resolve_to_their_files_in_replay_order() {
+        let dir = TestDir::new("manifest");
+        let manifest = Manifest {
+            base: Some(AofFileInfo::base("appendonly.aof", 2, true)),
+            incrs: vec![AofFileInfo::incr("appendonly.aof", 3), AofFileInfo::incr("appendonly.aof", 4)],
+        };
+        manifest.persist(&dir.0, "appendonly.aof").unwrap();
+
+        let expected: Vec<PathBuf> = manifest.files().map(|f| dir.0.join(&f.name)).collect();
+        assert_eq!(resolve_files(&dir.0).unwrap(), expected);
+        assert_eq!(resolve_files(&dir.0.join("appendonly.aof.manifest")).unwrap(), expected);
+
+        let single = dir.0.join("single.aof");
+        assert_eq!(resolve_files(&single).unwrap(), [single]);
+
+        manifest.persist(&dir.0, "other.aof").unwrap();
+        assert!(resolve_files(&dir.0).is_err());
+        assert!(resolve_files(&TestDir::new("empty").0).is_err());
+    }
+}
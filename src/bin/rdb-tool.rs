@@ -0,0 +1,629 @@
+//! Offline RDB inspection and conversion, built on the server's `rdb` module.
+//!
+//! ```text
+//! rdb-tool check <file.rdb>
+//! rdb-tool stats <file.rdb>
+//! rdb-tool dump [--format json|resp] <file.rdb>
+//! rdb-tool restore-from-json <dump.json> <out.rdb>
+//! ```
+
+use codecrafters_redis::dict::Dict;
+use codecrafters_redis::rdb::decode::{RdbDecoder, RdbEvent};
+use codecrafters_redis::rdb::encode::write_rdb_snapshot;
+use codecrafters_redis::rdb::{Db, SortedSet, StreamEntry, Value};
+use codecrafters_redis::resp::{write_resp_array, write_resp_bytes_array};
+use serde_json::{json, Map, Value as Json};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::env;
+
+type ToolResult = Result<(), Box<dyn Error>>;
+
+const USAGE: &str = "usage:
+  rdb-tool check <file.rdb>
+  rdb-tool stats <file.rdb>
+  rdb-tool dump [--format json|resp] <file.rdb>
+  rdb-tool restore-from-json <dump.json> <out.rdb>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        ["check", path] => check(path),
+        ["stats", path] => stats(path),
+        ["dump", path] => dump(path, "json"),
+        ["dump", "--format", format, path] | ["dump", path, "--format", format] => dump(path, format),
+        ["restore-from-json", input, output] => restore_from_json(input, output),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rdb-tool: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn open_decoder(path: &str) -> Result<RdbDecoder<File>, Box<dyn Error>> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(RdbDecoder::new(file).map_err(|e| format!("{}: {}", path, e))?)
+}
+
+fn unix_millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Number of elements (bytes, for strings) in a value.
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.len(),
+        Value::List(items) => items.len(),
+        Value::Set(members) => members.len(),
+        Value::ZSet(zset) => zset.len(),
+        Value::Hash(fields) => fields.len(),
+        Value::Stream(entries) => entries.len(),
+    }
+}
+
+/// check: decode every record and verify the checksum footer.
+fn check(path: &str) -> ToolResult {
+    let mut decoder = open_decoder(path)?;
+    println!("RDB version: {}", decoder.version());
+
+    let mut aux = 0;
+    let mut keys: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut skipped_modules = 0;
+    let mut db = 0;
+    let mut last_key: Option<Vec<u8>> = None;
+
+    loop {
+        let event = match decoder.next_event() {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(e) => {
+                let after = last_key
+                    .map(|k| format!(" after key '{}'", String::from_utf8_lossy(&k)))
+                    .unwrap_or_default();
+                return Err(format!("{}: corrupt in DB {}{}: {}", path, db, after, e).into());
+            }
+        };
+        match event {
+            RdbEvent::Aux { .. } => aux += 1,
+            RdbEvent::SelectDb(index) => db = index,
+            RdbEvent::Entry { key, .. } => {
+                *keys.entry(db).or_default() += 1;
+                last_key = Some(key);
+            }
+            RdbEvent::ModuleValue { key } => {
+                skipped_modules += 1;
+                last_key = Some(key);
+            }
+            RdbEvent::Function { .. } | RdbEvent::ModuleAux { .. } | RdbEvent::ResizeDb { .. } => {}
+        }
+    }
+
+    println!("Aux fields: {}", aux);
+    for (db, count) in &keys {
+        println!("DB {}: {} key(s)", db, count);
+    }
+    if skipped_modules > 0 {
+        println!("Module values (not decodable): {}", skipped_modules);
+    }
+    match decoder.checksum() {
+        Some(crc) => println!("Checksum: OK ({:016x})", crc),
+        None => println!("Checksum: not present (written with rdbchecksum no)"),
+    }
+
+    let trailing = decoder.into_inner().fill_buf()?.len();
+    if trailing > 0 {
+        println!("Warning: unexpected data after the end of the RDB");
+    }
+    println!("{}: OK", path);
+    Ok(())
+}
+
+/// Per-type totals for `stats`.
+#[derive(Default)]
+struct TypeStats {
+    keys: usize,
+    elements: usize,
+    largest: Option<(usize, Vec<u8>)>,
+    /// Key counts bucketed by element count (bytes for strings).
+    buckets: [usize; SIZE_BUCKETS.len()],
+}
+
+const SIZE_BUCKETS: [(usize, &str); 6] = [
+    (1, "0-1"),
+    (10, "2-10"),
+    (100, "11-100"),
+    (1_000, "101-1000"),
+    (10_000, "1001-10000"),
+    (usize::MAX, ">10000"),
+];
+
+/// stats: key counts, expiring keys, and type and size distribution.
+fn stats(path: &str) -> ToolResult {
+    let mut decoder = open_decoder(path)?;
+    let now = SystemTime::now();
+
+    let mut dbs: BTreeMap<usize, (usize, usize, usize)> = BTreeMap::new(); // keys, expiring, expired
+    let mut types: BTreeMap<&'static str, TypeStats> = BTreeMap::new();
+    let mut db = 0;
+
+    while let Some(event) = decoder.next_event()? {
+        match event {
+            RdbEvent::SelectDb(index) => db = index,
+            RdbEvent::Entry { key, value, expiry } => {
+                let counts = dbs.entry(db).or_default();
+                counts.0 += 1;
+                if let Some(at) = expiry {
+                    counts.1 += 1;
+                    if at <= now {
+                        counts.2 += 1;
+                    }
+                }
+
+                let size = value_size(&value);
+                let stats = types.entry(value.type_name()).or_default();
+                stats.keys += 1;
+                stats.elements += size;
+                if stats.largest.as_ref().is_none_or(|(max, _)| size > *max) {
+                    stats.largest = Some((size, key));
+                }
+                let bucket = SIZE_BUCKETS.iter().position(|(max, _)| size <= *max).unwrap();
+                stats.buckets[bucket] += 1;
+            }
+            _ => {}
+        }
+    }
+
+    println!("RDB version: {}", decoder.version());
+    let total: usize = dbs.values().map(|c| c.0).sum();
+    println!("Total keys: {}", total);
+    for (db, (keys, expiring, expired)) in &dbs {
+        println!("DB {}: keys={} expiring={} already_expired={}", db, keys, expiring, expired);
+    }
+
+    for (name, stats) in &types {
+        let unit = if *name == "string" { "bytes" } else { "elements" };
+        println!();
+        println!("{}: {} key(s), {} {} total", name, stats.keys, stats.elements, unit);
+        if let Some((size, key)) = &stats.largest {
+            println!("  largest: '{}' ({} {})", String::from_utf8_lossy(key), size, unit);
+        }
+        for ((_, label), count) in SIZE_BUCKETS.iter().zip(stats.buckets) {
+            if count > 0 {
+                println!("  {:>10} {}: {}", label, unit, count);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// dump: every key as JSON (one object per line inside an array) or as the
+/// RESP commands that recreate it.
+fn dump(path: &str, format: &str) -> ToolResult {
+    if format != "json" && format != "resp" {
+        return Err(format!("unknown dump format '{}': expected json or resp", format).into());
+    }
+
+    let mut decoder = open_decoder(path)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut db = 0;
+    let mut selected = None;
+    let mut first = true;
+
+    if format == "json" {
+        writeln!(out, "[")?;
+    }
+    while let Some(event) = decoder.next_event()? {
+        match event {
+            RdbEvent::SelectDb(index) => db = index,
+            RdbEvent::Entry { key, value, expiry } if format == "json" => {
+                if !first {
+                    writeln!(out, ",")?;
+                }
+                first = false;
+                write!(out, "{}", entry_to_json(db, &key, &value, expiry))?;
+            }
+            RdbEvent::Entry { key, value, expiry } => {
+                if selected != Some(db) {
+                    write_resp_array(&mut out, &["SELECT", &db.to_string()])?;
+                    selected = Some(db);
+                }
+                write_entry_commands(&mut out, &key, &value, expiry)?;
+            }
+            RdbEvent::ModuleValue { key } => {
+                eprintln!("rdb-tool: skipping module value '{}'", String::from_utf8_lossy(&key))
+            }
+            _ => {}
+        }
+    }
+    if format == "json" {
+        if !first {
+            writeln!(out)?;
+        }
+        writeln!(out, "]")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn score_to_json(score: f64) -> Json {
+    if score.is_finite() {
+        json!(score)
+    } else if score > 0.0 {
+        json!("inf")
+    } else {
+        json!("-inf")
+    }
+}
+
+/// JSON strings are text: other bytes are written as an array of numbers.
+fn bytes_to_json(bytes: &[u8]) -> Json {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => json!(text),
+        Err(_) => json!(bytes),
+    }
+}
+
+fn entry_to_json(db: usize, key: &[u8], value: &Value, expiry: Option<SystemTime>) -> Json {
+    let sorted = |members: &Dict<Vec<u8>, ()>| {
+        let mut members: Vec<&Vec<u8>> = members.keys().collect();
+        members.sort();
+        Json::Array(members.into_iter().map(|m| bytes_to_json(m)).collect())
+    };
+    let value_json = match value {
+        Value::String(s) => bytes_to_json(s),
+        Value::List(items) => Json::Array(items.iter().map(|item| bytes_to_json(item)).collect()),
+        Value::Set(members) => sorted(members),
+        Value::ZSet(zset) => Json::Array(
+            zset.iter()
+                .map(|(member, score)| json!({ "member": bytes_to_json(member), "score": score_to_json(score) }))
+                .collect(),
+        ),
+        // an object when every field can be a JSON key, [field, value] pairs otherwise
+        Value::Hash(fields) => match fields.iter().map(|(f, v)| Some((String::from_utf8(f.clone()).ok()?, bytes_to_json(v)))).collect() {
+            Some(obj) => Json::Object(obj),
+            None => Json::Array(fields.iter().map(|(f, v)| json!([bytes_to_json(f), bytes_to_json(v)])).collect()),
+        },
+        Value::Stream(entries) => Json::Array(
+            entries
+                .iter()
+                .map(|e| {
+                    let fields: Vec<Json> = e.fields.iter().flat_map(|(f, v)| [bytes_to_json(f), bytes_to_json(v)]).collect();
+                    json!({ "id": e.id, "fields": fields })
+                })
+                .collect(),
+        ),
+    };
+
+    let mut obj = Map::new();
+    obj.insert("db".into(), json!(db));
+    obj.insert("key".into(), bytes_to_json(key));
+    obj.insert("type".into(), json!(value.type_name()));
+    if let Some(at) = expiry {
+        obj.insert("expire_at_ms".into(), json!(unix_millis(at)));
+    }
+    obj.insert("value".into(), value_json);
+    Json::Object(obj)
+}
+
+fn write_entry_commands(out: &mut dyn Write, key: &[u8], value: &Value, expiry: Option<SystemTime>) -> io::Result<()> {
+    let name = String::from_utf8_lossy(key);
+    let mut args: Vec<Vec<u8>> = Vec::new();
+    match value {
+        Value::String(s) => args.extend(["SET".into(), key.into(), s.clone()]),
+        Value::List(items) => {
+            args.extend(["RPUSH".into(), key.into()]);
+            args.extend(items.iter().cloned());
+        }
+        Value::Set(members) => {
+            args.extend(["SADD".into(), key.into()]);
+            args.extend(members.keys().cloned());
+        }
+        Value::ZSet(zset) => {
+            args.extend(["ZADD".into(), key.into()]);
+            for (member, score) in zset.iter() {
+                args.extend([score.to_string().into_bytes(), member.to_vec()]);
+            }
+        }
+        Value::Hash(fields) => {
+            args.extend(["HSET".into(), key.into()]);
+            for (f, v) in fields {
+                args.extend([f.clone(), v.clone()]);
+            }
+        }
+        Value::Stream(entries) => {
+            for entry in entries {
+                if entry.fields.is_empty() {
+                    eprintln!("rdb-tool: stream '{}' entry {} has no fields; skipped", name, entry.id);
+                    continue;
+                }
+                let mut xadd: Vec<&[u8]> = vec![b"XADD", key, entry.id.as_bytes()];
+                for (f, v) in &entry.fields {
+                    xadd.extend([f.as_slice(), v.as_slice()]);
+                }
+                write_resp_bytes_array(out, &xadd)?;
+            }
+        }
+    }
+
+    // empty collections and streams have no single command to recreate them
+    if args.len() > 2 || matches!(value, Value::String(_)) {
+        let args: Vec<&[u8]> = args.iter().map(Vec::as_slice).collect();
+        write_resp_bytes_array(out, &args)?;
+    } else if !matches!(value, Value::Stream(_)) {
+        eprintln!("rdb-tool: '{}' is an empty {}; skipped", name, value.type_name());
+        return Ok(());
+    }
+
+    if let Some(at) = expiry {
+        write_resp_bytes_array(out, &[b"PEXPIREAT", key, unix_millis(at).to_string().as_bytes()])?;
+    }
+    Ok(())
+}
+
+/// restore-from-json: read the `dump --format json` layout back and write an RDB.
+fn restore_from_json(input: &str, output: &str) -> ToolResult {
+    let reader = BufReader::new(File::open(input).map_err(|e| format!("{}: {}", input, e))?);
+    let doc: Json = serde_json::from_reader(reader).map_err(|e| format!("{}: {}", input, e))?;
+    let items = doc.as_array().ok_or("expected a top-level JSON array")?;
+
+    let mut dbs: Vec<Db> = vec![Db::new()];
+    for (i, item) in items.iter().enumerate() {
+        let (db, key, value, expiry) = json_to_entry(item).map_err(|e| format!("entry {}: {}", i, e))?;
+        if dbs.len() <= db {
+            dbs.resize(db + 1, Db::new());
+        }
+        dbs[db].insert(key, (value, expiry));
+    }
+
+    let tmp_path = format!("{}.tmp", output);
+    let mut out = BufWriter::new(File::create(&tmp_path)?);
+    write_rdb_snapshot(&mut out, &dbs)?;
+    out.flush()?;
+    out.get_ref().sync_all()?;
+    fs::rename(&tmp_path, output)?;
+
+    let total: usize = dbs.iter().map(|db| db.len()).sum();
+    eprintln!("rdb-tool: wrote {} key(s) to {}", total, output);
+    Ok(())
+}
+
+fn json_str(v: &Json, what: &str) -> Result<String, String> {
+    v.as_str().map(str::to_string).ok_or_else(|| format!("{} must be a string", what))
+}
+
+/// A string value: JSON text, or an array of byte values for anything else.
+fn json_bytes(v: &Json, what: &str) -> Result<Vec<u8>, String> {
+    if let Some(text) = v.as_str() {
+        return Ok(text.as_bytes().to_vec());
+    }
+    v.as_array()
+        .ok_or_else(|| format!("{} must be a string or an array of bytes", what))?
+        .iter()
+        .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()).ok_or_else(|| format!("{} bytes must be 0-255", what)))
+        .collect()
+}
+
+fn json_strings(v: &Json, what: &str) -> Result<Vec<Vec<u8>>, String> {
+    v.as_array()
+        .ok_or_else(|| format!("{} must be an array", what))?
+        .iter()
+        .map(|s| json_bytes(s, what))
+        .collect()
+}
+
+fn json_score(v: &Json) -> Result<f64, String> {
+    match v {
+        Json::Number(n) => n.as_f64().ok_or_else(|| "score out of range".to_string()),
+        Json::String(s) => match s.as_str() {
+            "inf" | "+inf" => Ok(f64::INFINITY),
+            "-inf" => Ok(f64::NEG_INFINITY),
+            _ => Err(format!("invalid score '{}'", s)),
+        },
+        _ => Err("score must be a number".to_string()),
+    }
+}
+
+fn json_to_entry(item: &Json) -> Result<(usize, Vec<u8>, Value, Option<SystemTime>), String> {
+    let db = match item.get("db") {
+        None => 0,
+        Some(v) => v.as_u64().ok_or("db must be a non-negative integer")? as usize,
+    };
+    let key = json_bytes(item.get("key").ok_or("missing key")?, "key")?;
+    let kind = json_str(item.get("type").ok_or("missing type")?, "type")?;
+    let raw = item.get("value").ok_or("missing value")?;
+    let expiry = match item.get("expire_at_ms") {
+        None | Some(Json::Null) => None,
+        Some(v) => Some(UNIX_EPOCH + Duration::from_millis(v.as_u64().ok_or("expire_at_ms must be an integer")?)),
+    };
+
+    let value = match kind.as_str() {
+        "string" => Value::String(json_bytes(raw, "value")?),
+        "list" => Value::List(json_strings(raw, "value")?.into()),
+        "set" => Value::Set(json_strings(raw, "value")?.into_iter().map(|m| (m, ())).collect()),
+        "zset" => {
+            let mut zset = SortedSet::default();
+            for member in raw.as_array().ok_or("value must be an array")? {
+                let name = json_bytes(member.get("member").ok_or("missing member")?, "member")?;
+                zset.insert(name, json_score(member.get("score").ok_or("missing score")?)?);
+            }
+            Value::ZSet(zset)
+        }
+        "hash" => {
+            let fields: Result<Dict<Vec<u8>, Vec<u8>>, String> = match raw {
+                Json::Object(obj) => {
+                    obj.iter().map(|(f, v)| Ok((f.as_bytes().to_vec(), json_bytes(v, "hash value")?))).collect()
+                }
+                Json::Array(pairs) => pairs
+                    .iter()
+                    .map(|pair| match json_strings(pair, "hash field")?.as_slice() {
+                        [f, v] => Ok((f.clone(), v.clone())),
+                        _ => Err("hash pairs must be [field, value]".to_string()),
+                    })
+                    .collect(),
+                _ => Err("value must be an object or an array of pairs".to_string()),
+            };
+            Value::Hash(fields?)
+        }
+        "stream" => {
+            let mut entries = Vec::new();
+            for entry in raw.as_array().ok_or("value must be an array")? {
+                let id = json_str(entry.get("id").ok_or("missing stream id")?, "id")?;
+                let flat = json_strings(entry.get("fields").ok_or("missing stream fields")?, "fields")?;
+                if !flat.len().is_multiple_of(2) {
+                    return Err(format!("stream entry {} has an odd number of field items", id));
+                }
+                let fields = flat.chunks(2).map(|p| (p[0].clone(), p[1].clone())).collect();
+                entries.push(StreamEntry { id, fields });
+            }
+            Value::Stream(entries)
+        }
+        other => return Err(format!("unknown type '{}'", other)),
+    };
+    Ok((db, key, value, expiry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codecrafters_redis::resp::{parse_resp_array, Arg};
+
+    /// A scratch file path, removed when dropped.
+    struct TempPath(String);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("rdb-tool-test-{}-{}", name, std::process::id()));
+            TempPath(path.to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn round_trip(item: Json) {
+        let (db, key, value, expiry) = json_to_entry(&item).unwrap();
+        assert_eq!(entry_to_json(db, &key, &value, expiry), item);
+    }
+
+    fn commands(key: &str, value: &Value, expiry: Option<SystemTime>) -> Vec<Vec<Arg>> {
+        let mut out = Vec::new();
+        write_entry_commands(&mut out, key.as_bytes(), value, expiry).unwrap();
+        let mut reader = &out[..];
+        let mut parsed = Vec::new();
+        while let Some(args) = parse_resp_array(&mut reader).unwrap() {
+            parsed.push(args);
+        }
+        parsed
+    }
+
+    #[test]
+    fn every_type_round_trips_through_json() {
+        round_trip(json!({ "db": 0, "key": "s", "type": "string", "value": "hello" }));
+        // bytes that aren't UTF-8 are exported as numbers
+        round_trip(json!({ "db": 0, "key": "b", "type": "string", "value": [255, 0, 233] }));
+        round_trip(json!({ "db": 3, "key": "l", "type": "list", "expire_at_ms": 1_700_000_000_000u64, "value": ["a", "b"] }));
+        round_trip(json!({ "db": 0, "key": "st", "type": "set", "value": ["x", "y", "z"] }));
+        round_trip(json!({ "db": 0, "key": "h", "type": "hash", "value": { "f": "v" } }));
+        round_trip(json!({
+            "db": 0, "key": "z", "type": "zset",
+            "value": [{ "member": "low", "score": "-inf" }, { "member": "mid", "score": 1.5 }, { "member": "top", "score": "inf" }]
+        }));
+        round_trip(json!({
+            "db": 1, "key": "x", "type": "stream",
+            "value": [{ "id": "1-0", "fields": ["temp", "20"] }, { "id": "2-0", "fields": [] }]
+        }));
+    }
+
+    #[test]
+    fn malformed_json_entries_are_rejected() {
+        assert!(json_to_entry(&json!({ "type": "string", "value": "v" })).is_err());
+        assert!(json_to_entry(&json!({ "key": "k", "type": "blob", "value": "v" })).is_err());
+        assert!(json_to_entry(&json!({ "key": "k", "type": "list", "value": "v" })).is_err());
+        assert!(json_to_entry(&json!({ "key": "k", "type": "zset", "value": [{ "member": "m", "score": "lots" }] })).is_err());
+        assert!(json_to_entry(&json!({ "key": "k", "type": "stream", "value": [{ "id": "1-0", "fields": ["f"] }] })).is_err());
+        assert!(json_to_entry(&json!({ "db": -1, "key": "k", "type": "string", "value": "v" })).is_err());
+    }
+
+    #[test]
+    fn resp_dump_recreates_each_key() {
+        let at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        assert_eq!(
+            commands("k", &Value::String("v".into()), Some(at)),
+            [vec!["SET", "k", "v"], vec!["PEXPIREAT", "k", "1700000000000"]]
+        );
+        let set = commands("b", &Value::String(vec![0xFF, 0]), None);
+        assert_eq!(set[0][2].as_bytes(), b"\xff\x00");
+        assert_eq!(commands("l", &Value::List(["a".into(), "b".into()].into()), None), [vec!["RPUSH", "l", "a", "b"]]);
+
+        let stream = Value::Stream(vec![
+            StreamEntry { id: "1-0".into(), fields: vec![("f".into(), "v".into())] },
+            StreamEntry { id: "2-0".into(), fields: Vec::new() },
+        ]);
+        assert_eq!(commands("x", &stream, None), [vec!["XADD", "x", "1-0", "f", "v"]]);
+
+        // nothing recreates an empty collection, so nor is its expiry set
+        assert!(commands("e", &Value::List(Default::default()), Some(at)).is_empty());
+    }
+
+    #[test]
+    fn restored_json_passes_check_and_decodes_back() {
+        let json = TempPath::new("restore.json");
+        let rdb = TempPath::new("restore.rdb");
+        let doc = json!([
+            { "db": 0, "key": "a", "type": "string", "value": "1" },
+            { "db": 2, "key": "b", "type": "list", "value": ["x"] },
+        ]);
+        fs::write(&json.0, doc.to_string()).unwrap();
+
+        restore_from_json(&json.0, &rdb.0).unwrap();
+        check(&rdb.0).unwrap();
+        stats(&rdb.0).unwrap();
+
+        let mut decoder = open_decoder(&rdb.0).unwrap();
+        let mut db = 0;
+        let mut restored = Vec::new();
+        while let Some(event) = decoder.next_event().unwrap() {
+            match event {
+                RdbEvent::SelectDb(index) => db = index,
+                RdbEvent::Entry { key, value, expiry } => restored.push(entry_to_json(db, &key, &value, expiry)),
+                _ => {}
+            }
+        }
+        assert_eq!(Json::Array(restored), doc);
+    }
+
+    #[test]
+    fn check_reports_corruption() {
+        let rdb = TempPath::new("corrupt.rdb");
+        let mut db = Db::new();
+        db.insert(b"k".to_vec(), (Value::String("value".into()), None));
+        let mut bytes = Vec::new();
+        write_rdb_snapshot(&mut bytes, &[db]).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&rdb.0, &bytes).unwrap();
+
+        let err = check(&rdb.0).unwrap_err().to_string();
+        assert!(err.contains("corrupt in DB 0 after key 'k'"), "{}", err);
+        assert!(check("/nonexistent/dump.rdb").is_err());
+    }
+}
@@ -1,38 +1,55 @@
 use crate::commands::Context;
-use crate::resp::{encode_bulk_resp_string, encode_resp_array, encode_resp_error};
+use crate::config::format_save_params;
+use crate::glob::glob_match_nocase;
+use crate::notify::format_notify_flags;
+use crate::resp::{Arg, encode_bulk_resp_string, encode_map_reply, encode_resp_error};
 use std::io;
 
-/// CONFIG GET <dir|dbfilename>
-pub fn cmd_config(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// CONFIG GET pattern [pattern ...] -> [name value ...] for every parameter
+/// matching one of the glob patterns (case-insensitively); a map in RESP3
+pub fn cmd_config(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_config] Received CONFIG command with args: {:?}", args);
 
-    if args.len() != 3 || args[1].to_uppercase() != "GET" {
+    if args.len() < 3 || args[1].to_uppercase() != "GET" {
         println!("[cmd_config] Incorrect argument length or subcommand");
-        return Ok(encode_resp_error("usage: CONFIG GET <dir|dbfilename>"));
+        return Ok(encode_resp_error("usage: CONFIG GET <pattern> [pattern ...]"));
     }
 
-    let key = &args[2];
-    println!("[cmd_config] Requested config key: '{}'", key);
+    let patterns = &args[2..];
+    println!("[cmd_config] Requested config patterns: {:?}", patterns);
 
-    let val = match key.as_str() {
-        "dir" => {
-            println!("[cmd_config] Returning value for 'dir': {}", ctx.cfg.dir);
-            &ctx.cfg.dir
+    let mut pairs = Vec::new();
+    for (name, value) in config_params(ctx) {
+        if patterns.iter().any(|p| glob_match_nocase(p, name)) {
+            println!("[cmd_config] Returning value for '{}': {}", name, value);
+            pairs.push((encode_bulk_resp_string(name), encode_bulk_resp_string(&value)));
         }
-        "dbfilename" => {
-            println!("[cmd_config] Returning value for 'dbfilename': {}", ctx.cfg.dbfilename);
-            &ctx.cfg.dbfilename
-        }
-        _ => {
-            eprintln!("[cmd_config] Unknown config parameter: '{}'", key);
-            return Ok(encode_resp_error("unknown config parameter"));
-        }
-    };
+    }
+    if pairs.is_empty() {
+        eprintln!("[cmd_config] No config parameter matches {:?}", patterns);
+    }
 
-    let response = encode_resp_array(&[
-        encode_bulk_resp_string(key),
-        encode_bulk_resp_string(val),
-    ]);
+    Ok(encode_map_reply(ctx.protocol, &pairs))
+}
 
-    Ok(response)
+/// Every parameter CONFIG GET knows, with its current value.
+fn config_params(ctx: &Context) -> Vec<(&'static str, String)> {
+    let cfg = &ctx.cfg;
+    vec![
+        ("dir", cfg.dir.clone()),
+        ("dbfilename", cfg.dbfilename.clone()),
+        ("databases", cfg.databases.to_string()),
+        ("appendonly", if cfg.appendonly { "yes" } else { "no" }.to_string()),
+        ("appendfsync", cfg.appendfsync.as_str().to_string()),
+        ("appenddirname", cfg.appenddirname.clone()),
+        ("auto-aof-rewrite-percentage", cfg.auto_aof_rewrite_percentage.to_string()),
+        ("auto-aof-rewrite-min-size", cfg.auto_aof_rewrite_min_size.to_string()),
+        ("save", format_save_params(&cfg.save_params)),
+        ("proto-max-bulk-len", cfg.proto_max_bulk_len.to_string()),
+        ("notify-keyspace-events", format_notify_flags(cfg.notify_keyspace_events)),
+        ("client-output-buffer-limit", {
+            let pubsub = &cfg.client_output_buffer_limit_pubsub;
+            format!("pubsub {} {} {}", pubsub.hard_bytes, pubsub.soft_bytes, pubsub.soft_seconds)
+        }),
+    ]
 }
@@ -1,14 +1,24 @@
 use crate::commands::Context;
-use crate::resp::{encode_bulk_resp_string};
+use crate::resp::{Arg, encode_text_reply};
 use std::io;
+use std::sync::atomic::Ordering;
+use std::time::UNIX_EPOCH;
 
-/// INFO replication
-pub fn cmd_info(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// INFO <replication|persistence|keyspace> -> verbatim text in RESP3
+pub fn cmd_info(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_info] Received INFO command with args: {:?}", args);
 
+    if args.len() == 2 && args[1].eq_ignore_ascii_case("persistence") {
+        return Ok(encode_text_reply(ctx.protocol, &persistence_info(ctx)));
+    }
+
+    if args.len() == 2 && args[1].eq_ignore_ascii_case("keyspace") {
+        return Ok(encode_text_reply(ctx.protocol, &keyspace_info(ctx)));
+    }
+
     if args.len() != 2 || !args[1].eq_ignore_ascii_case("replication") {
         println!("[cmd_info] Invalid or unsupported INFO section");
-        return Ok(encode_bulk_resp_string("")); // Empty response if unsupported
+        return Ok(encode_text_reply(ctx.protocol, "")); // Empty response if unsupported
     }
 
     println!("[cmd_info] Generating replication info…");
@@ -17,10 +27,42 @@ pub fn cmd_info(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         "role:{}\r\nmaster_replid:{}\r\nmaster_repl_offset:{}",
         ctx.cfg.role,
         ctx.cfg.master_replid,
-        ctx.master_repl_offset
+        ctx.master_repl_offset.load(Ordering::Relaxed)
     );
 
     println!("[cmd_info] INFO response:\n{}", info.replace("\r\n", "\\r\\n"));
 
-    Ok(encode_bulk_resp_string(&info))
+    Ok(encode_text_reply(ctx.protocol, &info))
+}
+
+fn persistence_info(ctx: &Context) -> String {
+    let state = ctx.save_state.lock().unwrap();
+    let aof = ctx.aof.as_ref().map(|a| a.status());
+    let last_save = state.last_save.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!(
+        "rdb_changes_since_last_save:{}\r\nrdb_bgsave_in_progress:{}\r\nrdb_last_save_time:{}\r\nrdb_last_bgsave_status:{}\r\naof_enabled:{}\r\naof_rewrite_in_progress:{}\r\naof_last_bgrewrite_status:{}\r\naof_current_size:{}\r\naof_base_size:{}",
+        state.dirty,
+        state.bgsave_in_progress as u8,
+        last_save,
+        if state.last_bgsave_ok { "ok" } else { "err" },
+        ctx.aof.is_some() as u8,
+        aof.as_ref().is_some_and(|a| a.rewrite_in_progress) as u8,
+        if aof.as_ref().is_none_or(|a| a.last_rewrite_ok) { "ok" } else { "err" },
+        aof.as_ref().map_or(0, |a| a.current_size),
+        aof.as_ref().map_or(0, |a| a.base_size),
+    )
+}
+
+/// One `dbN:keys=..,expires=..` line per non-empty database.
+fn keyspace_info(ctx: &Context) -> String {
+    let mut lines = Vec::new();
+    for (index, store) in ctx.dbs.iter().enumerate() {
+        let db = store.lock_all();
+        if db.is_empty() {
+            continue;
+        }
+        let expires = db.values().filter(|(_, expiry)| expiry.is_some()).count();
+        lines.push(format!("db{}:keys={},expires={}", index, db.len(), expires));
+    }
+    lines.join("\r\n")
 }
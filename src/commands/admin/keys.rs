@@ -1,30 +1,35 @@
 use crate::commands::Context;
-use crate::resp::{encode_bulk_resp_string, encode_resp_array, encode_resp_error};
+use crate::glob::glob_match_bytes;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_resp_array, encode_resp_error};
 use std::io;
+use std::time::SystemTime;
 
-/// KEYS "*"
-pub fn cmd_keys(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// KEYS pattern -> every live key matching the glob `pattern`
+pub fn cmd_keys(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_keys] Received KEYS command with args: {:?}", args);
 
     if args.len() != 2 {
         println!("[cmd_keys] Invalid number of arguments.");
-        return Ok(encode_resp_error("usage: KEYS *"));
+        return Ok(encode_resp_error("usage: KEYS <pattern>"));
     }
 
-    if args[1] != "*" {
-        eprintln!("[cmd_keys] Unsupported pattern '{}'. Only '*' is allowed.", args[1]);
-        return Ok(encode_resp_error("only '*' supported"));
-    }
-
-    let map = ctx.store.lock().unwrap();
-    let mut ks: Vec<&String> = map.keys().collect();
+    let pattern = &args[1];
+    let now = SystemTime::now();
+    // every shard at once, so the reply is one point in time
+    let map = ctx.store.lock_all();
+    let mut ks: Vec<&Vec<u8>> = map
+        .iter()
+        .filter(|(_, (_, expiry))| expiry.is_none_or(|t| now < t))
+        .filter(|(k, _)| pattern == "*" || glob_match_bytes(pattern.as_bytes(), k))
+        .map(|(k, _)| k)
+        .collect();
     ks.sort();
 
-    println!("[cmd_keys] Found {} key(s)", ks.len());
+    println!("[cmd_keys] Found {} key(s) matching '{}'", ks.len(), pattern);
     for k in &ks {
-        println!("[cmd_keys] Key: '{}'", k);
+        println!("[cmd_keys] Key: '{}'", String::from_utf8_lossy(k));
     }
 
-    let chunks: Vec<Vec<u8>> = ks.iter().map(|k| encode_bulk_resp_string(k)).collect();
+    let chunks: Vec<Vec<u8>> = ks.iter().map(|k| encode_bulk_resp_bytes(k)).collect();
     Ok(encode_resp_array(&chunks))
 }
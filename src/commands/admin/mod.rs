@@ -1,3 +1,4 @@
 pub mod config;
 pub mod info;
 pub mod keys;
+pub mod shutdown;
@@ -0,0 +1,44 @@
+use crate::commands::Context;
+use crate::persistence::{shutdown, shutdown_blocking};
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// SHUTDOWN [NOSAVE|SAVE]
+/// Only replies if the final save fails; otherwise the process exits.
+pub fn cmd_shutdown(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_shutdown] Received SHUTDOWN command with args: {:?}", args);
+
+    let save = match parse_save(args) {
+        Ok(save) => save,
+        Err(reply) => return Ok(reply),
+    };
+    Ok(shutdown_reply(shutdown(ctx, save)))
+}
+
+/// SHUTDOWN for a client connection, saving off the runtime worker.
+pub async fn cmd_shutdown_blocking(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_shutdown] Received SHUTDOWN command with args: {:?}", args);
+
+    let save = match parse_save(args) {
+        Ok(save) => save,
+        Err(reply) => return Ok(reply),
+    };
+    Ok(shutdown_reply(shutdown_blocking(ctx, save).await))
+}
+
+/// Whether to save: forced either way, or (`None`) if save points are set.
+fn parse_save(args: &[Arg]) -> Result<Option<bool>, Vec<u8>> {
+    match args.get(1).map(|s| s.to_ascii_uppercase()) {
+        None => Ok(None),
+        Some(ref m) if m == "SAVE" => Ok(Some(true)),
+        Some(ref m) if m == "NOSAVE" => Ok(Some(false)),
+        Some(_) => Err(encode_resp_error("usage: SHUTDOWN [NOSAVE|SAVE]")),
+    }
+}
+
+fn shutdown_reply(result: io::Result<()>) -> Vec<u8> {
+    match result {
+        Ok(()) => vec![],
+        Err(_) => encode_resp_error("Errors trying to SHUTDOWN. Check logs."),
+    }
+}
@@ -0,0 +1,38 @@
+use crate::commands::Context;
+use crate::resp::{Arg, encode_resp_error, encode_resp_error_code, encode_simple_resp_string};
+use std::io;
+
+/// AUTH [username] password -> OK
+/// There is no requirepass or ACL: the only user is "default", which needs
+/// no password, so AUTH succeeds for it and fails for anyone else. A bare
+/// password is refused the way Redis refuses it with no password set.
+pub fn cmd_auth(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_auth] Client {} authenticating", ctx.client_id);
+
+    match args.len() {
+        2 => Ok(encode_resp_error(
+            "AUTH <password> called without any password configured for the default user. \
+             Are you sure your configuration is correct?",
+        )),
+        3 if args[1] == "default" => Ok(encode_simple_resp_string("OK")),
+        3 => {
+            println!("[cmd_auth] AUTH as unknown user '{}'", args[1]);
+            Ok(encode_resp_error_code("WRONGPASS", "invalid username-password pair or user is disabled."))
+        }
+        _ => Ok(encode_resp_error("wrong number of arguments for 'auth' command")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+
+    #[test]
+    fn only_the_default_user_authenticates() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd(&mut ctx, &["AUTH", "default", "anything"]), b"+OK\r\n");
+        assert!(run_cmd(&mut ctx, &["AUTH", "alice", "secret"]).starts_with(b"-WRONGPASS"));
+        assert!(run_cmd(&mut ctx, &["AUTH", "secret"]).starts_with(b"-ERR AUTH <password> called without"));
+        assert!(run_cmd(&mut ctx, &["AUTH"]).starts_with(b"-ERR wrong number of arguments"));
+    }
+}
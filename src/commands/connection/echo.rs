@@ -1,9 +1,9 @@
 use crate::commands::Context;
-use crate::resp::{encode_bulk_resp_string, encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_resp_error};
 use std::io;
 
 /// ECHO <msg> -> BulkString(msg)
-pub fn cmd_echo(args: &[String], _ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_echo(args: &[Arg], _ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_echo] Received ECHO command with args: {:?}", args);
 
     if args.len() != 2 {
@@ -12,5 +12,5 @@ pub fn cmd_echo(args: &[String], _ctx: &mut Context) -> io::Result<Vec<u8>> {
     }
 
     println!("[cmd_echo] Echoing message: '{}'", args[1]);
-    Ok(encode_bulk_resp_string(&args[1]))
+    Ok(encode_bulk_resp_bytes(args[1].as_bytes()))
 }
@@ -0,0 +1,149 @@
+use crate::commands::Context;
+use crate::rdb::encode::REDIS_VERSION;
+use crate::resp::{Arg, 
+    encode_bulk_resp_string, encode_int, encode_map_reply, encode_resp_array, encode_resp_error, encode_resp_error_code,
+};
+use crate::role::Role;
+use std::io;
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+/// Switches the connection to RESP `protover` (2 or 3) and replies with the
+/// server's properties: a map in RESP3, a flat array in RESP2
+pub fn cmd_hello(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_hello] Received HELLO command with args: {:?}", args);
+
+    let mut protocol = ctx.protocol;
+    if let Some(raw) = args.get(1) {
+        protocol = match raw.parse::<i64>() {
+            Ok(v @ 2..=3) => v as u8,
+            Ok(_) => return Ok(encode_resp_error_code("NOPROTO", "unsupported protocol version")),
+            Err(_) => return Ok(encode_resp_error("Protocol version is not an integer or out of range")),
+        };
+    }
+
+    let mut name = None;
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].to_uppercase().as_str() {
+            "AUTH" if i + 2 < args.len() => {
+                // no ACLs or requirepass: only the default user exists, and it takes any password
+                if args[i + 1] != "default" {
+                    println!("[cmd_hello] AUTH as unknown user '{}'", args[i + 1]);
+                    return Ok(encode_resp_error_code(
+                        "WRONGPASS",
+                        "invalid username-password pair or user is disabled.",
+                    ));
+                }
+                i += 3;
+            }
+            "SETNAME" if i + 1 < args.len() => {
+                let candidate = &args[i + 1];
+                if candidate.chars().any(|c| !('!'..='~').contains(&c)) {
+                    return Ok(encode_resp_error(
+                        "Client names cannot contain spaces, newlines or special characters.",
+                    ));
+                }
+                name = Some(candidate.to_string());
+                i += 2;
+            }
+            _ => return Ok(encode_resp_error(&format!("Syntax error in HELLO option '{}'", args[i]))),
+        }
+    }
+
+    // only now that every option checked out does the connection change
+    ctx.protocol = protocol;
+    if let Some(outbox) = &ctx.outbox {
+        outbox.set_protocol(protocol);
+    }
+    if name.is_some() {
+        ctx.client_name = name;
+    }
+    println!("[cmd_hello] Client {} now speaks RESP{}", ctx.client_id, protocol);
+
+    let role = match ctx.cfg.role {
+        Role::Master => "master",
+        Role::Slave => "replica",
+    };
+    let properties = [
+        ("server", encode_bulk_resp_string("redis")),
+        ("version", encode_bulk_resp_string(REDIS_VERSION)),
+        ("proto", encode_int(protocol as i64)),
+        ("id", encode_int(ctx.client_id as i64)),
+        ("mode", encode_bulk_resp_string("standalone")),
+        ("role", encode_bulk_resp_string(role)),
+        ("modules", encode_resp_array(&[])),
+    ];
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = properties
+        .into_iter()
+        .map(|(k, v)| (encode_bulk_resp_string(k), v))
+        .collect();
+    Ok(encode_map_reply(protocol, &pairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{assert_pushed, attach_client, run_cmd, test_context};
+
+    const PROPERTIES: &str = "$6\r\nserver\r\n$5\r\nredis\r\n$7\r\nversion\r\n$5\r\n7.2.0\r\n\
+        $5\r\nproto\r\n:3\r\n$2\r\nid\r\n:0\r\n$4\r\nmode\r\n$10\r\nstandalone\r\n\
+        $4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n";
+
+    #[test]
+    fn switches_protocol_and_describes_the_server() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd(&mut ctx, &["HELLO", "3"]), format!("%7\r\n{}", PROPERTIES).into_bytes());
+        assert_eq!(ctx.protocol, 3);
+        assert_eq!(run_cmd(&mut ctx, &["GET", "missing"]), b"_\r\n");
+
+        let reply = run_cmd(&mut ctx, &["HELLO", "2"]);
+        assert!(reply.starts_with(b"*14\r\n$6\r\nserver\r\n"), "{:?}", String::from_utf8_lossy(&reply));
+        assert_eq!(run_cmd(&mut ctx, &["GET", "missing"]), b"$-1\r\n");
+
+        // no version keeps the current protocol
+        assert!(run_cmd(&mut ctx, &["HELLO"]).starts_with(b"*14\r\n"));
+    }
+
+    #[test]
+    fn checks_every_option_before_switching() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd(&mut ctx, &["HELLO", "4"]), b"-NOPROTO unsupported protocol version\r\n");
+        assert!(run_cmd(&mut ctx, &["HELLO", "three"]).starts_with(b"-ERR Protocol version"));
+        assert!(run_cmd(&mut ctx, &["HELLO", "3", "AUTH", "bob", "pw"]).starts_with(b"-WRONGPASS"));
+        assert!(run_cmd(&mut ctx, &["HELLO", "3", "SETNAME", "a b"]).starts_with(b"-ERR Client names"));
+        assert!(run_cmd(&mut ctx, &["HELLO", "3", "SETNAME"]).starts_with(b"-ERR Syntax error"));
+        assert_eq!(ctx.protocol, 2);
+        assert_eq!(ctx.client_name, None);
+
+        run_cmd(&mut ctx, &["HELLO", "3", "AUTH", "default", "any", "SETNAME", "worker-1"]);
+        assert_eq!(ctx.protocol, 3);
+        assert_eq!(ctx.client_name.as_deref(), Some("worker-1"));
+
+        assert_eq!(run_cmd(&mut ctx, &["RESET"]), b"+RESET\r\n");
+        assert_eq!(ctx.protocol, 2);
+        assert_eq!(ctx.client_name, None);
+    }
+
+    #[test]
+    fn resp3_subscribers_get_push_frames() {
+        let mut subscriber = test_context();
+        let mut client = attach_client(&mut subscriber);
+        run_cmd(&mut subscriber, &["HELLO", "3"]);
+        run_cmd(&mut subscriber, &["SUBSCRIBE", "news"]);
+        assert_pushed(&mut client, b">3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n");
+
+        // RESP3 clients may keep running ordinary commands
+        assert_eq!(run_cmd(&mut subscriber, &["SET", "k", "v"]), b"+OK\r\n");
+
+        let mut publisher = subscriber.clone();
+        publisher.outbox = None;
+        publisher.protocol = 2;
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "news", "hi"]), b":1\r\n");
+        assert_pushed(&mut client, b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n");
+
+        assert_eq!(
+            run_cmd(&mut subscriber, &["UNSUBSCRIBE"]),
+            b">3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:0\r\n"
+        );
+        assert_eq!(run_cmd(&mut subscriber, &["UNSUBSCRIBE"]), b">3\r\n$11\r\nunsubscribe\r\n_\r\n:0\r\n");
+    }
+}
@@ -1,2 +1,6 @@
+pub mod auth;
 pub mod echo;
+pub mod hello;
 pub mod ping;
+pub mod reset;
+pub mod select;
@@ -0,0 +1,65 @@
+use crate::commands::pubsub::unsubscribe_all;
+use crate::commands::Context;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// RESET -> RESET; puts the connection back in its initial state: discards
+/// any MULTI, drops every subscription, selects DB 0, forgets the client
+/// name and goes back to RESP2
+pub fn cmd_reset(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_reset] Received RESET command with args: {:?}", args);
+
+    if args.len() != 1 {
+        return Ok(encode_resp_error("wrong number of arguments for 'reset' command"));
+    }
+
+    ctx.in_transaction = false;
+    ctx.queued.clear();
+    ctx.transaction_failed = false;
+
+    let dropped = unsubscribe_all(ctx);
+
+    ctx.db_index = 0;
+    ctx.store = ctx.dbs[0].clone();
+    ctx.client_name = None;
+    ctx.protocol = 2;
+    if let Some(outbox) = &ctx.outbox {
+        outbox.set_protocol(2);
+    }
+
+    println!("[cmd_reset] Connection reset ({} subscription(s) dropped)", dropped);
+    Ok(encode_simple_resp_string("RESET"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{attach_client, run_cmd};
+    use crate::config::ServerConfig;
+    use crate::context::Context;
+    use std::sync::Arc;
+
+    #[test]
+    fn returns_the_connection_to_its_initial_state() {
+        let mut cfg = ServerConfig::for_tests(&std::env::temp_dir());
+        cfg.client_output_buffer_limit_pubsub.hard_bytes = 1 << 20;
+        let databases = cfg.databases;
+        let mut ctx = Context::new(Arc::new(cfg), vec![Default::default(); databases]);
+        let _client = attach_client(&mut ctx);
+        run_cmd(&mut ctx, &["SELECT", "2"]);
+        run_cmd(&mut ctx, &["SUBSCRIBE", "a", "b"]);
+        run_cmd(&mut ctx, &["PSUBSCRIBE", "c*"]);
+        assert_eq!(ctx.outbox.as_ref().unwrap().limit().hard_bytes, 1 << 20);
+        run_cmd(&mut ctx, &["MULTI"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["RESET"]), b"+RESET\r\n");
+        assert!(!ctx.in_transaction);
+        assert_eq!(ctx.subscription_count(), 0);
+        assert!(ctx.pubsub.lock().unwrap().is_empty());
+        assert!(ctx.pattern_pubsub.lock().unwrap().is_empty());
+        assert_eq!(ctx.outbox.as_ref().unwrap().limit().hard_bytes, 0);
+        assert_eq!(ctx.db_index, 0);
+        assert!(std::sync::Arc::ptr_eq(&ctx.store, &ctx.dbs[0]));
+
+        assert!(run_cmd(&mut ctx, &["RESET", "now"]).starts_with(b"-ERR wrong number"));
+    }
+}
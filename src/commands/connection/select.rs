@@ -0,0 +1,51 @@
+use crate::commands::Context;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// SELECT index -> OK; later commands on this connection use that database
+pub fn cmd_select(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_select] Received SELECT command with args: {:?}", args);
+
+    if args.len() != 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'select' command"));
+    }
+
+    let Ok(index) = args[1].parse::<i64>() else {
+        return Ok(encode_resp_error("value is not an integer or out of range"));
+    };
+    if index < 0 || index as usize >= ctx.dbs.len() {
+        return Ok(encode_resp_error("DB index is out of range"));
+    }
+
+    ctx.db_index = index as usize;
+    ctx.store = ctx.dbs[ctx.db_index].clone();
+    println!("[cmd_select] Connection now on DB {}", ctx.db_index);
+    Ok(encode_simple_resp_string("OK"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+
+    #[test]
+    fn each_database_has_its_own_keys() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "zero"]);
+        assert_eq!(run_cmd(&mut ctx, &["SELECT", "2"]), b"+OK\r\n");
+        assert_eq!(ctx.db_index, 2);
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$-1\r\n");
+        run_cmd(&mut ctx, &["SET", "k", "two"]);
+
+        run_cmd(&mut ctx, &["SELECT", "0"]);
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$4\r\nzero\r\n");
+    }
+
+    #[test]
+    fn rejects_indexes_outside_the_configured_databases() {
+        let mut ctx = test_context();
+        assert!(run_cmd(&mut ctx, &["SELECT", "4"]).starts_with(b"-ERR DB index is out of range"));
+        assert!(run_cmd(&mut ctx, &["SELECT", "-1"]).starts_with(b"-ERR DB index is out of range"));
+        assert!(run_cmd(&mut ctx, &["SELECT", "one"]).starts_with(b"-ERR value is not an integer"));
+        assert_eq!(ctx.db_index, 0);
+    }
+}
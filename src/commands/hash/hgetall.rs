@@ -0,0 +1,57 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_map_reply, encode_resp_error};
+use std::io;
+
+/// HGETALL key -> every field and value: a map in RESP3, a flat
+/// [field value ...] array in RESP2
+pub fn cmd_hgetall(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_hgetall] Received HGETALL command with args: {:?}", args);
+
+    if args.len() != 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'hgetall' command"));
+    }
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((Value::Hash(hash), _)) => hash
+            .iter()
+            .map(|(field, value)| (encode_bulk_resp_bytes(field), encode_bulk_resp_bytes(value)))
+            .collect(),
+        Some(_) => {
+            eprintln!("[cmd_hgetall] WRONGTYPE: key '{}' is not a hash", args[1]);
+            return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            Vec::new()
+        }
+    };
+
+    println!("[cmd_hgetall] Returning {} field(s)", pairs.len());
+    Ok(encode_map_reply(ctx.protocol, &pairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+    use crate::rdb::Value;
+    use codecrafters_redis::dict::Dict;
+
+    #[test]
+    fn replies_with_a_map_in_resp3_only() {
+        let mut ctx = test_context();
+        let hash = Dict::from_iter([(b"f".to_vec(), b"v".to_vec())]);
+        ctx.store.lock(b"h").insert(b"h".to_vec(), (Value::Hash(hash), None));
+        ctx.store.lock(b"s").insert(b"s".to_vec(), (Value::String("x".into()), None));
+
+        assert_eq!(run_cmd(&mut ctx, &["HGETALL", "h"]), b"*2\r\n$1\r\nf\r\n$1\r\nv\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["HGETALL", "missing"]), b"*0\r\n");
+        ctx.protocol = 3;
+        assert_eq!(run_cmd(&mut ctx, &["HGETALL", "h"]), b"%1\r\n$1\r\nf\r\n$1\r\nv\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["HGETALL", "missing"]), b"%0\r\n");
+        assert!(run_cmd(&mut ctx, &["HGETALL", "s"]).starts_with(b"-ERR WRONGTYPE"));
+    }
+}
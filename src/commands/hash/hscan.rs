@@ -0,0 +1,48 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::keyspace::scan::{encode_scan_reply, parse_scan_args, scan_collection};
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+/// -> [cursor, [field value ...]]
+pub fn cmd_hscan(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_hscan] Received HSCAN command with args: {:?}", args);
+
+    if args.len() < 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'hscan' command"));
+    }
+    let (cursor, opts) = match parse_scan_args(&args[2..], Some("NOVALUES")) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
+    };
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    let hash = match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((Value::Hash(hash), _)) => hash,
+        Some(_) => {
+            eprintln!("[cmd_hscan] WRONGTYPE: key '{}' is not a hash", args[1]);
+            return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            return Ok(encode_scan_reply(0, &[]));
+        }
+    };
+
+    let (cursor, fields) = scan_collection(cursor, opts.count, |cursor, out| {
+        hash.scan(cursor, |field, value| out.push((field.clone(), value.clone())))
+    });
+    let mut reply = Vec::new();
+    for (field, value) in fields.into_iter().filter(|(f, _)| opts.matches(f)) {
+        reply.push(field);
+        if !opts.no_values {
+            reply.push(value);
+        }
+    }
+
+    println!("[cmd_hscan] Returning {} element(s), next cursor {}", reply.len(), cursor);
+    Ok(encode_scan_reply(cursor, &reply))
+}
@@ -0,0 +1,2 @@
+pub mod hgetall;
+pub mod hscan;
@@ -0,0 +1,31 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_GENERIC};
+use crate::resp::{Arg, encode_int, encode_resp_error};
+use std::io;
+
+/// DEL key [key ...] -> number of keys removed
+pub fn cmd_del(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_del] Received DEL command with args: {:?}", args);
+
+    if args.len() < 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'del' command"));
+    }
+
+    let store = ctx.store.clone();
+    let mut shards = store.lock_keys(args[1..].iter().map(Arg::as_bytes));
+    let mut removed = 0;
+    for key in args[1..].iter().map(Arg::as_bytes) {
+        if live_entry(ctx, ctx.db_index, shards.shard_mut(key), key).is_some() {
+            shards.shard_mut(key).remove(key);
+            notify_keyspace_event(ctx, NOTIFY_GENERIC, "del", key, ctx.db_index);
+            removed += 1;
+        }
+    }
+
+    println!("[cmd_del] Removed {} key(s)", removed);
+    if removed > 0 {
+        record_write(ctx, args)?;
+    }
+    Ok(encode_int(removed))
+}
@@ -0,0 +1,28 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::encode::encode_dump_payload;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_null_reply, encode_resp_error};
+use std::io;
+
+/// DUMP key -> the value in the serialized format RESTORE accepts, or nil
+pub fn cmd_dump(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_dump] Received DUMP command with args: {:?}", args);
+
+    if args.len() != 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'dump' command"));
+    }
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((value, _)) => {
+            let payload = encode_dump_payload(value)?;
+            println!("[cmd_dump] Serialized '{}' into {} bytes", args[1], payload.len());
+            Ok(encode_bulk_resp_bytes(&payload))
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            Ok(encode_null_reply(ctx.protocol))
+        }
+    }
+}
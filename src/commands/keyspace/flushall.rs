@@ -0,0 +1,20 @@
+use crate::commands::keyspace::{flush_db, parse_flush_mode};
+use crate::commands::{record_write, Context};
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// FLUSHALL [ASYNC|SYNC] -> OK; empties every database
+pub fn cmd_flushall(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_flushall] Received FLUSHALL command with args: {:?}", args);
+
+    let Some(lazy) = parse_flush_mode(args) else {
+        return Ok(encode_resp_error("syntax error"));
+    };
+
+    let dbs = ctx.dbs.clone();
+    let mut locked: Vec<_> = dbs.iter().map(|store| store.lock_all()).collect();
+    let removed: usize = locked.iter_mut().map(|shards| flush_db(shards, lazy)).sum();
+    println!("[cmd_flushall] Removed {} key(s) across {} database(s)", removed, ctx.dbs.len());
+    record_write(ctx, args)?;
+    Ok(encode_simple_resp_string("OK"))
+}
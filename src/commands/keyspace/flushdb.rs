@@ -0,0 +1,20 @@
+use crate::commands::keyspace::{flush_db, parse_flush_mode};
+use crate::commands::{record_write, Context};
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// FLUSHDB [ASYNC|SYNC] -> OK; empties the selected database
+pub fn cmd_flushdb(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_flushdb] Received FLUSHDB command with args: {:?}", args);
+
+    let Some(lazy) = parse_flush_mode(args) else {
+        return Ok(encode_resp_error("syntax error"));
+    };
+
+    let store = ctx.store.clone();
+    let mut shards = store.lock_all();
+    let removed = flush_db(&mut shards, lazy);
+    println!("[cmd_flushdb] Removed {} key(s) from DB {}", removed, ctx.db_index);
+    record_write(ctx, args)?;
+    Ok(encode_simple_resp_string("OK"))
+}
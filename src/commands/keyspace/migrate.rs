@@ -0,0 +1,323 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_GENERIC};
+use crate::rdb::encode::encode_dump_payload;
+use crate::resp::{Arg, 
+    encode_bulk_resp_bytes, encode_bulk_resp_string, encode_resp_array, encode_resp_error, encode_resp_error_code,
+    encode_simple_resp_string,
+};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime};
+
+/// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE]
+///         [AUTH password] [AUTH2 username password] [KEYS key [key ...]]
+///
+/// Sends each key to the target as a RESTORE and, unless COPY is given,
+/// deletes the keys the target accepted. The keys are serialized under
+/// their shard locks, which are released for the network round trip; a key
+/// that changed meanwhile (value or expiry) is kept here, since the target
+/// got the old one. The sync barrier is only held shared while the keys are
+/// read and while they are deleted, not while waiting on the target.
+pub fn cmd_migrate(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_migrate] Received MIGRATE command with args: {:?}", args);
+
+    if args.len() < 6 {
+        return Ok(encode_resp_error("wrong number of arguments for 'migrate' command"));
+    }
+
+    let host = &args[1];
+    let Ok(port) = args[2].parse::<u16>() else {
+        return Ok(encode_resp_error("value is not an integer or out of range"));
+    };
+    let Ok(db) = args[4].parse::<usize>() else {
+        return Ok(encode_resp_error("value is not an integer or out of range"));
+    };
+    let timeout = match args[5].parse::<i64>() {
+        Ok(ms) if ms > 0 => Duration::from_millis(ms as u64),
+        Ok(_) => Duration::from_millis(1000),
+        Err(_) => return Ok(encode_resp_error("value is not an integer or out of range")),
+    };
+
+    let mut copy = false;
+    let mut replace = false;
+    let mut auth: Vec<&str> = Vec::new();
+    let mut keys: Vec<Vec<u8>> = Vec::new();
+    let mut i = 6;
+    while i < args.len() {
+        match args[i].to_uppercase().as_str() {
+            "COPY" => copy = true,
+            "REPLACE" => replace = true,
+            "AUTH" if i + 1 < args.len() => {
+                auth = vec![&args[i + 1]];
+                i += 1;
+            }
+            "AUTH2" if i + 2 < args.len() => {
+                auth = vec![&args[i + 1], &args[i + 2]];
+                i += 2;
+            }
+            "KEYS" => {
+                if !args[3].is_empty() {
+                    return Ok(encode_resp_error(
+                        "When using MIGRATE KEYS option, the key argument must be set to the empty string",
+                    ));
+                }
+                keys = args[i + 1..].iter().map(|key| key.as_bytes().to_vec()).collect();
+                break;
+            }
+            _ => return Ok(encode_resp_error("syntax error")),
+        }
+        i += 1;
+    }
+    if keys.is_empty() {
+        if args[3].is_empty() {
+            return Ok(encode_resp_error("syntax error"));
+        }
+        keys.push(args[3].as_bytes().to_vec());
+    }
+
+    let store = ctx.store.clone();
+    // inside EXEC the barrier is already held, exclusively
+    let sync_lock = ctx.sync_lock.clone();
+    let barrier = || ctx.multi_propagated.is_none().then(|| sync_lock.read().unwrap());
+
+    // (key, expiry, remaining TTL in ms or 0, payload) for every key that exists
+    let now = SystemTime::now();
+    let mut batch = Vec::new();
+    {
+        let _shared = barrier();
+        let mut shards = store.lock_keys(keys.iter().map(Vec::as_slice));
+        for key in &keys {
+            if let Some((value, expiry)) = live_entry(ctx, ctx.db_index, shards.shard_mut(key), key) {
+                let ttl = expiry.map_or(0, |t| t.duration_since(now).map_or(1, |d| d.as_millis().max(1)));
+                batch.push((key.clone(), *expiry, ttl, encode_dump_payload(value)?));
+            }
+        }
+    }
+    if batch.is_empty() {
+        println!("[cmd_migrate] None of the keys exist");
+        return Ok(encode_simple_resp_string("NOKEY"));
+    }
+
+    let mut pipeline = Vec::new();
+    let mut preamble = 0;
+    if !auth.is_empty() {
+        let mut cmd = vec![encode_bulk_resp_string("AUTH")];
+        cmd.extend(auth.iter().map(|a| encode_bulk_resp_string(a)));
+        pipeline.extend(encode_resp_array(&cmd));
+        preamble += 1;
+    }
+    if db != 0 {
+        pipeline.extend(encode_resp_array(&[
+            encode_bulk_resp_string("SELECT"),
+            encode_bulk_resp_string(&db.to_string()),
+        ]));
+        preamble += 1;
+    }
+    for (key, _, ttl, payload) in &batch {
+        let mut cmd = vec![
+            encode_bulk_resp_string("RESTORE"),
+            encode_bulk_resp_bytes(key),
+            encode_bulk_resp_string(&ttl.to_string()),
+            encode_bulk_resp_bytes(payload),
+        ];
+        if replace {
+            cmd.push(encode_bulk_resp_string("REPLACE"));
+        }
+        pipeline.extend(encode_resp_array(&cmd));
+    }
+
+    let mut target = match connect(host, port, timeout) {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("[cmd_migrate] Connecting to {}:{} failed: {}", host, port, e);
+            return Ok(encode_resp_error_code("IOERR", "error or timeout connecting to the client"));
+        }
+    };
+    let replies = target
+        .write_all(&pipeline)
+        .and_then(|_| read_replies(&mut BufReader::new(&target), preamble + batch.len()));
+    let replies = match replies {
+        Ok(replies) => replies,
+        Err(e) => {
+            eprintln!("[cmd_migrate] Talking to {}:{} failed: {}", host, port, e);
+            return Ok(encode_resp_error_code("IOERR", "error or timeout reading to target instance"));
+        }
+    };
+
+    if let Some(err) = replies[..preamble].iter().find_map(|r| r.as_ref().err()) {
+        return Ok(encode_resp_error(&format!("Target instance replied with error: {}", err)));
+    }
+
+    let mut first_error = None;
+    let mut moved = Vec::new();
+    for ((key, expiry, _, payload), reply) in batch.iter().zip(&replies[preamble..]) {
+        match reply {
+            Ok(()) => moved.push((key, expiry, payload)),
+            Err(e) => {
+                eprintln!("[cmd_migrate] Target rejected '{}': {}", String::from_utf8_lossy(key), e);
+                first_error.get_or_insert_with(|| e.clone());
+            }
+        }
+    }
+    println!("[cmd_migrate] Target accepted {} of {} key(s)", moved.len(), batch.len());
+
+    if !copy && !moved.is_empty() {
+        let _shared = barrier();
+        let mut shards = store.lock_keys(moved.iter().map(|(key, _, _)| key.as_slice()));
+        let mut del = vec![Arg::from("DEL")];
+        for (key, expiry, payload) in moved {
+            let shard = shards.shard_mut(key);
+            let unchanged = match shard.get(key) {
+                Some((value, now_expiring)) => now_expiring == expiry && encode_dump_payload(value)? == *payload,
+                None => false,
+            };
+            if !unchanged {
+                println!("[cmd_migrate] '{}' changed during the transfer; keeping it", String::from_utf8_lossy(key));
+                continue;
+            }
+            shard.remove(key);
+            notify_keyspace_event(ctx, NOTIFY_GENERIC, "del", key, ctx.db_index);
+            del.push(Arg::from(key.clone()));
+        }
+        if del.len() > 1 {
+            record_write(ctx, &del)?;
+        }
+    }
+
+    match first_error {
+        Some(e) => Ok(encode_resp_error(&format!("Target instance replied with error: {}", e))),
+        None => Ok(encode_simple_resp_string("OK")),
+    }
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for host"))?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+/// Reads `count` status replies: `Ok` for `+...`, `Err(message)` for `-...`.
+fn read_replies<R: BufRead>(reader: &mut R, count: usize) -> io::Result<Vec<Result<(), String>>> {
+    let mut replies = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "target closed the connection"));
+        }
+        let line = line.trim_end();
+        match line.as_bytes().first() {
+            Some(b'+') => replies.push(Ok(())),
+            Some(b'-') => replies.push(Err(line[1..].to_string())),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected reply from target: '{}'", line),
+                ))
+            }
+        }
+    }
+    Ok(replies)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context, test_runtime};
+    use crate::context::Context;
+    use crate::server::serve_client_connection;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Serves `target` on a fresh port, the way the server would, and
+    /// returns the port.
+    fn serve(target: &Context) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        listener.set_nonblocking(true).unwrap();
+        let target = target.clone();
+        test_runtime().spawn(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_client_connection(stream, target.clone()));
+            }
+        });
+        port.to_string()
+    }
+
+    #[test]
+    fn moves_keys_with_their_ttl_and_deletes_them_here() {
+        let mut ctx = test_context();
+        let mut target = test_context();
+        let port = serve(&target);
+        run_cmd(&mut ctx, &["SET", "a", "1", "PX", "100000"]);
+        run_cmd(&mut ctx, &["RPUSH", "b", "x", "y"]);
+
+        let reply = run_cmd(&mut ctx, &["MIGRATE", "127.0.0.1", &port, "", "0", "1000", "KEYS", "a", "b", "gone"]);
+        assert_eq!(reply, b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["KEYS", "*"]), b"*0\r\n");
+        assert_eq!(run_cmd(&mut target, &["GET", "a"]), b"$1\r\n1\r\n");
+        assert_eq!(run_cmd(&mut target, &["LRANGE", "b", "0", "-1"]), b"*2\r\n$1\r\nx\r\n$1\r\ny\r\n");
+        let (_, expiry) = target.store.lock(b"a").get(&b"a"[..]).cloned().unwrap();
+        assert!(expiry.is_some());
+
+        assert_eq!(run_cmd(&mut ctx, &["MIGRATE", "127.0.0.1", &port, "a", "0", "1000"]), b"+NOKEY\r\n");
+    }
+
+    #[test]
+    fn copy_keeps_the_key_and_replace_overwrites_the_target() {
+        let mut ctx = test_context();
+        let mut target = test_context();
+        let port = serve(&target);
+        run_cmd(&mut ctx, &["SET", "k", "new"]);
+        run_cmd(&mut target, &["SET", "k", "old"]);
+
+        let busy = run_cmd(&mut ctx, &["MIGRATE", "127.0.0.1", &port, "k", "0", "1000", "COPY"]);
+        assert_eq!(busy, b"-ERR Target instance replied with error: BUSYKEY Target key name already exists.\r\n");
+        assert_eq!(run_cmd(&mut target, &["GET", "k"]), b"$3\r\nold\r\n");
+
+        assert_eq!(run_cmd(&mut ctx, &["MIGRATE", "127.0.0.1", &port, "k", "0", "1000", "COPY", "REPLACE"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut target, &["GET", "k"]), b"$3\r\nnew\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$3\r\nnew\r\n");
+    }
+
+    #[test]
+    fn the_barrier_is_free_during_the_transfer_and_a_changed_expiry_keeps_the_key() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "v", "PX", "100000"]);
+
+        // a target that only answers once told to
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let (received_tx, received) = mpsc::channel();
+        let (go, go_rx) = mpsc::channel::<()>();
+        let fake = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            assert!(stream.read(&mut buf).unwrap() > 0);
+            received_tx.send(()).unwrap();
+            go_rx.recv().unwrap();
+            stream.write_all(b"+OK\r\n").unwrap();
+        });
+
+        let mut migrating = ctx.clone();
+        let migrate = thread::spawn(move || run_cmd(&mut migrating, &["MIGRATE", "127.0.0.1", &port, "k", "0", "5000"]));
+        received.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // the same value, but no longer expiring
+        assert!(ctx.sync_lock.try_write().is_ok());
+        run_cmd(&mut ctx, &["SET", "k", "v"]);
+        go.send(()).unwrap();
+
+        assert_eq!(migrate.join().unwrap(), b"+OK\r\n");
+        fake.join().unwrap();
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$1\r\nv\r\n");
+    }
+}
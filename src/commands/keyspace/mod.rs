@@ -0,0 +1,101 @@
+pub mod del;
+pub mod dump;
+pub mod flushall;
+pub mod flushdb;
+pub mod migrate;
+pub mod movee;
+pub mod restore;
+pub mod scan;
+pub mod swapdb;
+
+use crate::context::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED};
+use crate::rdb::{Db, Value};
+use crate::store::Shards;
+use std::time::SystemTime;
+use std::thread;
+use crate::resp::Arg;
+
+/// Looks up `key` in DB `db_index`, first dropping it (and announcing the
+/// expiry) if it has expired.
+pub(crate) fn live_entry<'a>(
+    ctx: &Context,
+    db_index: usize,
+    db: &'a mut Db,
+    key: &[u8],
+) -> Option<&'a (Value, Option<SystemTime>)> {
+    if db.get(key).is_some_and(|(_, expiry)| expiry.is_some_and(|t| SystemTime::now() >= t)) {
+        println!("[keyspace::live_entry] key expired: {}", String::from_utf8_lossy(key));
+        db.remove(key);
+        notify_keyspace_event(ctx, NOTIFY_EXPIRED, "expired", key, db_index);
+    }
+    db.get(key)
+}
+
+/// Parses a DB index argument, `None` if it isn't one of this server's DBs.
+pub(crate) fn parse_db_index(arg: &str, ctx: &Context) -> Option<usize> {
+    arg.parse::<usize>().ok().filter(|&index| index < ctx.dbs.len())
+}
+
+/// FLUSHDB / FLUSHALL modifier: `Some(true)` for ASYNC, `Some(false)` for
+/// SYNC or none, `None` for anything else.
+pub(crate) fn parse_flush_mode(args: &[Arg]) -> Option<bool> {
+    match args.get(1).map(|a| a.to_uppercase()) {
+        None => Some(false),
+        Some(mode) if args.len() == 2 && mode == "SYNC" => Some(false),
+        Some(mode) if args.len() == 2 && mode == "ASYNC" => Some(true),
+        _ => None,
+    }
+}
+
+/// Empties the locked shards of `db`; with `lazy`, the old contents are
+/// freed on another thread. Returns how many keys were removed.
+pub(crate) fn flush_db(db: &mut Shards, lazy: bool) -> usize {
+    let old = db.take();
+    let removed = old.iter().map(Db::len).sum();
+    if lazy {
+        thread::spawn(move || drop(old));
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{run_cmd, test_context};
+
+    #[test]
+    fn flush_modes() {
+        let args = |a: &[&str]| a.iter().map(|&s| Arg::from(s)).collect::<Vec<_>>();
+        assert_eq!(parse_flush_mode(&args(&["FLUSHDB"])), Some(false));
+        assert_eq!(parse_flush_mode(&args(&["FLUSHDB", "sync"])), Some(false));
+        assert_eq!(parse_flush_mode(&args(&["FLUSHDB", "ASYNC"])), Some(true));
+        assert_eq!(parse_flush_mode(&args(&["FLUSHDB", "later"])), None);
+        assert_eq!(parse_flush_mode(&args(&["FLUSHDB", "ASYNC", "SYNC"])), None);
+    }
+
+    #[test]
+    fn flushdb_empties_only_the_selected_database() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "a", "1"]);
+        run_cmd(&mut ctx, &["SELECT", "1"]);
+        run_cmd(&mut ctx, &["SET", "b", "2"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["FLUSHDB", "ASYNC"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "b"]), b"$-1\r\n");
+        run_cmd(&mut ctx, &["SELECT", "0"]);
+        assert_eq!(run_cmd(&mut ctx, &["GET", "a"]), b"$1\r\n1\r\n");
+        assert!(run_cmd(&mut ctx, &["FLUSHDB", "now"]).starts_with(b"-ERR syntax error"));
+    }
+
+    #[test]
+    fn flushall_empties_every_database() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "a", "1"]);
+        run_cmd(&mut ctx, &["SELECT", "2"]);
+        run_cmd(&mut ctx, &["SET", "b", "2"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["FLUSHALL"]), b"+OK\r\n");
+        assert!(ctx.dbs.iter().all(|db| db.lock_all().is_empty()));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::commands::keyspace::{live_entry, parse_db_index};
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_GENERIC};
+use crate::resp::{Arg, encode_int, encode_resp_error};
+use std::io;
+
+/// MOVE key db -> 1 if moved, 0 if the key is missing here or already exists there
+pub fn cmd_move(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_move] Received MOVE command with args: {:?}", args);
+
+    if args.len() != 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'move' command"));
+    }
+    let Some(target) = parse_db_index(&args[2], ctx) else {
+        return Ok(encode_resp_error("DB index is out of range"));
+    };
+    if target == ctx.db_index {
+        return Ok(encode_resp_error("source and destination objects are the same"));
+    }
+
+    // lock in index order, as SWAPDB does, so the two can't deadlock
+    let key = args[1].as_bytes();
+    let (source, dest) = (ctx.store.clone(), ctx.dbs[target].clone());
+    let (mut src, mut dst) = if ctx.db_index < target {
+        let src = source.lock(key);
+        (src, dest.lock(key))
+    } else {
+        let dst = dest.lock(key);
+        (source.lock(key), dst)
+    };
+
+    if live_entry(ctx, ctx.db_index, &mut src, key).is_none() || live_entry(ctx, target, &mut dst, key).is_some() {
+        println!("[cmd_move] '{}' not moved to DB {}", args[1], target);
+        return Ok(encode_int(0));
+    }
+
+    let entry = src.remove(key).unwrap();
+    dst.insert(key.to_vec(), entry);
+    notify_keyspace_event(ctx, NOTIFY_GENERIC, "move_from", key, ctx.db_index);
+    notify_keyspace_event(ctx, NOTIFY_GENERIC, "move_to", key, target);
+    println!("[cmd_move] Moved '{}' from DB {} to DB {}", args[1], ctx.db_index, target);
+    record_write(ctx, args)?;
+    Ok(encode_int(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+
+    #[test]
+    fn moves_a_key_to_another_database() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "v"]);
+        assert_eq!(run_cmd(&mut ctx, &["MOVE", "k", "1"]), b":1\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$-1\r\n");
+
+        run_cmd(&mut ctx, &["SELECT", "1"]);
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$1\r\nv\r\n");
+        // and back again, locking the databases the other way round
+        assert_eq!(run_cmd(&mut ctx, &["MOVE", "k", "0"]), b":1\r\n");
+    }
+
+    #[test]
+    fn never_overwrites_or_moves_a_missing_key() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "here"]);
+        run_cmd(&mut ctx, &["SELECT", "1"]);
+        run_cmd(&mut ctx, &["SET", "k", "there"]);
+        run_cmd(&mut ctx, &["SELECT", "0"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["MOVE", "k", "1"]), b":0\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$4\r\nhere\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["MOVE", "missing", "1"]), b":0\r\n");
+
+        assert!(run_cmd(&mut ctx, &["MOVE", "k", "0"]).starts_with(b"-ERR source and destination"));
+        assert!(run_cmd(&mut ctx, &["MOVE", "k", "9"]).starts_with(b"-ERR DB index is out of range"));
+    }
+}
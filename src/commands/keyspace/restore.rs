@@ -0,0 +1,155 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_GENERIC, NOTIFY_NEW};
+use crate::rdb::decode::decode_dump_payload;
+use crate::resp::{Arg, encode_resp_error, encode_resp_error_code, encode_simple_resp_string};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+pub fn cmd_restore(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_restore] Received RESTORE command with args: {:?}", &args[..args.len().min(3)]);
+
+    if args.len() < 4 {
+        return Ok(encode_resp_error("wrong number of arguments for 'restore' command"));
+    }
+
+    let key = args[1].as_bytes();
+    let ttl = match args[2].parse::<i64>() {
+        Ok(ttl) if ttl >= 0 => ttl as u64,
+        Ok(_) => return Ok(encode_resp_error("Invalid TTL value, must be >= 0")),
+        Err(_) => return Ok(encode_resp_error("value is not an integer or out of range")),
+    };
+
+    let mut replace = false;
+    let mut absttl = false;
+    let mut i = 4;
+    while i < args.len() {
+        match args[i].to_uppercase().as_str() {
+            "REPLACE" => replace = true,
+            "ABSTTL" => absttl = true,
+            // eviction hints: validated, but there is no LRU/LFU state to restore them into
+            "IDLETIME" if i + 1 < args.len() => {
+                if !args[i + 1].parse::<i64>().is_ok_and(|idle| idle >= 0) {
+                    return Ok(encode_resp_error("Invalid IDLETIME value, must be >= 0"));
+                }
+                i += 1;
+            }
+            "FREQ" if i + 1 < args.len() => {
+                if !args[i + 1].parse::<i64>().is_ok_and(|freq| (0..=255).contains(&freq)) {
+                    return Ok(encode_resp_error("Invalid FREQ value, must be >= 0 and <= 255"));
+                }
+                i += 1;
+            }
+            _ => return Ok(encode_resp_error("syntax error")),
+        }
+        i += 1;
+    }
+
+    let store = ctx.store.clone();
+    let mut map = store.lock(key);
+    let exists = live_entry(ctx, ctx.db_index, &mut map, key).is_some();
+    if !replace && exists {
+        println!("[cmd_restore] '{}' already exists and REPLACE not given", args[1]);
+        return Ok(encode_resp_error_code("BUSYKEY", "Target key name already exists."));
+    }
+
+    let value = match decode_dump_payload(args[3].as_bytes()) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("[cmd_restore] Rejected payload for '{}': {}", args[1], e);
+            return Ok(encode_resp_error("DUMP payload version or checksum are wrong"));
+        }
+    };
+
+    let expiry = match (ttl, absttl) {
+        (0, _) => None,
+        (ms, true) => Some(UNIX_EPOCH + Duration::from_millis(ms)),
+        (ms, false) => Some(SystemTime::now() + Duration::from_millis(ms)),
+    };
+    if expiry.is_some_and(|t| t <= SystemTime::now()) {
+        // already expired: the key simply ends up absent
+        println!("[cmd_restore] '{}' restored with a TTL in the past; dropping it", args[1]);
+        if map.remove(key).is_some() {
+            notify_keyspace_event(ctx, NOTIFY_GENERIC, "del", key, ctx.db_index);
+            record_write(ctx, &["DEL".into(), args[1].clone()])?;
+        }
+        return Ok(encode_simple_resp_string("OK"));
+    }
+
+    println!("[cmd_restore] Restored '{}' as a {}", args[1], value.type_name());
+    map.insert(key.to_vec(), (value, expiry));
+    if !exists {
+        notify_keyspace_event(ctx, NOTIFY_NEW, "new", key, ctx.db_index);
+    }
+    notify_keyspace_event(ctx, NOTIFY_GENERIC, "restore", key, ctx.db_index);
+    record_write(ctx, args)?;
+    Ok(encode_simple_resp_string("OK"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, run_cmd_bytes, test_context};
+    use crate::resp::parse_resp_array;
+
+    /// The payload inside a DUMP reply, as the parser hands it to RESTORE.
+    fn dumped(ctx: &mut crate::context::Context, key: &str) -> Vec<u8> {
+        let reply = run_cmd(ctx, &["DUMP", key]);
+        let mut framed = b"*1\r\n".to_vec();
+        framed.extend_from_slice(&reply);
+        parse_resp_array(&mut &framed[..]).unwrap().unwrap().remove(0).into_bytes()
+    }
+
+    #[test]
+    fn dump_then_restore_recreates_the_value() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["RPUSH", "src", "a", "b", "c"]);
+        let payload = dumped(&mut ctx, "src");
+
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"dst", b"0", &payload]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LRANGE", "dst", "0", "-1"]), b"*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["DUMP", "missing"]), b"$-1\r\n");
+    }
+
+    #[test]
+    fn restore_refuses_to_overwrite_without_replace() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "old"]);
+        run_cmd(&mut ctx, &["SET", "other", "new"]);
+        let payload = dumped(&mut ctx, "other");
+
+        let busy = run_cmd_bytes(&mut ctx, &[b"RESTORE", b"k", b"0", &payload]);
+        assert_eq!(busy, b"-BUSYKEY Target key name already exists.\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$3\r\nold\r\n");
+
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"k", b"0", &payload, b"REPLACE"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$3\r\nnew\r\n");
+    }
+
+    #[test]
+    fn restore_validates_its_arguments_and_payload() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "v"]);
+        let payload = dumped(&mut ctx, "k");
+
+        assert!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"x", b"-1", &payload]).starts_with(b"-ERR Invalid TTL"));
+        assert!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"x", b"0", &payload, b"FREQ", b"300"]).starts_with(b"-ERR Invalid FREQ"));
+        assert!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"x", b"0", &payload, b"BOGUS"]).starts_with(b"-ERR syntax"));
+        assert!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"x", b"0", b"not a payload"]).starts_with(b"-ERR DUMP payload"));
+        assert_eq!(run_cmd(&mut ctx, &["GET", "x"]), b"$-1\r\n");
+    }
+
+    #[test]
+    fn restore_ttls_are_relative_unless_absttl() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "k", "v"]);
+        let payload = dumped(&mut ctx, "k");
+
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"live", b"60000", &payload]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "live"]), b"$1\r\nv\r\n");
+
+        // an absolute time in 1970 is already past, so the key never appears
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"RESTORE", b"gone", b"1000", &payload, b"ABSTTL"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "gone"]), b"$-1\r\n");
+    }
+}
@@ -0,0 +1,271 @@
+use crate::commands::Context;
+use crate::commands::keyspace::live_entry;
+use crate::glob::glob_match_bytes;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_bulk_resp_string, encode_resp_array, encode_resp_error};
+use std::io;
+use std::time::SystemTime;
+
+/// Options shared by SCAN, HSCAN, SSCAN and ZSCAN.
+pub(crate) struct ScanOptions {
+    pub pattern: Option<Vec<u8>>,
+    pub count: usize,
+    /// SCAN only: keep keys of this type.
+    pub type_name: Option<String>,
+    /// HSCAN only: return fields without their values.
+    pub no_values: bool,
+}
+
+impl ScanOptions {
+    pub fn matches(&self, element: &[u8]) -> bool {
+        self.pattern.as_deref().is_none_or(|pattern| glob_match_bytes(pattern, element))
+    }
+}
+
+/// Parses `cursor [MATCH pattern] [COUNT count] ...` starting at `args[0]`.
+/// `extra` names the one command-specific option allowed ("TYPE" or
+/// "NOVALUES"), if any. Errors are ready-to-send replies.
+pub(crate) fn parse_scan_args(args: &[Arg], extra: Option<&str>) -> Result<(u64, ScanOptions), Vec<u8>> {
+    let Ok(cursor) = args[0].parse::<u64>() else {
+        return Err(encode_resp_error("invalid cursor"));
+    };
+
+    let mut opts = ScanOptions { pattern: None, count: 10, type_name: None, no_values: false };
+    let mut i = 1;
+    while i < args.len() {
+        let option = args[i].to_uppercase();
+        let value = args.get(i + 1);
+        match (option.as_str(), value) {
+            ("MATCH", Some(pattern)) => {
+                // "*" matches everything; skip the matcher entirely
+                opts.pattern = (pattern != "*").then(|| pattern.as_bytes().to_vec());
+                i += 2;
+            }
+            ("COUNT", Some(count)) => {
+                match count.parse::<i64>() {
+                    Ok(n) if n >= 1 => opts.count = n as usize,
+                    Ok(_) => return Err(encode_resp_error("syntax error")),
+                    Err(_) => return Err(encode_resp_error("value is not an integer or out of range")),
+                }
+                i += 2;
+            }
+            ("TYPE", Some(type_name)) if extra == Some("TYPE") => {
+                opts.type_name = Some(type_name.to_lowercase());
+                i += 2;
+            }
+            ("NOVALUES", _) if extra == Some("NOVALUES") => {
+                opts.no_values = true;
+                i += 1;
+            }
+            _ => return Err(encode_resp_error("syntax error")),
+        }
+    }
+    Ok((cursor, opts))
+}
+
+/// `[cursor, [element ...]]`
+pub(crate) fn encode_scan_reply(cursor: u64, elements: &[Vec<u8>]) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = elements.iter().map(|e| encode_bulk_resp_bytes(e)).collect();
+    encode_resp_array(&[encode_bulk_resp_string(&cursor.to_string()), encode_resp_array(&items)])
+}
+
+/// One HSCAN/SSCAN/ZSCAN step: `step` walks one bucket of the collection's
+/// `Dict` from the cursor it is given, pushing what it finds, and returns
+/// the next cursor. Buckets are walked until `count` elements were visited
+/// or the walk is over, and at most 10 per COUNT, as for SCAN.
+///
+/// Returns the next cursor and the visited elements in cursor order.
+pub(crate) fn scan_collection<T, F>(cursor: u64, count: usize, mut step: F) -> (u64, Vec<T>)
+where
+    F: FnMut(u64, &mut Vec<T>) -> u64,
+{
+    let mut visited = Vec::new();
+    let mut cursor = cursor;
+    let mut budget = count.saturating_mul(10);
+    loop {
+        cursor = step(cursor, &mut visited);
+        budget -= 1;
+        if cursor == 0 || visited.len() >= count || budget == 0 {
+            return (cursor, visited);
+        }
+    }
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+///
+/// Walks the selected DB a few buckets at a time, shard by shard. A key
+/// present for the whole iteration is returned at least once; keys may be
+/// returned twice.
+pub fn cmd_scan(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_scan] Received SCAN command with args: {:?}", args);
+
+    if args.len() < 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'scan' command"));
+    }
+    let (mut cursor, opts) = match parse_scan_args(&args[1..], Some("TYPE")) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
+    };
+
+    let now = SystemTime::now();
+    let mut visited = 0;
+    let mut keys = Vec::new();
+    let mut expired = Vec::new();
+
+    // bound the work on sparse tables the way Redis does: at most 10 empty buckets per COUNT
+    let mut budget = opts.count.saturating_mul(10);
+    loop {
+        cursor = ctx.store.scan(cursor, |key, (value, expiry)| {
+            visited += 1;
+            if expiry.is_some_and(|t| now >= t) {
+                expired.push(key.clone());
+            } else if opts.type_name.as_deref().is_none_or(|t| t == value.type_name()) && opts.matches(key) {
+                keys.push(key.clone());
+            }
+        });
+        budget -= 1;
+        if cursor == 0 || visited >= opts.count || budget == 0 {
+            break;
+        }
+    }
+
+    // the shard was let go in between: live_entry checks the expiry again
+    for key in &expired {
+        live_entry(ctx, ctx.db_index, &mut ctx.store.lock(key), key);
+    }
+
+    println!("[cmd_scan] Returning {} key(s), next cursor {}", keys.len(), cursor);
+    Ok(encode_scan_reply(cursor, &keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{run_cmd, test_context};
+    use codecrafters_redis::dict::Dict;
+    use crate::rdb::{SortedSet, Value};
+    use std::collections::HashSet;
+
+    /// Splits a `[cursor, [element ...]]` reply.
+    fn scan_reply(reply: &[u8]) -> (u64, Vec<String>) {
+        let text = std::str::from_utf8(reply).unwrap();
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        let elements = lines[5..].iter().step_by(2).take_while(|l| !l.is_empty()).map(|l| l.to_string());
+        (lines[2].parse().unwrap(), elements.collect())
+    }
+
+    /// Runs `args` (with the cursor at `cursor_at`) until the cursor is back to 0.
+    fn scan_all(ctx: &mut Context, args: &[&str], cursor_at: usize) -> Vec<String> {
+        let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut seen = Vec::new();
+        loop {
+            let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+            let (cursor, elements) = scan_reply(&run_cmd(ctx, &arg_refs));
+            seen.extend(elements);
+            if cursor == 0 {
+                return seen;
+            }
+            args[cursor_at] = cursor.to_string();
+        }
+    }
+
+    fn args(a: &[&str]) -> Vec<Arg> {
+        a.iter().map(|&s| s.into()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let (cursor, opts) = parse_scan_args(&args(&["17", "match", "k*", "COUNT", "3", "TYPE", "List"]), Some("TYPE")).ok().unwrap();
+        assert_eq!(cursor, 17);
+        assert_eq!(opts.pattern.as_deref(), Some(&b"k*"[..]));
+        assert_eq!(opts.count, 3);
+        assert_eq!(opts.type_name.as_deref(), Some("list"));
+
+        let (_, opts) = parse_scan_args(&args(&["0", "MATCH", "*", "NOVALUES"]), Some("NOVALUES")).ok().unwrap();
+        assert!(opts.pattern.is_none() && opts.no_values);
+
+        assert!(parse_scan_args(&args(&["-1"]), None).is_err());
+        assert!(parse_scan_args(&args(&["0", "COUNT", "0"]), None).is_err());
+        assert!(parse_scan_args(&args(&["0", "COUNT"]), None).is_err());
+        assert!(parse_scan_args(&args(&["0", "NOVALUES"]), Some("TYPE")).is_err());
+    }
+
+    #[test]
+    fn scan_walks_every_key_with_match_and_type() {
+        let mut ctx = test_context();
+        for i in 0..100 {
+            run_cmd(&mut ctx, &["SET", &format!("s{}", i), "v"]);
+        }
+        run_cmd(&mut ctx, &["RPUSH", "list", "x"]);
+
+        let all: HashSet<String> = scan_all(&mut ctx, &["SCAN", "0", "COUNT", "7"], 1).into_iter().collect();
+        assert_eq!(all.len(), 101);
+
+        let lists = scan_all(&mut ctx, &["SCAN", "0", "TYPE", "list"], 1);
+        assert_eq!(lists, ["list"]);
+
+        let matched: HashSet<String> = scan_all(&mut ctx, &["SCAN", "0", "MATCH", "s9*"], 1).into_iter().collect();
+        assert_eq!(matched.len(), 11);
+    }
+
+    #[test]
+    fn collection_scans_return_every_element() {
+        let mut ctx = test_context();
+        let mut zset = SortedSet::default();
+        for i in 0..50 {
+            zset.insert(format!("m{}", i).into_bytes(), i as f64);
+        }
+        let hash = (0..50).map(|i| (format!("f{}", i).into_bytes(), i.to_string().into_bytes())).collect();
+        ctx.store.lock(b"h").insert(b"h".to_vec(), (Value::Hash(hash), None));
+        let set = (0..50).map(|i| (format!("m{}", i).into_bytes(), ())).collect();
+        ctx.store.lock(b"s").insert(b"s".to_vec(), (Value::Set(set), None));
+        ctx.store.lock(b"z").insert(b"z".to_vec(), (Value::ZSet(zset), None));
+
+        let hash = scan_all(&mut ctx, &["HSCAN", "h", "0", "COUNT", "5"], 2);
+        assert_eq!(hash.len(), 100);
+        assert_eq!(scan_all(&mut ctx, &["HSCAN", "h", "0", "NOVALUES"], 2).len(), 50);
+        assert_eq!(scan_all(&mut ctx, &["SSCAN", "s", "0", "COUNT", "5"], 2).into_iter().collect::<HashSet<_>>().len(), 50);
+        assert_eq!(scan_all(&mut ctx, &["ZSCAN", "z", "0", "MATCH", "m1*"], 2).len(), 22);
+
+        assert_eq!(scan_all(&mut ctx, &["SSCAN", "missing", "0"], 2), Vec::<String>::new());
+        assert!(run_cmd(&mut ctx, &["HSCAN", "s", "0"]).starts_with(b"-ERR WRONGTYPE"));
+    }
+
+    #[test]
+    fn huge_counts_walk_everything_in_one_call() {
+        let mut ctx = test_context();
+        for i in 0..20 {
+            run_cmd(&mut ctx, &["SET", &format!("k{}", i), "v"]);
+        }
+        let set = (0..20).map(|i| (format!("m{}", i).into_bytes(), ())).collect();
+        ctx.store.lock(b"s").insert(b"s".to_vec(), (Value::Set(set), None));
+
+        let huge = i64::MAX.to_string();
+        let (cursor, keys) = scan_reply(&run_cmd(&mut ctx, &["SCAN", "0", "COUNT", &huge]));
+        assert_eq!((cursor, keys.len()), (0, 21));
+        let (cursor, members) = scan_reply(&run_cmd(&mut ctx, &["SSCAN", "s", "0", "COUNT", &huge]));
+        assert_eq!((cursor, members.len()), (0, 20));
+    }
+
+    #[test]
+    fn collection_cursor_survives_growth() {
+        let items: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut grown: Dict<String, ()> = items.iter().map(|i| (i.clone(), ())).collect();
+        loop {
+            let (next, batch) = scan_collection(cursor, 5, |c, out| grown.scan(c, |s, _| out.push(s.clone())));
+            seen.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+            if grown.len() < 200 {
+                let round = grown.len();
+                for i in 0..20 {
+                    grown.insert(format!("new{}-{}", round, i), ());
+                }
+            }
+        }
+        assert!(items.iter().all(|i| seen.contains(i)));
+    }
+}
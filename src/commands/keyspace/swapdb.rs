@@ -0,0 +1,53 @@
+use crate::commands::keyspace::parse_db_index;
+use crate::commands::{record_write, Context};
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// SWAPDB index1 index2 -> OK; every connection sees the two databases swapped
+pub fn cmd_swapdb(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_swapdb] Received SWAPDB command with args: {:?}", args);
+
+    if args.len() != 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'swapdb' command"));
+    }
+    let Some(first) = parse_db_index(&args[1], ctx) else {
+        return Ok(encode_resp_error("invalid first DB index"));
+    };
+    let Some(second) = parse_db_index(&args[2], ctx) else {
+        return Ok(encode_resp_error("invalid second DB index"));
+    };
+
+    let dbs = ctx.dbs.clone();
+    let (low, high) = (first.min(second), first.max(second));
+    let mut a = dbs[low].lock_all();
+    if first != second {
+        let mut b = dbs[high].lock_all();
+        a.swap(&mut b);
+    }
+
+    println!("[cmd_swapdb] Swapped DB {} and DB {}", first, second);
+    record_write(ctx, args)?;
+    Ok(encode_simple_resp_string("OK"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+
+    #[test]
+    fn swaps_contents_for_every_connection() {
+        let mut ctx = test_context();
+        let mut other = ctx.clone();
+        run_cmd(&mut ctx, &["SET", "k", "zero"]);
+        run_cmd(&mut ctx, &["SELECT", "3"]);
+        run_cmd(&mut ctx, &["SET", "k", "three"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["SWAPDB", "3", "0"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$4\r\nzero\r\n");
+        assert_eq!(run_cmd(&mut other, &["GET", "k"]), b"$5\r\nthree\r\n");
+
+        assert_eq!(run_cmd(&mut ctx, &["SWAPDB", "1", "1"]), b"+OK\r\n");
+        assert!(run_cmd(&mut ctx, &["SWAPDB", "0", "4"]).starts_with(b"-ERR invalid second DB index"));
+        assert!(run_cmd(&mut ctx, &["SWAPDB", "x", "0"]).starts_with(b"-ERR invalid first DB index"));
+    }
+}
@@ -1,101 +1,138 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::list::{propagate_pop, remove_if_empty};
 use crate::commands::Context;
-use crate::rdb::Value;
-use crate::resp::encode_resp_error;
-use std::sync::Arc;
+use crate::context::BlockedClient;
+use crate::notify::{notify_keyspace_event, NOTIFY_LIST};
+use crate::rdb::{Db, Value};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_null_reply, encode_resp_array, encode_resp_error};
+use std::io;
 use std::time::Duration;
-use std::{io, thread};
-use std::io::Write;
+use tokio::sync::oneshot;
 
-pub fn cmd_blpop(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
-    println!("[cmd_blpop] Received BLPOP command with args: {:?}", args);
-
-    if args.len() != 3 {
-        println!("[cmd_blpop] Invalid argument count.");
-        return Ok(encode_resp_error("usage: BLPOP <key> <timeout>"));
-    }
+/// BLPOP as run inside MULTI/EXEC: there's no waiting, so an empty list is
+/// a timeout.
+pub fn cmd_blpop(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let (key, _) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
+    };
 
-    let key = args[1].clone();
-    let timeout_secs: f64 = match args[2].parse() {
-        Ok(t) => t,
-        Err(_) => {
-            eprintln!("[cmd_blpop] Invalid timeout '{}'", args[2]);
-            return Ok(encode_resp_error("ERR timeout must be a float"));
+    let store = ctx.store.clone();
+    let mut shard = store.lock(key.as_bytes());
+    Ok(match pop_front(ctx, &mut shard, key) {
+        Some(val) => {
+            propagate_pop(ctx, key.as_bytes())?;
+            pop_reply(key, &val)
         }
+        None => encode_null_reply(ctx.protocol),
+    })
+}
+
+/// BLPOP <key> <timeout> from a connection: pops right away if it can,
+/// otherwise waits up to `timeout` seconds (0: forever) for a push to hand
+/// it an element. The connection awaits it, so commands pipelined after
+/// BLPOP only run once it has been served.
+pub async fn cmd_blpop_blocking(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let (key, timeout_secs) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
     };
+    let client_id = ctx.client_id;
+    let sync_lock = ctx.sync_lock.clone();
+    let blocked_on = (ctx.db_index, key.as_bytes().to_vec());
 
-    println!("[cmd_blpop] Attempting immediate pop from '{}'", key);
-    let mut store = ctx.store.lock().unwrap();
-    if let Some((Value::List(ref mut list), _)) = store.get_mut(&key) {
-        if !list.is_empty() {
-            let val = list.remove(0);
-            println!("[cmd_blpop] Immediate pop successful. Returning value '{}'", val);
+    let mut waiting = {
+        // shared, like any other command: EXEC's writes are seen all or none
+        let _shared = sync_lock.read().unwrap();
+        let store = ctx.store.clone();
+        let mut shard = store.lock(key.as_bytes());
+        if let Some(val) = pop_front(ctx, &mut shard, key) {
+            propagate_pop(ctx, key.as_bytes())?;
+            return Ok(pop_reply(key, &val));
+        }
+
+        // Register before releasing the store, so no push can slip in between
+        let (popped, waiting) = oneshot::channel();
+        ctx.blocking.lock().unwrap().entry(blocked_on.clone()).or_default().push(BlockedClient { client_id, popped });
+        println!("[cmd_blpop] Client {} added to blocking list for key '{}'", client_id, key);
+        waiting
+    };
 
-            let response = format!(
-                "*2\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
-                key.len(),
-                key,
-                val.len(),
-                val
-            );
-            return Ok(response.into_bytes());
-        } else {
-            println!("[cmd_blpop] List at '{}' is empty. Blocking client.", key);
+    let received = if timeout_secs > 0.0 {
+        match tokio::time::timeout(Duration::from_secs_f64(timeout_secs), &mut waiting).await {
+            Ok(popped) => popped.ok(),
+            Err(_) => None,
         }
     } else {
-        println!("[cmd_blpop] Key '{}' does not exist or is not a list. Blocking client.", key);
-    }
-    drop(store);
+        (&mut waiting).await.ok()
+    };
 
-    // Grab stream from context (you must have saved it per client previously)
-    let stream = match &ctx.this_client {
-        Some(s) => match s.try_clone() {
-            Ok(clone) => clone,
-            Err(e) => {
-                eprintln!("[cmd_blpop] Failed to clone TcpStream: {}", e);
-                return Ok(vec![]);
-            }
-        },
+    let val = match received {
+        Some(val) => Some(val),
         None => {
-            eprintln!("[cmd_blpop] No stream found in context");
-            return Ok(vec![]);
+            println!("[cmd_blpop] Timeout triggered for key '{}'", key);
+            let mut blockers = ctx.blocking.lock().unwrap();
+            if let Some(waiters) = blockers.get_mut(&blocked_on) {
+                waiters.retain(|w| w.client_id != client_id);
+                if waiters.is_empty() {
+                    blockers.remove(&blocked_on);
+                    println!("[cmd_blpop] No more clients blocking on '{}'", key);
+                }
+            }
+            drop(blockers);
+            // a push may have served us just before we left the list
+            waiting.try_recv().ok()
         }
     };
-    let client_addr = stream.peer_addr().ok();
-    println!("[cmd_blpop] Blocking client from {:?}", client_addr);
 
-    let mut blockers = ctx.blocking.lock().unwrap();
-    blockers.entry(key.clone()).or_default().push(stream);
-    println!("[cmd_blpop] Client added to blocking list for key '{}'", key);
-    drop(blockers);
+    match val {
+        Some(val) => {
+            println!("[cmd_blpop] Unblocked with value '{}'", String::from_utf8_lossy(&val));
+            Ok(pop_reply(key, &val))
+        }
+        None => Ok(encode_null_reply(ctx.protocol)),
+    }
+}
 
-    if timeout_secs > 0.0 {
-        println!("[cmd_blpop] Starting timeout thread for {:.3} seconds", timeout_secs);
-        let key = key.clone();
-        let blocking = Arc::clone(&ctx.blocking);
+/// The key and timeout (in seconds), or the error reply.
+fn parse_args(args: &[Arg]) -> Result<(&Arg, f64), Vec<u8>> {
+    println!("[cmd_blpop] Received BLPOP command with args: {:?}", args);
 
-        thread::spawn(move || {
-            thread::sleep(Duration::from_secs_f64(timeout_secs));
+    if args.len() != 3 {
+        println!("[cmd_blpop] Invalid argument count.");
+        return Err(encode_resp_error("usage: BLPOP <key> <timeout>"));
+    }
 
-            println!("[cmd_blpop::timeout] Timeout triggered for key '{}'", key);
-            let mut blockers = blocking.lock().unwrap();
-            if let Some(waiters) = blockers.get_mut(&key) {
-                if let Some(index) = client_addr.and_then(|addr| {
-                    waiters.iter().position(|s| s.peer_addr().ok() == Some(addr))
-                }) {
-                    println!("[cmd_blpop::timeout] Timing out client {:?}", client_addr);
-                    if let Some(stream) = waiters.get_mut(index) {
-                        let _ = stream.write_all(b"$-1\r\n"); // Null bulk string
-                    }
-                    waiters.remove(index);
-                }
+    match args[2].parse::<f64>() {
+        Ok(t) if t < 0.0 => Err(encode_resp_error("timeout is negative")),
+        Ok(t) if t.is_finite() => Ok((&args[1], t)),
+        _ => {
+            eprintln!("[cmd_blpop] Invalid timeout '{}'", args[2]);
+            Err(encode_resp_error("timeout is not a float or out of range"))
+        }
+    }
+}
 
-                if waiters.is_empty() {
-                    blockers.remove(&key);
-                    println!("[cmd_blpop::timeout] No more clients blocking on '{}'", key);
-                }
-            }
-        });
+/// Pops the head of the list at `key`, if it has one, removing the list
+/// if that was its last element.
+fn pop_front(ctx: &Context, store: &mut Db, key: &Arg) -> Option<Vec<u8>> {
+    println!("[cmd_blpop] Attempting immediate pop from '{}'", key);
+    live_entry(ctx, ctx.db_index, store, key.as_bytes());
+    match store.get_mut(key.as_bytes()) {
+        Some((Value::List(ref mut list), _)) if !list.is_empty() => {
+            let val = list.pop_front()?;
+            println!("[cmd_blpop] Immediate pop successful. Returning value '{}'", String::from_utf8_lossy(&val));
+            notify_keyspace_event(ctx, NOTIFY_LIST, "lpop", key.as_bytes(), ctx.db_index);
+            remove_if_empty(ctx, store, key.as_bytes());
+            Some(val)
+        }
+        _ => {
+            println!("[cmd_blpop] Nothing to pop from '{}'", key);
+            None
+        }
     }
+}
 
-    Ok(vec![]) // Don't send anything immediately
+fn pop_reply(key: &Arg, val: &[u8]) -> Vec<u8> {
+    encode_resp_array(&[encode_bulk_resp_bytes(key.as_bytes()), encode_bulk_resp_bytes(val)])
 }
@@ -0,0 +1,98 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_null_reply, encode_resp_error};
+use std::io;
+
+/// LINDEX key index -> the element at `index` (negative counts from the tail)
+pub fn cmd_lindex(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_lindex] Received LINDEX command with args: {:?}", args);
+
+    if args.len() != 3 {
+        println!("[cmd_lindex] Invalid number of arguments.");
+        return Ok(encode_resp_error("usage: LINDEX <key> <index>"));
+    }
+
+    let key = args[1].as_bytes();
+    let Ok(index) = args[2].parse::<i64>() else {
+        eprintln!("[cmd_lindex] Invalid index: '{}'", args[2]);
+        return Ok(encode_resp_error("value is not an integer or out of range"));
+    };
+    let mut map = ctx.store.lock(key);
+
+    let response = match live_entry(ctx, ctx.db_index, &mut map, key) {
+        Some((Value::List(list), _)) => {
+            let position = if index < 0 { list.len() as i64 + index } else { index };
+            match usize::try_from(position).ok().and_then(|i| list.get(i)) {
+                Some(item) => encode_bulk_resp_bytes(item),
+                None => {
+                    println!("[cmd_lindex] Index {} out of range for '{}'", index, args[1]);
+                    encode_null_reply(ctx.protocol)
+                }
+            }
+        }
+        Some(_) => {
+            eprintln!("[cmd_lindex] WRONGTYPE: Key '{}' is not a list", args[1]);
+            encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")
+        }
+        None => {
+            println!("[cmd_lindex] Key '{}' not found.", args[1]);
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+            encode_null_reply(ctx.protocol)
+        }
+    };
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+    use crate::rdb::Value;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn indexes_from_either_end() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd(&mut ctx, &["RPUSH", "l", "a", "b", "c"]), b":3\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "0"]), b"$1\r\na\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "2"]), b"$1\r\nc\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "-1"]), b"$1\r\nc\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "-3"]), b"$1\r\na\r\n");
+
+        // LPUSH prepends one at a time, so the last argument ends up first
+        run_cmd(&mut ctx, &["LPUSH", "l", "x", "y"]);
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "0"]), b"$1\r\ny\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "1"]), b"$1\r\nx\r\n");
+    }
+
+    #[test]
+    fn out_of_range_and_missing_keys_are_nil() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["RPUSH", "l", "a"]);
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "1"]), b"$-1\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "-2"]), b"$-1\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "missing", "0"]), b"$-1\r\n");
+    }
+
+    #[test]
+    fn rejects_bad_indexes_and_other_types() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SET", "s", "v"]);
+        assert!(run_cmd(&mut ctx, &["LINDEX", "s", "0"]).starts_with(b"-ERR WRONGTYPE"));
+        assert!(run_cmd(&mut ctx, &["LINDEX", "s", "one"]).starts_with(b"-ERR value is not an integer"));
+    }
+
+    #[test]
+    fn expired_lists_read_as_missing() {
+        let mut ctx = test_context();
+        let past = SystemTime::now() - Duration::from_secs(1);
+        ctx.store.lock(b"l").insert(b"l".to_vec(), (Value::List(["a".into()].into()), Some(past)));
+        assert_eq!(run_cmd(&mut ctx, &["LINDEX", "l", "0"]), b"$-1\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LLEN", "l"]), b":0\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["LRANGE", "l", "0", "-1"]), b"*0\r\n");
+        assert!(!ctx.store.lock(b"l").contains_key(&b"l"[..]));
+    }
+
+}
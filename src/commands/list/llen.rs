@@ -1,9 +1,11 @@
+use crate::commands::keyspace::live_entry;
 use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
 use crate::rdb::Value;
-use crate::resp::encode_resp_error;
+use crate::resp::{Arg, encode_int, encode_resp_error};
 use std::io;
 
-pub fn cmd_llen(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_llen(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_llen] Received LLEN command with args: {:?}", args);
 
     if args.len() != 2 {
@@ -11,22 +13,23 @@ pub fn cmd_llen(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         return Ok(encode_resp_error("usage: LLEN <key>"));
     }
 
-    let key = &args[1];
-    let map = ctx.store.lock().unwrap();
-    println!("[cmd_llen] Checking length of key '{}'", key);
+    let key = args[1].as_bytes();
+    let mut map = ctx.store.lock(key);
+    println!("[cmd_llen] Checking length of key '{}'", args[1]);
 
-    let response = match map.get(key) {
+    let response = match live_entry(ctx, ctx.db_index, &mut map, key) {
         Some((Value::List(list), _)) => {
             println!("[cmd_llen] List found with {} element(s)", list.len());
-            format!(":{}\r\n", list.len()).into_bytes()
+            encode_int(list.len() as i64)
         }
-        Some((Value::String(_), _)) | Some((Value::Stream(_), _)) => {
-            eprintln!("[cmd_llen] WRONGTYPE: Key '{}' is not a list", key);
+        Some(_) => {
+            eprintln!("[cmd_llen] WRONGTYPE: Key '{}' is not a list", args[1]);
             encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")
         }
         None => {
-            println!("[cmd_llen] Key '{}' not found. Returning 0.", key);
-            b":0\r\n".to_vec()
+            println!("[cmd_llen] Key '{}' not found. Returning 0.", args[1]);
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+            encode_int(0)
         }
     };
 
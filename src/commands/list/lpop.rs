@@ -1,9 +1,12 @@
-use crate::commands::Context;
+use crate::commands::keyspace::live_entry;
+use crate::commands::list::remove_if_empty;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_LIST};
 use crate::rdb::Value;
-use crate::resp::{encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_null_reply, encode_resp_array, encode_resp_error};
 use std::io;
 
-pub fn cmd_lpop(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_lpop(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_lpop] Received LPOP command with args: {:?}", args);
 
     if args.len() != 2 && args.len() != 3 {
@@ -20,58 +23,64 @@ pub fn cmd_lpop(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
             }
             _ => {
                 eprintln!("[cmd_lpop] Invalid count: '{}'", args[2]);
-                return Ok(encode_resp_error("ERR count must be a positive integer"));
+                return Ok(encode_resp_error("count must be a positive integer"));
             }
         }
     } else {
         None
     };
 
-    let mut map = ctx.store.lock().unwrap();
+    let store = ctx.store.clone();
+    let mut map = store.lock(key.as_bytes());
     println!("[cmd_lpop] Accessing key: '{}'", key);
+    live_entry(ctx, ctx.db_index, &mut map, key.as_bytes());
 
-    let response = match map.get_mut(key) {
+    let response = match map.get_mut(key.as_bytes()) {
         Some((Value::List(ref mut list), _)) => {
             if list.is_empty() {
                 println!("[cmd_lpop] List is empty at key: '{}'", key);
                 return Ok(match count {
-                    Some(_) => b"*0\r\n".to_vec(),
-                    None => b"$-1\r\n".to_vec(),
+                    Some(_) => encode_resp_array(&[]),
+                    None => encode_null_reply(ctx.protocol),
                 });
             }
 
-            match count {
+            let response = match count {
                 Some(n) => {
                     let actual_n = n.min(list.len());
                     println!("[cmd_lpop] Removing {} item(s) from list '{}'", actual_n, key);
 
-                    let mut response = format!("*{}\r\n", actual_n).into_bytes();
-                    for _ in 0..actual_n {
-                        let item = list.remove(0);
-                        println!("[cmd_lpop] -> '{}'", item);
-                        response.extend_from_slice(format!("${}\r\n{}\r\n", item.len(), item).as_bytes());
-                    }
-                    response
+                    let items: Vec<Vec<u8>> = list
+                        .drain(..actual_n)
+                        .inspect(|item| println!("[cmd_lpop] -> '{}'", String::from_utf8_lossy(item)))
+                        .map(|item| encode_bulk_resp_bytes(&item))
+                        .collect();
+                    encode_resp_array(&items)
                 }
                 None => {
-                    let popped = list.remove(0);
-                    println!("[cmd_lpop] Popped one item from '{}': '{}'", key, popped);
-                    format!("${}\r\n{}\r\n", popped.len(), popped).into_bytes()
+                    // checked non-empty above
+                    let popped = list.pop_front().unwrap_or_default();
+                    println!("[cmd_lpop] Popped one item from '{}': '{}'", key, String::from_utf8_lossy(&popped));
+                    encode_bulk_resp_bytes(&popped)
                 }
-            }
+            };
+            notify_keyspace_event(ctx, NOTIFY_LIST, "lpop", key.as_bytes(), ctx.db_index);
+            record_write(ctx, args)?;
+            response
         }
-        Some((Value::String(_), _)) | Some((Value::Stream(_), _)) => {
+        Some(_) => {
             eprintln!("[cmd_lpop] WRONGTYPE for key: '{}'", key);
             encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")
         }
         None => {
             println!("[cmd_lpop] Key '{}' not found. Returning empty/null response.", key);
             match count {
-                Some(_) => b"*0\r\n".to_vec(),
-                None => b"$-1\r\n".to_vec(),
+                Some(_) => encode_resp_array(&[]),
+                None => encode_null_reply(ctx.protocol),
             }
         }
     };
+    remove_if_empty(ctx, &mut map, key.as_bytes());
 
     Ok(response)
 }
@@ -1,9 +1,12 @@
-use crate::commands::Context;
+use crate::commands::keyspace::live_entry;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_LIST, NOTIFY_NEW};
 use crate::rdb::Value;
-use crate::resp::encode_resp_error;
+use crate::resp::{Arg, encode_int, encode_resp_error};
+use std::collections::VecDeque;
 use std::io;
 
-pub fn cmd_lpush(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_lpush(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_lpush] Received LPUSH command with args: {:?}", args);
 
     if args.len() < 3 {
@@ -15,18 +18,20 @@ pub fn cmd_lpush(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     let values = &args[2..];
     println!("[cmd_lpush] Target key: '{}', values to push: {:?}", key, values);
 
-    let mut store = ctx.store.lock().unwrap();
+    let shard = ctx.store.clone();
+    let mut store = shard.lock(key.as_bytes());
+    live_entry(ctx, ctx.db_index, &mut store, key.as_bytes());
 
-    let new_len = match store.get_mut(key) {
+    let new_len = match store.get_mut(key.as_bytes()) {
         Some((Value::List(ref mut list), _)) => {
             println!("[cmd_lpush] Key exists and is a list. Prepending {} item(s).", values.len());
             for v in values {
                 println!("[cmd_lpush] -> Inserting at front: '{}'", v);
-                list.insert(0, v.clone());
+                list.push_front(v.as_bytes().to_vec());
             }
             list.len()
         }
-        Some((Value::String(_), _)) | Some((Value::Stream(_), _)) => {
+        Some(_) => {
             eprintln!("[cmd_lpush] WRONGTYPE: Key '{}' is not a list", key);
             return Ok(encode_resp_error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value",
@@ -34,16 +39,19 @@ pub fn cmd_lpush(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         }
         None => {
             println!("[cmd_lpush] Key does not exist. Creating new list.");
-            let mut new_list = Vec::with_capacity(values.len());
+            let mut new_list = VecDeque::with_capacity(values.len());
             for v in values.iter().rev() {
                 println!("[cmd_lpush] -> Adding to new list (reversed): '{}'", v);
-                new_list.push(v.clone());
+                new_list.push_back(v.as_bytes().to_vec());
             }
             let len = new_list.len();
-            store.insert(key.clone(), (Value::List(new_list), None));
+            store.insert(key.as_bytes().to_vec(), (Value::List(new_list), None));
+            notify_keyspace_event(ctx, NOTIFY_NEW, "new", key.as_bytes(), ctx.db_index);
             len
         }
     };
+    notify_keyspace_event(ctx, NOTIFY_LIST, "lpush", key.as_bytes(), ctx.db_index);
+    record_write(ctx, args)?;
 
-    Ok(format!(":{}\r\n", new_len).into_bytes())
+    Ok(encode_int(new_len as i64))
 }
@@ -1,9 +1,11 @@
+use crate::commands::keyspace::live_entry;
 use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
 use crate::rdb::Value;
-use crate::resp::encode_resp_error;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_resp_array, encode_resp_error};
 use std::io;
 
-pub fn cmd_lrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_lrange(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_lrange] Received LRANGE command with args: {:?}", args);
 
     if args.len() != 4 {
@@ -11,7 +13,7 @@ pub fn cmd_lrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         return Ok(encode_resp_error("usage: LRANGE <key> <start> <stop>"));
     }
 
-    let key = &args[1];
+    let key = args[1].as_bytes();
     let start_raw = args[2].parse::<isize>().unwrap_or(isize::MAX);
     let stop_raw = args[3].parse::<isize>().unwrap_or(isize::MAX);
 
@@ -21,12 +23,12 @@ pub fn cmd_lrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     }
 
     println!("[cmd_lrange] Parsed indices: start={}, stop={}", start_raw, stop_raw);
-    let map = ctx.store.lock().unwrap();
+    let mut map = ctx.store.lock(key);
 
-    match map.get(key) {
+    match live_entry(ctx, ctx.db_index, &mut map, key) {
         Some((Value::List(list), _)) => {
             let len = list.len() as isize;
-            println!("[cmd_lrange] List '{}' found with length {}", key, len);
+            println!("[cmd_lrange] List '{}' found with length {}", args[1], len);
 
             let start = if start_raw < 0 {
                 (len + start_raw).max(0)
@@ -44,29 +46,25 @@ pub fn cmd_lrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
 
             if start > stop || start >= list.len() {
                 println!("[cmd_lrange] Empty result: start > stop or out of bounds");
-                return Ok(b"*0\r\n".to_vec());
+                return Ok(encode_resp_array(&[]));
             }
 
             let stop = stop.min(list.len() - 1);
-            let slice = &list[start..=stop];
-            println!("[cmd_lrange] Returning {} item(s) from index {} to {}", slice.len(), start, stop);
+            let items: Vec<Vec<u8>> = list.range(start..=stop).map(|item| encode_bulk_resp_bytes(item)).collect();
+            println!("[cmd_lrange] Returning {} item(s) from index {} to {}", items.len(), start, stop);
 
-            let mut resp = format!("*{}\r\n", slice.len()).into_bytes();
-            for item in slice {
-                println!("[cmd_lrange] -> '{}'", item);
-                resp.extend_from_slice(format!("${}\r\n{}\r\n", item.len(), item).as_bytes());
-            }
-            Ok(resp)
+            Ok(encode_resp_array(&items))
         }
-        Some((Value::String(_), _)) | Some((Value::Stream(_), _)) => {
-            eprintln!("[cmd_lrange] WRONGTYPE: key '{}' is not a list", key);
+        Some(_) => {
+            eprintln!("[cmd_lrange] WRONGTYPE: key '{}' is not a list", args[1]);
             Ok(encode_resp_error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value",
             ))
         }
         None => {
-            println!("[cmd_lrange] Key '{}' not found. Returning empty array.", key);
-            Ok(b"*0\r\n".to_vec())
+            println!("[cmd_lrange] Key '{}' not found. Returning empty array.", args[1]);
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+            Ok(encode_resp_array(&[]))
         }
     }
 }
@@ -1,6 +1,31 @@
 pub mod blpop;
+pub mod lindex;
 pub mod llen;
 pub mod lpop;
 pub mod lpush;
 pub mod lrange;
 pub mod rpush;
+
+use crate::commands::record_write;
+use crate::context::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_GENERIC};
+use crate::rdb::{Db, Value};
+use std::io;
+
+/// Removes the list at `key` once its last element has been popped: an
+/// empty list is never kept.
+pub(crate) fn remove_if_empty(ctx: &Context, db: &mut Db, key: &[u8]) {
+    if matches!(db.get(key), Some((Value::List(list), _)) if list.is_empty()) {
+        println!("[list::remove_if_empty] list emptied, removing key: {}", String::from_utf8_lossy(key));
+        db.remove(key);
+        notify_keyspace_event(ctx, NOTIFY_GENERIC, "del", key, ctx.db_index);
+    }
+}
+
+/// Records a pop made outside LPOP itself (BLPOP, or RPUSH handing an
+/// element to a blocked client) as the LPOP it amounts to, so the AOF and
+/// replicas drop the element too. Callers hold the key's shard, so nothing
+/// else on the list is logged in between.
+pub(crate) fn propagate_pop(ctx: &mut Context, key: &[u8]) -> io::Result<()> {
+    record_write(ctx, &["LPOP".into(), key.into()])
+}
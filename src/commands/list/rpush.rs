@@ -1,10 +1,12 @@
-use crate::commands::Context;
+use crate::commands::keyspace::live_entry;
+use crate::commands::list::{propagate_pop, remove_if_empty};
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_LIST, NOTIFY_NEW};
 use crate::rdb::Value;
-use crate::resp::encode_resp_error;
+use crate::resp::{Arg, encode_int, encode_resp_error};
 use std::io;
-use std::io::Write;
 
-pub fn cmd_rpush(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_rpush(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_rpush] Received RPUSH command with args: {:?}", args);
 
     if args.len() < 3 {
@@ -16,54 +18,61 @@ pub fn cmd_rpush(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     let values = &args[2..];
     println!("[cmd_rpush] Pushing to key '{}': {:?}", key, values);
 
-    let mut store = ctx.store.lock().unwrap();
+    let shard = ctx.store.clone();
+    let mut store = shard.lock(key.as_bytes());
+    live_entry(ctx, ctx.db_index, &mut store, key.as_bytes());
     let new_len;
 
-    match store.get_mut(key) {
+    match store.get_mut(key.as_bytes()) {
         Some((Value::List(ref mut list), _)) => {
-            list.extend_from_slice(values);
+            list.extend(values.iter().map(|v| v.as_bytes().to_vec()));
             new_len = list.len();
             println!("[cmd_rpush] Appended {} item(s). New list length: {}", values.len(), new_len);
         }
-        Some((Value::String(_), _)) | Some((Value::Stream(_), _)) => {
+        Some(_) => {
             eprintln!("[cmd_rpush] WRONGTYPE: Key '{}' holds incompatible value", key);
             return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
         }
         None => {
-            store.insert(key.clone(), (Value::List(values.to_vec()), None));
+            store.insert(key.as_bytes().to_vec(), (Value::List(values.iter().map(|v| v.as_bytes().to_vec()).collect()), None));
             new_len = values.len();
             println!("[cmd_rpush] Created new list with {} item(s).", new_len);
+            notify_keyspace_event(ctx, NOTIFY_NEW, "new", key.as_bytes(), ctx.db_index);
         }
     }
+    notify_keyspace_event(ctx, NOTIFY_LIST, "rpush", key.as_bytes(), ctx.db_index);
+    // before the pops handing elements to blocked clients, which follow it
+    record_write(ctx, args)?;
 
     // Handle blocking clients (BLPOP) waiting on this key
-    let mut blockers = ctx.blocking.lock().unwrap();
-    if let Some(waiters) = blockers.get_mut(key) {
-        if !waiters.is_empty() {
-            println!("[cmd_rpush] Unblocking 1 waiting client for key '{}'", key);
-            let mut client = waiters.remove(0);
-
-            if let Some((Value::List(ref mut list), _)) = store.get_mut(key) {
-                if !list.is_empty() {
-                    let val = list.remove(0);
-                    let response = format!(
-                        "*2\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
-                        key.len(),
-                        key,
-                        val.len(),
-                        val
-                    );
-                    println!("[cmd_rpush] Sending '{}' to unblocked client", val);
-                    let _ = client.write_all(response.as_bytes());
-                }
+    let blocking = ctx.blocking.clone();
+    let mut blockers = blocking.lock().unwrap();
+    let blocked_on = (ctx.db_index, key.as_bytes().to_vec());
+    if let Some(waiters) = blockers.get_mut(&blocked_on) {
+        while !waiters.is_empty() {
+            let Some((Value::List(ref mut list), _)) = store.get_mut(key.as_bytes()) else {
+                break;
+            };
+            let Some(val) = list.pop_front() else {
+                break;
+            };
+            let waiter = waiters.remove(0);
+            println!("[cmd_rpush] Handing '{}' to blocked client {}", String::from_utf8_lossy(&val), waiter.client_id);
+            if let Err(unsent) = waiter.popped.send(val) {
+                // that client went away: the element stays for the next one
+                list.push_front(unsent);
+                continue;
             }
+            notify_keyspace_event(ctx, NOTIFY_LIST, "lpop", key.as_bytes(), ctx.db_index);
+            propagate_pop(ctx, key.as_bytes())?;
         }
 
         if waiters.is_empty() {
             println!("[cmd_rpush] No more waiters for key '{}', cleaning up", key);
-            blockers.remove(key);
+            blockers.remove(&blocked_on);
         }
+        remove_if_empty(ctx, &mut store, key.as_bytes());
     }
 
-    Ok(format!(":{}\r\n", new_len).into_bytes())
+    Ok(encode_int(new_len as i64))
 }
@@ -1,37 +1,69 @@
 mod admin;
 mod connection;
+mod hash;
+mod keyspace;
 mod list;
+mod persistence;
 mod replication;
+mod set;
 mod stream;
 mod string;
 mod transaction;
-mod pubsub;
+mod zset;
+pub mod pubsub;
 
 use lazy_static::lazy_static;
 use std::{collections::HashMap, io};
 use std::io::Write;
-use std::net::TcpStream;
 
 use crate::commands::admin::config::cmd_config;
 use crate::commands::admin::info::cmd_info;
 use crate::commands::admin::keys::cmd_keys;
+use crate::commands::admin::shutdown::{cmd_shutdown, cmd_shutdown_blocking};
+use crate::commands::connection::auth::cmd_auth;
 use crate::commands::connection::echo::cmd_echo;
+use crate::commands::connection::hello::cmd_hello;
 use crate::commands::connection::ping::cmd_ping;
-use crate::commands::list::blpop::cmd_blpop;
+use crate::commands::connection::reset::cmd_reset;
+use crate::commands::connection::select::cmd_select;
+use crate::commands::keyspace::del::cmd_del;
+use crate::commands::keyspace::dump::cmd_dump;
+use crate::commands::keyspace::flushall::cmd_flushall;
+use crate::commands::keyspace::flushdb::cmd_flushdb;
+use crate::commands::keyspace::migrate::cmd_migrate;
+use crate::commands::keyspace::movee::cmd_move;
+use crate::commands::hash::hgetall::cmd_hgetall;
+use crate::commands::hash::hscan::cmd_hscan;
+use crate::commands::keyspace::restore::cmd_restore;
+use crate::commands::keyspace::scan::cmd_scan;
+use crate::commands::keyspace::swapdb::cmd_swapdb;
+use crate::commands::list::blpop::{cmd_blpop, cmd_blpop_blocking};
+use crate::commands::list::lindex::cmd_lindex;
 use crate::commands::list::llen::cmd_llen;
 use crate::commands::list::lpop::cmd_lpop;
 use crate::commands::list::lpush::cmd_lpush;
 use crate::commands::list::lrange::cmd_lrange;
 use crate::commands::list::rpush::cmd_rpush;
+use crate::commands::persistence::bgrewriteaof::cmd_bgrewriteaof;
+use crate::commands::persistence::bgsave::cmd_bgsave;
+use crate::commands::persistence::lastsave::cmd_lastsave;
+use crate::commands::persistence::save::cmd_save;
+use crate::commands::pubsub::psubscribe::cmd_psubscribe;
 use crate::commands::pubsub::publish::cmd_publish;
+use crate::commands::pubsub::introspection::cmd_pubsub;
+use crate::commands::pubsub::punsubscribe::cmd_punsubscribe;
+use crate::commands::pubsub::spublish::cmd_spublish;
+use crate::commands::pubsub::ssubscribe::cmd_ssubscribe;
+use crate::commands::pubsub::sunsubscribe::cmd_sunsubscribe;
 use crate::commands::pubsub::subscribe::cmd_subscribe;
 use crate::commands::pubsub::unsubscribe::cmd_unsubscribe;
-use crate::commands::replication::psync::cmd_psync;
+use crate::commands::replication::psync::{cmd_psync, cmd_psync_blocking};
 use crate::commands::replication::replconf::cmd_replconf;
-use crate::commands::replication::wait::cmd_wait;
+use crate::commands::replication::wait::{cmd_wait, cmd_wait_blocking};
 use crate::commands::stream::xadd::cmd_xadd;
 use crate::commands::stream::xrange::cmd_xrange;
-use crate::commands::stream::xread::cmd_xread;
+use crate::commands::stream::xread::{cmd_xread, cmd_xread_blocking};
+use crate::commands::set::sscan::cmd_sscan;
 use crate::commands::string::get::cmd_get;
 use crate::commands::string::incr::cmd_incr;
 use crate::commands::string::set::cmd_set;
@@ -39,12 +71,15 @@ use crate::commands::string::typee::cmd_type;
 use crate::commands::transaction::discard::cmd_discard;
 use crate::commands::transaction::exec::cmd_exec;
 use crate::commands::transaction::multi::cmd_multi;
+use crate::commands::zset::zscan::cmd_zscan;
+use crate::commands::zset::zscore::cmd_zscore;
 
-use crate::resp::write_resp_error;
+use crate::resp::{Arg, write_resp_error};
+use crate::server::propagate_write;
 use crate::Context;
 use crate::role::Role;
 
-pub type CmdFn = fn(&[String], &mut Context) -> io::Result<Vec<u8>>;
+pub type CmdFn = fn(&[Arg], &mut Context) -> io::Result<Vec<u8>>;
 
 lazy_static! {
     /// Full map of *all* commands
@@ -63,6 +98,7 @@ lazy_static! {
         m.insert("LRANGE".into(),   cmd_lrange  as CmdFn);
         m.insert("LPUSH".into(),    cmd_lpush   as CmdFn);
         m.insert("LLEN".into(),     cmd_llen    as CmdFn);
+        m.insert("LINDEX".into(),   cmd_lindex  as CmdFn);
         m.insert("LPOP".into(),     cmd_lpop    as CmdFn);
         m.insert("BLPOP".into(),    cmd_blpop   as CmdFn);
         m.insert("TYPE".into(),     cmd_type    as CmdFn);
@@ -77,24 +113,99 @@ lazy_static! {
         m.insert("SUBSCRIBE".into(), cmd_subscribe as CmdFn);
         m.insert("PUBLISH".into(), cmd_publish as CmdFn);
         m.insert("UNSUBSCRIBE".into(), cmd_unsubscribe as CmdFn);
+        m.insert("PSUBSCRIBE".into(), cmd_psubscribe as CmdFn);
+        m.insert("PUNSUBSCRIBE".into(), cmd_punsubscribe as CmdFn);
+        m.insert("PUBSUB".into(),   cmd_pubsub  as CmdFn);
+        m.insert("SSUBSCRIBE".into(), cmd_ssubscribe as CmdFn);
+        m.insert("SUNSUBSCRIBE".into(), cmd_sunsubscribe as CmdFn);
+        m.insert("SPUBLISH".into(), cmd_spublish as CmdFn);
+        m.insert("SAVE".into(),     cmd_save    as CmdFn);
+        m.insert("BGSAVE".into(),   cmd_bgsave  as CmdFn);
+        m.insert("BGREWRITEAOF".into(), cmd_bgrewriteaof as CmdFn);
+        m.insert("LASTSAVE".into(), cmd_lastsave as CmdFn);
+        m.insert("SHUTDOWN".into(), cmd_shutdown as CmdFn);
+        m.insert("DEL".into(),      cmd_del     as CmdFn);
+        m.insert("DUMP".into(),     cmd_dump    as CmdFn);
+        m.insert("RESTORE".into(),  cmd_restore as CmdFn);
+        m.insert("MIGRATE".into(),  cmd_migrate as CmdFn);
+        m.insert("SELECT".into(),   cmd_select  as CmdFn);
+        m.insert("RESET".into(),    cmd_reset   as CmdFn);
+        m.insert("HELLO".into(),    cmd_hello   as CmdFn);
+        m.insert("AUTH".into(),     cmd_auth    as CmdFn);
+        m.insert("MOVE".into(),     cmd_move    as CmdFn);
+        m.insert("SWAPDB".into(),   cmd_swapdb  as CmdFn);
+        m.insert("FLUSHDB".into(),  cmd_flushdb as CmdFn);
+        m.insert("FLUSHALL".into(), cmd_flushall as CmdFn);
+        m.insert("SCAN".into(),     cmd_scan    as CmdFn);
+        m.insert("HSCAN".into(),    cmd_hscan   as CmdFn);
+        m.insert("SSCAN".into(),    cmd_sscan   as CmdFn);
+        m.insert("ZSCAN".into(),    cmd_zscan   as CmdFn);
+        m.insert("HGETALL".into(),  cmd_hgetall as CmdFn);
+        m.insert("ZSCORE".into(),   cmd_zscore  as CmdFn);
         m
     };
+}
+
+/// Whether `cmd` (uppercased) names a command at all.
+pub fn is_known_cmd(cmd: &str) -> bool {
+    ALL_CMDS.contains_key(cmd)
+}
+
+/// Records a write a command has just made: counts it towards the next save
+/// and propagates `args` to the AOF and the replicas. Write commands call it
+/// only once they have changed something, while still holding the key's
+/// shard, so the writes to a key are propagated in the order they were made.
+/// Inside EXEC, the first one opens the MULTI that EXEC closes.
+pub(crate) fn record_write(ctx: &mut Context, args: &[Arg]) -> io::Result<()> {
+    if ctx.loading {
+        return Ok(());
+    }
+    ctx.save_state.lock().unwrap().dirty += 1;
+    if ctx.multi_propagated == Some(false) {
+        propagate_write(ctx, &["MULTI".into()])?;
+        ctx.multi_propagated = Some(true);
+    }
+    propagate_write(ctx, args)
+}
+
+/// Commands that may wait for something to happen, plus PSYNC and SHUTDOWN,
+/// whose full resync and final save are too much work for a runtime worker. Connections await them
+/// through `dispatch_blocking_cmd`; their `ALL_CMDS` entries never wait,
+/// which is how they run inside MULTI/EXEC.
+pub fn is_blocking_cmd(cmd: &str) -> bool {
+    matches!(cmd, "BLPOP" | "XREAD" | "WAIT" | "PSYNC" | "SHUTDOWN")
+}
 
-    /// Pre‐filtered map of only the write‐type commands
-    static ref WRITE_CMDS: HashMap<String, CmdFn> = {
-        ALL_CMDS
-            .iter()
-            .filter(|(name, _)| is_write_cmd(name))
-            .map(|(k, v)| (k.clone(), *v))
-            .collect()
+/// Runs a command for which `is_blocking_cmd` holds, for a client.
+pub async fn dispatch_blocking_cmd(name: &str, out: &mut Vec<u8>, args: &[Arg], ctx: &mut Context) -> io::Result<()> {
+    println!("[dispatch_cmd] Dispatching blocking command: '{}'", name);
+    let response = match name {
+        "BLPOP" => cmd_blpop_blocking(args, ctx).await?,
+        "XREAD" => cmd_xread_blocking(args, ctx).await?,
+        "PSYNC" => cmd_psync_blocking(args, ctx).await?,
+        "SHUTDOWN" => cmd_shutdown_blocking(args, ctx).await?,
+        _ => cmd_wait_blocking(args, ctx).await?,
     };
+    out.extend_from_slice(&response);
+    Ok(())
 }
 
-pub fn is_write_cmd(cmd: &str) -> bool {
-    matches!(
-        cmd.to_ascii_uppercase().as_str(),
-        "SET" | "DEL" | "RPUSH" | "LPUSH" | "LPOP" | "INCR" | "XADD"
-    )
+/// Runs a command with no reply routing, e.g. while replaying the AOF.
+/// Returns `None` for unknown commands.
+pub fn execute_cmd(name: &str, args: &[Arg], ctx: &mut Context) -> Option<io::Result<Vec<u8>>> {
+    ALL_CMDS.get(name).map(|cmd_fn| cmd_fn(args, ctx))
+}
+
+/// For command streams that are replayed rather than served (the AOF, a
+/// master's replication stream): queues `args` if a MULTI is open and `cmd`
+/// doesn't end it, so the transaction is applied by its EXEC as one step.
+/// Returns whether it was queued.
+pub fn queue_in_transaction(cmd: &str, args: &[Arg], ctx: &mut Context) -> bool {
+    if !ctx.in_transaction || matches!(cmd, "MULTI" | "EXEC" | "DISCARD") {
+        return false;
+    }
+    ctx.queued.push((cmd.to_string(), args.to_vec()));
+    true
 }
 
 /// Should a normal client get a reply?
@@ -111,25 +222,28 @@ fn should_respond(cmd: &str, ctx: &Context) -> bool {
 ///   (so you don’t echo `+PONG` for `PING`, etc.).
 pub fn dispatch_cmd(
     name: &str,
-    out: &mut TcpStream,
-    args: &[String],
+    out: &mut dyn Write,
+    args: &[Arg],
     ctx: &mut Context,
 ) -> io::Result<()> {
     println!("[dispatch_cmd] Dispatching command: '{}'", name);
 
     // Are we in replica mode, and is this socket the replication link back to the master?
     let is_repl_link = if ctx.cfg.role == Role::Slave {
-        out.peer_addr()
-            .map(|peer| peer.port() == ctx.cfg.master_port)
-            .unwrap_or(false)
+        ctx.peer.is_some_and(|peer| peer.port() == ctx.cfg.master_port)
     } else {
         false
     };
 
     if let Some(cmd_fn) = ALL_CMDS.get(name) {
         // Execute for side‐effects (store update, offsets, etc.)
-        let response = cmd_fn(args, ctx)?;
-
+        let response = if name == "MIGRATE" {
+            // talks to the target over a blocking socket: let the runtime
+            // move other connections off this worker meanwhile
+            tokio::task::block_in_place(|| cmd_fn(args, ctx))?
+        } else {
+            cmd_fn(args, ctx)?
+        };
         if is_repl_link {
             // Swallow everything except REPLCONF
             if name.eq_ignore_ascii_case("REPLCONF") {
@@ -158,3 +272,109 @@ pub fn dispatch_cmd(
         Ok(())
     }
 }
+
+/// A fresh four-database server context for command tests.
+#[cfg(test)]
+pub fn test_context() -> Context {
+    use crate::config::ServerConfig;
+    use std::sync::Arc;
+
+    let cfg = ServerConfig::for_tests(&std::env::temp_dir());
+    let databases = cfg.databases;
+    Context::new(Arc::new(cfg), vec![Default::default(); databases])
+}
+
+/// Runs one command the way a client would send it and returns the raw reply.
+#[cfg(test)]
+pub fn run_cmd(ctx: &mut Context, args: &[&str]) -> Vec<u8> {
+    let args: Vec<Arg> = args.iter().map(|&a| a.into()).collect();
+    execute_cmd(&args[0].to_uppercase(), &args, ctx)
+        .expect("unknown command")
+        .expect("command failed")
+}
+
+/// Like `run_cmd`, for arguments that aren't all text (a DUMP payload).
+#[cfg(test)]
+pub fn run_cmd_bytes(ctx: &mut Context, args: &[&[u8]]) -> Vec<u8> {
+    let args: Vec<Arg> = args.iter().map(|a| Arg::from(*a)).collect();
+    execute_cmd(&args[0].to_uppercase(), &args, ctx)
+        .expect("unknown command")
+        .expect("command failed")
+}
+
+/// The runtime the connection tasks of tests (outbox writers, served
+/// connections) run on.
+#[cfg(test)]
+pub fn test_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().unwrap())
+}
+
+/// A connected loopback pair: the server's end of a client connection, and
+/// the client's end to read what the server sent.
+#[cfg(test)]
+pub fn client_pair() -> (tokio::net::TcpStream, std::net::TcpStream) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    server.set_nonblocking(true).unwrap();
+    let _entered = test_runtime().enter();
+    (tokio::net::TcpStream::from_std(server).unwrap(), client)
+}
+
+/// Gives `ctx` an outbox writing to a fresh connection, as a served client
+/// has, and returns the client's end of it.
+#[cfg(test)]
+pub fn attach_client(ctx: &mut Context) -> std::net::TcpStream {
+    use crate::config::OutputBufferLimit;
+    use crate::outbox::Outbox;
+
+    let (server, client) = client_pair();
+    let (_, writer) = server.into_split();
+    let _entered = test_runtime().enter();
+    ctx.outbox = Some(Outbox::start(writer, OutputBufferLimit::UNLIMITED, ctx.protocol));
+    client
+}
+
+/// Registers a replica that is online and has DB 0 selected, and returns
+/// the end its replication stream is read from.
+#[cfg(test)]
+pub fn attach_replica(ctx: &Context) -> std::net::TcpStream {
+    use crate::config::OutputBufferLimit;
+    use crate::context::ReplicaLink;
+    use crate::outbox::Outbox;
+
+    let (server, client) = client_pair();
+    let addr = server.peer_addr().unwrap();
+    let (_, writer) = server.into_split();
+    let _entered = test_runtime().enter();
+    let link = ReplicaLink {
+        outbox: Outbox::start(writer, OutputBufferLimit::UNLIMITED, 2),
+        ack_offset: 0,
+        backlog: None,
+        selected_db: Some(0),
+    };
+    ctx.replicas.lock().unwrap().insert(addr, link);
+    client
+}
+
+/// Reads exactly `expected.len()` bytes pushed to `client` and checks them.
+#[cfg(test)]
+pub fn assert_pushed(client: &mut std::net::TcpStream, expected: &[u8]) {
+    use std::io::Read;
+    let mut buf = vec![0; expected.len()];
+    client.read_exact(&mut buf).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), String::from_utf8_lossy(expected));
+}
+
+/// Checks nothing more was pushed to `client`.
+#[cfg(test)]
+pub fn assert_nothing_pushed(client: &mut std::net::TcpStream) {
+    use std::io::Read;
+    client.set_nonblocking(true).unwrap();
+    let mut buf = [0; 64];
+    let read = client.read(&mut buf);
+    client.set_nonblocking(false).unwrap();
+    assert!(matches!(&read, Err(e) if e.kind() == io::ErrorKind::WouldBlock), "unexpected {:?}", read);
+}
@@ -0,0 +1,22 @@
+use crate::aof::start_rewrite;
+use crate::commands::Context;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// BGREWRITEAOF -> +Background append only file rewriting started
+pub fn cmd_bgrewriteaof(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_bgrewriteaof] Received BGREWRITEAOF command with args: {:?}", args);
+
+    if args.len() != 1 {
+        return Ok(encode_resp_error("wrong number of arguments for 'bgrewriteaof' command"));
+    }
+    if ctx.aof.is_none() {
+        return Ok(encode_resp_error("Append only file is disabled; set appendonly yes first"));
+    }
+
+    if start_rewrite(ctx) {
+        Ok(encode_simple_resp_string("Background append only file rewriting started"))
+    } else {
+        Ok(encode_resp_error("Background append only file rewriting already in progress"))
+    }
+}
@@ -0,0 +1,19 @@
+use crate::commands::Context;
+use crate::persistence::start_background_save;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// BGSAVE -> +Background saving started
+pub fn cmd_bgsave(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_bgsave] Received BGSAVE command with args: {:?}", args);
+
+    if args.len() > 2 {
+        return Ok(encode_resp_error("usage: BGSAVE [SCHEDULE]"));
+    }
+
+    if start_background_save(ctx) {
+        Ok(encode_simple_resp_string("Background saving started"))
+    } else {
+        Ok(encode_resp_error("Background save already in progress"))
+    }
+}
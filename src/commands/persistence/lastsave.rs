@@ -0,0 +1,19 @@
+use crate::commands::Context;
+use crate::resp::{Arg, encode_int, encode_resp_error};
+use std::io;
+use std::time::UNIX_EPOCH;
+
+/// LASTSAVE -> unix time of the last successful save
+pub fn cmd_lastsave(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_lastsave] Received LASTSAVE command with args: {:?}", args);
+
+    if args.len() != 1 {
+        return Ok(encode_resp_error("usage: LASTSAVE"));
+    }
+
+    let last_save = ctx.save_state.lock().unwrap().last_save;
+    let secs = last_save.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    println!("[cmd_lastsave] Last save at {}", secs);
+
+    Ok(encode_int(secs as i64))
+}
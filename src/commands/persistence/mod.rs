@@ -0,0 +1,4 @@
+pub mod bgrewriteaof;
+pub mod bgsave;
+pub mod lastsave;
+pub mod save;
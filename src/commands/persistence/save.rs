@@ -0,0 +1,24 @@
+use crate::commands::Context;
+use crate::persistence::save_snapshot;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
+use std::io;
+
+/// SAVE -> +OK once the dump is on disk
+pub fn cmd_save(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_save] Received SAVE command with args: {:?}", args);
+
+    if args.len() != 1 {
+        return Ok(encode_resp_error("usage: SAVE"));
+    }
+
+    match save_snapshot(ctx) {
+        Ok(()) => {
+            println!("[cmd_save] DB saved on disk");
+            Ok(encode_simple_resp_string("OK"))
+        }
+        Err(e) => {
+            eprintln!("[cmd_save] Save failed: {}", e);
+            Ok(encode_resp_error(&e.to_string()))
+        }
+    }
+}
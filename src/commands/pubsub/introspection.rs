@@ -0,0 +1,104 @@
+use crate::context::PubSubRegistry;
+use crate::glob::glob_match_bytes;
+use crate::Context;
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_int, encode_resp_array, encode_resp_error};
+use std::io;
+
+/// PUBSUB CHANNELS [pattern] -> active channels, optionally glob-filtered
+/// PUBSUB NUMSUB [channel ...] -> [channel count ...]
+/// PUBSUB NUMPAT -> (integer) number of patterns with subscribers
+/// PUBSUB SHARDCHANNELS [pattern] / SHARDNUMSUB [channel ...] -> the same
+/// for shard channels
+pub fn cmd_pubsub(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_pubsub] Received PUBSUB command with args: {:?}", args);
+
+    let Some(sub) = args.get(1).map(|s| s.to_uppercase()) else {
+        return Ok(encode_resp_error("wrong number of arguments for 'pubsub' command"));
+    };
+
+    match (sub.as_str(), args.len()) {
+        ("CHANNELS", 2 | 3) => Ok(active_channels(&ctx.pubsub, args.get(2))),
+        ("SHARDCHANNELS", 2 | 3) => Ok(active_channels(&ctx.shard_pubsub, args.get(2))),
+        ("NUMSUB", _) => Ok(subscriber_counts(&ctx.pubsub, &args[2..])),
+        ("SHARDNUMSUB", _) => Ok(subscriber_counts(&ctx.shard_pubsub, &args[2..])),
+        ("NUMPAT", 2) => {
+            let count = ctx.pattern_pubsub.lock().unwrap().len();
+            Ok(encode_int(count as i64))
+        }
+        _ => {
+            eprintln!("[cmd_pubsub] Unknown subcommand or wrong arity: {:?}", args);
+            Ok(encode_resp_error(&format!(
+                "unknown subcommand or wrong number of arguments for '{}'. Try PUBSUB HELP.",
+                args[1]
+            )))
+        }
+    }
+}
+
+/// Channels in `registry` with at least one subscriber, sorted.
+fn active_channels(registry: &PubSubRegistry, pattern: Option<&Arg>) -> Vec<u8> {
+    let registry = registry.lock().unwrap();
+    let mut channels: Vec<&Vec<u8>> = registry
+        .iter()
+        .filter(|(channel, subs)| !subs.is_empty() && pattern.is_none_or(|p| glob_match_bytes(p.as_bytes(), channel)))
+        .map(|(channel, _)| channel)
+        .collect();
+    channels.sort();
+
+    println!("[cmd_pubsub] {} active channel(s)", channels.len());
+    let chunks: Vec<Vec<u8>> = channels.iter().map(|c| encode_bulk_resp_bytes(c)).collect();
+    encode_resp_array(&chunks)
+}
+
+/// [channel count ...] for each of `channels`.
+fn subscriber_counts(registry: &PubSubRegistry, channels: &[Arg]) -> Vec<u8> {
+    let registry = registry.lock().unwrap();
+    let mut chunks = Vec::new();
+    for channel in channels {
+        let count = registry.get(channel.as_bytes()).map_or(0, |subs| subs.len());
+        chunks.push(encode_bulk_resp_bytes(channel.as_bytes()));
+        chunks.push(encode_int(count as i64));
+    }
+    encode_resp_array(&chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{attach_client, run_cmd, test_context};
+
+    #[test]
+    fn reports_channels_subscribers_and_patterns() {
+        // the client ends stay open so neither subscriber is dropped
+        let mut first = test_context();
+        let _first_client = attach_client(&mut first);
+        let mut second = first.clone();
+        let _second_client = attach_client(&mut second);
+
+        run_cmd(&mut first, &["SUBSCRIBE", "news.tech", "news.art", "chat"]);
+        run_cmd(&mut second, &["SUBSCRIBE", "chat"]);
+        run_cmd(&mut second, &["PSUBSCRIBE", "news.*", "x?"]);
+
+        assert_eq!(
+            run_cmd(&mut first, &["PUBSUB", "CHANNELS"]),
+            b"*3\r\n$4\r\nchat\r\n$8\r\nnews.art\r\n$9\r\nnews.tech\r\n"
+        );
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "channels", "news.t*"]), b"*1\r\n$9\r\nnews.tech\r\n");
+        assert_eq!(
+            run_cmd(&mut first, &["PUBSUB", "NUMSUB", "chat", "news.art", "none"]),
+            b"*6\r\n$4\r\nchat\r\n:2\r\n$8\r\nnews.art\r\n:1\r\n$4\r\nnone\r\n:0\r\n"
+        );
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "NUMPAT"]), b":2\r\n");
+
+        run_cmd(&mut first, &["UNSUBSCRIBE", "chat"]);
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "NUMSUB", "chat"]), b"*2\r\n$4\r\nchat\r\n:1\r\n");
+    }
+
+    #[test]
+    fn rejects_unknown_subcommands_and_arities() {
+        let mut ctx = test_context();
+        assert!(run_cmd(&mut ctx, &["PUBSUB"]).starts_with(b"-ERR wrong number"));
+        assert!(run_cmd(&mut ctx, &["PUBSUB", "NUMPAT", "x"]).starts_with(b"-ERR unknown subcommand"));
+        assert!(run_cmd(&mut ctx, &["PUBSUB", "CHANNELS", "a", "b"]).starts_with(b"-ERR unknown subcommand"));
+        assert!(run_cmd(&mut ctx, &["PUBSUB", "SHARDS"]).starts_with(b"-ERR unknown subcommand"));
+    }
+}
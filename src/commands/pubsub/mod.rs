@@ -1,3 +1,223 @@
+pub mod introspection;
+pub mod psubscribe;
 pub mod publish;
+pub mod punsubscribe;
+pub mod spublish;
+pub mod ssubscribe;
 pub mod subscribe;
+pub mod sunsubscribe;
 pub mod unsubscribe;
+
+use crate::config::OutputBufferLimit;
+use crate::context::PubSubRegistry;
+use crate::glob::glob_match_bytes;
+use crate::outbox::Outbox;
+use crate::resp::{
+    encode_bulk_resp_bytes, encode_bulk_resp_string, encode_int, encode_null_reply, encode_push_reply, encode_resp_array, encode_resp_error_code,
+    encode_resp_push,
+};
+use crate::resp::Arg;
+use crate::slot::key_hash_slot;
+use crate::Context;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// This client's outbox, put under the pubsub output buffer limit now that
+/// it subscribes. `None` for connections without a socket (e.g. AOF
+/// replay), which can't receive messages anyway.
+pub(crate) fn client_outbox(ctx: &Context) -> Option<Arc<Outbox>> {
+    let outbox = ctx.outbox.clone()?;
+    outbox.set_limit(ctx.cfg.client_output_buffer_limit_pubsub);
+    Some(outbox)
+}
+
+/// Registers `outbox` on `name` (when `first`, i.e. newly subscribed) and
+/// confirms with a [kind, name, count] frame. The frame is queued on the
+/// outbox under the registry lock, so no message can overtake it; without
+/// an outbox it is returned for the caller to send instead.
+pub(crate) fn subscribe_to(
+    registry: &PubSubRegistry,
+    kind: &str,
+    name: &[u8],
+    first: bool,
+    count: usize,
+    outbox: Option<&Arc<Outbox>>,
+    protocol: u8,
+) -> Vec<u8> {
+    let frame = encode_pubsub_reply(protocol, kind, Some(name), count);
+    let Some(outbox) = outbox else {
+        return frame;
+    };
+
+    let mut registry = registry.lock().unwrap();
+    if first {
+        registry.entry(name.to_vec()).or_default().push(outbox.clone());
+    }
+    outbox.push(frame);
+    Vec::new()
+}
+
+/// Queues `message` for every subscriber of `channel` and every pattern
+/// subscription matching it; returns how many it reached.
+///
+/// Messages are only queued on each subscriber's outbox, so a slow reader
+/// never holds up the publisher; subscribers found disconnected are pruned.
+pub(crate) fn publish_message(ctx: &Context, channel: &[u8], message: &[u8]) -> usize {
+    let mut delivered = 0;
+
+    // ["message", channel, message] to each subscriber
+    let frames = PubSubFrames::new("message", &[channel, message]);
+    let mut registry = ctx.pubsub.lock().unwrap();
+    if let Some(subscribers) = registry.get_mut(channel) {
+        subscribers.retain(|subscriber| frames.deliver(subscriber));
+        delivered += subscribers.len();
+        if subscribers.is_empty() {
+            registry.remove(channel);
+        }
+    }
+    drop(registry);
+
+    // ["pmessage", pattern, channel, message] to every matching pattern subscription
+    let mut patterns = ctx.pattern_pubsub.lock().unwrap();
+    for (pattern, subscribers) in patterns.iter_mut() {
+        if !glob_match_bytes(pattern, channel) {
+            continue;
+        }
+        let frames = PubSubFrames::new("pmessage", &[pattern, channel, message]);
+        subscribers.retain(|subscriber| frames.deliver(subscriber));
+        delivered += subscribers.len();
+    }
+    patterns.retain(|_, subscribers| !subscribers.is_empty());
+
+    delivered
+}
+
+/// One message, encoded for both protocol versions so each subscriber gets
+/// it the way it negotiated: a RESP2 array or a RESP3 push frame.
+pub(crate) struct PubSubFrames {
+    resp2: Vec<u8>,
+    resp3: Vec<u8>,
+}
+
+impl PubSubFrames {
+    /// [kind, parts...] as bulk strings; a message may hold any bytes.
+    pub fn new(kind: &str, parts: &[&[u8]]) -> PubSubFrames {
+        let mut chunks = vec![encode_bulk_resp_string(kind)];
+        chunks.extend(parts.iter().map(|p| encode_bulk_resp_bytes(p)));
+        PubSubFrames {
+            resp2: encode_resp_array(&chunks),
+            resp3: encode_resp_push(&chunks),
+        }
+    }
+
+    /// Queues the frame for `subscriber`; `false` if it is gone.
+    pub fn deliver(&self, subscriber: &Outbox) -> bool {
+        let frame = if subscriber.protocol() >= 3 { &self.resp3 } else { &self.resp2 };
+        subscriber.push(frame.clone())
+    }
+}
+
+/// A [kind, name, count] (un)subscribe confirmation; `name` is nil when
+/// there was nothing to unsubscribe from.
+pub(crate) fn encode_pubsub_reply(protocol: u8, kind: &str, name: Option<&[u8]>, count: usize) -> Vec<u8> {
+    encode_push_reply(
+        protocol,
+        &[
+            encode_bulk_resp_string(kind),
+            name.map_or_else(|| encode_null_reply(protocol), encode_bulk_resp_bytes),
+            encode_int(count as i64),
+        ],
+    )
+}
+
+/// Drops this client (and any disconnected subscriber) from `name`'s
+/// subscribers in `registry`, removing the entry once nobody is left on it.
+fn unregister(registry: &mut HashMap<Vec<u8>, Vec<Arc<Outbox>>>, name: &[u8], outbox: Option<&Arc<Outbox>>) {
+    if let Some(subs) = registry.get_mut(name) {
+        subs.retain(|s| !outbox.is_some_and(|o| Arc::ptr_eq(s, o)) && !s.is_closed());
+        if subs.is_empty() {
+            registry.remove(name);
+        }
+    }
+}
+
+/// Puts this client back under the normal-client output buffer limit once
+/// its last subscription is gone.
+fn leave_pubsub_limit(ctx: &Context) {
+    if let Some(outbox) = &ctx.outbox {
+        if !ctx.in_subscribed_mode() {
+            outbox.set_limit(OutputBufferLimit::UNLIMITED);
+        }
+    }
+}
+
+/// Unsubscribes this client from `channel`; returns whether it was subscribed.
+pub(crate) fn unsubscribe_channel(ctx: &mut Context, channel: &[u8]) -> bool {
+    if !ctx.subscribed_channels.remove(channel) {
+        return false;
+    }
+    unregister(&mut ctx.pubsub.lock().unwrap(), channel, ctx.outbox.as_ref());
+    leave_pubsub_limit(ctx);
+    true
+}
+
+/// Unsubscribes this client from `pattern`; returns whether it was subscribed.
+pub(crate) fn unsubscribe_pattern(ctx: &mut Context, pattern: &[u8]) -> bool {
+    if !ctx.subscribed_patterns.remove(pattern) {
+        return false;
+    }
+    unregister(&mut ctx.pattern_pubsub.lock().unwrap(), pattern, ctx.outbox.as_ref());
+    leave_pubsub_limit(ctx);
+    true
+}
+
+/// The names to drop for an (P)UNSUBSCRIBE: the arguments, or with none,
+/// everything the client is subscribed to.
+pub(crate) fn unsubscribe_targets(args: &[Arg], subscribed: &HashSet<Vec<u8>>) -> Vec<Vec<u8>> {
+    if args.len() > 1 {
+        return args[1..].iter().map(|name| name.as_bytes().to_vec()).collect();
+    }
+    let mut all: Vec<Vec<u8>> = subscribed.iter().cloned().collect();
+    all.sort();
+    all
+}
+
+/// Unsubscribes this client from shard channel `channel`; returns whether
+/// it was subscribed.
+pub(crate) fn unsubscribe_shard_channel(ctx: &mut Context, channel: &[u8]) -> bool {
+    if !ctx.subscribed_shard_channels.remove(channel) {
+        return false;
+    }
+    unregister(&mut ctx.shard_pubsub.lock().unwrap(), channel, ctx.outbox.as_ref());
+    leave_pubsub_limit(ctx);
+    true
+}
+
+/// Drops every channel, pattern and shard channel subscription of this
+/// client; returns how many there were.
+pub(crate) fn unsubscribe_all(ctx: &mut Context) -> usize {
+    let channels: Vec<Vec<u8>> = ctx.subscribed_channels.iter().cloned().collect();
+    for channel in &channels {
+        unsubscribe_channel(ctx, channel);
+    }
+    let patterns: Vec<Vec<u8>> = ctx.subscribed_patterns.iter().cloned().collect();
+    for pattern in &patterns {
+        unsubscribe_pattern(ctx, pattern);
+    }
+    let shard_channels: Vec<Vec<u8>> = ctx.subscribed_shard_channels.iter().cloned().collect();
+    for channel in &shard_channels {
+        unsubscribe_shard_channel(ctx, channel);
+    }
+    channels.len() + patterns.len() + shard_channels.len()
+}
+
+/// Sharded commands must stay within one slot, as they would on a cluster
+/// node: CROSSSLOT error reply if `channels` span several.
+pub(crate) fn check_same_slot(channels: &[Arg]) -> Result<(), Vec<u8>> {
+    let mut slots = channels.iter().map(|c| key_hash_slot(c.as_bytes()));
+    let first = slots.next();
+    if slots.any(|slot| Some(slot) != first) {
+        return Err(encode_resp_error_code("CROSSSLOT", "Keys in request don't hash to the same slot"));
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::commands::pubsub::{client_outbox, subscribe_to};
+use crate::Context;
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// PSUBSCRIBE <pattern> [pattern ...]
+/// Returns one ["psubscribe", pattern, count] frame per pattern
+pub fn cmd_psubscribe(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_psubscribe] Received PSUBSCRIBE command with args: {:?}", args);
+
+    if args.len() < 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'psubscribe' command"));
+    }
+
+    let outbox = client_outbox(ctx);
+    let mut resp = Vec::new();
+    for pattern in &args[1..] {
+        let first = ctx.subscribed_patterns.insert(pattern.as_bytes().to_vec());
+        let count = ctx.subscription_count();
+        resp.extend(subscribe_to(&ctx.pattern_pubsub, "psubscribe", pattern.as_bytes(), first, count, outbox.as_ref(), ctx.protocol));
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{assert_nothing_pushed, assert_pushed, attach_client, run_cmd, test_context};
+
+    #[test]
+    fn replies_count_channels_and_patterns_together() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SUBSCRIBE", "news"]);
+        let reply = run_cmd(&mut ctx, &["PSUBSCRIBE", "a*", "b?", "a*"]);
+        assert_eq!(
+            reply,
+            [
+                &b"*3\r\n$10\r\npsubscribe\r\n$2\r\na*\r\n:2\r\n"[..],
+                b"*3\r\n$10\r\npsubscribe\r\n$2\r\nb?\r\n:3\r\n",
+                b"*3\r\n$10\r\npsubscribe\r\n$2\r\na*\r\n:3\r\n",
+            ]
+            .concat()
+        );
+        assert_eq!(ctx.subscription_count(), 3);
+        assert!(run_cmd(&mut ctx, &["PSUBSCRIBE"]).starts_with(b"-ERR wrong number"));
+    }
+
+    #[test]
+    fn publish_delivers_pmessage_per_matching_pattern() {
+        let mut subscriber = test_context();
+        let mut client = attach_client(&mut subscriber);
+        // with a socket, the confirmations are queued behind any message
+        assert_eq!(run_cmd(&mut subscriber, &["PSUBSCRIBE", "news.*", "*.sports", "weather"]), b"");
+        assert_pushed(
+            &mut client,
+            &[
+                &b"*3\r\n$10\r\npsubscribe\r\n$6\r\nnews.*\r\n:1\r\n"[..],
+                b"*3\r\n$10\r\npsubscribe\r\n$8\r\n*.sports\r\n:2\r\n",
+                b"*3\r\n$10\r\npsubscribe\r\n$7\r\nweather\r\n:3\r\n",
+            ]
+            .concat(),
+        );
+
+        let mut publisher = subscriber.clone();
+        publisher.outbox = None;
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "news.sports", "goal"]), b":2\r\n");
+        let by_prefix = "*4\r\n$8\r\npmessage\r\n$6\r\nnews.*\r\n$11\r\nnews.sports\r\n$4\r\ngoal\r\n";
+        let by_suffix = "*4\r\n$8\r\npmessage\r\n$8\r\n*.sports\r\n$11\r\nnews.sports\r\n$4\r\ngoal\r\n";
+        // map order decides which pattern goes first
+        let mut got = vec![0u8; by_prefix.len() + by_suffix.len()];
+        std::io::Read::read_exact(&mut client, &mut got).unwrap();
+        let got = String::from_utf8_lossy(&got);
+        assert!(got.contains(by_prefix) && got.contains(by_suffix), "{:?}", got);
+
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "weathers", "rain"]), b":0\r\n");
+        assert_nothing_pushed(&mut client);
+    }
+
+    #[test]
+    fn punsubscribe_stops_delivery() {
+        let mut subscriber = test_context();
+        let mut client = attach_client(&mut subscriber);
+        run_cmd(&mut subscriber, &["PSUBSCRIBE", "a*", "b*"]);
+        assert_pushed(
+            &mut client,
+            b"*3\r\n$10\r\npsubscribe\r\n$2\r\na*\r\n:1\r\n*3\r\n$10\r\npsubscribe\r\n$2\r\nb*\r\n:2\r\n",
+        );
+
+        let reply = run_cmd(&mut subscriber, &["PUNSUBSCRIBE", "a*"]);
+        assert_eq!(reply, b"*3\r\n$12\r\npunsubscribe\r\n$2\r\na*\r\n:1\r\n");
+        assert!(subscriber.pattern_pubsub.lock().unwrap().get(&b"a*"[..]).is_none());
+
+        let mut publisher = subscriber.clone();
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "apple", "x"]), b":0\r\n");
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "banana", "y"]), b":1\r\n");
+        assert_pushed(&mut client, b"*4\r\n$8\r\npmessage\r\n$2\r\nb*\r\n$6\r\nbanana\r\n$1\r\ny\r\n");
+
+        // no arguments drops the rest; with none left the pattern is nil
+        assert_eq!(run_cmd(&mut subscriber, &["PUNSUBSCRIBE"]), b"*3\r\n$12\r\npunsubscribe\r\n$2\r\nb*\r\n:0\r\n");
+        assert_eq!(run_cmd(&mut subscriber, &["PUNSUBSCRIBE"]), b"*3\r\n$12\r\npunsubscribe\r\n$-1\r\n:0\r\n");
+        assert!(subscriber.pattern_pubsub.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::commands::pubsub::{encode_pubsub_reply, unsubscribe_pattern, unsubscribe_targets};
+use crate::Context;
+use std::io;
+use crate::resp::Arg;
+
+/// PUNSUBSCRIBE [pattern ...]
+/// Returns one ["punsubscribe", pattern, remaining_count] frame per pattern;
+/// with no arguments, drops every pattern this client is subscribed to
+pub fn cmd_punsubscribe(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_punsubscribe] Received PUNSUBSCRIBE command with args: {:?}", args);
+
+    let patterns = unsubscribe_targets(args, &ctx.subscribed_patterns);
+    if patterns.is_empty() {
+        // nothing to drop: a single frame with a nil pattern
+        return Ok(encode_pubsub_reply(ctx.protocol, "punsubscribe", None, ctx.subscription_count()));
+    }
+
+    let mut resp = Vec::new();
+    for pattern in &patterns {
+        unsubscribe_pattern(ctx, pattern);
+        resp.extend(encode_pubsub_reply(ctx.protocol, "punsubscribe", Some(pattern), ctx.subscription_count()));
+    }
+    Ok(resp)
+}
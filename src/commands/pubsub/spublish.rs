@@ -0,0 +1,31 @@
+use crate::commands::pubsub::PubSubFrames;
+use crate::Context;
+use crate::resp::{Arg, encode_int, encode_resp_error};
+use std::io;
+
+/// SPUBLISH <shardchannel> <message>
+/// Reply: (integer) number of shard subscribers the message was queued for.
+/// Pattern subscriptions never see shard messages.
+pub fn cmd_spublish(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    if args.len() != 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'spublish' command"));
+    }
+    let channel = args[1].as_bytes();
+    let message = args[2].as_bytes();
+
+    // ["smessage", channel, message] to each subscriber
+    let frames = PubSubFrames::new("smessage", &[channel, message]);
+
+    let mut delivered = 0;
+    let mut registry = ctx.shard_pubsub.lock().unwrap();
+    if let Some(subscribers) = registry.get_mut(channel) {
+        subscribers.retain(|subscriber| frames.deliver(subscriber));
+        delivered = subscribers.len();
+        if subscribers.is_empty() {
+            registry.remove(channel);
+        }
+    }
+
+    println!("[cmd_spublish] Delivered to {} subscriber(s) on shard channel '{}'", delivered, args[1]);
+    Ok(encode_int(delivered as i64))
+}
@@ -0,0 +1,110 @@
+use crate::commands::pubsub::{check_same_slot, client_outbox, subscribe_to};
+use crate::Context;
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// SSUBSCRIBE <shardchannel> [shardchannel ...]
+/// Returns one ["ssubscribe", channel, count] frame per channel, where count
+/// only covers shard channels. All channels must hash to the same slot.
+pub fn cmd_ssubscribe(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_ssubscribe] Received SSUBSCRIBE command with args: {:?}", args);
+
+    if args.len() < 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'ssubscribe' command"));
+    }
+    if let Err(reply) = check_same_slot(&args[1..]) {
+        return Ok(reply);
+    }
+
+    let outbox = client_outbox(ctx);
+    let mut resp = Vec::new();
+    for channel in &args[1..] {
+        let first = ctx.subscribed_shard_channels.insert(channel.as_bytes().to_vec());
+        let count = ctx.subscribed_shard_channels.len();
+        resp.extend(subscribe_to(&ctx.shard_pubsub, "ssubscribe", channel.as_bytes(), first, count, outbox.as_ref(), ctx.protocol));
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{assert_nothing_pushed, assert_pushed, attach_client, run_cmd, test_context};
+
+    #[test]
+    fn counts_shard_channels_apart_and_checks_slots() {
+        let mut ctx = test_context();
+        run_cmd(&mut ctx, &["SUBSCRIBE", "news"]);
+        let reply = run_cmd(&mut ctx, &["SSUBSCRIBE", "{a}1", "{a}2"]);
+        assert_eq!(
+            reply,
+            [
+                &b"*3\r\n$10\r\nssubscribe\r\n$4\r\n{a}1\r\n:1\r\n"[..],
+                b"*3\r\n$10\r\nssubscribe\r\n$4\r\n{a}2\r\n:2\r\n",
+            ]
+            .concat()
+        );
+        assert_eq!(ctx.subscription_count(), 1);
+        assert!(ctx.in_subscribed_mode());
+
+        assert_eq!(
+            run_cmd(&mut ctx, &["SSUBSCRIBE", "foo", "bar"]),
+            b"-CROSSSLOT Keys in request don't hash to the same slot\r\n"
+        );
+        assert!(!ctx.subscribed_shard_channels.contains(&b"foo"[..]));
+        assert!(run_cmd(&mut ctx, &["SSUBSCRIBE"]).starts_with(b"-ERR wrong number"));
+    }
+
+    #[test]
+    fn spublish_reaches_shard_subscribers_only() {
+        let mut subscriber = test_context();
+        let mut client = attach_client(&mut subscriber);
+        assert_eq!(run_cmd(&mut subscriber, &["SSUBSCRIBE", "orders"]), b"");
+        assert_pushed(&mut client, b"*3\r\n$10\r\nssubscribe\r\n$6\r\norders\r\n:1\r\n");
+        run_cmd(&mut subscriber, &["PSUBSCRIBE", "*"]);
+        assert_pushed(&mut client, b"*3\r\n$10\r\npsubscribe\r\n$1\r\n*\r\n:1\r\n");
+
+        let mut publisher = subscriber.clone();
+        publisher.outbox = None;
+        assert_eq!(run_cmd(&mut publisher, &["SPUBLISH", "orders", "new"]), b":1\r\n");
+        assert_pushed(&mut client, b"*3\r\n$8\r\nsmessage\r\n$6\r\norders\r\n$3\r\nnew\r\n");
+
+        // classic PUBLISH does not reach shard subscribers
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "orders", "old"]), b":1\r\n");
+        assert_pushed(&mut client, b"*4\r\n$8\r\npmessage\r\n$1\r\n*\r\n$6\r\norders\r\n$3\r\nold\r\n");
+        assert_eq!(run_cmd(&mut publisher, &["SPUBLISH", "other", "x"]), b":0\r\n");
+        assert_nothing_pushed(&mut client);
+        assert!(run_cmd(&mut publisher, &["SPUBLISH", "orders"]).starts_with(b"-ERR wrong number"));
+    }
+
+    #[test]
+    fn sunsubscribe_drops_shard_channels() {
+        let mut first = test_context();
+        let _first_client = attach_client(&mut first);
+        let mut second = first.clone();
+        let _second_client = attach_client(&mut second);
+        run_cmd(&mut first, &["SSUBSCRIBE", "{t}a", "{t}b"]);
+        run_cmd(&mut second, &["SSUBSCRIBE", "{t}a"]);
+
+        assert_eq!(
+            run_cmd(&mut first, &["PUBSUB", "SHARDCHANNELS"]),
+            b"*2\r\n$4\r\n{t}a\r\n$4\r\n{t}b\r\n"
+        );
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "SHARDCHANNELS", "*b"]), b"*1\r\n$4\r\n{t}b\r\n");
+        assert_eq!(
+            run_cmd(&mut first, &["PUBSUB", "SHARDNUMSUB", "{t}a", "{t}b", "none"]),
+            b"*6\r\n$4\r\n{t}a\r\n:2\r\n$4\r\n{t}b\r\n:1\r\n$4\r\nnone\r\n:0\r\n"
+        );
+        // shard channels never show up among classic ones
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "CHANNELS"]), b"*0\r\n");
+
+        assert_eq!(
+            run_cmd(&mut first, &["SUNSUBSCRIBE", "{t}a"]),
+            b"*3\r\n$12\r\nsunsubscribe\r\n$4\r\n{t}a\r\n:1\r\n"
+        );
+        assert_eq!(run_cmd(&mut first, &["PUBSUB", "SHARDNUMSUB", "{t}a"]), b"*2\r\n$4\r\n{t}a\r\n:1\r\n");
+        assert_eq!(run_cmd(&mut first, &["SUNSUBSCRIBE"]), b"*3\r\n$12\r\nsunsubscribe\r\n$4\r\n{t}b\r\n:0\r\n");
+        assert_eq!(run_cmd(&mut first, &["SUNSUBSCRIBE"]), b"*3\r\n$12\r\nsunsubscribe\r\n$-1\r\n:0\r\n");
+        assert!(!first.in_subscribed_mode());
+        assert!(run_cmd(&mut first, &["SUNSUBSCRIBE", "foo", "bar"]).starts_with(b"-CROSSSLOT"));
+    }
+}
@@ -1,37 +1,85 @@
+use crate::commands::pubsub::{client_outbox, subscribe_to};
 use crate::Context;
-use crate::resp::encode_resp_error;
+use crate::resp::{Arg, encode_resp_error};
 use std::io;
 
-/// SUBSCRIBE <channel>
-/// Returns: ["subscribe", channel, count]
-pub fn cmd_subscribe(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
-    if args.len() != 2 {
-        return Ok(encode_resp_error(
-            "ERR wrong number of arguments for 'subscribe' command",
-        ));
-    }
-    let channel = &args[1];
-
-    // Track per-client subscriptions (avoid duplicates)
-    let first = ctx.subscribed_channels.insert(channel.clone());
-    if first {
-        // First time subscribing → register in global pubsub registry
-        if let Some(stream) = ctx.this_client.as_ref() {
-            let subscriber = stream.try_clone()?;
-            let mut registry = ctx.pubsub.lock().unwrap();
-            registry
-                .entry(channel.clone())
-                .or_insert_with(Vec::new)
-                .push(subscriber);
-        }
+/// SUBSCRIBE <channel> [channel ...]
+/// Returns one ["subscribe", channel, count] frame per channel
+pub fn cmd_subscribe(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    if args.len() < 2 {
+        return Ok(encode_resp_error("wrong number of arguments for 'subscribe' command"));
     }
 
-    let count = ctx.subscribed_channels.len();
+    let outbox = client_outbox(ctx);
     let mut resp = Vec::new();
-    resp.extend_from_slice(b"*3\r\n$9\r\nsubscribe\r\n");
-    resp.extend_from_slice(format!("${}\r\n", channel.len()).as_bytes());
-    resp.extend_from_slice(channel.as_bytes());
-    resp.extend_from_slice(b"\r\n");
-    resp.extend_from_slice(format!(":{}\r\n", count).as_bytes());
+    for channel in &args[1..] {
+        // Track per-client subscriptions (avoid duplicates)
+        let first = ctx.subscribed_channels.insert(channel.as_bytes().to_vec());
+        let count = ctx.subscription_count();
+        resp.extend(subscribe_to(&ctx.pubsub, "subscribe", channel.as_bytes(), first, count, outbox.as_ref(), ctx.protocol));
+    }
     Ok(resp)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{assert_nothing_pushed, assert_pushed, attach_client, run_cmd, test_context};
+    use crate::config::ServerConfig;
+    use crate::context::Context;
+    use std::sync::Arc;
+
+    fn frame(kind: &str, channel: &str, count: i64) -> Vec<u8> {
+        format!("*3\r\n${}\r\n{}\r\n${}\r\n{}\r\n:{}\r\n", kind.len(), kind, channel.len(), channel, count).into_bytes()
+    }
+
+    #[test]
+    fn subscribes_to_every_channel_given() {
+        let mut ctx = test_context();
+        let reply = run_cmd(&mut ctx, &["SUBSCRIBE", "a", "b", "a"]);
+        assert_eq!(reply, [frame("subscribe", "a", 1), frame("subscribe", "b", 2), frame("subscribe", "a", 2)].concat());
+        assert!(run_cmd(&mut ctx, &["SUBSCRIBE"]).starts_with(b"-ERR wrong number"));
+    }
+
+    #[test]
+    fn unsubscribe_drops_the_named_channels_or_all_of_them() {
+        let mut subscriber = test_context();
+        let mut client = attach_client(&mut subscriber);
+        run_cmd(&mut subscriber, &["SUBSCRIBE", "a", "b", "c"]);
+        assert_pushed(&mut client, &[frame("subscribe", "a", 1), frame("subscribe", "b", 2), frame("subscribe", "c", 3)].concat());
+
+        let reply = run_cmd(&mut subscriber, &["UNSUBSCRIBE", "b", "zzz"]);
+        assert_eq!(reply, [frame("unsubscribe", "b", 2), frame("unsubscribe", "zzz", 2)].concat());
+
+        let mut publisher = test_context();
+        publisher.pubsub = subscriber.pubsub.clone();
+        publisher.pattern_pubsub = subscriber.pattern_pubsub.clone();
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "b", "x"]), b":0\r\n");
+        assert_eq!(run_cmd(&mut publisher, &["PUBLISH", "c", "y"]), b":1\r\n");
+        assert_pushed(&mut client, b"*3\r\n$7\r\nmessage\r\n$1\r\nc\r\n$1\r\ny\r\n");
+        assert_nothing_pushed(&mut client);
+
+        let reply = run_cmd(&mut subscriber, &["UNSUBSCRIBE"]);
+        assert_eq!(reply, [frame("unsubscribe", "a", 1), frame("unsubscribe", "c", 0)].concat());
+        assert!(subscriber.pubsub.lock().unwrap().is_empty());
+        assert_eq!(run_cmd(&mut subscriber, &["UNSUBSCRIBE"]), b"*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n");
+    }
+
+    #[test]
+    fn the_pubsub_output_limit_lasts_until_the_last_subscription_goes() {
+        let mut cfg = ServerConfig::for_tests(&std::env::temp_dir());
+        cfg.client_output_buffer_limit_pubsub.hard_bytes = 1 << 20;
+        let databases = cfg.databases;
+        let mut ctx = Context::new(Arc::new(cfg), vec![Default::default(); databases]);
+        let _client = attach_client(&mut ctx);
+        let hard_limit = |ctx: &Context| ctx.outbox.as_ref().unwrap().limit().hard_bytes;
+        assert_eq!(hard_limit(&ctx), 0);
+
+        run_cmd(&mut ctx, &["SUBSCRIBE", "a"]);
+        run_cmd(&mut ctx, &["PSUBSCRIBE", "p*"]);
+        assert_eq!(hard_limit(&ctx), 1 << 20);
+        run_cmd(&mut ctx, &["UNSUBSCRIBE"]);
+        assert_eq!(hard_limit(&ctx), 1 << 20);
+        run_cmd(&mut ctx, &["PUNSUBSCRIBE"]);
+        assert_eq!(hard_limit(&ctx), 0);
+    }
+}
@@ -0,0 +1,28 @@
+use crate::commands::pubsub::{encode_pubsub_reply, check_same_slot, unsubscribe_shard_channel, unsubscribe_targets};
+use crate::Context;
+use std::io;
+use crate::resp::Arg;
+
+/// SUNSUBSCRIBE [shardchannel ...]
+/// Returns one ["sunsubscribe", channel, remaining_count] frame per channel;
+/// with no arguments, drops every shard channel this client is subscribed to
+pub fn cmd_sunsubscribe(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_sunsubscribe] Received SUNSUBSCRIBE command with args: {:?}", args);
+
+    if let Err(reply) = check_same_slot(&args[1..]) {
+        return Ok(reply);
+    }
+
+    let channels = unsubscribe_targets(args, &ctx.subscribed_shard_channels);
+    if channels.is_empty() {
+        // nothing to drop: a single frame with a nil channel
+        return Ok(encode_pubsub_reply(ctx.protocol, "sunsubscribe", None, 0));
+    }
+
+    let mut resp = Vec::new();
+    for channel in &channels {
+        unsubscribe_shard_channel(ctx, channel);
+        resp.extend(encode_pubsub_reply(ctx.protocol, "sunsubscribe", Some(channel), ctx.subscribed_shard_channels.len()));
+    }
+    Ok(resp)
+}
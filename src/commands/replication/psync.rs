@@ -1,9 +1,23 @@
 use crate::commands::Context;
-use crate::rdb::EMPTY_RDB;
-use crate::resp::{encode_simple_resp_string, encode_resp_error};
+use crate::context::ReplicaLink;
+use crate::persistence::snapshot_databases;
+use crate::rdb::encode::encode_rdb_snapshot;
+use crate::resp::{Arg, encode_simple_resp_string, encode_resp_error};
 use std::io;
+use std::sync::atomic::Ordering;
 
-pub fn cmd_psync(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// PSYNC for a client connection: copying the dataset and serializing it
+/// are heavy synchronous work, so they run on the blocking pool rather than
+/// on the connection's runtime worker.
+pub async fn cmd_psync_blocking(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let args = args.to_vec();
+    let mut ctx = ctx.clone();
+    tokio::task::spawn_blocking(move || cmd_psync(&args, &mut ctx))
+        .await
+        .map_err(io::Error::other)?
+}
+
+pub fn cmd_psync(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_psync] Received PSYNC command with args: {:?}", args);
 
     // Validate argument count
@@ -21,44 +35,69 @@ pub fn cmd_psync(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     let requested_offset = args[2].parse::<usize>().unwrap_or(0);
     println!("[cmd_psync] Parsed requested offset: {}", requested_offset);
 
-    // Attempt to register the current client as a replica
-    if let Some(ref stream) = ctx.this_client {
-        match stream.peer_addr() {
-            Ok(peer) => {
-                ctx.replicas
-                    .lock()
-                    .unwrap()
-                    .insert(peer, (stream.try_clone()?, requested_offset));
-                println!(
-                    "[cmd_psync] Registered replica: {:?} with offset {}",
-                    peer, requested_offset
-                );
-            }
-            Err(e) => {
-                eprintln!("[cmd_psync] Failed to get peer address: {}", e);
-            }
+    let (outbox, peer) = match (ctx.outbox.clone(), ctx.peer) {
+        (Some(outbox), Some(peer)) => (outbox, peer),
+        _ => {
+            eprintln!("[cmd_psync] No active client connection found in Context.");
+            return Ok(encode_resp_error("PSYNC requires a client connection"));
         }
-    } else {
-        eprintln!("[cmd_psync] No active client stream found in Context.");
-    }
+    };
 
-    // Respond with FULLRESYNC header and initial RDB
-    let full = format!(
-        "FULLRESYNC {} {}",
-        ctx.cfg.master_replid, ctx.master_repl_offset
+    // Capture the dataset and register the replica in one step: every write
+    // either lands in this copy or is buffered in the replica's backlog, and
+    // the offset is where the stream the replica gets starts.
+    let (snapshot, offset) = {
+        let _barrier = ctx.sync_lock.write().unwrap();
+        let data = snapshot_databases(ctx);
+        let mut reps = ctx.replicas.lock().unwrap();
+        let offset = ctx.master_repl_offset.load(Ordering::Relaxed);
+        // the replicas share one stream, so they all SELECT again for the new one
+        for link in reps.values_mut() {
+            link.selected_db = None;
+        }
+        reps.insert(
+            peer,
+            ReplicaLink {
+                outbox: outbox.clone(),
+                ack_offset: offset,
+                backlog: Some(Vec::new()),
+                selected_db: None,
+            },
+        );
+        (data, offset)
+    };
+    println!(
+        "[cmd_psync] Registered replica {:?}; captured {} key(s) for full resync",
+        peer,
+        snapshot.iter().map(|db| db.len()).sum::<usize>()
     );
+
+    // Serialize outside every lock so other clients keep running meanwhile
+    let rdb = encode_rdb_snapshot(&snapshot)?;
+
+    // Respond with FULLRESYNC header and the snapshot
+    let full = format!("FULLRESYNC {} {}", ctx.cfg.master_replid, offset);
     println!("[cmd_psync] Responding to replica with: {}", full);
 
     let mut out = encode_simple_resp_string(&full);
-
-    let rdb_len = EMPTY_RDB.len();
-    out.extend_from_slice(format!("${}\r\n", rdb_len).as_bytes());
-    out.extend_from_slice(EMPTY_RDB);
+    out.extend_from_slice(format!("${}\r\n", rdb.len()).as_bytes());
+    out.extend_from_slice(&rdb);
+    outbox.push(out);
 
     println!(
         "[cmd_psync] Sent FULLRESYNC header and RDB payload ({} bytes)",
-        rdb_len
+        rdb.len()
     );
 
-    Ok(out)
+    // Stream whatever was written meanwhile, then switch the replica online
+    let mut reps = ctx.replicas.lock().unwrap();
+    if let Some(link) = reps.get_mut(&peer) {
+        let backlog = link.backlog.take().unwrap_or_default();
+        println!("[cmd_psync] Flushing {} buffered write(s) to replica", backlog.len());
+        for buf in backlog {
+            link.outbox.push(buf);
+        }
+    }
+
+    Ok(vec![]) // already queued on the replica's outbox
 }
@@ -1,15 +1,12 @@
 use crate::commands::Context;
-use crate::resp::{encode_simple_resp_string, encode_resp_error, write_resp_array};
-use std::io::{self, Write};
+use crate::resp::{Arg, encode_simple_resp_string, encode_resp_error, write_resp_array};
+use std::io;
+use std::sync::atomic::Ordering;
 use std::net::SocketAddr;
 
 /// REPLCONF <option> <value>
-pub fn cmd_replconf(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
-    let peer = ctx
-        .this_client
-        .as_ref()
-        .and_then(|s| s.peer_addr().ok())
-        .unwrap_or_else(|| "unknown address".parse().unwrap());
+pub fn cmd_replconf(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let peer = ctx.peer.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
 
     println!("[cmd_replconf] Received REPLCONF from {:?} with args: {:?}", peer, args);
 
@@ -32,15 +29,13 @@ pub fn cmd_replconf(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
             Ok(encode_simple_resp_string("OK"))
         }
         "ack" => {
-            if let Ok(offset) = value.parse::<usize>() {
-                if let Some(ref stream) = ctx.this_client {
-                    let peer =stream.peer_addr().unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
-                    if let Some(replica) = ctx.replicas.lock().unwrap().get_mut(&peer) {
-                        replica.1 = offset;
-                        println!("[cmd_replconf] Updated replica offset: {:?} -> {}", peer, offset);
-                    } else {
-                        println!("[cmd_replconf] ACK received from unregistered replica: {:?}", peer);
-                    }
+            if let Ok(offset) = value.parse::<u64>() {
+                if let Some(replica) = ctx.replicas.lock().unwrap().get_mut(&peer) {
+                    replica.ack_offset = offset;
+                    println!("[cmd_replconf] Updated replica offset: {:?} -> {}", peer, offset);
+                    ctx.replica_acked.notify_waiters();
+                } else {
+                    println!("[cmd_replconf] ACK received from unregistered replica: {:?}", peer);
                 }
             } else {
                 eprintln!("[cmd_replconf] Invalid ACK offset: '{}'", value);
@@ -49,15 +44,11 @@ pub fn cmd_replconf(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         }
         "getack" if value == "*" => {
             println!("[cmd_replconf] GETACK received - replying with current offset");
-            if let Some(ref mut stream) = ctx.this_client {
-                let ack_value = ctx.master_repl_offset.to_string();
-                write_resp_array(stream, &["REPLCONF", "ACK", &ack_value])?;
-                stream.flush()?;
-                println!("[cmd_replconf] Sent: REPLCONF ACK {}", ack_value);
-            } else {
-                eprintln!("[cmd_replconf] No stream available to reply with ACK");
-            }
-            Ok(vec![]) // No RESP reply needed, we already wrote directly
+            let ack_value = ctx.master_repl_offset.load(Ordering::Relaxed).to_string();
+            let mut ack = Vec::new();
+            write_resp_array(&mut ack, &["REPLCONF", "ACK", &ack_value])?;
+            println!("[cmd_replconf] Replying: REPLCONF ACK {}", ack_value);
+            Ok(ack)
         }
         _ => {
             println!("[cmd_replconf] Unhandled REPLCONF option: '{}'", option);
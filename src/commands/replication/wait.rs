@@ -1,64 +1,103 @@
 use crate::context::Context;
-use crate::resp::{encode_int, encode_resp_error, write_resp_array};
+use crate::resp::{Arg, encode_bulk_resp_string, encode_int, encode_resp_array, encode_resp_error};
 use std::io;
-use std::io::Write;
-use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::time::Instant;
 
-pub fn cmd_wait(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// WAIT as run inside MULTI/EXEC: asks for ACKs but doesn't wait for them,
+/// replying with the replicas already caught up.
+pub fn cmd_wait(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let (_, target, _) = match request_acks(args, ctx) {
+        Ok(wait) => wait,
+        Err(reply) => return Ok(reply),
+    };
+    Ok(encode_int(acked_count(ctx, target) as i64))
+}
+
+/// WAIT <num_replicas> <timeout_ms> from a connection: counts the ACKs
+/// again each time a replica sends one, until enough replicas reached our
+/// offset or the timeout passes (0: no timeout).
+pub async fn cmd_wait_blocking(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let replica_acked = ctx.replica_acked.clone();
+    // listen before sending GETACK, so no ACK can come in unnoticed
+    let mut next_ack = Box::pin(replica_acked.notified());
+    next_ack.as_mut().enable();
+
+    let (needed, target, deadline) = match request_acks(args, ctx) {
+        Ok(wait) => wait,
+        Err(reply) => return Ok(reply),
+    };
+
+    let mut acked = acked_count(ctx, target);
+    while acked < needed {
+        match deadline {
+            Some(deadline) => {
+                if tokio::time::timeout_at(deadline, next_ack.as_mut()).await.is_err() {
+                    break;
+                }
+            }
+            None => next_ack.as_mut().await,
+        }
+        next_ack.set(replica_acked.notified());
+        next_ack.as_mut().enable();
+        acked = acked_count(ctx, target);
+    }
+
+    if acked >= needed {
+        println!("[cmd_wait] Required ACKs received: {}", acked);
+    } else {
+        println!("[cmd_wait] Timeout reached with {} ACKs", acked);
+    }
+    Ok(encode_int(acked as i64))
+}
+
+/// Parses WAIT's arguments and sends one REPLCONF GETACK * to each replica.
+/// Returns how many replicas to wait for, the offset they must reach and
+/// the deadline (`None` for timeout 0), or the error reply.
+fn request_acks(args: &[Arg], ctx: &mut Context) -> Result<(usize, u64, Option<Instant>), Vec<u8>> {
     // exactly two arguments
     if args.len() != 3 {
-        return Ok(encode_resp_error("usage: WAIT <num_replicas> <timeout_ms>"));
+        return Err(encode_resp_error("usage: WAIT <num_replicas> <timeout_ms>"));
     }
 
     let needed = match args[1].parse::<usize>() {
         Ok(n) => n,
-        Err(_) => return Ok(encode_resp_error("ERR invalid replica count")),
+        Err(_) => return Err(encode_resp_error("ERR invalid replica count")),
     };
     let timeout_ms = match args[2].parse::<u64>() {
         Ok(ms) => ms,
-        Err(_) => return Ok(encode_resp_error("ERR invalid timeout")),
+        Err(_) => return Err(encode_resp_error("ERR invalid timeout")),
     };
 
-    // snapshot the offset our replicas must reach
-    let target = ctx.master_repl_offset;
-    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    // the offset our replicas must reach; the GETACK itself comes after it
+    let mut reps = ctx.replicas.lock().unwrap();
+    let target = ctx.master_repl_offset.load(Ordering::Relaxed);
+    let deadline = (timeout_ms > 0).then(|| Instant::now() + Duration::from_millis(timeout_ms));
 
     println!(
         "[cmd_wait] WAIT for {} replicas to ACK offset {} (timeout={}ms)",
         needed, target, timeout_ms
     );
 
-    // send exactly one REPLCONF GETACK * to each replica
-    {
-        let mut reps = ctx.replicas.lock().unwrap();
-        for (&addr, (rs, _)) in reps.iter_mut() {
-            let getack = ["REPLCONF", "GETACK", "*"];
-            write_resp_array(rs, &getack)?;
-            rs.flush()?;
-            println!("[cmd_wait] Sent GETACK to replica {}", addr);
-        }
+    let getack = encode_resp_array(&["REPLCONF", "GETACK", "*"].map(encode_bulk_resp_string));
+    if !reps.is_empty() {
+        ctx.master_repl_offset.fetch_add(getack.len() as u64, Ordering::Relaxed);
     }
-
-    // spin until enough ACKs or we time out
-    let mut acked = 0;
-    while Instant::now() < deadline {
-        acked = {
-            let reps = ctx.replicas.lock().unwrap();
-            reps
-                .values()
-                .filter(|&(_, last_ack)| *last_ack >= target)
-                .count()
-        };
-        if acked >= needed {
-            println!("[cmd_wait] Required ACKs received: {}", acked);
-            break;
+    for (&addr, link) in reps.iter_mut() {
+        match link.backlog.as_mut() {
+            Some(backlog) => backlog.push(getack.clone()),
+            None => {
+                link.outbox.push(getack.clone());
+            }
         }
-        sleep(Duration::from_millis(1));
+        println!("[cmd_wait] Sent GETACK to replica {}", addr);
     }
 
-    if acked < needed {
-        println!("[cmd_wait] Timeout reached with {} ACKs", acked);
-    }
-    Ok(encode_int(acked as i64))
+    Ok((needed, target, deadline))
+}
+
+fn acked_count(ctx: &Context, target: u64) -> usize {
+    let reps = ctx.replicas.lock().unwrap();
+    reps.values().filter(|link| link.ack_offset >= target).count()
 }
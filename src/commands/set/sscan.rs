@@ -0,0 +1,41 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::keyspace::scan::{encode_scan_reply, parse_scan_args, scan_collection};
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count] -> [cursor, [member ...]]
+pub fn cmd_sscan(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_sscan] Received SSCAN command with args: {:?}", args);
+
+    if args.len() < 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'sscan' command"));
+    }
+    let (cursor, opts) = match parse_scan_args(&args[2..], None) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
+    };
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    let set = match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((Value::Set(set), _)) => set,
+        Some(_) => {
+            eprintln!("[cmd_sscan] WRONGTYPE: key '{}' is not a set", args[1]);
+            return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            return Ok(encode_scan_reply(0, &[]));
+        }
+    };
+
+    let (cursor, members) = scan_collection(cursor, opts.count, |cursor, out| {
+        set.scan(cursor, |member, _| out.push(member.clone()))
+    });
+    let reply: Vec<Vec<u8>> = members.into_iter().filter(|m| opts.matches(m)).collect();
+
+    println!("[cmd_sscan] Returning {} member(s), next cursor {}", reply.len(), cursor);
+    Ok(encode_scan_reply(cursor, &reply))
+}
@@ -1,30 +1,31 @@
-use crate::commands::Context;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_NEW, NOTIFY_STREAM};
 use crate::rdb::{StreamEntry, Value};
-use crate::resp::{encode_bulk_resp_string, encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_string, encode_resp_error};
 use std::io;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn cmd_xadd(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_xadd(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_xadd] Received XADD command with args: {:?}", args);
 
-    if args.len() < 5 || (args.len() - 3) % 2 != 0 {
+    if args.len() < 5 || !(args.len() - 3).is_multiple_of(2) {
         println!("[cmd_xadd] Invalid number of arguments.");
         return Ok(encode_resp_error(
             "usage: XADD <key> <id> <field> <value> [<field> <value> ...]",
         ));
     }
 
-    let key = &args[1];
+    let key = args[1].as_bytes();
     let id_raw = &args[2];
 
-    println!("[cmd_xadd] Target stream key: '{}', Raw ID: '{}'", key, id_raw);
+    println!("[cmd_xadd] Target stream key: '{}', Raw ID: '{}'", args[1], id_raw);
 
     let (ms, seq, final_id) = if id_raw == "*" {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Clock error");
         let ms = now.as_millis() as u64;
 
         let max_seq = {
-            let map = ctx.store.lock().unwrap();
+            let map = ctx.store.lock(key);
             map.get(key).and_then(|pair| {
                 if let Value::Stream(entries) = &pair.0 {
                     entries
@@ -55,7 +56,7 @@ pub fn cmd_xadd(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         };
 
         let max_seq = {
-            let map = ctx.store.lock().unwrap();
+            let map = ctx.store.lock(key);
             map.get(key).and_then(|pair| {
                 if let Value::Stream(entries) = &pair.0 {
                     entries
@@ -82,12 +83,12 @@ pub fn cmd_xadd(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         let ms = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
         let seq = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
         println!("[cmd_xadd] Using manual ID: {}-{}", ms, seq);
-        (ms, seq, id_raw.clone())
+        (ms, seq, id_raw.to_string())
     };
 
     if !id_raw.ends_with("-*") && id_raw != "*" {
         let (last_ms, last_seq) = {
-            let map = ctx.store.lock().unwrap();
+            let map = ctx.store.lock(key);
             if let Some(pair) = map.get(key) {
                 if let Value::Stream(entries) = &pair.0 {
                     if let Some(last) = entries.last() {
@@ -120,32 +121,33 @@ pub fn cmd_xadd(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         }
     }
 
-    let fields: Vec<(String, String)> = args[3..]
+    let fields: Vec<(Vec<u8>, Vec<u8>)> = args[3..]
         .chunks(2)
-        .map(|chunk| (chunk[0].clone(), chunk[1].clone()))
+        .map(|chunk| (chunk[0].as_bytes().to_vec(), chunk[1].as_bytes().to_vec()))
         .collect();
 
     println!("[cmd_xadd] Parsed {} field-value pair(s)", fields.len());
 
-    let mut map = ctx.store.lock().unwrap();
+    let store = ctx.store.clone();
+    let mut map = store.lock(key);
     match map.get_mut(key) {
         Some((Value::Stream(ref mut entries), _)) => {
-            println!("[cmd_xadd] Appending entry to existing stream at key '{}'", key);
+            println!("[cmd_xadd] Appending entry to existing stream at key '{}'", args[1]);
             entries.push(StreamEntry {
                 id: final_id.clone(),
                 fields,
             });
         }
         Some(_) => {
-            eprintln!("[cmd_xadd] WRONGTYPE: Key '{}' exists but is not a stream", key);
+            eprintln!("[cmd_xadd] WRONGTYPE: Key '{}' exists but is not a stream", args[1]);
             return Ok(encode_resp_error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value",
             ));
         }
         None => {
-            println!("[cmd_xadd] Creating new stream at key '{}'", key);
+            println!("[cmd_xadd] Creating new stream at key '{}'", args[1]);
             map.insert(
-                key.clone(),
+                key.to_vec(),
                 (
                     Value::Stream(vec![StreamEntry {
                         id: final_id.clone(),
@@ -154,8 +156,12 @@ pub fn cmd_xadd(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
                     None,
                 ),
             );
+            notify_keyspace_event(ctx, NOTIFY_NEW, "new", key, ctx.db_index);
         }
     }
+    notify_keyspace_event(ctx, NOTIFY_STREAM, "xadd", key, ctx.db_index);
+    record_write(ctx, args)?;
+    ctx.stream_added.notify_waiters();
 
     println!("[cmd_xadd] Successfully added entry with ID: {}", final_id);
     Ok(encode_bulk_resp_string(&final_id))
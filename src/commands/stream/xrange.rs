@@ -1,9 +1,11 @@
+use crate::commands::keyspace::live_entry;
 use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
 use crate::rdb::{StreamEntry, Value};
-use crate::resp::{encode_bulk_resp_string, encode_resp_array, encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_bulk_resp_string, encode_resp_array, encode_resp_error};
 use std::io;
 
-pub fn cmd_xrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_xrange(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_xrange] Received XRANGE command: {:?}", args);
 
     if args.len() != 4 {
@@ -11,14 +13,17 @@ pub fn cmd_xrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         return Ok(encode_resp_error("usage: XRANGE <key> <start> <end>"));
     }
 
-    let key = &args[1];
+    let key = args[1].as_bytes();
     let start_raw = &args[2];
     let end_raw = &args[3];
 
     let entries: Vec<StreamEntry> = {
-        let map = ctx.store.lock().unwrap();
-        match map.get(key) {
-            None => return Ok(b"*0\r\n".to_vec()),
+        let mut map = ctx.store.lock(key);
+        match live_entry(ctx, ctx.db_index, &mut map, key) {
+            None => {
+                notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+                return Ok(encode_resp_array(&[]));
+            }
             Some((Value::Stream(v), _)) => v.clone(),
             Some(_) => {
                 return Ok(encode_resp_error(
@@ -92,8 +97,8 @@ pub fn cmd_xrange(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         let mut inner = vec![encode_bulk_resp_string(&entry.id)];
         let mut kv_array = Vec::with_capacity(entry.fields.len() * 2);
         for (k, v) in entry.fields {
-            kv_array.push(encode_bulk_resp_string(&k));
-            kv_array.push(encode_bulk_resp_string(&v));
+            kv_array.push(encode_bulk_resp_bytes(&k));
+            kv_array.push(encode_bulk_resp_bytes(&v));
         }
         inner.push(encode_resp_array(&kv_array));
         outer.push(encode_resp_array(&inner));
@@ -1,11 +1,93 @@
 use crate::commands::Context;
 use crate::rdb::{StreamEntry, Value};
-use crate::resp::{encode_bulk_resp_string, encode_resp_array, encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_bulk_resp_string, encode_null_reply, encode_resp_array, encode_resp_error};
 use std::io;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use tokio::time::Instant;
 
-pub fn cmd_xread(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// A stream's key and the entries read from it.
+type StreamRead = (Vec<u8>, Vec<StreamEntry>);
+
+struct XreadRequest {
+    block_ms: Option<u64>,
+    keys: Vec<Vec<u8>>,
+    /// (ms, seq) each stream is read past
+    start_positions: Vec<(u64, u64)>,
+}
+
+/// XREAD as run inside MULTI/EXEC: BLOCK doesn't wait, so nothing new is a
+/// timeout.
+pub fn cmd_xread(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let req = match parse_request(args, ctx) {
+        Ok(req) => req,
+        Err(reply) => return Ok(reply),
+    };
+    Ok(read_now(ctx, &req))
+}
+
+/// XREAD [BLOCK <ms>] STREAMS ... from a connection. With BLOCK and nothing
+/// new yet, looks again each time an XADD wakes it, until an entry arrives
+/// or the timeout passes (BLOCK 0: forever). Each look at the streams holds
+/// the sync barrier shared, so it never sees a transaction half applied.
+pub async fn cmd_xread_blocking(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let sync_lock = ctx.sync_lock.clone();
+    let parsed = {
+        let _shared = sync_lock.read().unwrap();
+        parse_request(args, ctx)
+    };
+    let req = match parsed {
+        Ok(req) => req,
+        Err(reply) => return Ok(reply),
+    };
+    let deadline = match req.block_ms {
+        Some(0) => None,
+        Some(ms) => Some(Instant::now() + Duration::from_millis(ms)),
+        None => {
+            let _shared = sync_lock.read().unwrap();
+            return Ok(read_now(ctx, &req));
+        }
+    };
+
+    let stream_added = ctx.stream_added.clone();
+    loop {
+        // listen before looking, so an XADD in between still wakes us
+        let added = stream_added.notified();
+        tokio::pin!(added);
+        added.as_mut().enable();
+
+        let collected = {
+            let _shared = sync_lock.read().unwrap();
+            collect(ctx, &req)
+        };
+        match collected {
+            Err(_) => return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
+            Ok(results) if !results.is_empty() => return Ok(encode_results(results)),
+            Ok(_) => {}
+        }
+        match deadline {
+            Some(deadline) => {
+                if tokio::time::timeout_at(deadline, added).await.is_err() {
+                    return Ok(encode_null_reply(ctx.protocol));
+                }
+            }
+            None => added.await,
+        }
+    }
+}
+
+/// The reply without waiting for new entries.
+fn read_now(ctx: &Context, req: &XreadRequest) -> Vec<u8> {
+    match collect(ctx, req) {
+        Err(_) => encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"),
+        Ok(results) if !results.is_empty() => encode_results(results),
+        Ok(_) if req.block_ms.is_some() => encode_null_reply(ctx.protocol),
+        Ok(_) => encode_resp_array(&[]),
+    }
+}
+
+/// Parses XREAD's arguments and resolves each start ID (`$` included) to a
+/// position, or returns the error reply.
+fn parse_request(args: &[Arg], ctx: &Context) -> Result<XreadRequest, Vec<u8>> {
     println!("[cmd_xread] called with args: {:?}", args);
 
     let mut idx = 1;
@@ -19,27 +101,27 @@ pub fn cmd_xread(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     };
 
     if args.get(idx).map(|s| s.to_lowercase()) != Some("streams".into()) {
-        return Ok(encode_resp_error("usage: XREAD [BLOCK <ms>] STREAMS <key> [<key> ...] <id> [<id> ...]"));
+        return Err(encode_resp_error("usage: XREAD [BLOCK <ms>] STREAMS <key> [<key> ...] <id> [<id> ...]"));
     }
     idx += 1;
 
     let rem = args.len() - idx;
-    if rem < 2 || rem % 2 != 0 {
-        return Ok(encode_resp_error("usage: XREAD [BLOCK <ms>] STREAMS <key> [<key> ...] <id> [<id> ...]"));
+    if rem < 2 || !rem.is_multiple_of(2) {
+        return Err(encode_resp_error("usage: XREAD [BLOCK <ms>] STREAMS <key> [<key> ...] <id> [<id> ...]"));
     }
 
     let n_streams = rem / 2;
-    let keys = &args[idx..idx + n_streams];
+    let keys: Vec<Vec<u8>> = args[idx..idx + n_streams].iter().map(|key| key.as_bytes().to_vec()).collect();
     let starts = &args[idx + n_streams..];
 
     let mut start_positions = Vec::with_capacity(n_streams);
     {
-        let store = ctx.store.lock().unwrap();
+        let shards = ctx.store.lock_keys(keys.iter().map(Vec::as_slice));
         for (key, start_raw) in keys.iter().zip(starts.iter()) {
-            let entries = match store.get(key) {
-                Some((Value::Stream(v), _)) => v.clone(),
-                Some(_) => return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
-                None => Vec::new(),
+            let entries: &[StreamEntry] = match shards.shard(key).get(key) {
+                Some((Value::Stream(v), _)) => v,
+                Some(_) => return Err(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
+                None => &[],
             };
 
             let pos = if start_raw == "$" {
@@ -67,85 +149,53 @@ pub fn cmd_xread(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         }
     }
 
-    let collect = || -> Result<Vec<(String, Vec<StreamEntry>)>, ()> {
-        let store = ctx.store.lock().unwrap();
-        let mut out = Vec::new();
-
-        for (key, &(start_ms, start_seq)) in keys.iter().zip(start_positions.iter()) {
-            let entries = match store.get(key) {
-                Some((Value::Stream(v), _)) => v.clone(),
-                Some(_) => return Err(()),
-                None => Vec::new(),
-            };
-
-            let filtered = entries
-                .into_iter()
-                .filter(|e| {
-                    let mut p = e.id.splitn(2, '-');
-                    let ems = p.next().and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
-                    let eseq = p.next().and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
-                    ems > start_ms || (ems == start_ms && eseq > start_seq)
-                })
-                .collect::<Vec<_>>();
-
-            if !filtered.is_empty() {
-                out.push((key.clone(), filtered));
-            }
-        }
-
-        Ok(out)
-    };
-
-    let mut results = match collect() {
-        Ok(r) => r,
-        Err(_) => return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
-    };
+    Ok(XreadRequest { block_ms, keys, start_positions })
+}
 
-    if results.is_empty() {
-        match block_ms {
-            None => return Ok(b"*0\r\n".to_vec()),
-            Some(0) => {
-                loop {
-                    thread::sleep(Duration::from_millis(10));
-                    results = match collect() {
-                        Ok(r) => r,
-                        Err(_) => return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
-                    };
-                    if !results.is_empty() {
-                        break;
-                    }
-                }
-            }
-            Some(ms) => {
-                let deadline = Instant::now() + Duration::from_millis(ms);
-                while Instant::now() < deadline {
-                    thread::sleep(Duration::from_millis(10));
-                    results = match collect() {
-                        Ok(r) => r,
-                        Err(_) => return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value")),
-                    };
-                    if !results.is_empty() {
-                        break;
-                    }
-                }
-                if results.is_empty() {
-                    return Ok(b"$-1\r\n".to_vec()); // null bulk
-                }
-            }
+/// The entries past each stream's start position, for the streams that
+/// have any; `Err` if a key holds something other than a stream.
+fn collect(ctx: &Context, req: &XreadRequest) -> Result<Vec<StreamRead>, ()> {
+    let shards = ctx.store.lock_keys(req.keys.iter().map(Vec::as_slice));
+    let mut out = Vec::new();
+
+    for (key, &(start_ms, start_seq)) in req.keys.iter().zip(req.start_positions.iter()) {
+        let entries: &[StreamEntry] = match shards.shard(key).get(key) {
+            Some((Value::Stream(v), _)) => v,
+            Some(_) => return Err(()),
+            None => &[],
+        };
+
+        // only the entries past the start are copied out
+        let filtered = entries
+            .iter()
+            .filter(|e| {
+                let mut p = e.id.splitn(2, '-');
+                let ems = p.next().and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
+                let eseq = p.next().and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
+                ems > start_ms || (ems == start_ms && eseq > start_seq)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !filtered.is_empty() {
+            out.push((key.clone(), filtered));
         }
     }
 
-    // Encode result
+    Ok(out)
+}
+
+fn encode_results(results: Vec<StreamRead>) -> Vec<u8> {
     let mut outer = Vec::new();
     for (key, entries) in results {
-        let mut stream_data = vec![encode_bulk_resp_string(&key)];
+        let mut stream_data = vec![encode_bulk_resp_bytes(&key)];
 
         let mut entry_arrs = Vec::with_capacity(entries.len());
         for entry in entries {
             let mut fields = Vec::with_capacity(entry.fields.len() * 2);
             for (k, v) in entry.fields {
-                fields.push(encode_bulk_resp_string(&k));
-                fields.push(encode_bulk_resp_string(&v));
+                fields.push(encode_bulk_resp_bytes(&k));
+                fields.push(encode_bulk_resp_bytes(&v));
             }
 
             let entry_row = vec![
@@ -159,5 +209,5 @@ pub fn cmd_xread(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         outer.push(encode_resp_array(&stream_data));
     }
 
-    Ok(encode_resp_array(&outer))
+    encode_resp_array(&outer)
 }
@@ -1,11 +1,12 @@
 use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED, NOTIFY_KEY_MISS};
 use crate::rdb::Value;
-use crate::resp::{encode_bulk_resp_string, encode_resp_error};
+use crate::resp::{Arg, encode_bulk_resp_bytes, encode_null_reply, encode_resp_error};
 use std::io;
 use std::time::SystemTime;
 
 /// GET key -> BulkString or NullBulk
-pub fn cmd_get(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_get(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_get] called with args: {:?}", args);
 
     if args.len() != 2 {
@@ -13,31 +14,34 @@ pub fn cmd_get(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         return Ok(encode_resp_error("usage: GET <key>"));
     }
 
-    let key = &args[1];
-    println!("[cmd_get] looking up key: {}", key);
+    let key = args[1].as_bytes();
+    println!("[cmd_get] looking up key: {}", args[1]);
 
-    let mut map = ctx.store.lock().unwrap();
+    let mut map = ctx.store.lock(key);
     if let Some((val, opt_expiry)) = map.get(key).cloned() {
         if let Some(exp) = opt_expiry {
             if SystemTime::now() >= exp {
-                println!("[cmd_get] key expired: {}", key);
+                println!("[cmd_get] key expired: {}", args[1]);
                 map.remove(key);
-                return Ok(b"$-1\r\n".to_vec()); // Null bulk
+                notify_keyspace_event(ctx, NOTIFY_EXPIRED, "expired", key, ctx.db_index);
+                notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+                return Ok(encode_null_reply(ctx.protocol));
             }
         }
 
         match val {
             Value::String(s) => {
-                println!("[cmd_get] found string value for key: {}", key);
-                Ok(encode_bulk_resp_string(&s))
+                println!("[cmd_get] found string value for key: {}", args[1]);
+                Ok(encode_bulk_resp_bytes(&s))
             }
             _ => {
-                println!("[cmd_get] wrong type for key: {}", key);
+                println!("[cmd_get] wrong type for key: {}", args[1]);
                 Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"))
             }
         }
     } else {
-        println!("[cmd_get] key not found: {}", key);
-        Ok(b"$-1\r\n".to_vec()) // Null bulk
+        println!("[cmd_get] key not found: {}", args[1]);
+        notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", key, ctx.db_index);
+        Ok(encode_null_reply(ctx.protocol))
     }
 }
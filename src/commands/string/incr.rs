@@ -1,10 +1,11 @@
-use crate::commands::Context;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_NEW, NOTIFY_STRING};
 use crate::rdb::Value;
-use crate::resp::{encode_resp_error};
+use crate::resp::{Arg, encode_int, encode_resp_error};
 use std::io;
 
 /// INCR <key>
-pub fn cmd_incr(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_incr(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_incr] called with args: {:?}", args);
 
     // 1) Validate args
@@ -12,24 +13,27 @@ pub fn cmd_incr(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         println!("[cmd_incr] invalid number of arguments");
         return Ok(encode_resp_error("usage: INCR <key>"));
     }
-    let key = &args[1];
-    println!("[cmd_incr] operating on key: {}", key);
+    let key = args[1].as_bytes();
+    println!("[cmd_incr] operating on key: {}", args[1]);
 
     // 2) Lock store
-    let mut map = ctx.store.lock().unwrap();
+    let store = ctx.store.clone();
+    let mut map = store.lock(key);
 
     match map.get_mut(key) {
         Some((val, _)) => match val {
             Value::String(s) => {
-                println!("[cmd_incr] found existing string value: {}", s);
-                match s.parse::<i64>() {
-                    Ok(n) => {
+                println!("[cmd_incr] found existing string value: {}", String::from_utf8_lossy(s));
+                match std::str::from_utf8(s).ok().and_then(|s| s.parse::<i64>().ok()) {
+                    Some(n) => {
                         let new = n + 1;
-                        *s = new.to_string();
+                        *s = new.to_string().into_bytes();
                         println!("[cmd_incr] incremented value to: {}", new);
-                        Ok(format!(":{}\r\n", new).into_bytes())
+                        notify_keyspace_event(ctx, NOTIFY_STRING, "incrby", key, ctx.db_index);
+                        record_write(ctx, args)?;
+                        Ok(encode_int(new))
                     }
-                    Err(_) => {
+                    None => {
                         println!("[cmd_incr] value is not an integer");
                         Ok(encode_resp_error("value is not an integer or out of range"))
                     }
@@ -42,8 +46,11 @@ pub fn cmd_incr(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         },
         None => {
             println!("[cmd_incr] key not found, setting to 1");
-            map.insert(key.clone(), (Value::String("1".into()), None));
-            Ok(b":1\r\n".to_vec())
+            map.insert(key.to_vec(), (Value::String(b"1".to_vec()), None));
+            notify_keyspace_event(ctx, NOTIFY_NEW, "new", key, ctx.db_index);
+            notify_keyspace_event(ctx, NOTIFY_STRING, "incrby", key, ctx.db_index);
+            record_write(ctx, args)?;
+            Ok(encode_int(1))
         }
     }
 }
@@ -1,52 +1,92 @@
-use crate::commands::Context;
+use crate::commands::{record_write, Context};
+use crate::notify::{notify_keyspace_event, NOTIFY_NEW, NOTIFY_STRING};
 use crate::rdb::Value;
-use crate::resp::{encode_resp_error, encode_simple_resp_string};
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
 use std::io;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// SET <key> <value> [PX ms] → OK or error
-pub fn cmd_set(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+/// SET <key> <value> [PX ms | PXAT unix-ms] → OK or error
+pub fn cmd_set(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_set] called with args: {:?}", args);
 
     // validate argument count
     if args.len() != 3 && args.len() != 5 {
         println!("[cmd_set] invalid number of arguments");
-        return Ok(encode_resp_error("usage: SET <key> <val> [PX ms]"));
+        return Ok(encode_resp_error("usage: SET <key> <val> [PX ms | PXAT unix-ms]"));
     }
 
-    let key = &args[1];
-    let val = &args[2];
-    println!("[cmd_set] setting key: '{}', value: '{}'", key, val);
+    let key = args[1].as_bytes();
+    let val = args[2].as_bytes();
+    println!("[cmd_set] setting key: '{}', value: {:?}", args[1], args[2]);
 
-    let mut map = ctx.store.lock().unwrap();
+    let store = ctx.store.clone();
+    let mut map = store.lock(key);
+    let is_new = map.get(key).is_none_or(|(_, expiry)| expiry.is_some_and(|t| SystemTime::now() >= t));
 
     if args.len() == 3 {
         // simple set without expiry
         println!("[cmd_set] no expiry provided");
-        map.insert(key.clone(), (Value::String(val.clone()), None));
+        map.insert(key.to_vec(), (Value::String(val.to_vec()), None));
     } else {
-        // validate optional args: must be "PX" and a number
-        if args[3].to_uppercase() != "PX" {
-            println!("[cmd_set] expected 'PX', found '{}'", args[3]);
-            return Ok(encode_resp_error("expected PX for expiry"));
+        // validate optional args: "PX" or "PXAT" and a number
+        let option = args[3].to_uppercase();
+        if option != "PX" && option != "PXAT" {
+            println!("[cmd_set] expected 'PX' or 'PXAT', found '{}'", args[3]);
+            return Ok(encode_resp_error("expected PX or PXAT for expiry"));
         }
 
         let ms = match args[4].parse::<u64>() {
             Ok(n) => n,
             Err(_) => {
-                println!("[cmd_set] PX argument is not a valid integer: '{}'", args[4]);
+                println!("[cmd_set] {} argument is not a valid integer: '{}'", option, args[4]);
                 return Ok(encode_resp_error("PX must be integer"));
             }
         };
 
-        let expiry = SystemTime::now()
-            .checked_add(Duration::from_millis(ms))
-            .unwrap();
+        // PXAT comes from propagated writes: an absolute unix time in ms
+        let expiry = if option == "PXAT" {
+            UNIX_EPOCH + Duration::from_millis(ms)
+        } else {
+            SystemTime::now().checked_add(Duration::from_millis(ms)).unwrap()
+        };
 
-        println!("[cmd_set] setting expiry in {}ms", ms);
-        map.insert(key.clone(), (Value::String(val.clone()), Some(expiry)));
+        println!("[cmd_set] setting expiry via {} {}", option, ms);
+        map.insert(key.to_vec(), (Value::String(val.to_vec()), Some(expiry)));
     }
 
+    if is_new {
+        notify_keyspace_event(ctx, NOTIFY_NEW, "new", key, ctx.db_index);
+    }
+    notify_keyspace_event(ctx, NOTIFY_STRING, "set", key, ctx.db_index);
+    record_write(ctx, args)?;
     println!("[cmd_set] set successful");
     Ok(encode_simple_resp_string("OK"))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, run_cmd_bytes, test_context};
+
+    #[test]
+    fn values_are_stored_as_the_bytes_sent() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"SET", b"k", b"\xff\x00\xe9"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), b"$3\r\n\xff\x00\xe9\r\n");
+
+        // valid UTF-8 is stored as is, not re-encoded
+        assert_eq!(run_cmd(&mut ctx, &["SET", "k", "é"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "k"]), "$2\r\né\r\n".as_bytes());
+    }
+
+    #[test]
+    fn binary_keys_are_not_folded_into_utf8_ones() {
+        let mut ctx = test_context();
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"SET", b"\xe9", b"raw"]), b"+OK\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["SET", "é", "text"]), b"+OK\r\n");
+
+        assert_eq!(run_cmd_bytes(&mut ctx, &[b"GET", b"\xe9"]), b"$3\r\nraw\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["GET", "é"]), b"$4\r\ntext\r\n");
+        let keys = run_cmd(&mut ctx, &["KEYS", "*"]);
+        assert!(keys.starts_with(b"*2\r\n"), "{:?}", String::from_utf8_lossy(&keys));
+    }
+}
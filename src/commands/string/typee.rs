@@ -1,10 +1,9 @@
+use crate::commands::keyspace::live_entry;
 use crate::commands::Context;
-use crate::rdb::Value;
-use crate::resp::{encode_resp_error, encode_simple_resp_string};
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
 use std::io;
-use std::time::SystemTime;
 
-pub fn cmd_type(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_type(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_type] called with args: {:?}", args);
 
     if args.len() != 2 {
@@ -12,35 +11,35 @@ pub fn cmd_type(args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
         return Ok(encode_resp_error("usage: TYPE <key>"));
     }
 
-    let key = &args[1];
-    let map = ctx.store.lock().unwrap();
-
-    let response = match map.get(key) {
-        Some((val, opt_expiry)) => {
-            if let Some(exp) = opt_expiry {
-                if SystemTime::now() >= *exp {
-                    println!("[cmd_type] key '{}' is expired", key);
-                    "none"
-                } else {
-                    match val {
-                        Value::String(_) => "string",
-                        Value::List(_) => "list",
-                        Value::Stream(_) => "stream",
-                    }
-                }
-            } else {
-                match val {
-                    Value::String(_) => "string",
-                    Value::List(_) => "list",
-                    Value::Stream(_) => "stream",
-                }
-            }
-        }
+    let key = args[1].as_bytes();
+    let mut map = ctx.store.lock(key);
+
+    let response = match live_entry(ctx, ctx.db_index, &mut map, key) {
+        Some((val, _)) => val.type_name(),
         None => {
-            println!("[cmd_type] key '{}' does not exist", key);
+            println!("[cmd_type] key '{}' does not exist", args[1]);
             "none"
         }
     };
 
     Ok(encode_simple_resp_string(response))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{run_cmd, test_context};
+    use crate::rdb::Value;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn expired_keys_are_none_and_removed() {
+        let mut ctx = test_context();
+        let past = SystemTime::now() - Duration::from_secs(1);
+        ctx.store.lock(b"old").insert(b"old".to_vec(), (Value::String(b"v".to_vec()), Some(past)));
+        run_cmd(&mut ctx, &["RPUSH", "l", "a"]);
+
+        assert_eq!(run_cmd(&mut ctx, &["TYPE", "l"]), b"+list\r\n");
+        assert_eq!(run_cmd(&mut ctx, &["TYPE", "old"]), b"+none\r\n");
+        assert!(!ctx.store.lock(b"old").contains_key(&b"old"[..]));
+    }
+}
@@ -1,10 +1,11 @@
 use std::io;
 use crate::commands::{Context, ALL_CMDS};
-use crate::resp::{encode_resp_array, encode_resp_error};
+use crate::resp::{Arg, encode_resp_array, encode_resp_error, encode_resp_error_code};
+use crate::server::propagate_write;
 
 /// EXEC → if no MULTI, error; otherwise execute every queued command
 /// and emit them as a RESP array, then clear the transaction.
-pub fn cmd_exec(_args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_exec(_args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_exec] called");
 
     if !ctx.in_transaction {
@@ -13,10 +14,23 @@ pub fn cmd_exec(_args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
     }
 
     let queued = std::mem::take(&mut ctx.queued);
+    ctx.in_transaction = false;
+
+    if std::mem::take(&mut ctx.transaction_failed) {
+        println!("[cmd_exec] discarding {} queued command(s) after a refused one", queued.len());
+        return Ok(encode_resp_error_code(
+            "EXECABORT",
+            "Transaction discarded because of previous errors.",
+        ));
+    }
+
     println!("[cmd_exec] executing {} queued command(s)", queued.len());
 
     let mut responses = Vec::with_capacity(queued.len());
 
+    // the writes are propagated wrapped in MULTI/EXEC, so replicas and the
+    // AOF apply them as one step too; the first one sends the MULTI
+    ctx.multi_propagated = Some(false);
     for (cmd_name, cmd_args) in queued {
         println!("[cmd_exec] dispatching command: {} {:?}", cmd_name, cmd_args);
         if let Some(cmd_fn) = ALL_CMDS.get(&cmd_name.to_uppercase()) {
@@ -24,16 +38,19 @@ pub fn cmd_exec(_args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
                 Ok(resp) => responses.push(resp),
                 Err(_) => {
                     println!("[cmd_exec] command '{}' failed", cmd_name);
-                    responses.push(b"-ERR command failed\r\n".to_vec());
+                    responses.push(encode_resp_error("command failed"));
                 }
             }
         } else {
             println!("[cmd_exec] unknown command: {}", cmd_name);
-            responses.push(b"-ERR unknown command\r\n".to_vec());
+            responses.push(encode_resp_error("unknown command"));
         }
     }
 
-    ctx.in_transaction = false;
+    if ctx.multi_propagated.take() == Some(true) {
+        propagate_write(ctx, &["EXEC".into()])?;
+    }
+
     println!("[cmd_exec] transaction complete, state cleared");
 
     Ok(encode_resp_array(&responses))
@@ -1,12 +1,18 @@
 use std::io;
-use crate::resp::encode_simple_resp_string;
+use crate::resp::{Arg, encode_resp_error, encode_simple_resp_string};
 use crate::commands::Context;
 
-pub fn cmd_multi(_args: &[String], ctx: &mut Context) -> io::Result<Vec<u8>> {
+pub fn cmd_multi(_args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
     println!("[cmd_multi] MULTI received, entering transaction mode");
 
+    if ctx.in_transaction {
+        println!("[cmd_multi] error: MULTI inside MULTI");
+        return Ok(encode_resp_error("MULTI calls can not be nested"));
+    }
+
     ctx.in_transaction = true;
     ctx.queued.clear();
+    ctx.transaction_failed = false;
 
     println!("[cmd_multi] transaction state initialized");
 
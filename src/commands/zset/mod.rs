@@ -0,0 +1,2 @@
+pub mod zscan;
+pub mod zscore;
@@ -0,0 +1,45 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::keyspace::scan::{encode_scan_reply, parse_scan_args, scan_collection};
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_resp_error};
+use std::io;
+
+/// ZSCAN key cursor [MATCH pattern] [COUNT count] -> [cursor, [member score ...]]
+pub fn cmd_zscan(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_zscan] Received ZSCAN command with args: {:?}", args);
+
+    if args.len() < 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'zscan' command"));
+    }
+    let (cursor, opts) = match parse_scan_args(&args[2..], None) {
+        Ok(parsed) => parsed,
+        Err(reply) => return Ok(reply),
+    };
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    let zset = match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((Value::ZSet(zset), _)) => zset,
+        Some(_) => {
+            eprintln!("[cmd_zscan] WRONGTYPE: key '{}' is not a sorted set", args[1]);
+            return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            return Ok(encode_scan_reply(0, &[]));
+        }
+    };
+
+    let (cursor, members) = scan_collection(cursor, opts.count, |cursor, out| {
+        zset.scan(cursor, |member, score| out.push((member.to_vec(), score)))
+    });
+    let mut reply = Vec::new();
+    for (member, score) in members.into_iter().filter(|(m, _)| opts.matches(m)) {
+        reply.push(member);
+        reply.push(score.to_string().into_bytes());
+    }
+
+    println!("[cmd_zscan] Returning {} element(s), next cursor {}", reply.len(), cursor);
+    Ok(encode_scan_reply(cursor, &reply))
+}
@@ -0,0 +1,34 @@
+use crate::commands::keyspace::live_entry;
+use crate::commands::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_KEY_MISS};
+use crate::rdb::Value;
+use crate::resp::{Arg, encode_double_reply, encode_null_reply, encode_resp_error};
+use std::io;
+
+/// ZSCORE key member -> the member's score (a double in RESP3, a bulk
+/// string in RESP2), or nil
+pub fn cmd_zscore(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    println!("[cmd_zscore] Received ZSCORE command with args: {:?}", args);
+
+    if args.len() != 3 {
+        return Ok(encode_resp_error("wrong number of arguments for 'zscore' command"));
+    }
+
+    let mut map = ctx.store.lock(args[1].as_bytes());
+    let score = match live_entry(ctx, ctx.db_index, &mut map, args[1].as_bytes()) {
+        Some((Value::ZSet(zset), _)) => zset.score(args[2].as_bytes()),
+        Some(_) => {
+            eprintln!("[cmd_zscore] WRONGTYPE: key '{}' is not a sorted set", args[1]);
+            return Ok(encode_resp_error("WRONGTYPE Operation against a key holding the wrong kind of value"));
+        }
+        None => {
+            notify_keyspace_event(ctx, NOTIFY_KEY_MISS, "keymiss", args[1].as_bytes(), ctx.db_index);
+            None
+        }
+    };
+
+    Ok(match score {
+        Some(score) => encode_double_reply(ctx.protocol, score),
+        None => encode_null_reply(ctx.protocol),
+    })
+}
@@ -1,3 +1,6 @@
+use crate::aof::AppendFsync;
+use crate::notify::parse_notify_flags;
+use crate::resp::DEFAULT_MAX_BULK_LEN;
 use crate::role::Role;
 use std::env;
 
@@ -11,6 +14,45 @@ pub struct ServerConfig {
     pub master_host: String,
     pub master_port: u16,
     pub master_replid: String,
+    /// `save <seconds> <changes>` points; empty disables automatic saving.
+    pub save_params: Vec<(u64, u64)>,
+    /// Number of logical databases.
+    pub databases: usize,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    /// Directory (under `dir`) holding the multi-part AOF and its manifest.
+    pub appenddirname: String,
+    pub appendfsync: AppendFsync,
+    /// Load an AOF whose last command was cut short instead of refusing to start.
+    pub aof_load_truncated: bool,
+    /// Write rewritten AOF bases as RDB instead of commands.
+    pub aof_use_rdb_preamble: bool,
+    /// Growth over the last rewrite's size that triggers a rewrite; 0 disables.
+    pub auto_aof_rewrite_percentage: u64,
+    /// Bytes the AOF must reach before growth triggers a rewrite.
+    pub auto_aof_rewrite_min_size: u64,
+    /// `client-output-buffer-limit pubsub`: when to disconnect a subscriber
+    /// that isn't reading its messages.
+    pub client_output_buffer_limit_pubsub: OutputBufferLimit,
+    /// `notify-keyspace-events` classes (`notify::NOTIFY_*`); 0 disables.
+    pub notify_keyspace_events: u32,
+    /// Largest bulk string a client may send (`proto-max-bulk-len`).
+    pub proto_max_bulk_len: u64,
+}
+
+/// One `client-output-buffer-limit` class. A client is disconnected once its
+/// pending output exceeds `hard_bytes`, or stays above `soft_bytes` for
+/// `soft_seconds`; a limit of 0 is disabled.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputBufferLimit {
+    pub hard_bytes: u64,
+    pub soft_bytes: u64,
+    pub soft_seconds: u64,
+}
+
+impl OutputBufferLimit {
+    /// Redis' default for normal clients: no limit.
+    pub const UNLIMITED: OutputBufferLimit = OutputBufferLimit { hard_bytes: 0, soft_bytes: 0, soft_seconds: 0 };
 }
 
 pub fn parse_config() -> ServerConfig {
@@ -23,6 +65,23 @@ pub fn parse_config() -> ServerConfig {
     let mut master_host = String::new();
     let mut master_port: u16 = 0;
     let master_replid = "8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb".to_string();
+    let mut save_params: Vec<(u64, u64)> = vec![(3600, 1), (300, 100), (60, 10000)];
+    let mut databases: usize = 16;
+    let mut appendonly = false;
+    let mut appendfilename = "appendonly.aof".to_string();
+    let mut appendfsync = AppendFsync::EverySec;
+    let mut aof_load_truncated = true;
+    let mut appenddirname = "appendonlydir".to_string();
+    let mut aof_use_rdb_preamble = true;
+    let mut auto_aof_rewrite_percentage: u64 = 100;
+    let mut auto_aof_rewrite_min_size: u64 = 64 * 1024 * 1024;
+    let mut notify_keyspace_events: u32 = 0;
+    let mut proto_max_bulk_len: u64 = DEFAULT_MAX_BULK_LEN as u64;
+    let mut client_output_buffer_limit_pubsub = OutputBufferLimit {
+        hard_bytes: 32 * 1024 * 1024,
+        soft_bytes: 8 * 1024 * 1024,
+        soft_seconds: 60,
+    };
 
     let args: Vec<_> = env::args().collect();
     println!("[config::parse_config] Command-line arguments: {:?}", args);
@@ -61,6 +120,82 @@ pub fn parse_config() -> ServerConfig {
                     master_host, master_port, role
                 );
             }
+            "--save" => {
+                save_params = parse_save_params(&args[i + 1]);
+                println!("[config::parse_config] --save set to {:?}", save_params);
+            }
+            "--databases" => {
+                databases = args[i + 1]
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .expect("[config::parse_config] Error: databases must be a positive number");
+                println!("[config::parse_config] --databases set to {}", databases);
+            }
+            "--appendonly" => {
+                appendonly = parse_yes_no(&args[i + 1], "appendonly");
+                println!("[config::parse_config] --appendonly set to {}", appendonly);
+            }
+            "--appendfilename" => {
+                appendfilename = args[i + 1].clone();
+                println!("[config::parse_config] --appendfilename set to '{}'", appendfilename);
+            }
+            "--appendfsync" => {
+                appendfsync = AppendFsync::parse(&args[i + 1])
+                    .expect("[config::parse_config] Error: appendfsync must be always, everysec or no");
+                println!("[config::parse_config] --appendfsync set to {}", appendfsync.as_str());
+            }
+            "--aof-load-truncated" => {
+                aof_load_truncated = parse_yes_no(&args[i + 1], "aof-load-truncated");
+                println!("[config::parse_config] --aof-load-truncated set to {}", aof_load_truncated);
+            }
+            "--appenddirname" => {
+                appenddirname = args[i + 1].clone();
+                println!("[config::parse_config] --appenddirname set to '{}'", appenddirname);
+            }
+            "--aof-use-rdb-preamble" => {
+                aof_use_rdb_preamble = parse_yes_no(&args[i + 1], "aof-use-rdb-preamble");
+                println!("[config::parse_config] --aof-use-rdb-preamble set to {}", aof_use_rdb_preamble);
+            }
+            "--auto-aof-rewrite-percentage" => {
+                auto_aof_rewrite_percentage = args[i + 1]
+                    .parse()
+                    .expect("[config::parse_config] Error: auto-aof-rewrite-percentage must be a number");
+                println!(
+                    "[config::parse_config] --auto-aof-rewrite-percentage set to {}",
+                    auto_aof_rewrite_percentage
+                );
+            }
+            "--auto-aof-rewrite-min-size" => {
+                auto_aof_rewrite_min_size = parse_memory(&args[i + 1])
+                    .expect("[config::parse_config] Error: auto-aof-rewrite-min-size must be a size like 64mb");
+                println!(
+                    "[config::parse_config] --auto-aof-rewrite-min-size set to {}",
+                    auto_aof_rewrite_min_size
+                );
+            }
+            "--client-output-buffer-limit" => {
+                let (class, limit) = parse_output_buffer_limit(&args[i + 1]).expect(
+                    "[config::parse_config] Error: client-output-buffer-limit must be <class> <hard> <soft> <soft seconds>",
+                );
+                if class == "pubsub" {
+                    client_output_buffer_limit_pubsub = limit;
+                    println!("[config::parse_config] --client-output-buffer-limit pubsub set to {:?}", limit);
+                } else {
+                    println!("[config::parse_config] Warning: client-output-buffer-limit class '{}' is not enforced", class);
+                }
+            }
+            "--proto-max-bulk-len" => {
+                proto_max_bulk_len = parse_memory(&args[i + 1])
+                    .filter(|&len| len >= 1024 * 1024)
+                    .expect("[config::parse_config] Error: proto-max-bulk-len must be a size of at least 1mb");
+                println!("[config::parse_config] --proto-max-bulk-len set to {}", proto_max_bulk_len);
+            }
+            "--notify-keyspace-events" => {
+                notify_keyspace_events = parse_notify_flags(&args[i + 1])
+                    .expect("[config::parse_config] Error: notify-keyspace-events takes the flags KEg$lshzxetmnA");
+                println!("[config::parse_config] --notify-keyspace-events set to '{}'", args[i + 1]);
+            }
             unknown => {
                 println!("[config::parse_config] Warning: Unknown argument '{}'", unknown);
             }
@@ -76,8 +211,149 @@ pub fn parse_config() -> ServerConfig {
         master_host,
         master_port,
         master_replid,
+        save_params,
+        databases,
+        appendonly,
+        appendfilename,
+        appenddirname,
+        appendfsync,
+        aof_load_truncated,
+        aof_use_rdb_preamble,
+        auto_aof_rewrite_percentage,
+        auto_aof_rewrite_min_size,
+        client_output_buffer_limit_pubsub,
+        notify_keyspace_events,
+        proto_max_bulk_len,
     };
 
     println!("[config::parse_config] Final parsed config: {:?}", config);
     config
 }
+
+fn parse_yes_no(raw: &str, name: &str) -> bool {
+    match raw.to_ascii_lowercase().as_str() {
+        "yes" => true,
+        "no" => false,
+        _ => panic!("[config::parse_config] Error: {} must be yes or no", name),
+    }
+}
+
+/// "64mb" -> 67108864, with Redis' units: k/m/g are powers of 1000,
+/// kb/mb/gb powers of 1024.
+pub fn parse_memory(raw: &str) -> Option<u64> {
+    let lower = raw.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let mul: u64 = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(mul)
+}
+
+/// "900 1 300 10" -> [(900, 1), (300, 10)]; "" disables saving.
+pub fn parse_save_params(raw: &str) -> Vec<(u64, u64)> {
+    let nums: Vec<u64> = raw
+        .split_whitespace()
+        .map(|n| {
+            n.parse()
+                .expect("[config::parse_config] Error: save points must be numbers")
+        })
+        .collect();
+    if !nums.len().is_multiple_of(2) {
+        panic!("[config::parse_config] Error: save expects <seconds> <changes> pairs");
+    }
+    nums.chunks(2).map(|p| (p[0], p[1])).collect()
+}
+
+/// Inverse of `parse_save_params`, for CONFIG GET.
+pub fn format_save_params(params: &[(u64, u64)]) -> String {
+    params
+        .iter()
+        .map(|(secs, changes)| format!("{} {}", secs, changes))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "pubsub 32mb 8mb 60" -> ("pubsub", limit); the class is lowercased and
+/// "slave" is accepted as an alias of "replica".
+pub fn parse_output_buffer_limit(raw: &str) -> Option<(String, OutputBufferLimit)> {
+    let parts: Vec<&str> = raw.split_whitespace().collect();
+    let [class, hard, soft, secs] = parts.as_slice() else {
+        return None;
+    };
+    let class = match class.to_ascii_lowercase().as_str() {
+        "slave" => "replica".to_string(),
+        other @ ("normal" | "replica" | "pubsub") => other.to_string(),
+        _ => return None,
+    };
+    let limit = OutputBufferLimit {
+        hard_bytes: parse_memory(hard)?,
+        soft_bytes: parse_memory(soft)?,
+        soft_seconds: secs.parse().ok()?,
+    };
+    Some((class, limit))
+}
+
+#[cfg(test)]
+impl ServerConfig {
+    /// A master with defaults that keep tests quiet: no save points and a
+    /// data directory of `dir`.
+    pub fn for_tests(dir: &std::path::Path) -> Self {
+        ServerConfig {
+            dir: dir.to_string_lossy().into_owned(),
+            dbfilename: "dump.rdb".to_string(),
+            port: 0,
+            role: Role::Master,
+            master_host: String::new(),
+            master_port: 0,
+            master_replid: "8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb".to_string(),
+            save_params: Vec::new(),
+            databases: 4,
+            appendonly: false,
+            appendfilename: "appendonly.aof".to_string(),
+            appenddirname: "appendonlydir".to_string(),
+            appendfsync: AppendFsync::Always,
+            aof_load_truncated: true,
+            aof_use_rdb_preamble: true,
+            auto_aof_rewrite_percentage: 0,
+            auto_aof_rewrite_min_size: 0,
+            client_output_buffer_limit_pubsub: OutputBufferLimit { hard_bytes: 0, soft_bytes: 0, soft_seconds: 0 },
+            notify_keyspace_events: 0,
+            proto_max_bulk_len: DEFAULT_MAX_BULK_LEN as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_units() {
+        assert_eq!(parse_memory("100"), Some(100));
+        assert_eq!(parse_memory("1k"), Some(1000));
+        assert_eq!(parse_memory("1KB"), Some(1024));
+        assert_eq!(parse_memory("32mb"), Some(32 * 1024 * 1024));
+        assert_eq!(parse_memory("2g"), Some(2_000_000_000));
+        assert_eq!(parse_memory("1tb"), None);
+        assert_eq!(parse_memory("mb"), None);
+        assert_eq!(parse_memory("99999999999gb"), None);
+    }
+
+    #[test]
+    fn output_buffer_limits() {
+        let (class, limit) = parse_output_buffer_limit("PubSub 32mb 8mb 60").unwrap();
+        assert_eq!(class, "pubsub");
+        assert_eq!((limit.hard_bytes, limit.soft_bytes, limit.soft_seconds), (32 << 20, 8 << 20, 60));
+        assert_eq!(parse_output_buffer_limit("slave 0 0 0").unwrap().0, "replica");
+        assert!(parse_output_buffer_limit("pubsub 32mb 8mb").is_none());
+        assert!(parse_output_buffer_limit("master 0 0 0").is_none());
+        assert!(parse_output_buffer_limit("normal 1x 0 0").is_none());
+    }
+}
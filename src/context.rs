@@ -1,54 +1,194 @@
 use std::collections::{HashMap, HashSet};
-use std::net::TcpStream;
-use std::sync::{Arc, Mutex};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use crate::aof::Aof;
 use crate::config::ServerConfig;
-use crate::rdb::Store;
+use crate::outbox::Outbox;
+use crate::persistence::SaveState;
+use crate::rdb::Db;
+use crate::store::Store;
+use tokio::sync::{oneshot, Notify};
+use crate::resp::Arg;
 
-pub type Replicas = Arc<Mutex<HashMap<std::net::SocketAddr, (TcpStream, usize)>>>;
-pub type BlockingList = Arc<Mutex<HashMap<String, Vec<TcpStream>>>>;
+pub type Replicas = Arc<Mutex<HashMap<SocketAddr, ReplicaLink>>>;
+/// (database index, key) → the BLPOP clients waiting on it, longest-waiting
+/// first. The same key name in two databases is two different lists.
+pub type BlockingList = Arc<Mutex<HashMap<(usize, Vec<u8>), Vec<BlockedClient>>>>;
+/// Channel (or pattern) → the outboxes of its subscribers.
+pub type PubSubRegistry = Arc<Mutex<HashMap<Vec<u8>, Vec<Arc<Outbox>>>>>;
+
+/// A client blocked in BLPOP. Its connection awaits the other end of
+/// `popped` for the element a push hands it.
+pub struct BlockedClient {
+    pub client_id: u64,
+    pub popped: oneshot::Sender<Vec<u8>>,
+}
+
+/// A replica attached to this master via PSYNC.
+pub struct ReplicaLink {
+    /// The replica connection's outbox, which carries the replication stream.
+    pub outbox: Arc<Outbox>,
+    /// Replication offset the replica last ACKed.
+    pub ack_offset: u64,
+    /// Writes propagated while the initial snapshot is still being sent;
+    /// `None` once the replica is online and receives writes directly.
+    pub backlog: Option<Vec<Vec<u8>>>,
+    /// DB the replica's stream last SELECTed; `None` forces a SELECT first.
+    pub selected_db: Option<usize>,
+}
 
 pub struct Context {
     // global state
     pub cfg:       Arc<ServerConfig>,
+    // the selected database (`dbs[db_index]`)
     pub store:     Arc<Store>,
+    // every logical database
+    pub dbs:       Arc<Vec<Arc<Store>>>,
     pub replicas:  Replicas,
     pub blocking:  BlockingList,
-    pub master_repl_offset: usize,
-    pub pending_writes: Arc<Mutex<Vec<Vec<String>>>>,
+    // woken by every XADD, for the XREAD BLOCK clients to look again
+    pub stream_added: Arc<Notify>,
+    // woken whenever a replica ACKs an offset, for the WAIT clients
+    pub replica_acked: Arc<Notify>,
+    // bytes of replication stream sent to the replicas (on a master) or
+    // processed from the master (on a replica), shared by every connection
+    pub master_repl_offset: Arc<AtomicU64>,
+    pub pending_writes: Arc<Mutex<Vec<Vec<Arg>>>>,
+    // commands hold this shared while they run (writes across execute +
+    // propagate); EXEC, a full resync and an AOF rewrite hold it exclusively
+    pub sync_lock: Arc<RwLock<()>>,
+    pub save_state: Arc<Mutex<SaveState>>,
+    // open append-only file, when `appendonly yes`
+    pub aof: Option<Arc<Aof>>,
 
     // pub/sub registry: channel → list of subscribers
-    pub pubsub:   Arc<Mutex<HashMap<String, Vec<TcpStream>>>>,
+    pub pubsub:   PubSubRegistry,
+    // pattern subscriptions: glob pattern → list of subscribers
+    pub pattern_pubsub: PubSubRegistry,
+    // sharded pub/sub: shard channel → list of subscribers, apart from classic channels
+    pub shard_pubsub: PubSubRegistry,
 
     // per‐connection state
+    pub client_id: u64,
+    // RESP version negotiated with HELLO (2 until then)
+    pub protocol: u8,
+    pub client_name: Option<String>,
+    pub db_index: usize,
     pub in_transaction: bool,
-    pub queued: Vec<(String, Vec<String>)>,
-    pub this_client: Option<TcpStream>,
+    pub queued: Vec<(String, Vec<Arg>)>,
+    // a command was refused while queuing; EXEC then discards the queue
+    pub transaction_failed: bool,
+    // while EXEC runs its queue: whether the MULTI wrapping the writes it
+    // propagates has gone out yet (the first write sends it)
+    pub multi_propagated: Option<bool>,
+    // set while the AOF is replayed: those writes are already on disk
+    pub loading: bool,
+    // address of the peer; `None` for internal contexts (e.g. AOF replay)
+    pub peer: Option<SocketAddr>,
+    // everything sent to the client is queued here; `None` without a socket
+    // and on the replica's link to its master, which writes directly
+    pub outbox: Option<Arc<Outbox>>,
 
     // which channels *this* client is on
-    pub subscribed_channels: HashSet<String>,
+    pub subscribed_channels: HashSet<Vec<u8>>,
+    // and which patterns
+    pub subscribed_patterns: HashSet<Vec<u8>>,
+    // and which shard channels
+    pub subscribed_shard_channels: HashSet<Vec<u8>>,
+}
+
+impl Context {
+    /// The context of a fresh server holding `dbs`, before any client
+    /// connects.
+    pub fn new(cfg: Arc<ServerConfig>, dbs: Vec<Db>) -> Self {
+        let dbs: Vec<Arc<Store>> = dbs.into_iter().map(|db| Arc::new(Store::from_db(db))).collect();
+        let store = dbs[0].clone();
+        Context {
+            cfg,
+            store,
+            dbs: Arc::new(dbs),
+            replicas: Arc::new(Mutex::new(HashMap::new())),
+            blocking: Arc::new(Mutex::new(HashMap::new())),
+            stream_added: Arc::new(Notify::new()),
+            replica_acked: Arc::new(Notify::new()),
+            master_repl_offset: Arc::new(AtomicU64::new(0)),
+            pending_writes: Arc::new(Mutex::new(Vec::new())),
+            sync_lock: Arc::new(RwLock::new(())),
+            save_state: Arc::new(Mutex::new(SaveState::default())),
+            aof: None,
+            pubsub: Arc::new(Mutex::new(HashMap::new())),
+            pattern_pubsub: Arc::new(Mutex::new(HashMap::new())),
+            shard_pubsub: Arc::new(Mutex::new(HashMap::new())),
+            db_index: 0,
+            in_transaction: false,
+            queued: Vec::new(),
+            transaction_failed: false,
+            multi_propagated: None,
+            loading: false,
+            peer: None,
+            outbox: None,
+            subscribed_channels: HashSet::new(),
+            subscribed_patterns: HashSet::new(),
+            subscribed_shard_channels: HashSet::new(),
+            client_id: 0,
+            protocol: 2,
+            client_name: None,
+        }
+    }
+
+    /// Channels plus patterns this client is subscribed to, as (P)SUBSCRIBE
+    /// replies count them.
+    pub fn subscription_count(&self) -> usize {
+        self.subscribed_channels.len() + self.subscribed_patterns.len()
+    }
+
+    /// Whether the client holds any subscription, shard channels included;
+    /// if so only the pub/sub commands are allowed.
+    pub fn in_subscribed_mode(&self) -> bool {
+        self.subscription_count() + self.subscribed_shard_channels.len() > 0
+    }
 }
 
 impl Clone for Context {
     fn clone(&self) -> Self {
         println!(
             "[Context::clone] Cloning Context (repl_offset={}, tx_mode={}, queued_cmds={})",
-            self.master_repl_offset, self.in_transaction, self.queued.len()
+            self.master_repl_offset.load(Ordering::Relaxed), self.in_transaction, self.queued.len()
         );
         Self {
             cfg:                   self.cfg.clone(),
             store:                 self.store.clone(),
+            dbs:                   self.dbs.clone(),
             replicas:              self.replicas.clone(),
             blocking:              self.blocking.clone(),
-            master_repl_offset:    self.master_repl_offset,
+            stream_added:          self.stream_added.clone(),
+            replica_acked:         self.replica_acked.clone(),
+            master_repl_offset:    self.master_repl_offset.clone(),
             pending_writes:        self.pending_writes.clone(),
+            sync_lock:             self.sync_lock.clone(),
+            save_state:            self.save_state.clone(),
+            aof:                   self.aof.clone(),
 
             pubsub:               self.pubsub.clone(),
+            pattern_pubsub:       self.pattern_pubsub.clone(),
+            shard_pubsub:         self.shard_pubsub.clone(),
 
+            client_id:             self.client_id,
+            protocol:              self.protocol,
+            client_name:           self.client_name.clone(),
+            db_index:              self.db_index,
             in_transaction:        self.in_transaction,
             queued:                self.queued.clone(),
-            this_client:          self.this_client.as_ref().and_then(|s| s.try_clone().ok()),
+            transaction_failed:    self.transaction_failed,
+            multi_propagated:      self.multi_propagated,
+            loading:               self.loading,
+            peer:                 self.peer,
+            outbox:               self.outbox.clone(),
 
             subscribed_channels:  self.subscribed_channels.clone(),
+            subscribed_patterns:  self.subscribed_patterns.clone(),
+            subscribed_shard_channels: self.subscribed_shard_channels.clone(),
         }
     }
 }
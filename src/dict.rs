@@ -0,0 +1,370 @@
+//! A chained hash table with power-of-two bucket counts, modelled on Redis'
+//! `dict`. Unlike `HashMap` it exposes its buckets, which is what SCAN needs:
+//! walking them with a reverse-binary cursor visits every entry that is
+//! present for the whole iteration at least once, even across resizes.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// Bucket count of a table's first allocation.
+const DICT_INITIAL_SIZE: usize = 4;
+
+/// Shrink once fewer than 1 in this many buckets would be in use.
+const DICT_MIN_FILL: usize = 10;
+
+#[derive(Clone)]
+pub struct Dict<K, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    len: usize,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V> {
+    fn default() -> Self {
+        Self { buckets: Vec::new(), len: 0, hasher: RandomState::new() }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Dict<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> Dict<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { buckets: self.buckets.iter(), bucket: [].iter() }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Calls `f` on every entry of the bucket `cursor` points at and returns
+    /// the cursor of the next bucket; 0 once the table has been walked.
+    ///
+    /// The cursor is incremented from its most significant masked bit down,
+    /// so buckets are visited in an order that stays consistent when the
+    /// table is resized between calls: doubling splits bucket `b` into `b`
+    /// and `b + size`, which are visited back to back.
+    pub fn scan<F: FnMut(&K, &V)>(&self, cursor: u64, mut f: F) -> u64 {
+        if self.buckets.is_empty() {
+            return 0;
+        }
+        let mask = (self.buckets.len() - 1) as u64;
+        for (k, v) in &self.buckets[(cursor & mask) as usize] {
+            f(k, v);
+        }
+        next_cursor(cursor, mask)
+    }
+}
+
+/// Advances a SCAN cursor over a table of `mask + 1` buckets by adding one
+/// to its bit-reversed value; 0 once every bucket has been visited.
+pub fn next_cursor(cursor: u64, mask: u64) -> u64 {
+    // set the unmasked bits so the increment carries straight into the masked ones
+    let v = (cursor | !mask).reverse_bits().wrapping_add(1);
+    v.reverse_bits()
+}
+
+impl<K: Hash + Eq, V> Dict<K, V> {
+    fn bucket_of<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        (self.hasher.hash_one(key) as usize) & (self.buckets.len() - 1)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        self.buckets[self.bucket_of(key)]
+            .iter()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let bucket = self.bucket_of(key);
+        self.buckets[bucket]
+            .iter_mut()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts or replaces `key`, returning the previous value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(slot) = self.get_mut(&key) {
+            return Some(std::mem::replace(slot, value));
+        }
+
+        if self.len >= self.buckets.len() {
+            self.resize((self.len + 1).next_power_of_two().max(DICT_INITIAL_SIZE));
+        }
+        let bucket = self.bucket_of(&key);
+        self.buckets[bucket].push((key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let bucket = self.bucket_of(key);
+        let pos = self.buckets[bucket].iter().position(|(k, _)| k.borrow() == key)?;
+        let (_, value) = self.buckets[bucket].swap_remove(pos);
+        self.len -= 1;
+
+        if self.buckets.len() > DICT_INITIAL_SIZE && self.len * DICT_MIN_FILL < self.buckets.len() {
+            self.resize(self.len.next_power_of_two().max(DICT_INITIAL_SIZE));
+        }
+        Some(value)
+    }
+
+    /// Makes room for `additional` more entries without further resizing.
+    pub fn reserve(&mut self, additional: usize) {
+        let wanted = (self.len + additional).next_power_of_two().max(DICT_INITIAL_SIZE);
+        if wanted > self.buckets.len() {
+            self.resize(wanted);
+        }
+    }
+
+    fn resize(&mut self, size: usize) {
+        let old = std::mem::replace(&mut self.buckets, (0..size).map(|_| Vec::new()).collect());
+        for (k, v) in old.into_iter().flatten() {
+            let bucket = self.bucket_of(&k);
+            self.buckets[bucket].push((k, v));
+        }
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    buckets: std::slice::Iter<'a, Vec<(K, V)>>,
+    bucket: std::slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.bucket.next() {
+                return Some((k, v));
+            }
+            self.bucket = self.buckets.next()?.iter();
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a Dict<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> IntoIterator for Dict<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Vec<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.buckets.into_iter().flatten()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Dict<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut dict = Dict::new();
+        for (k, v) in iter {
+            dict.insert(k, v);
+        }
+        dict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Scans `dict` to completion, calling `between` after every step, and
+    /// returns every key seen.
+    fn scan_all<F: FnMut(&mut Dict<u32, ()>)>(dict: &mut Dict<u32, ()>, mut between: F) -> HashSet<u32> {
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        loop {
+            cursor = dict.scan(cursor, |k, _| {
+                seen.insert(*k);
+            });
+            if cursor == 0 {
+                return seen;
+            }
+            between(dict);
+        }
+    }
+
+    #[test]
+    fn insert_get_replace_remove() {
+        let mut dict = Dict::new();
+        assert!(dict.is_empty());
+        assert_eq!(dict.get("a"), None);
+        assert_eq!(dict.remove("a"), None);
+
+        assert_eq!(dict.insert("a".to_string(), 1), None);
+        assert_eq!(dict.insert("b".to_string(), 2), None);
+        assert_eq!(dict.insert("a".to_string(), 3), Some(1));
+        assert_eq!(dict.len(), 2);
+        assert_eq!(dict.get("a"), Some(&3));
+        *dict.get_mut("b").unwrap() += 10;
+        assert_eq!(dict.get("b"), Some(&12));
+
+        assert_eq!(dict.remove("a"), Some(3));
+        assert!(!dict.contains_key("a"));
+        assert_eq!(dict.len(), 1);
+    }
+
+    #[test]
+    fn grows_and_shrinks_keeping_every_entry() {
+        let mut dict: Dict<u32, u32> = (0..10_000).map(|i| (i, i * 2)).collect();
+        assert_eq!(dict.len(), 10_000);
+        assert!(dict.buckets.len() >= 10_000);
+
+        for i in 0..9_990 {
+            assert_eq!(dict.remove(&i), Some(i * 2));
+        }
+        assert_eq!(dict.len(), 10);
+        assert!(dict.buckets.len() <= 10 * DICT_MIN_FILL);
+        assert!((9_990..10_000).all(|i| dict.get(&i) == Some(&(i * 2))));
+
+        let mut left: Vec<_> = dict.into_iter().collect();
+        left.sort();
+        assert_eq!(left, (9_990..10_000).map(|i| (i, i * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cursor_visits_every_bucket_once() {
+        for mask in [0u64, 3, 7, 63] {
+            let mut visited = vec![0; mask as usize + 1];
+            let mut cursor = 0;
+            loop {
+                visited[cursor as usize] += 1;
+                cursor = next_cursor(cursor, mask);
+                if cursor == 0 {
+                    break;
+                }
+            }
+            assert!(visited.iter().all(|&n| n == 1), "mask {}", mask);
+        }
+    }
+
+    #[test]
+    fn scan_of_an_unchanged_table_returns_every_key_once() {
+        assert_eq!(Dict::<u32, ()>::new().scan(0, |_, _| panic!("empty table")), 0);
+
+        let mut dict: Dict<u32, ()> = (0..1000).map(|i| (i, ())).collect();
+        let mut count = 0;
+        let mut cursor = 0;
+        loop {
+            cursor = dict.scan(cursor, |_, _| count += 1);
+            if cursor == 0 {
+                break;
+            }
+        }
+        assert_eq!(count, 1000);
+        assert_eq!(scan_all(&mut dict, |_| {}), (0..1000).collect());
+    }
+
+    #[test]
+    fn scan_returns_stable_keys_while_the_table_grows() {
+        let mut dict: Dict<u32, ()> = (0..100).map(|i| (i, ())).collect();
+        let mut next = 100_000;
+        let seen = scan_all(&mut dict, |dict| {
+            // growing for ever, the table would outrun the cursor
+            if next < 102_000 {
+                for _ in 0..50 {
+                    dict.insert(next, ());
+                    next += 1;
+                }
+            }
+        });
+        assert!(dict.len() > 1000);
+        assert!((0..100).all(|k| seen.contains(&k)));
+    }
+
+    #[test]
+    fn scan_returns_stable_keys_while_the_table_shrinks() {
+        let mut dict: Dict<u32, ()> = (0..5000).map(|i| (i, ())).collect();
+        let mut doomed = 100..5000;
+        let seen = scan_all(&mut dict, |dict| {
+            for k in doomed.by_ref().take(200) {
+                dict.remove(&k);
+            }
+        });
+        assert_eq!(dict.len(), 100);
+        assert!((0..100).all(|k| seen.contains(&k)));
+    }
+
+    #[test]
+    fn scan_returns_stable_keys_across_repeated_resizes() {
+        let mut dict: Dict<u32, ()> = (0..64).map(|i| (i, ())).collect();
+        let mut step = 0u32;
+        let seen = scan_all(&mut dict, |dict| {
+            step += 1;
+            // alternate between growing well past and shrinking back to the stable keys
+            if step > 20 {
+                return;
+            }
+            for k in 0..2000 {
+                if step % 2 == 1 {
+                    dict.insert(1_000_000 + k, ());
+                } else {
+                    dict.remove(&(1_000_000 + k));
+                }
+            }
+        });
+        assert!(step > 20);
+        assert!((0..64).all(|k| seen.contains(&k)));
+    }
+}
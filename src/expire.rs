@@ -0,0 +1,162 @@
+//! Active expiry: a background sweep that drops keys whose TTL ran out even
+//! if nobody touches them again, as Redis' `activeExpireCycle` does.
+//!
+//! Lookups drop an expired key they run into (`live_entry`); this catches
+//! the rest. Ten times a second each database is walked a little further
+//! with a SCAN cursor, and expired keys found on the way are removed with
+//! an "expired" notification. A database keeps being walked while a good
+//! share of what was sampled had expired, up to a time budget per cycle.
+
+use crate::context::Context;
+use crate::notify::{notify_keyspace_event, NOTIFY_EXPIRED};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between two cycles (Redis' default `hz` of 10).
+const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+/// Longest a cycle keeps the shards busy, across all databases.
+const ACTIVE_EXPIRE_CYCLE_BUDGET: Duration = Duration::from_millis(25);
+/// Keys with a TTL to look at in one round on one database.
+const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;
+/// Keys of any kind one round walks past at most, so a database with few
+/// TTLs doesn't get walked whole every cycle.
+const ACTIVE_EXPIRE_VISITS_PER_LOOP: usize = 20 * ACTIVE_EXPIRE_KEYS_PER_LOOP;
+/// Another round follows while more than this percentage of the keys with
+/// a TTL sampled had expired.
+const ACTIVE_EXPIRE_ACCEPTABLE_STALE: usize = 10;
+
+pub fn spawn_active_expire_thread(ctx: Context) {
+    thread::spawn(move || {
+        let mut sweep = Sweep { cursors: vec![0; ctx.dbs.len()], next_db: 0 };
+        loop {
+            thread::sleep(ACTIVE_EXPIRE_PERIOD);
+            let removed = active_expire_cycle(&ctx, &mut sweep);
+            if removed > 0 {
+                println!("[expire::active] Removed {} expired key(s)", removed);
+            }
+        }
+    });
+}
+
+/// Where the sweep is at, from one cycle to the next.
+struct Sweep {
+    /// SCAN cursor of each database's walk.
+    cursors: Vec<u64>,
+    /// Database the next cycle starts with, so one that ran out of time
+    /// doesn't keep the last databases from being swept.
+    next_db: usize,
+}
+
+/// One cycle over the databases; returns how many keys it removed.
+fn active_expire_cycle(ctx: &Context, sweep: &mut Sweep) -> usize {
+    let started = Instant::now();
+    let mut removed = 0;
+
+    for step in 0..sweep.cursors.len() {
+        let db_index = (sweep.next_db + step) % sweep.cursors.len();
+        let cursor = &mut sweep.cursors[db_index];
+        loop {
+            let now = SystemTime::now();
+            let (mut visited, mut sampled) = (0, 0);
+            let mut expired = Vec::new();
+            loop {
+                *cursor = ctx.dbs[db_index].scan(*cursor, |key, (_, expiry)| {
+                    visited += 1;
+                    if let Some(at) = expiry {
+                        sampled += 1;
+                        if *at <= now {
+                            expired.push(key.clone());
+                        }
+                    }
+                });
+                if *cursor == 0 || sampled >= ACTIVE_EXPIRE_KEYS_PER_LOOP || visited >= ACTIVE_EXPIRE_VISITS_PER_LOOP
+                {
+                    break;
+                }
+            }
+
+            for key in &expired {
+                // the shard was let go in between: the key may have been
+                // rewritten or dropped since
+                let mut db = ctx.dbs[db_index].lock(key);
+                if db.get(key).is_some_and(|(_, expiry)| expiry.is_some_and(|t| SystemTime::now() >= t)) {
+                    db.remove(key);
+                    drop(db);
+                    notify_keyspace_event(ctx, NOTIFY_EXPIRED, "expired", key, db_index);
+                    removed += 1;
+                }
+            }
+
+            let stale = expired.len() * 100 > sampled * ACTIVE_EXPIRE_ACCEPTABLE_STALE;
+            if *cursor == 0 || !stale || started.elapsed() >= ACTIVE_EXPIRE_CYCLE_BUDGET {
+                break;
+            }
+        }
+        if started.elapsed() >= ACTIVE_EXPIRE_CYCLE_BUDGET {
+            sweep.next_db = db_index + 1;
+            break;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_context;
+    use crate::rdb::Value;
+
+    fn insert(ctx: &Context, db: usize, key: &str, expiry: Option<SystemTime>) {
+        ctx.dbs[db].lock(key.as_bytes()).insert(key.as_bytes().to_vec(), (Value::String(b"v".to_vec()), expiry));
+    }
+
+    fn keys(ctx: &Context, db: usize) -> usize {
+        ctx.dbs[db].lock_all().len()
+    }
+
+    #[test]
+    fn cycles_remove_expired_keys_nobody_reads() {
+        let ctx = test_context();
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let future = SystemTime::now() + Duration::from_secs(60);
+        for i in 0..100 {
+            insert(&ctx, 0, &format!("gone:{}", i), Some(past));
+            insert(&ctx, 3, &format!("gone:{}", i), Some(past));
+        }
+        insert(&ctx, 0, "live", Some(future));
+        insert(&ctx, 0, "forever", None);
+
+        let mut sweep = Sweep { cursors: vec![0; ctx.dbs.len()], next_db: 0 };
+        let mut removed = 0;
+        for _ in 0..20 {
+            removed += active_expire_cycle(&ctx, &mut sweep);
+        }
+        assert_eq!(removed, 200);
+        assert_eq!(keys(&ctx, 0), 2);
+        assert_eq!(keys(&ctx, 3), 0);
+    }
+
+    #[test]
+    fn a_database_with_few_expired_keys_is_walked_a_little_per_cycle() {
+        let ctx = test_context();
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let future = SystemTime::now() + Duration::from_secs(60);
+        for i in 0..1_000 {
+            insert(&ctx, 0, &format!("live:{}", i), Some(future));
+        }
+        insert(&ctx, 0, "gone", Some(past));
+
+        // under 10% stale: one round, not a walk over everything
+        let mut sweep = Sweep { cursors: vec![0; ctx.dbs.len()], next_db: 0 };
+        active_expire_cycle(&ctx, &mut sweep);
+        assert_ne!(sweep.cursors[0], 0);
+
+        // the cursor picks up where it left off, so the key goes eventually
+        let mut removed = 0;
+        for _ in 0..100 {
+            removed += active_expire_cycle(&ctx, &mut sweep);
+        }
+        assert_eq!(removed, 1);
+        assert_eq!(keys(&ctx, 0), 1_000);
+    }
+}
@@ -0,0 +1,214 @@
+//! Redis-compatible glob matching, as used by KEYS, SCAN MATCH, PSUBSCRIBE,
+//! CONFIG GET and PUBSUB CHANNELS.
+//!
+//! - `*` matches any run of characters, `?` any single character;
+//! - `[abc]` matches one of the listed characters, `[^abc]` anything else,
+//!   and `[a-z]` a range (the bounds may come in either order);
+//! - `\` makes the next character literal, both inside and outside `[...]`.
+//!
+//! As in Redis, an unterminated `[` class runs to the end of the pattern.
+
+/// Whether `text` matches `pattern`, case-sensitively.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    matches(&chars(pattern), &chars(text), false)
+}
+
+/// Whether `text` matches `pattern`, ignoring ASCII case.
+pub fn glob_match_nocase(pattern: &str, text: &str) -> bool {
+    matches(&chars(pattern), &chars(text), true)
+}
+
+/// `glob_match` for keys, which may be any bytes: character by character
+/// when both sides are UTF-8, byte by byte otherwise.
+pub fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+    match (std::str::from_utf8(pattern), std::str::from_utf8(text)) {
+        (Ok(pattern), Ok(text)) => glob_match(pattern, text),
+        _ => matches(pattern, text, false),
+    }
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+/// What a pattern is made of: characters, or bytes for binary keys.
+trait Symbol: Copy + Ord {
+    fn is(self, ascii: u8) -> bool;
+    fn to_ascii_lowercase(self) -> Self;
+}
+
+impl Symbol for char {
+    fn is(self, ascii: u8) -> bool {
+        self == ascii as char
+    }
+
+    fn to_ascii_lowercase(self) -> Self {
+        char::to_ascii_lowercase(&self)
+    }
+}
+
+impl Symbol for u8 {
+    fn is(self, ascii: u8) -> bool {
+        self == ascii
+    }
+
+    fn to_ascii_lowercase(self) -> Self {
+        u8::to_ascii_lowercase(&self)
+    }
+}
+
+fn matches<S: Symbol>(pattern: &[S], text: &[S], nocase: bool) -> bool {
+    let fold = |c: S| if nocase { c.to_ascii_lowercase() } else { c };
+    let eq = |a: S, b: S| fold(a) == fold(b);
+
+    let (mut p, mut t) = (0, 0);
+    // where to resume after the last `*`: (pattern index past it, text index)
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some(c) if c.is(b'*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some(c) if c.is(b'?') => Some(p + 1),
+            Some(c) if c.is(b'[') => match_class(pattern, p + 1, text[t], nocase),
+            Some(c) if c.is(b'\\') && p + 1 < pattern.len() => eq(pattern[p + 1], text[t]).then_some(p + 2),
+            Some(&c) => eq(c, text[t]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            // let the last `*` swallow one more character and retry
+            (None, Some((star_next, star_t))) => {
+                p = star_next;
+                t = star_t + 1;
+                backtrack = Some((star_next, star_t + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| c.is(b'*'))
+}
+
+/// Matches `c` against the class whose body starts at `p` (just past `[`);
+/// returns the pattern index after the class on a match.
+fn match_class<S: Symbol>(pattern: &[S], mut p: usize, c: S, nocase: bool) -> Option<usize> {
+    let fold = |ch: S| if nocase { ch.to_ascii_lowercase() } else { ch };
+    let c = fold(c);
+
+    let negate = pattern.get(p).is_some_and(|ch| ch.is(b'^'));
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() && !pattern[p].is(b']') {
+        if pattern[p].is(b'\\') && p + 1 < pattern.len() {
+            p += 1;
+            matched |= fold(pattern[p]) == c;
+        } else if p + 2 < pattern.len() && pattern[p + 1].is(b'-') {
+            let (a, b) = (fold(pattern[p]), fold(pattern[p + 2]));
+            matched |= (a.min(b)..=a.max(b)).contains(&c);
+            p += 2;
+        } else {
+            matched |= fold(pattern[p]) == c;
+        }
+        p += 1;
+    }
+
+    // step past the closing `]`, if there is one
+    (matched != negate).then_some((p + 1).min(pattern.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_and_wildcards() {
+        assert!(glob_match("hello", "hello"));
+        assert!(!glob_match("hello", "hell"));
+        assert!(!glob_match("hell", "hello"));
+        assert!(glob_match("h?llo", "hallo"));
+        assert!(!glob_match("h?llo", "hllo"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+        assert!(glob_match("user:*", "user:42"));
+        assert!(glob_match("*:42", "user:42"));
+        assert!(glob_match("**", "anything"));
+    }
+
+    #[test]
+    fn star_backtracks() {
+        assert!(glob_match("*a*b", "xaxxaxb"));
+        assert!(glob_match("a*b*c", "abbbcbc"));
+        assert!(!glob_match("a*b*c", "abbbcb"));
+        assert!(glob_match("*.txt", "notes.txt.txt"));
+        assert!(!glob_match("*.txt", "notes.txt.bak"));
+        assert!(glob_match("h*?o", "hello"));
+    }
+
+    #[test]
+    fn classes_ranges_and_negation() {
+        assert!(glob_match("h[ae]llo", "hello"));
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[ae]llo", "hillo"));
+        assert!(glob_match("h[^e]llo", "hallo"));
+        assert!(!glob_match("h[^e]llo", "hello"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(glob_match("[c-a]x", "bx"));
+        assert!(!glob_match("[a-c]x", "dx"));
+        assert!(glob_match("[0-9][0-9]", "42"));
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert!(glob_match("a\\*b", "a*b"));
+        assert!(!glob_match("a\\*b", "axb"));
+        assert!(glob_match("\\?", "?"));
+        assert!(!glob_match("\\?", "x"));
+        assert!(glob_match("[\\]]", "]"));
+        assert!(glob_match("[\\^a]", "^"));
+        // a trailing backslash is itself
+        assert!(glob_match("a\\", "a\\"));
+    }
+
+    #[test]
+    fn unterminated_class_runs_to_the_end() {
+        assert!(glob_match("a[bc", "ab"));
+        assert!(glob_match("a[bc", "ac"));
+        assert!(!glob_match("a[bc", "abc"));
+    }
+
+    #[test]
+    fn case_folding() {
+        assert!(!glob_match("MaxMemory*", "maxmemory-policy"));
+        assert!(glob_match_nocase("MaxMemory*", "maxmemory-policy"));
+        assert!(glob_match_nocase("[A-C]", "b"));
+        assert!(glob_match_nocase("[^A-C]", "d"));
+        assert!(!glob_match_nocase("[^A-C]", "B"));
+    }
+
+    #[test]
+    fn matches_characters_not_bytes() {
+        assert!(glob_match("?", "é"));
+        assert!(glob_match("caf?", "café"));
+        assert!(glob_match("[é]", "é"));
+    }
+
+    #[test]
+    fn binary_keys_match_byte_by_byte() {
+        assert!(glob_match_bytes(b"k?y", b"k\xffy"));
+        assert!(glob_match_bytes(b"*\xff", b"key\xff"));
+        assert!(!glob_match_bytes(b"k?y", b"k\xc3\xa9\xffy"));
+        assert!(glob_match_bytes("caf?".as_bytes(), "café".as_bytes()));
+    }
+}
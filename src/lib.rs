@@ -0,0 +1,9 @@
+//! Data structures and on-disk and wire formats shared by the server and the offline tools in `src/bin`.
+
+pub mod aof_manifest;
+pub mod dict;
+pub mod glob;
+pub mod rdb;
+pub mod resp;
+pub mod slot;
+pub mod store;
@@ -1,33 +1,37 @@
 extern crate core;
 
+mod aof;
 mod commands;
 mod config;
 mod context;
-mod rdb;
+mod expire;
+mod notify;
+mod outbox;
+mod persistence;
 mod replication;
-mod resp;
 mod role;
 mod server;
 
+use codecrafters_redis::{aof_manifest, glob, rdb, resp, slot, store};
+
 use crate::{
+    aof::{aof_exists, load_append_only_file, spawn_aof_rewrite_policy_thread, Aof},
     config::{parse_config, ServerConfig},
-    context::{BlockingList, Context},
+    context::Context,
+    expire::spawn_active_expire_thread,
+    persistence::{spawn_save_policy_thread, spawn_shutdown_handler},
     rdb::load_rdb_snapshot_from_path,
     replication::connect_and_sync_master,
     role::Role,
     server::serve_client_connection,
 };
 
-use crate::context::Replicas;
-use crate::rdb::Store;
+use crate::rdb::Db;
 use std::{
-    collections::HashMap,
     io,
-    net::{SocketAddr, TcpListener, TcpStream},
-    sync::{Arc, Mutex},
-    thread,
+    sync::Arc,
 };
-use std::collections::HashSet;
+use tokio::net::TcpListener;
 
 fn main() -> io::Result<()> {
     println!("[main] Starting Redis-like server...");
@@ -35,14 +39,29 @@ fn main() -> io::Result<()> {
     let cfg = Arc::new(parse_config());
     println!("[main] Configuration parsed: {:?}", cfg);
 
-    let shared_ctx = build_context(&cfg)?;
+    let mut shared_ctx = build_context(&cfg)?;
     println!("[main] Context initialized.");
 
-    if cfg.role == Role::Slave {
-        spawn_replica_sync_thread(shared_ctx.clone());
+    if cfg.appendonly {
+        if cfg.role == Role::Master {
+            load_append_only_file(&mut shared_ctx)?;
+        }
+        shared_ctx.aof = Some(Aof::open(&shared_ctx)?);
     }
 
-    start_tcp_server(cfg.port, shared_ctx)?;
+    spawn_save_policy_thread(shared_ctx.clone());
+    spawn_aof_rewrite_policy_thread(shared_ctx.clone());
+    spawn_active_expire_thread(shared_ctx.clone());
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        if cfg.role == Role::Slave {
+            spawn_replica_sync_task(shared_ctx.clone());
+        }
+        spawn_shutdown_handler(shared_ctx.clone());
+
+        start_tcp_server(cfg.port, shared_ctx).await
+    })?;
 
     println!("[main] Shutting down server cleanly.");
     Ok(())
@@ -50,69 +69,50 @@ fn main() -> io::Result<()> {
 
 fn build_context(cfg: &Arc<ServerConfig>) -> io::Result<Context> {
     let store_data = match cfg.role {
+        Role::Master if cfg.appendonly && aof_exists(cfg) => {
+            println!("[init] Append only file present; it takes precedence over the RDB snapshot.");
+            vec![Db::new(); cfg.databases]
+        }
         Role::Master => {
             let snapshot_path = format!("{}/{}", cfg.dir, cfg.dbfilename);
             println!("[init] Loading RDB snapshot from {}", snapshot_path);
-            let snapshot = load_rdb_snapshot_from_path(snapshot_path)?;
+            let snapshot = load_rdb_snapshot_from_path(snapshot_path, cfg.databases)?;
             println!("[init] Snapshot loaded successfully.");
             snapshot
         }
         Role::Slave => {
             println!("[init] Replica node - skipping local snapshot load.");
-            HashMap::new()
+            vec![Db::new(); cfg.databases]
         }
     };
 
-    let store: Arc<Store> = Arc::new(Mutex::new(store_data));
-    let replicas: Replicas = Arc::new(Mutex::new(HashMap::<SocketAddr, (TcpStream, usize)>::new()));
-    let blocking: BlockingList = Arc::new(Mutex::new(HashMap::new()));
-
-    let pubsub: Arc<Mutex<HashMap<String, Vec<TcpStream>>>> =
-        Arc::new(Mutex::new(HashMap::new()));
-
-    Ok(Context {
-        cfg: cfg.clone(),
-        store,
-        replicas,
-        blocking,
-        master_repl_offset: 0,
-        pending_writes: Arc::new(Mutex::new(Vec::new())),
-        pubsub,
-        in_transaction: false,
-        queued: Vec::new(),
-        this_client: None,
-        subscribed_channels: HashSet::new(),
-    })
+    Ok(Context::new(cfg.clone(), store_data))
 }
 
-fn spawn_replica_sync_thread(ctx: Context) {
-    println!("[main] Node is a replica. Spawning replication thread...");
-    let ctx_clone = ctx.clone();
-    thread::spawn(move || {
-        println!("[replication_thread] Starting replication handler...");
-        if let Err(e) = connect_and_sync_master(ctx_clone) {
-            eprintln!("[replication_thread] Replication error: {}", e);
+fn spawn_replica_sync_task(ctx: Context) {
+    println!("[main] Node is a replica. Spawning replication task...");
+    tokio::spawn(async move {
+        println!("[replication_task] Starting replication handler...");
+        if let Err(e) = connect_and_sync_master(ctx).await {
+            eprintln!("[replication_task] Replication error: {}", e);
         }
-        println!("[replication_thread] Replication thread exited.");
+        println!("[replication_task] Replication task exited.");
     });
 }
 
-fn start_tcp_server(port: u16, ctx: Context) -> io::Result<()> {
+async fn start_tcp_server(port: u16, ctx: Context) -> io::Result<()> {
     let bind_addr = format!("127.0.0.1:{}", port);
-    let listener = TcpListener::bind(&bind_addr)?;
+    let listener = TcpListener::bind(&bind_addr).await?;
     println!("[net] Listening for clients on {}...", bind_addr);
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let peer = stream
-                    .peer_addr()
-                    .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
                 println!("[net] Accepted client from {:?}", peer);
 
                 let ctx_clone = ctx.clone();
-                thread::spawn(move || {
-                    if let Err(e) = serve_client_connection(stream, ctx_clone) {
+                tokio::spawn(async move {
+                    if let Err(e) = serve_client_connection(stream, ctx_clone).await {
                         eprintln!("[client] Error: {}", e);
                     }
                 });
@@ -122,6 +122,4 @@ fn start_tcp_server(port: u16, ctx: Context) -> io::Result<()> {
             }
         }
     }
-
-    Ok(())
 }
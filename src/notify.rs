@@ -0,0 +1,212 @@
+//! Keyspace notifications, as enabled by `notify-keyspace-events`.
+//!
+//! Every event belongs to a class; when that class and K and/or E are
+//! enabled, the event is published on `__keyspace@<db>__:<key>` (message:
+//! the event name) and/or `__keyevent@<db>__:<event>` (message: the key).
+
+use crate::commands::pubsub::publish_message;
+use crate::context::Context;
+
+pub const NOTIFY_KEYSPACE: u32 = 1 << 0; // K
+pub const NOTIFY_KEYEVENT: u32 = 1 << 1; // E
+pub const NOTIFY_GENERIC: u32 = 1 << 2; // g
+pub const NOTIFY_STRING: u32 = 1 << 3; // $
+pub const NOTIFY_LIST: u32 = 1 << 4; // l
+pub const NOTIFY_SET: u32 = 1 << 5; // s
+pub const NOTIFY_HASH: u32 = 1 << 6; // h
+pub const NOTIFY_ZSET: u32 = 1 << 7; // z
+pub const NOTIFY_EXPIRED: u32 = 1 << 8; // x
+pub const NOTIFY_EVICTED: u32 = 1 << 9; // e
+pub const NOTIFY_STREAM: u32 = 1 << 10; // t
+pub const NOTIFY_KEY_MISS: u32 = 1 << 11; // m, not part of A
+pub const NOTIFY_NEW: u32 = 1 << 12; // n, not part of A
+
+/// A: every data class, but not key misses or new keys.
+pub const NOTIFY_ALL: u32 = NOTIFY_GENERIC
+    | NOTIFY_STRING
+    | NOTIFY_LIST
+    | NOTIFY_SET
+    | NOTIFY_HASH
+    | NOTIFY_ZSET
+    | NOTIFY_EXPIRED
+    | NOTIFY_EVICTED
+    | NOTIFY_STREAM;
+
+const CLASS_CHARS: [(char, u32); 9] = [
+    ('g', NOTIFY_GENERIC),
+    ('$', NOTIFY_STRING),
+    ('l', NOTIFY_LIST),
+    ('s', NOTIFY_SET),
+    ('h', NOTIFY_HASH),
+    ('z', NOTIFY_ZSET),
+    ('x', NOTIFY_EXPIRED),
+    ('e', NOTIFY_EVICTED),
+    ('t', NOTIFY_STREAM),
+];
+
+/// "KEA" -> flags; `None` on an unknown character.
+pub fn parse_notify_flags(raw: &str) -> Option<u32> {
+    let mut flags = 0;
+    for c in raw.chars() {
+        flags |= match c {
+            'A' => NOTIFY_ALL,
+            'K' => NOTIFY_KEYSPACE,
+            'E' => NOTIFY_KEYEVENT,
+            'm' => NOTIFY_KEY_MISS,
+            'n' => NOTIFY_NEW,
+            _ => CLASS_CHARS.iter().find(|(ch, _)| *ch == c)?.1,
+        };
+    }
+    Some(flags)
+}
+
+/// Inverse of `parse_notify_flags`, in Redis' canonical order, for CONFIG GET.
+pub fn format_notify_flags(flags: u32) -> String {
+    let mut out = String::new();
+    if flags & NOTIFY_ALL == NOTIFY_ALL {
+        out.push('A');
+    } else {
+        out.extend(CLASS_CHARS.iter().filter(|(_, f)| flags & f != 0).map(|(c, _)| c));
+    }
+    for (c, f) in [('K', NOTIFY_KEYSPACE), ('E', NOTIFY_KEYEVENT), ('m', NOTIFY_KEY_MISS), ('n', NOTIFY_NEW)] {
+        if flags & f != 0 {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Publishes `event` on `key` in DB `db`, if its class is enabled.
+pub fn notify_keyspace_event(ctx: &Context, class: u32, event: &str, key: &[u8], db: usize) {
+    let flags = ctx.cfg.notify_keyspace_events;
+    if flags & class == 0 {
+        return;
+    }
+
+    if flags & NOTIFY_KEYSPACE != 0 {
+        publish_message(ctx, &[format!("__keyspace@{}__:", db).as_bytes(), key].concat(), event.as_bytes());
+    }
+    if flags & NOTIFY_KEYEVENT != 0 {
+        publish_message(ctx, format!("__keyevent@{}__:{}", db, event).as_bytes(), key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{assert_nothing_pushed, assert_pushed, attach_client, run_cmd};
+    use crate::config::ServerConfig;
+    use crate::rdb::Value;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    /// A context with `flags` enabled and a second one subscribed to every
+    /// keyspace and keyevent channel, plus the subscriber's client end.
+    fn notifying_context(flags: &str) -> (Context, TcpStream) {
+        let mut cfg = ServerConfig::for_tests(&std::env::temp_dir());
+        cfg.notify_keyspace_events = parse_notify_flags(flags).unwrap();
+        let databases = cfg.databases;
+        let ctx = Context::new(Arc::new(cfg), vec![Default::default(); databases]);
+
+        let mut subscriber = ctx.clone();
+        let mut client = attach_client(&mut subscriber);
+        run_cmd(&mut subscriber, &["PSUBSCRIBE", "__key*__:*"]);
+        assert_pushed(&mut client, b"*3\r\n$10\r\npsubscribe\r\n$10\r\n__key*__:*\r\n:1\r\n");
+        (ctx, client)
+    }
+
+    fn pmessage(channel: &str, message: &str) -> Vec<u8> {
+        format!(
+            "*4\r\n$8\r\npmessage\r\n$10\r\n__key*__:*\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+            channel.len(),
+            channel,
+            message.len(),
+            message
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn flags_parse_and_format() {
+        assert_eq!(parse_notify_flags(""), Some(0));
+        assert_eq!(parse_notify_flags("KEA"), Some(NOTIFY_KEYSPACE | NOTIFY_KEYEVENT | NOTIFY_ALL));
+        assert_eq!(parse_notify_flags("El$"), Some(NOTIFY_KEYEVENT | NOTIFY_LIST | NOTIFY_STRING));
+        assert_eq!(parse_notify_flags("Kq"), None);
+
+        assert_eq!(format_notify_flags(parse_notify_flags("AKE").unwrap()), "AKE");
+        assert_eq!(format_notify_flags(parse_notify_flags("nE$gm").unwrap()), "g$Emn");
+        // spelling out every class is A
+        assert_eq!(format_notify_flags(parse_notify_flags("g$lshzxetK").unwrap()), "AK");
+        assert_eq!(format_notify_flags(0), "");
+    }
+
+    #[test]
+    fn publishes_enabled_classes_on_both_channels() {
+        let (mut ctx, mut client) = notifying_context("KEl");
+        run_cmd(&mut ctx, &["RPUSH", "jobs", "a"]);
+        assert_pushed(
+            &mut client,
+            &[pmessage("__keyspace@0__:jobs", "rpush"), pmessage("__keyevent@0__:rpush", "jobs")].concat(),
+        );
+
+        // strings are not enabled, and neither are new keys
+        run_cmd(&mut ctx, &["SET", "name", "x"]);
+        assert_nothing_pushed(&mut client);
+
+        run_cmd(&mut ctx, &["SELECT", "2"]);
+        run_cmd(&mut ctx, &["LPUSH", "jobs", "b"]);
+        assert_pushed(
+            &mut client,
+            &[pmessage("__keyspace@2__:jobs", "lpush"), pmessage("__keyevent@2__:lpush", "jobs")].concat(),
+        );
+    }
+
+    #[test]
+    fn announces_expiries_and_misses_when_asked() {
+        let (mut ctx, mut client) = notifying_context("Exm");
+        let past = SystemTime::now() - Duration::from_secs(1);
+        ctx.store.lock(b"gone").insert(b"gone".to_vec(), (Value::String("v".into()), Some(past)));
+
+        assert_eq!(run_cmd(&mut ctx, &["GET", "gone"]), b"$-1\r\n");
+        assert_pushed(
+            &mut client,
+            &[pmessage("__keyevent@0__:expired", "gone"), pmessage("__keyevent@0__:keymiss", "gone")].concat(),
+        );
+        run_cmd(&mut ctx, &["LLEN", "none"]);
+        assert_pushed(&mut client, &pmessage("__keyevent@0__:keymiss", "none"));
+        assert_nothing_pushed(&mut client);
+    }
+
+    #[test]
+    fn list_writers_drop_expired_and_emptied_lists() {
+        let (mut ctx, mut client) = notifying_context("Egxln");
+        let past = SystemTime::now() - Duration::from_secs(1);
+        ctx.store.lock(b"q").insert(b"q".to_vec(), (Value::List(["old".into()].into()), Some(past)));
+
+        // the push starts a new list: the expired one is gone first
+        assert_eq!(run_cmd(&mut ctx, &["RPUSH", "q", "a", "b"]), b":2\r\n");
+        assert_pushed(
+            &mut client,
+            &[
+                pmessage("__keyevent@0__:expired", "q"),
+                pmessage("__keyevent@0__:new", "q"),
+                pmessage("__keyevent@0__:rpush", "q"),
+            ]
+            .concat(),
+        );
+
+        assert_eq!(run_cmd(&mut ctx, &["LPOP", "q"]), b"$1\r\na\r\n");
+        assert_pushed(&mut client, &pmessage("__keyevent@0__:lpop", "q"));
+        assert_eq!(run_cmd(&mut ctx, &["BLPOP", "q", "0"]), b"*2\r\n$1\r\nq\r\n$1\r\nb\r\n");
+        assert_pushed(&mut client, &[pmessage("__keyevent@0__:lpop", "q"), pmessage("__keyevent@0__:del", "q")].concat());
+        assert_eq!(run_cmd(&mut ctx, &["TYPE", "q"]), b"+none\r\n");
+
+        run_cmd(&mut ctx, &["LPUSH", "q", "c"]);
+        assert_pushed(&mut client, &[pmessage("__keyevent@0__:new", "q"), pmessage("__keyevent@0__:lpush", "q")].concat());
+        assert_eq!(run_cmd(&mut ctx, &["LPOP", "q", "5"]), b"*1\r\n$1\r\nc\r\n");
+        assert_pushed(&mut client, &[pmessage("__keyevent@0__:lpop", "q"), pmessage("__keyevent@0__:del", "q")].concat());
+        assert_eq!(run_cmd(&mut ctx, &["TYPE", "q"]), b"+none\r\n");
+        assert_nothing_pushed(&mut client);
+    }
+}
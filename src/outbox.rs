@@ -0,0 +1,306 @@
+use crate::config::OutputBufferLimit;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::Notify;
+
+/// Outbound queue of one connection, drained by its own writer task so
+/// publishers (and propagation to replicas) never wait on a slow consumer.
+///
+/// Everything sent to the client goes through the queue: replies, pub/sub
+/// messages and, on a replica link, the replication stream, which keeps them
+/// in order.
+pub struct Outbox {
+    peer: Option<SocketAddr>,
+    /// Output buffer limit of the client's class; a subscriber gets the
+    /// pubsub one.
+    limit: Mutex<OutputBufferLimit>,
+    /// The client's RESP version, so publishers can encode messages for it.
+    protocol: AtomicU8,
+    state: Mutex<OutboxState>,
+    /// Wakes the writer task when frames are queued or the outbox closes.
+    ready: Notify,
+    /// Wakes the connection's reader when the outbox closes.
+    closed: Notify,
+}
+
+/// How often a writer stuck on a client that isn't reading checks whether
+/// it has been over the soft limit for too long.
+const SOFT_LIMIT_CHECK: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct OutboxState {
+    frames: VecDeque<Vec<u8>>,
+    /// Bytes queued or being written and not yet accepted by the socket.
+    pending: u64,
+    /// When `pending` last went over the soft limit.
+    soft_since: Option<Instant>,
+    closed: bool,
+    /// No more frames are coming: the writer stops once the queue is empty.
+    finishing: bool,
+}
+
+impl Outbox {
+    /// Creates the outbox for `writer` and starts its writer task.
+    pub fn start(writer: OwnedWriteHalf, limit: OutputBufferLimit, protocol: u8) -> Arc<Outbox> {
+        let outbox = Arc::new(Outbox {
+            peer: writer.peer_addr().ok(),
+            limit: Mutex::new(limit),
+            protocol: AtomicU8::new(protocol),
+            state: Mutex::new(OutboxState::default()),
+            ready: Notify::new(),
+            closed: Notify::new(),
+        });
+
+        let drained = outbox.clone();
+        tokio::spawn(async move { drained.drain_into(writer).await });
+        println!("[outbox::start] Writer started for {:?}", outbox.peer);
+        outbox
+    }
+
+    /// Queues `frame`; returns `false` if the client is gone, either already
+    /// or because this frame took it past its output limit.
+    pub fn push(&self, frame: Vec<u8>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        state.pending += frame.len() as u64;
+        state.frames.push_back(frame);
+
+        if self.over_limit(&mut state) {
+            eprintln!(
+                "[outbox::push] {:?} over its output buffer limit ({} bytes pending); disconnecting",
+                self.peer, state.pending
+            );
+            drop(state);
+            self.close();
+            return false;
+        }
+
+        self.ready.notify_one();
+        true
+    }
+
+    /// Whether `state.pending` is over the hard limit, or has been over the
+    /// soft one for long enough; tracks when it went over the soft one.
+    fn over_limit(&self, state: &mut OutboxState) -> bool {
+        let limit = self.limit();
+        let over_hard = limit.hard_bytes > 0 && state.pending > limit.hard_bytes;
+        let over_soft = limit.soft_bytes > 0 && state.pending > limit.soft_bytes;
+        if over_soft {
+            state.soft_since.get_or_insert_with(Instant::now);
+        } else {
+            state.soft_since = None;
+        }
+        let soft_expired = state.soft_since.is_some_and(|t| t.elapsed().as_secs() >= limit.soft_seconds);
+        over_hard || soft_expired
+    }
+
+    /// Drops anything still queued, stops the writer (which closes the
+    /// socket) and tells the connection's reader to stop too.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        state.closed = true;
+        state.frames.clear();
+        state.pending = 0;
+        drop(state);
+
+        self.ready.notify_one();
+        self.closed.notify_waiters();
+    }
+
+    /// Lets the writer send what is queued, then stop: the connection is
+    /// done but its last replies still go out.
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finishing = true;
+        self.ready.notify_one();
+    }
+
+    /// Resolves once the outbox is closed.
+    pub async fn wait_closed(&self) {
+        loop {
+            let notified = self.closed.notified();
+            if self.is_closed() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn limit(&self) -> OutputBufferLimit {
+        *self.limit.lock().unwrap()
+    }
+
+    pub fn set_limit(&self, limit: OutputBufferLimit) {
+        *self.limit.lock().unwrap() = limit;
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.protocol.load(Ordering::Relaxed)
+    }
+
+    pub fn set_protocol(&self, protocol: u8) {
+        self.protocol.store(protocol, Ordering::Relaxed);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    async fn drain_into(&self, mut writer: OwnedWriteHalf) {
+        loop {
+            let batch: Vec<u8> = {
+                let mut state = self.state.lock().unwrap();
+                if state.closed || (state.finishing && state.frames.is_empty()) {
+                    break;
+                }
+                state.frames.drain(..).flatten().collect()
+            };
+            if batch.is_empty() {
+                self.ready.notified().await;
+                continue;
+            }
+
+            // one write for everything queued; waits while the client isn't
+            // reading, and new frames pile up meanwhile. The batch still
+            // counts towards the limits until the socket has taken it, and
+            // the soft limit is checked while waiting, as no push may come.
+            let write = writer.write_all(&batch);
+            tokio::pin!(write);
+            let written = loop {
+                tokio::select! {
+                    written = &mut write => break written,
+                    _ = tokio::time::sleep(SOFT_LIMIT_CHECK) => {
+                        let mut state = self.state.lock().unwrap();
+                        if state.closed {
+                            break Ok(());
+                        }
+                        if !self.over_limit(&mut state) {
+                            continue;
+                        }
+                        eprintln!(
+                            "[outbox::drain_into] {:?} stayed over its soft output buffer limit; disconnecting",
+                            self.peer
+                        );
+                        drop(state);
+                        self.close();
+                        break Ok(());
+                    }
+                }
+            };
+            if let Err(e) = written {
+                eprintln!("[outbox::drain_into] Write to {:?} failed: {}", self.peer, e);
+                self.close();
+                break;
+            }
+
+            let soft_bytes = self.limit().soft_bytes;
+            let mut state = self.state.lock().unwrap();
+            state.pending = state.pending.saturating_sub(batch.len() as u64);
+            if soft_bytes == 0 || state.pending <= soft_bytes {
+                state.soft_since = None;
+            }
+        }
+        // lets the client see the connection end
+        let _ = writer.shutdown().await;
+        println!("[outbox::drain_into] Writer for {:?} stopped", self.peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{assert_pushed, client_pair, test_runtime};
+    use std::io::Read;
+    use std::net::TcpStream;
+
+    fn limit(hard_bytes: u64, soft_bytes: u64, soft_seconds: u64) -> OutputBufferLimit {
+        OutputBufferLimit { hard_bytes, soft_bytes, soft_seconds }
+    }
+
+    /// An outbox on a fresh connection, and the client's end of it.
+    fn outbox_with(limit: OutputBufferLimit) -> (Arc<Outbox>, TcpStream) {
+        let (server, client) = client_pair();
+        let _entered = test_runtime().enter();
+        (Outbox::start(server.into_split().1, limit, 2), client)
+    }
+
+    #[test]
+    fn frames_are_written_in_order() {
+        let (outbox, mut client) = outbox_with(limit(0, 0, 0));
+        for i in 0..100 {
+            assert!(outbox.push(format!("+{}\r\n", i).into_bytes()));
+        }
+        let expected: String = (0..100).map(|i| format!("+{}\r\n", i)).collect();
+        assert_pushed(&mut client, expected.as_bytes());
+        assert!(!outbox.is_closed());
+    }
+
+    #[test]
+    fn going_over_the_hard_limit_disconnects() {
+        let (outbox, mut client) = outbox_with(limit(16, 0, 0));
+        assert!(!outbox.push(vec![b'x'; 17]));
+        assert!(outbox.is_closed());
+
+        // the socket is shut, so the client reads end of stream
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        assert!(!outbox.push(b"+late\r\n".to_vec()));
+    }
+
+    #[test]
+    fn a_soft_limit_of_zero_seconds_acts_at_once() {
+        let (outbox, _client) = outbox_with(limit(0, 8, 0));
+        assert!(outbox.push(b"+ok\r\n".to_vec()));
+        assert!(!outbox.push(vec![b'x'; 9]));
+        assert!(outbox.is_closed());
+    }
+
+    /// Polls until `outbox` is closed; whether it was within 5 seconds.
+    fn closes_soon(outbox: &Outbox) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !outbox.is_closed() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        outbox.is_closed()
+    }
+
+    #[test]
+    fn bytes_being_written_count_towards_the_limit() {
+        // the client never reads, so the writer stays stuck on the first
+        // frame, which is larger than the socket buffers
+        let (outbox, _client) = outbox_with(limit(24 << 20, 0, 0));
+        assert!(outbox.push(vec![b'x'; 16 << 20]));
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!outbox.push(vec![b'y'; 16 << 20]));
+        assert!(outbox.is_closed());
+    }
+
+    #[test]
+    fn the_soft_limit_is_enforced_without_further_pushes() {
+        let (outbox, _client) = outbox_with(limit(0, 1 << 20, 1));
+        assert!(outbox.push(vec![b'x'; 16 << 20]));
+        assert!(closes_soon(&outbox));
+    }
+
+    #[test]
+    fn finishing_sends_what_is_queued_then_closes() {
+        let (outbox, mut client) = outbox_with(limit(0, 0, 0));
+        assert!(outbox.push(b"+a\r\n".to_vec()));
+        assert!(outbox.push(b"+b\r\n".to_vec()));
+        outbox.finish();
+
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"+a\r\n+b\r\n");
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::ServerConfig;
+use crate::context::Context;
+use crate::rdb::encode::write_rdb_snapshot;
+use crate::rdb::Db;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{process, thread};
+
+/// Bookkeeping for RDB saves, shared by SAVE/BGSAVE and the save-policy thread.
+#[derive(Debug)]
+pub struct SaveState {
+    /// Writes applied since the last successful save.
+    pub dirty: u64,
+    pub last_save: SystemTime,
+    pub bgsave_in_progress: bool,
+    pub last_bgsave_ok: bool,
+}
+
+impl Default for SaveState {
+    fn default() -> Self {
+        Self {
+            dirty: 0,
+            last_save: SystemTime::now(),
+            bgsave_in_progress: false,
+            last_bgsave_ok: true,
+        }
+    }
+}
+
+/// Keeps temp file names unique when a SAVE and a BGSAVE overlap.
+static TEMP_FILE_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+pub fn now_unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+pub fn rdb_path(cfg: &ServerConfig) -> PathBuf {
+    PathBuf::from(&cfg.dir).join(&cfg.dbfilename)
+}
+
+/// Writes the snapshot to a temp file in `--dir`, fsyncs it, then renames it
+/// over `--dbfilename` so readers never observe a half-written dump.
+pub fn write_snapshot_file(cfg: &ServerConfig, data: &[Db]) -> io::Result<()> {
+    let final_path = rdb_path(cfg);
+    let seq = TEMP_FILE_SEQ.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = PathBuf::from(&cfg.dir).join(format!("temp-{}-{}.rdb", process::id(), seq));
+    println!("[persistence::write] Writing snapshot to {:?}", tmp_path);
+
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        write_rdb_snapshot(&mut out, data)?;
+        out.flush()?;
+        out.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &final_path)
+    })();
+
+    if let Err(e) = result {
+        eprintln!("[persistence::write] Failed writing {:?}: {}", final_path, e);
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    println!("[persistence::write] Snapshot saved to {:?}", final_path);
+    Ok(())
+}
+
+/// Copies every database while holding all of their shard locks (taken in
+/// database, then shard order), so the copy is one consistent point in time.
+/// The caller holds the sync barrier, so no EXEC is halfway through its
+/// queue; commands get it from dispatch, other callers take it themselves.
+pub fn snapshot_databases(ctx: &Context) -> Vec<Db> {
+    let guards: Vec<_> = ctx.dbs.iter().map(|db| db.lock_all()).collect();
+    guards.iter().map(|db| db.to_db()).collect()
+}
+
+/// SAVE: serialize the dataset on the calling thread.
+pub fn save_snapshot(ctx: &Context) -> io::Result<()> {
+    let (data, dirty_before) = {
+        let state = ctx.save_state.lock().unwrap();
+        if state.bgsave_in_progress {
+            return Err(io::Error::other("Background save already in progress"));
+        }
+        (snapshot_databases(ctx), state.dirty)
+    };
+
+    write_snapshot_file(&ctx.cfg, &data)?;
+
+    let mut state = ctx.save_state.lock().unwrap();
+    state.dirty -= dirty_before.min(state.dirty);
+    state.last_save = SystemTime::now();
+    Ok(())
+}
+
+/// BGSAVE: copy the dataset under the store lock, then serialize it on a
+/// separate thread so clients keep being served. Returns `false` if a
+/// background save is already running.
+pub fn start_background_save(ctx: &Context) -> bool {
+    let (data, dirty_before) = {
+        let mut state = ctx.save_state.lock().unwrap();
+        if state.bgsave_in_progress {
+            println!("[persistence::bgsave] Background save already in progress");
+            return false;
+        }
+        state.bgsave_in_progress = true;
+        (snapshot_databases(ctx), state.dirty)
+    };
+    println!(
+        "[persistence::bgsave] Captured {} key(s); saving in background",
+        data.iter().map(|db| db.len()).sum::<usize>()
+    );
+
+    let cfg = ctx.cfg.clone();
+    let save_state = ctx.save_state.clone();
+    thread::spawn(move || {
+        let result = write_snapshot_file(&cfg, &data);
+
+        let mut state = save_state.lock().unwrap();
+        state.bgsave_in_progress = false;
+        state.last_bgsave_ok = result.is_ok();
+        if result.is_ok() {
+            state.dirty -= dirty_before.min(state.dirty);
+            state.last_save = SystemTime::now();
+            println!("[persistence::bgsave] Background saving terminated with success");
+        }
+    });
+
+    true
+}
+
+/// Triggers a BGSAVE whenever one of the `save <seconds> <changes>` points is met.
+pub fn spawn_save_policy_thread(ctx: Context) {
+    if ctx.cfg.save_params.is_empty() {
+        println!("[persistence::policy] No save points configured; automatic saving disabled.");
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(100));
+
+        let due = {
+            let state = ctx.save_state.lock().unwrap();
+            let elapsed = state.last_save.elapsed().unwrap_or_default().as_secs();
+            if state.bgsave_in_progress {
+                None
+            } else {
+                ctx.cfg
+                    .save_params
+                    .iter()
+                    .find(|&&(secs, changes)| state.dirty >= changes && elapsed >= secs)
+                    .copied()
+            }
+        };
+
+        if let Some((secs, changes)) = due {
+            println!(
+                "[persistence::policy] {} changes in {} seconds. Saving...",
+                changes, secs
+            );
+            let _barrier = ctx.sync_lock.read().unwrap();
+            start_background_save(&ctx);
+        }
+    });
+}
+
+/// Saves (if requested, or if save points are configured) and exits.
+/// Returns only if the final save failed, in which case the server keeps running.
+pub fn shutdown(ctx: &Context, save: Option<bool>) -> io::Result<()> {
+    let save = save.unwrap_or(!ctx.cfg.save_params.is_empty());
+    println!("[persistence::shutdown] Shutting down (save={})", save);
+
+    if save {
+        // don't race a running BGSAVE for the same file
+        while ctx.save_state.lock().unwrap().bgsave_in_progress {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if let Err(e) = save_snapshot(ctx) {
+            eprintln!("[persistence::shutdown] Error trying to save the DB, can't exit: {}", e);
+            return Err(e);
+        }
+    }
+
+    if let Some(aof) = &ctx.aof {
+        if let Err(e) = aof.sync() {
+            eprintln!("[persistence::shutdown] Error flushing the append only file: {}", e);
+        }
+    }
+
+    println!("[persistence::shutdown] Redis-like server is now ready to exit, bye bye...");
+    process::exit(0);
+}
+
+/// `shutdown` for code on the server runtime: the final save (and the wait
+/// for a running BGSAVE) happen on the blocking pool, under the sync barrier.
+pub async fn shutdown_blocking(ctx: &Context, save: Option<bool>) -> io::Result<()> {
+    let ctx = ctx.clone();
+    tokio::task::spawn_blocking(move || {
+        let _barrier = ctx.sync_lock.read().unwrap();
+        shutdown(&ctx, save)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Runs `shutdown` on SIGINT/SIGTERM, as a task on the server runtime.
+pub fn spawn_shutdown_handler(ctx: Context) {
+    tokio::spawn(async move {
+        let mut term = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[persistence::signals] Failed to install SIGTERM handler: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => println!("[persistence::signals] Received SIGINT"),
+                _ = term.recv() => println!("[persistence::signals] Received SIGTERM"),
+            }
+            let _ = shutdown_blocking(&ctx, None).await;
+        }
+    });
+}
@@ -0,0 +1,147 @@
+use std::io::{self, BufRead, Read, Write};
+
+/// Reflected form of the Jones polynomial (0xad93d23594c935a9) Redis uses.
+const POLY: u64 = 0x95AC_9329_AC4B_C9B5;
+
+const TABLE: [u64; 256] = build_table();
+
+const fn build_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Continues a CRC-64/Jones checksum over `data` (`crc64(0, b"123456789") == 0xe9c6d914c4b8d9ca`).
+pub fn crc64(mut crc: u64, data: &[u8]) -> u64 {
+    for &b in data {
+        crc = TABLE[((crc ^ b as u64) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// Passes writes through while checksumming everything written.
+pub struct Crc64Writer<W: Write> {
+    inner: W,
+    crc: u64,
+}
+
+impl<W: Write> Crc64Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, crc: 0 }
+    }
+
+    pub fn crc(&self) -> u64 {
+        self.crc
+    }
+}
+
+impl<W: Write> Write for Crc64Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc = crc64(self.crc, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Checksums every byte consumed from the wrapped reader.
+pub struct Crc64Reader<R: BufRead> {
+    inner: R,
+    crc: u64,
+}
+
+impl<R: BufRead> Crc64Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, crc: 0 }
+    }
+
+    /// Checksum of everything consumed so far.
+    pub fn crc(&self) -> u64 {
+        self.crc
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: BufRead> Read for Crc64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Crc64Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // consume() always follows fill_buf(), so this is served from the buffer
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.crc = crc64(self.crc, &buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn crc64_check_value() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+        assert_eq!(crc64(0, b""), 0);
+    }
+
+    #[test]
+    fn crc64_continues_across_chunks() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        let (a, b) = data.split_at(17);
+        assert_eq!(crc64(crc64(0, a), b), crc64(0, data));
+    }
+
+    #[test]
+    fn writer_checksums_what_it_writes() {
+        let mut w = Crc64Writer::new(Vec::new());
+        w.write_all(b"12345").unwrap();
+        w.write_all(b"6789").unwrap();
+        assert_eq!(w.crc(), 0xe9c6d914c4b8d9ca);
+        assert_eq!(w.inner, b"123456789");
+    }
+
+    #[test]
+    fn reader_checksums_only_what_is_consumed() {
+        let mut r = Crc64Reader::new(Cursor::new(b"123456789trailer".to_vec()));
+        let mut buf = [0u8; 9];
+        r.read_exact(&mut buf).unwrap();
+        assert_eq!(r.crc(), 0xe9c6d914c4b8d9ca);
+
+        let mut rest = Vec::new();
+        r.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailer");
+        assert_eq!(r.crc(), crc64(0, b"123456789trailer"));
+    }
+}
@@ -0,0 +1,1034 @@
+use crate::dict::Dict;
+use crate::rdb::crc64::{crc64, Crc64Reader};
+use crate::rdb::encode::{STREAM_ITEM_FLAG_DELETED, STREAM_ITEM_FLAG_SAMEFIELDS};
+use crate::rdb::error::RdbError;
+use crate::rdb::listpack::{decode_listpack, ListpackEntry};
+use crate::rdb::lzf::lzf_decompress;
+use crate::rdb::ziplist::{decode_intset, decode_ziplist, decode_zipmap};
+use crate::rdb::{
+    SortedSet, StreamEntry, Value, RDB_OPCODE_AUX, RDB_OPCODE_EOF, RDB_OPCODE_EXPIRETIME,
+    RDB_OPCODE_EXPIRETIME_MS, RDB_OPCODE_FREQ, RDB_OPCODE_FUNCTION2, RDB_OPCODE_FUNCTION_PRE_GA,
+    RDB_OPCODE_IDLE, RDB_OPCODE_MODULE_AUX, RDB_OPCODE_RESIZEDB, RDB_OPCODE_SELECTDB,
+    RDB_OPCODE_SLOT_INFO, RDB_TYPE_HASH, RDB_TYPE_HASH_LISTPACK, RDB_TYPE_HASH_ZIPLIST,
+    RDB_TYPE_HASH_ZIPMAP, RDB_TYPE_LIST, RDB_TYPE_LIST_QUICKLIST, RDB_TYPE_LIST_QUICKLIST_2, QUICKLIST_NODE_CONTAINER_PLAIN,
+    RDB_TYPE_LIST_ZIPLIST, RDB_TYPE_MODULE_2, RDB_TYPE_MODULE_PRE_GA, RDB_TYPE_SET,
+    RDB_TYPE_SET_INTSET, RDB_TYPE_SET_LISTPACK, RDB_TYPE_STREAM_LISTPACKS,
+    RDB_TYPE_STREAM_LISTPACKS_2, RDB_TYPE_STREAM_LISTPACKS_3, RDB_TYPE_STRING, RDB_TYPE_ZSET,
+    RDB_TYPE_ZSET_2, RDB_TYPE_ZSET_LISTPACK, RDB_TYPE_ZSET_ZIPLIST, MAX_PREALLOC,
+};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Oldest and newest `REDIS00xx` versions the decoder understands.
+const RDB_MIN_VERSION: u32 = 6;
+const RDB_MAX_VERSION: u32 = 12;
+
+// module-serialized value opcodes
+const RDB_MODULE_OPCODE_EOF: usize = 0;
+const RDB_MODULE_OPCODE_SINT: usize = 1;
+const RDB_MODULE_OPCODE_UINT: usize = 2;
+const RDB_MODULE_OPCODE_FLOAT: usize = 3;
+const RDB_MODULE_OPCODE_DOUBLE: usize = 4;
+const RDB_MODULE_OPCODE_STRING: usize = 5;
+
+/// One top-level record of an RDB stream, in file order.
+#[derive(Debug)]
+pub enum RdbEvent {
+    Aux { key: String, value: String },
+    /// Source of a function library (FUNCTION2).
+    Function { code: Vec<u8> },
+    /// Module auxiliary data; the payload itself is skipped.
+    ModuleAux { module_id: u64 },
+    SelectDb(usize),
+    ResizeDb { keys: usize, expires: usize },
+    Entry { key: Vec<u8>, value: Value, expiry: Option<SystemTime> },
+    /// A module-typed value we can only skip.
+    ModuleValue { key: Vec<u8> },
+}
+
+/// Decodes an RDB stream from any reader one record at a time, so callers
+/// never need the whole file in memory. The CRC64 footer is verified when the
+/// EOF opcode is reached.
+pub struct RdbDecoder<R: Read> {
+    rdr: Crc64Reader<BufReader<R>>,
+    version: u32,
+    checksum: Option<u64>,
+    done: bool,
+}
+
+impl<R: Read> RdbDecoder<R> {
+    /// Reads and validates the `REDIS00xx` header.
+    pub fn new(inner: R) -> Result<Self, RdbError> {
+        let mut rdr = Crc64Reader::new(BufReader::new(inner));
+        let mut hdr = [0u8; 9];
+        rdr.read_exact(&mut hdr)?;
+        if &hdr[..5] != b"REDIS" {
+            return Err(RdbError::BadMagic);
+        }
+
+        let version: u32 = std::str::from_utf8(&hdr[5..])
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or(RdbError::BadMagic)?;
+        if !(RDB_MIN_VERSION..=RDB_MAX_VERSION).contains(&version) {
+            return Err(RdbError::UnsupportedVersion(version));
+        }
+
+        Ok(Self { rdr, version, checksum: None, done: false })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The verified checksum from the footer; `None` until the end is reached,
+    /// or if the file was written without one.
+    pub fn checksum(&self) -> Option<u64> {
+        self.checksum
+    }
+
+    /// The underlying reader, positioned just after whatever has been decoded
+    /// (e.g. after the checksum, for an AOF with an RDB preamble).
+    pub fn into_inner(self) -> BufReader<R> {
+        self.rdr.into_inner()
+    }
+
+    /// The next record, or `None` once the EOF opcode and checksum have been read.
+    pub fn next_event(&mut self) -> Result<Option<RdbEvent>, RdbError> {
+        if self.done {
+            return Ok(None);
+        }
+        let rdr = &mut self.rdr;
+        let mut expiry = None;
+
+        loop {
+            let mut op = [0u8; 1];
+            rdr.read_exact(&mut op)?;
+
+            let event = match op[0] {
+                RDB_OPCODE_EOF => {
+                    self.done = true;
+                    self.checksum = verify_checksum(rdr)?;
+                    return Ok(None);
+                }
+                RDB_OPCODE_AUX => {
+                    let key = read_text(rdr)?;
+                    let value = read_text(rdr)?;
+                    RdbEvent::Aux { key, value }
+                }
+                RDB_OPCODE_MODULE_AUX => RdbEvent::ModuleAux { module_id: skip_module_aux(rdr)? },
+                RDB_OPCODE_FUNCTION2 => RdbEvent::Function { code: read_string(rdr)? },
+                RDB_OPCODE_FUNCTION_PRE_GA => return Err(RdbError::Unsupported("pre-release function format")),
+                RDB_OPCODE_SELECTDB => RdbEvent::SelectDb(read_size(rdr)?),
+                RDB_OPCODE_RESIZEDB => {
+                    let keys = read_size(rdr)?;
+                    let expires = read_size(rdr)?;
+                    RdbEvent::ResizeDb { keys, expires }
+                }
+                RDB_OPCODE_SLOT_INFO => {
+                    // cluster slot sizing hints: slot id, keys, expires
+                    for _ in 0..3 {
+                        read_size(rdr)?;
+                    }
+                    continue;
+                }
+                RDB_OPCODE_EXPIRETIME => {
+                    let mut secs = [0u8; 4];
+                    rdr.read_exact(&mut secs)?;
+                    expiry = Some(UNIX_EPOCH + Duration::from_secs(u32::from_le_bytes(secs) as u64));
+                    continue;
+                }
+                RDB_OPCODE_EXPIRETIME_MS => {
+                    let mut ms = [0u8; 8];
+                    rdr.read_exact(&mut ms)?;
+                    expiry = Some(UNIX_EPOCH + Duration::from_millis(u64::from_le_bytes(ms)));
+                    continue;
+                }
+                // LRU idle time and LFU counter: eviction hints we don't track
+                RDB_OPCODE_IDLE => {
+                    read_size(rdr)?;
+                    continue;
+                }
+                RDB_OPCODE_FREQ => {
+                    let mut freq = [0u8; 1];
+                    rdr.read_exact(&mut freq)?;
+                    continue;
+                }
+                type_byte => {
+                    let key = read_string(rdr)?;
+                    match read_object(rdr, type_byte)? {
+                        Some(value) => RdbEvent::Entry { key, value, expiry },
+                        None => RdbEvent::ModuleValue { key },
+                    }
+                }
+            };
+            return Ok(Some(event));
+        }
+    }
+}
+
+impl<R: Read> Iterator for RdbDecoder<R> {
+    type Item = Result<RdbEvent, RdbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.next_event();
+        if result.is_err() {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+/// The 8-byte little-endian CRC64 after the EOF opcode; zero means "not computed".
+fn verify_checksum<R: BufRead>(rdr: &mut Crc64Reader<R>) -> Result<Option<u64>, RdbError> {
+    let computed = rdr.crc();
+    // every version we read (6+) has the footer: a short one is a truncated file
+    let mut stored = [0u8; 8];
+    rdr.read_exact(&mut stored)?;
+
+    match u64::from_le_bytes(stored) {
+        0 => Ok(None),
+        stored if stored != computed => Err(RdbError::ChecksumMismatch { stored, computed }),
+        stored => Ok(Some(stored)),
+    }
+}
+
+/// Inverse of `encode_dump_payload`: checks the version and CRC64 footer,
+/// then decodes the single value in between.
+pub fn decode_dump_payload(payload: &[u8]) -> Result<Value, RdbError> {
+    if payload.len() < 11 {
+        return Err(RdbError::corrupt("DUMP payload", "too short"));
+    }
+    let (body, crc) = payload.split_at(payload.len() - 8);
+    let stored = u64::from_le_bytes(crc.try_into().unwrap());
+    let computed = crc64(0, body);
+    if stored != computed {
+        return Err(RdbError::ChecksumMismatch { stored, computed });
+    }
+
+    let (object, version) = body.split_at(body.len() - 2);
+    let version = u16::from_le_bytes(version.try_into().unwrap()) as u32;
+    if version > RDB_MAX_VERSION {
+        return Err(RdbError::UnsupportedVersion(version));
+    }
+
+    let mut rdr = &object[1..];
+    let value = read_object(&mut rdr, object[0])?
+        .ok_or(RdbError::Unsupported("module value in DUMP payload"))?;
+    if !rdr.is_empty() {
+        return Err(RdbError::corrupt("DUMP payload", format!("{} trailing byte(s)", rdr.len())));
+    }
+    Ok(value)
+}
+
+/// Decodes one value of RDB type `type_byte`. Module values cannot be
+/// represented here and are skipped, returning `None`.
+fn read_object<R: BufRead>(rdr: &mut R, type_byte: u8) -> Result<Option<Value>, RdbError> {
+    let value = match type_byte {
+        RDB_TYPE_STRING => Value::String(read_string(rdr)?),
+        RDB_TYPE_LIST => {
+            let len = read_size(rdr)?;
+            let mut items = VecDeque::with_capacity(len.min(MAX_PREALLOC));
+            for _ in 0..len {
+                items.push_back(read_string(rdr)?);
+            }
+            Value::List(items)
+        }
+        RDB_TYPE_SET => {
+            let len = read_size(rdr)?;
+            let mut members = Dict::new();
+            members.reserve(len.min(MAX_PREALLOC));
+            for _ in 0..len {
+                members.insert(read_string(rdr)?, ());
+            }
+            Value::Set(members)
+        }
+        RDB_TYPE_ZSET | RDB_TYPE_ZSET_2 => {
+            let len = read_size(rdr)?;
+            let mut zset = SortedSet::default();
+            for _ in 0..len {
+                let member = read_string(rdr)?;
+                let score = if type_byte == RDB_TYPE_ZSET_2 {
+                    let mut raw = [0u8; 8];
+                    rdr.read_exact(&mut raw)?;
+                    f64::from_le_bytes(raw)
+                } else {
+                    read_double_string(rdr)?
+                };
+                zset.insert(member, score);
+            }
+            Value::ZSet(zset)
+        }
+        RDB_TYPE_HASH => {
+            let len = read_size(rdr)?;
+            let mut hash = Dict::new();
+            hash.reserve(len.min(MAX_PREALLOC));
+            for _ in 0..len {
+                let field = read_string(rdr)?;
+                hash.insert(field, read_string(rdr)?);
+            }
+            Value::Hash(hash)
+        }
+        RDB_TYPE_HASH_ZIPMAP => {
+            let mut hash = Dict::new();
+            for (field, val) in decode_zipmap(&read_string(rdr)?)? {
+                hash.insert(field, val);
+            }
+            Value::Hash(hash)
+        }
+        RDB_TYPE_LIST_ZIPLIST => Value::List(strings(decode_ziplist(&read_string(rdr)?)?).into()),
+        RDB_TYPE_SET_INTSET => {
+            let ints = decode_intset(&read_string(rdr)?)?;
+            Value::Set(ints.into_iter().map(|n| (n.to_string().into_bytes(), ())).collect())
+        }
+        RDB_TYPE_SET_LISTPACK => {
+            let members = strings(decode_listpack(&read_string(rdr)?)?);
+            Value::Set(members.into_iter().map(|m| (m, ())).collect())
+        }
+        RDB_TYPE_ZSET_ZIPLIST | RDB_TYPE_ZSET_LISTPACK => {
+            let blob = read_string(rdr)?;
+            let items = if type_byte == RDB_TYPE_ZSET_ZIPLIST {
+                decode_ziplist(&blob)?
+            } else {
+                decode_listpack(&blob)?
+            };
+            let mut zset = SortedSet::default();
+            let mut it = items.into_iter();
+            while let Some(member) = it.next() {
+                let score = it.next().ok_or_else(|| invalid("sorted set member without score"))?;
+                zset.insert(member.into_bytes(), entry_to_f64(score)?);
+            }
+            Value::ZSet(zset)
+        }
+        RDB_TYPE_HASH_ZIPLIST | RDB_TYPE_HASH_LISTPACK => {
+            let blob = read_string(rdr)?;
+            let items = if type_byte == RDB_TYPE_HASH_ZIPLIST {
+                decode_ziplist(&blob)?
+            } else {
+                decode_listpack(&blob)?
+            };
+            let mut hash = Dict::new();
+            let mut it = items.into_iter();
+            while let Some(field) = it.next() {
+                let val = it.next().ok_or_else(|| invalid("hash field without value"))?;
+                hash.insert(field.into_bytes(), val.into_bytes());
+            }
+            Value::Hash(hash)
+        }
+        RDB_TYPE_LIST_QUICKLIST => {
+            let nodes = read_size(rdr)?;
+            let mut items = VecDeque::new();
+            for _ in 0..nodes {
+                items.extend(strings(decode_ziplist(&read_string(rdr)?)?));
+            }
+            Value::List(items)
+        }
+        RDB_TYPE_LIST_QUICKLIST_2 => {
+            let nodes = read_size(rdr)?;
+            let mut items = VecDeque::new();
+            for _ in 0..nodes {
+                let container = read_size(rdr)?;
+                let blob = read_string(rdr)?;
+                if container as u64 == QUICKLIST_NODE_CONTAINER_PLAIN {
+                    items.push_back(blob);
+                } else {
+                    items.extend(strings(decode_listpack(&blob)?));
+                }
+            }
+            Value::List(items)
+        }
+        RDB_TYPE_STREAM_LISTPACKS | RDB_TYPE_STREAM_LISTPACKS_2 | RDB_TYPE_STREAM_LISTPACKS_3 => {
+            Value::Stream(read_stream_listpacks(rdr, type_byte)?)
+        }
+        RDB_TYPE_MODULE_2 => {
+            let module_id = read_size(rdr)?;
+            eprintln!("[rdb::read_object] Skipping value of module id {:#x}", module_id);
+            skip_module_value(rdr)?;
+            return Ok(None);
+        }
+        RDB_TYPE_MODULE_PRE_GA => return Err(RdbError::Unsupported("pre-GA module value")),
+        other => return Err(RdbError::UnknownType(other)),
+    };
+
+    Ok(Some(value))
+}
+
+fn invalid(msg: &str) -> RdbError {
+    RdbError::corrupt("RDB record", msg)
+}
+
+fn utf8(data: Vec<u8>) -> Result<String, RdbError> {
+    String::from_utf8(data).map_err(|_| RdbError::corrupt("string", "invalid UTF-8"))
+}
+
+fn strings(entries: Vec<ListpackEntry>) -> Vec<Vec<u8>> {
+    entries.into_iter().map(ListpackEntry::into_bytes).collect()
+}
+
+fn entry_to_f64(entry: ListpackEntry) -> Result<f64, RdbError> {
+    match entry {
+        ListpackEntry::Int(n) => Ok(n as f64),
+        other => other
+            .into_string()?
+            .parse()
+            .map_err(|_| invalid("sorted set score is not a number")),
+    }
+}
+
+/// Scores of RDB_TYPE_ZSET: a length byte, with 253/254/255 meaning NaN/+inf/-inf.
+fn read_double_string<R: BufRead>(rdr: &mut R) -> Result<f64, RdbError> {
+    let mut len = [0u8; 1];
+    rdr.read_exact(&mut len)?;
+    match len[0] {
+        253 => Ok(f64::NAN),
+        254 => Ok(f64::INFINITY),
+        255 => Ok(f64::NEG_INFINITY),
+        n => {
+            let mut buf = vec![0u8; n as usize];
+            rdr.read_exact(&mut buf)?;
+            utf8(buf)?.parse().map_err(|_| invalid("invalid double"))
+        }
+    }
+}
+
+/// MODULE_AUX: `<module id><when opcode><when>` followed by module-serialized
+/// data. Returns the module id.
+fn skip_module_aux<R: BufRead>(rdr: &mut R) -> Result<u64, RdbError> {
+    let module_id = read_size(rdr)? as u64;
+    let when_opcode = read_size(rdr)?;
+    if when_opcode != RDB_MODULE_OPCODE_UINT {
+        return Err(invalid("bad when opcode in module aux data"));
+    }
+    let _when = read_size(rdr)?;
+    skip_module_value(rdr)?;
+    Ok(module_id)
+}
+
+/// Module-serialized data is self-describing: typed opcodes until EOF.
+fn skip_module_value<R: BufRead>(rdr: &mut R) -> Result<(), RdbError> {
+    loop {
+        match read_size(rdr)? {
+            RDB_MODULE_OPCODE_EOF => return Ok(()),
+            RDB_MODULE_OPCODE_SINT | RDB_MODULE_OPCODE_UINT => {
+                let _ = read_size(rdr)?;
+            }
+            RDB_MODULE_OPCODE_FLOAT => {
+                let mut f = [0u8; 4];
+                rdr.read_exact(&mut f)?;
+            }
+            RDB_MODULE_OPCODE_DOUBLE => {
+                let mut d = [0u8; 8];
+                rdr.read_exact(&mut d)?;
+            }
+            RDB_MODULE_OPCODE_STRING => {
+                let _ = read_string(rdr)?;
+            }
+            other => {
+                return Err(RdbError::corrupt("module value", format!("unknown opcode {}", other)));
+            }
+        }
+    }
+}
+
+/// A length field, or the special-encoding marker of an encoded string.
+enum Length {
+    Plain(usize),
+    Encoded(u8),
+}
+
+fn read_length<R: BufRead>(rdr: &mut R) -> Result<Length, RdbError> {
+    let mut first = [0u8; 1];
+    rdr.read_exact(&mut first)?;
+    let b0 = first[0];
+
+    match b0 >> 6 {
+        0 => Ok(Length::Plain((b0 & 0x3F) as usize)),
+        1 => {
+            let mut next = [0u8; 1];
+            rdr.read_exact(&mut next)?;
+            Ok(Length::Plain((((b0 & 0x3F) as usize) << 8) | next[0] as usize))
+        }
+        2 if b0 == 0x80 => {
+            let mut arr = [0u8; 4];
+            rdr.read_exact(&mut arr)?;
+            Ok(Length::Plain(u32::from_be_bytes(arr) as usize))
+        }
+        2 if b0 == 0x81 => {
+            let mut arr = [0u8; 8];
+            rdr.read_exact(&mut arr)?;
+            Ok(Length::Plain(u64::from_be_bytes(arr) as usize))
+        }
+        3 => Ok(Length::Encoded(b0 & 0x3F)),
+        _ => Err(RdbError::corrupt("length", format!("invalid size tag 0x{:X}", b0))),
+    }
+}
+
+fn read_size<R: BufRead>(rdr: &mut R) -> Result<usize, RdbError> {
+    match read_length(rdr)? {
+        Length::Plain(n) => Ok(n),
+        Length::Encoded(_) => Err(RdbError::corrupt("length", "expected a plain length, got an encoded string")),
+    }
+}
+
+/// Metadata text, such as an AUX field. Keys and values are read with
+/// `read_string`, which keeps their bytes.
+fn read_text<R: BufRead>(rdr: &mut R) -> Result<String, RdbError> {
+    utf8(read_string(rdr)?)
+}
+
+/// A length-prefixed, integer-encoded or LZF-compressed string, as bytes.
+fn read_string<R: BufRead>(rdr: &mut R) -> Result<Vec<u8>, RdbError> {
+    let subtype = match read_length(rdr)? {
+        Length::Plain(len) => return read_exact_len(rdr, len),
+        Length::Encoded(subtype) => subtype,
+    };
+
+    let val = match subtype {
+        0 => {
+            let mut x = [0u8; 1];
+            rdr.read_exact(&mut x)?;
+            (x[0] as i8).to_string()
+        }
+        1 => {
+            let mut x = [0u8; 2];
+            rdr.read_exact(&mut x)?;
+            i16::from_le_bytes(x).to_string()
+        }
+        2 => {
+            let mut x = [0u8; 4];
+            rdr.read_exact(&mut x)?;
+            i32::from_le_bytes(x).to_string()
+        }
+        3 => {
+            let compressed_len = read_size(rdr)?;
+            let len = read_size(rdr)?;
+            let compressed = read_exact_len(rdr, compressed_len)?;
+            return lzf_decompress(&compressed, len);
+        }
+        _ => {
+            return Err(RdbError::corrupt("string", format!("unsupported encoding 0x{:X}", subtype)));
+        }
+    };
+
+    Ok(val.into_bytes())
+}
+
+/// Reads `len` bytes, growing the buffer as they arrive rather than trusting
+/// `len` up front: a bogus length ends in `UnexpectedEof`, not a huge
+/// allocation.
+fn read_exact_len<R: BufRead>(rdr: &mut R, len: usize) -> Result<Vec<u8>, RdbError> {
+    let mut data = Vec::with_capacity(len.min(MAX_PREALLOC));
+    rdr.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(RdbError::UnexpectedEof);
+    }
+    Ok(data)
+}
+
+/// RDB_TYPE_STREAM_LISTPACKS{,_2,_3}: listpack nodes, stream metadata, then
+/// consumer groups. Later versions add ID/counter metadata and consumer active times.
+fn read_stream_listpacks<R: BufRead>(rdr: &mut R, type_byte: u8) -> Result<Vec<StreamEntry>, RdbError> {
+    let bad = |msg: &str| RdbError::corrupt("stream", msg);
+
+    let nodes = read_size(rdr)?;
+    let mut entries = Vec::new();
+
+    for _ in 0..nodes {
+        let master_key = read_string(rdr)?;
+        if master_key.len() != 16 {
+            return Err(bad("master ID must be 16 bytes"));
+        }
+        let master_ms = u64::from_be_bytes(master_key[..8].try_into().unwrap());
+        let master_seq = u64::from_be_bytes(master_key[8..].try_into().unwrap());
+
+        let mut lp = decode_listpack(&read_string(rdr)?)?.into_iter();
+        let mut next = || lp.next().ok_or_else(|| bad("listpack ended early"));
+
+        let live = next()?.as_int()?;
+        let deleted = next()?.as_int()?;
+        let master_field_count = next()?.as_int()?;
+        let mut master_fields = Vec::new();
+        for _ in 0..master_field_count {
+            master_fields.push(next()?.into_bytes());
+        }
+        next()?; // master entry terminator
+
+        for _ in 0..live + deleted {
+            let flags = next()?.as_int()?;
+            let ms = master_ms.wrapping_add(next()?.as_int()? as u64);
+            let seq = master_seq.wrapping_add(next()?.as_int()? as u64);
+
+            let mut fields = Vec::new();
+            if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+                for field in &master_fields {
+                    fields.push((field.clone(), next()?.into_bytes()));
+                }
+            } else {
+                let count = next()?.as_int()?;
+                for _ in 0..count {
+                    let field = next()?.into_bytes();
+                    fields.push((field, next()?.into_bytes()));
+                }
+            }
+            next()?; // lp-count
+
+            if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+                entries.push(StreamEntry { id: format!("{ms}-{seq}"), fields });
+            }
+        }
+    }
+
+    let _length = read_size(rdr)?;
+    let _last_ms = read_size(rdr)?;
+    let _last_seq = read_size(rdr)?;
+    if type_byte >= RDB_TYPE_STREAM_LISTPACKS_2 {
+        let _first_ms = read_size(rdr)?;
+        let _first_seq = read_size(rdr)?;
+        let _max_deleted_ms = read_size(rdr)?;
+        let _max_deleted_seq = read_size(rdr)?;
+        let _entries_added = read_size(rdr)?;
+    }
+
+    // consumer groups are not modelled; skip them
+    let groups = read_size(rdr)?;
+    for _ in 0..groups {
+        let _name = read_string(rdr)?;
+        let _last_ms = read_size(rdr)?;
+        let _last_seq = read_size(rdr)?;
+        if type_byte >= RDB_TYPE_STREAM_LISTPACKS_2 {
+            let _entries_read = read_size(rdr)?;
+        }
+        let pel = read_size(rdr)?;
+        for _ in 0..pel {
+            let mut skip = [0u8; 16 + 8];
+            rdr.read_exact(&mut skip)?;
+            let _delivery_count = read_size(rdr)?;
+        }
+        let consumers = read_size(rdr)?;
+        for _ in 0..consumers {
+            let _name = read_string(rdr)?;
+            let mut seen = [0u8; 8];
+            rdr.read_exact(&mut seen)?;
+            if type_byte >= RDB_TYPE_STREAM_LISTPACKS_3 {
+                let mut active = [0u8; 8];
+                rdr.read_exact(&mut active)?;
+            }
+            let owned = read_size(rdr)?;
+            for _ in 0..owned {
+                let mut id = [0u8; 16];
+                rdr.read_exact(&mut id)?;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdb::crc64::crc64;
+    use crate::rdb::encode::{encode_dump_payload, RDB_VERSION};
+    use crate::rdb::listpack::ListpackWriter;
+
+    /// A length-prefixed RDB string.
+    fn string(s: &[u8]) -> Vec<u8> {
+        let mut out = if s.len() < 64 {
+            vec![s.len() as u8]
+        } else {
+            vec![0x40 | (s.len() >> 8) as u8, s.len() as u8]
+        };
+        out.extend_from_slice(s);
+        out
+    }
+
+    /// A ziplist of short strings.
+    fn ziplist(items: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut prev = 0;
+        for item in items {
+            body.extend_from_slice(&[prev as u8, item.len() as u8]);
+            body.extend_from_slice(item.as_bytes());
+            prev = 2 + item.len();
+        }
+        let mut out = ((11 + body.len()) as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&(items.len() as u16).to_le_bytes());
+        out.extend_from_slice(&body);
+        out.push(0xFF);
+        out
+    }
+
+    fn listpack(items: &[&str]) -> Vec<u8> {
+        let mut lp = ListpackWriter::new();
+        for item in items {
+            lp.push_str(item.as_bytes());
+        }
+        lp.finish()
+    }
+
+    /// An RDB file holding `body`, with a correct checksum.
+    fn rdb_file(body: &[u8]) -> Vec<u8> {
+        let mut out = b"REDIS0011".to_vec();
+        out.extend_from_slice(body);
+        out.push(RDB_OPCODE_EOF);
+        let crc = crc64(0, &out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// A DUMP payload of one object of `type_byte` encoded as `body`.
+    fn dump(type_byte: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![type_byte];
+        out.extend_from_slice(body);
+        out.extend_from_slice(&RDB_VERSION.to_le_bytes());
+        let crc = crc64(0, &out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// Decodes a file holding one key of `type_byte` encoded as `body`.
+    fn try_decode(type_byte: u8, body: &[u8]) -> Result<Value, RdbError> {
+        let mut entry = vec![type_byte];
+        entry.extend(string(b"k"));
+        entry.extend_from_slice(body);
+        let file = rdb_file(&entry);
+        match RdbDecoder::new(&file[..])?.next() {
+            Some(Ok(RdbEvent::Entry { value, .. })) => Ok(value),
+            Some(Err(e)) => Err(e),
+            other => panic!("expected an entry, got {:?}", other),
+        }
+    }
+
+    fn decode(type_byte: u8, body: &[u8]) -> Value {
+        try_decode(type_byte, body).unwrap()
+    }
+
+    fn text(bytes: Vec<u8>) -> String {
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn list(value: Value) -> Vec<String> {
+        match value {
+            Value::List(items) => items.into_iter().map(text).collect(),
+            other => panic!("expected a list, got {:?}", other),
+        }
+    }
+
+    fn set(value: Value) -> Vec<String> {
+        match value {
+            Value::Set(members) => {
+                let mut members: Vec<String> = members.into_iter().map(|(m, ())| text(m)).collect();
+                members.sort();
+                members
+            }
+            other => panic!("expected a set, got {:?}", other),
+        }
+    }
+
+    fn hash(value: Value) -> Vec<(String, String)> {
+        match value {
+            Value::Hash(fields) => {
+                let mut fields: Vec<_> = fields.into_iter().map(|(f, v)| (text(f), text(v))).collect();
+                fields.sort();
+                fields
+            }
+            other => panic!("expected a hash, got {:?}", other),
+        }
+    }
+
+    fn zset(value: Value) -> Vec<(String, f64)> {
+        match value {
+            Value::ZSet(zset) => zset.iter().map(|(m, s)| (text(m.to_vec()), s)).collect(),
+            other => panic!("expected a sorted set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn events_come_in_file_order_with_expiries_attached() {
+        let mut body = vec![RDB_OPCODE_AUX];
+        body.extend(string(b"redis-ver"));
+        body.extend(string(b"7.2.0"));
+        body.extend_from_slice(&[RDB_OPCODE_SELECTDB, 2, RDB_OPCODE_RESIZEDB, 2, 1]);
+        body.push(RDB_OPCODE_EXPIRETIME);
+        body.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        body.extend_from_slice(&[RDB_OPCODE_IDLE, 5, RDB_OPCODE_FREQ, 9]);
+        body.push(RDB_TYPE_STRING);
+        body.extend(string(b"volatile"));
+        body.extend(string(b"v"));
+        body.push(RDB_TYPE_STRING);
+        body.extend(string(b"plain"));
+        body.extend(string(b"p"));
+
+        let file = rdb_file(&body);
+        let mut decoder = RdbDecoder::new(&file[..]).unwrap();
+        assert_eq!(decoder.version(), 11);
+        let events: Vec<RdbEvent> = (&mut decoder).collect::<Result<_, _>>().unwrap();
+        assert!(decoder.checksum().is_some());
+
+        assert!(matches!(&events[0], RdbEvent::Aux { key, value } if key == "redis-ver" && value == "7.2.0"));
+        assert!(matches!(events[1], RdbEvent::SelectDb(2)));
+        assert!(matches!(events[2], RdbEvent::ResizeDb { keys: 2, expires: 1 }));
+        match &events[3] {
+            RdbEvent::Entry { key, expiry, .. } => {
+                assert_eq!(key, b"volatile");
+                assert_eq!(*expiry, Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+            }
+            other => panic!("expected an entry, got {:?}", other),
+        }
+        assert!(matches!(&events[4], RdbEvent::Entry { key, expiry: None, .. } if key == b"plain"));
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn checksum_is_verified_unless_absent() {
+        let mut body = vec![RDB_TYPE_STRING];
+        body.extend(string(b"k"));
+        body.extend(string(b"v"));
+        let good = rdb_file(&body);
+
+        let mut corrupted = good.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let result: Result<Vec<_>, _> = RdbDecoder::new(&corrupted[..]).unwrap().collect();
+        assert!(matches!(result, Err(RdbError::ChecksumMismatch { .. })));
+
+        // written with rdbchecksum off
+        let mut zeroed = good.clone();
+        let len = zeroed.len();
+        zeroed[len - 8..].fill(0);
+        let mut decoder = RdbDecoder::new(&zeroed[..]).unwrap();
+        assert_eq!((&mut decoder).count(), 1);
+        assert_eq!(decoder.checksum(), None);
+
+        // a footer cut short is a truncated file, not a missing checksum
+        for cut in [len - 8, len - 3] {
+            let result: Result<Vec<_>, _> = RdbDecoder::new(&good[..cut]).unwrap().collect();
+            assert!(matches!(result, Err(RdbError::UnexpectedEof)), "cut at {}: {:?}", cut, result);
+        }
+    }
+
+    #[test]
+    fn errors_are_typed_and_end_the_iteration() {
+        let mut body = vec![RDB_TYPE_STRING];
+        body.extend(string(b"key"));
+        body.extend(string(b"value"));
+        let file = rdb_file(&body);
+
+        let mut decoder = RdbDecoder::new(&file[..file.len() - 12]).unwrap();
+        assert!(matches!(decoder.next(), Some(Err(RdbError::UnexpectedEof))));
+        assert!(decoder.next().is_none());
+
+        let unknown = rdb_file(&[0x42, 1, b'k']);
+        let mut decoder = RdbDecoder::new(&unknown[..]).unwrap();
+        assert!(matches!(decoder.next(), Some(Err(RdbError::UnknownType(0x42)))));
+        assert!(decoder.next().is_none());
+
+        assert!(matches!(RdbDecoder::new(&b"REDIS001"[..]), Err(RdbError::UnexpectedEof)));
+        assert!(matches!(RdbDecoder::new(&b"REDIS00x1"[..]), Err(RdbError::BadMagic)));
+    }
+
+    #[test]
+    fn into_inner_resumes_after_the_checksum() {
+        let mut file = rdb_file(&[]);
+        file.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+
+        let mut decoder = RdbDecoder::new(&file[..]).unwrap();
+        assert!(decoder.next().is_none());
+        let mut rest = Vec::new();
+        decoder.into_inner().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"*1\r\n$4\r\nPING\r\n");
+    }
+
+    #[test]
+    fn integer_and_lzf_encoded_strings() {
+        let string_of = |body: &[u8]| match decode(RDB_TYPE_STRING, body) {
+            Value::String(s) => s,
+            other => panic!("expected a string, got {:?}", other),
+        };
+        assert_eq!(string_of(&[0xC0, 0xF6]), b"-10");
+        assert_eq!(string_of(&[0xC1, 0xE8, 0x03]), b"1000");
+        assert_eq!(string_of(&[0xC2, 0x90, 0xEE, 0xFE, 0xFF]), b"-70000");
+        // "a" then 9 bytes copied from 1 back
+        assert_eq!(string_of(&[0xC3, 5, 10, 0x00, b'a', 0xE0, 0x00, 0x00]), b"aaaaaaaaaa");
+
+        assert!(matches!(try_decode(RDB_TYPE_STRING, &[0xC4]), Err(RdbError::Corrupt { .. })));
+    }
+
+    #[test]
+    fn every_list_encoding() {
+        let mut plain = vec![2];
+        plain.extend(string(b"a"));
+        plain.extend(string(b"b"));
+        assert_eq!(list(decode(RDB_TYPE_LIST, &plain)), ["a", "b"]);
+
+        assert_eq!(list(decode(RDB_TYPE_LIST_ZIPLIST, &string(&ziplist(&["x", "y"])))), ["x", "y"]);
+
+        let mut quicklist = vec![2];
+        quicklist.extend(string(&ziplist(&["1", "2"])));
+        quicklist.extend(string(&ziplist(&["3"])));
+        assert_eq!(list(decode(RDB_TYPE_LIST_QUICKLIST, &quicklist)), ["1", "2", "3"]);
+
+        // a packed (listpack) node, then a plain one
+        let mut quicklist2 = vec![2, 2];
+        quicklist2.extend(string(&listpack(&["p", "-5"])));
+        quicklist2.push(QUICKLIST_NODE_CONTAINER_PLAIN as u8);
+        quicklist2.extend(string(&[b'L'; 100]));
+        assert_eq!(list(decode(RDB_TYPE_LIST_QUICKLIST_2, &quicklist2)), ["p", "-5", &"L".repeat(100)]);
+    }
+
+    #[test]
+    fn every_set_encoding() {
+        let mut intset = vec![];
+        intset.extend_from_slice(&2u32.to_le_bytes());
+        intset.extend_from_slice(&2u32.to_le_bytes());
+        intset.extend_from_slice(&(-1i16).to_le_bytes());
+        intset.extend_from_slice(&7i16.to_le_bytes());
+        assert_eq!(set(decode(RDB_TYPE_SET_INTSET, &string(&intset))), ["-1", "7"]);
+
+        assert_eq!(set(decode(RDB_TYPE_SET_LISTPACK, &string(&listpack(&["b", "a", "3"])))), ["3", "a", "b"]);
+
+        let mut plain = vec![1];
+        plain.extend(string(b"only"));
+        assert_eq!(set(decode(RDB_TYPE_SET, &plain)), ["only"]);
+    }
+
+    #[test]
+    fn every_hash_encoding() {
+        let expected = vec![("f1".to_string(), "v1".to_string()), ("f2".to_string(), "2".to_string())];
+        assert_eq!(hash(decode(RDB_TYPE_HASH_ZIPLIST, &string(&ziplist(&["f1", "v1", "f2", "2"])))), expected);
+        assert_eq!(hash(decode(RDB_TYPE_HASH_LISTPACK, &string(&listpack(&["f2", "2", "f1", "v1"])))), expected);
+
+        let zipmap = [2, 2, b'f', b'1', 2, 0, b'v', b'1', 2, b'f', b'2', 1, 1, b'2', 0, 0xFF];
+        assert_eq!(hash(decode(RDB_TYPE_HASH_ZIPMAP, &string(&zipmap))), expected);
+
+        let odd = try_decode(RDB_TYPE_HASH_LISTPACK, &string(&listpack(&["f1", "v1", "f2"])));
+        assert!(matches!(odd, Err(RdbError::Corrupt { .. })));
+    }
+
+    #[test]
+    fn every_sorted_set_encoding() {
+        let mut v1 = vec![3];
+        v1.extend(string(b"a"));
+        v1.extend(string(b"1.5"));
+        v1.extend(string(b"up"));
+        v1.push(254);
+        v1.extend(string(b"down"));
+        v1.push(255);
+        assert_eq!(
+            zset(decode(RDB_TYPE_ZSET, &v1)),
+            [("down".to_string(), f64::NEG_INFINITY), ("a".to_string(), 1.5), ("up".to_string(), f64::INFINITY)]
+        );
+
+        let expected = [("b".to_string(), -2.0), ("a".to_string(), 0.25)];
+        assert_eq!(zset(decode(RDB_TYPE_ZSET_ZIPLIST, &string(&ziplist(&["a", "0.25", "b", "-2"])))), expected);
+        assert_eq!(zset(decode(RDB_TYPE_ZSET_LISTPACK, &string(&listpack(&["a", "0.25", "b", "-2"])))), expected);
+    }
+
+    #[test]
+    fn module_values_are_skipped() {
+        // module id, then an unsigned int and the module EOF marker
+        let mut body = vec![RDB_TYPE_MODULE_2];
+        body.extend(string(b"m"));
+        body.extend_from_slice(&[0x05, RDB_MODULE_OPCODE_UINT as u8, 0x07, RDB_MODULE_OPCODE_EOF as u8]);
+        body.push(RDB_TYPE_STRING);
+        body.extend(string(b"k"));
+        body.extend(string(b"v"));
+
+        let file = rdb_file(&body);
+        let events: Vec<RdbEvent> = RdbDecoder::new(&file[..]).unwrap().collect::<Result<_, _>>().unwrap();
+        assert!(matches!(&events[0], RdbEvent::ModuleValue { key } if key == b"m"));
+        assert!(matches!(&events[1], RdbEvent::Entry { key, .. } if key == b"k"));
+    }
+
+    #[test]
+    fn dump_payloads_round_trip_and_are_checked() {
+        let payload = encode_dump_payload(&Value::List(["x".into(), "y".into()].into())).unwrap();
+        assert_eq!(list(decode_dump_payload(&payload).unwrap()), ["x", "y"]);
+
+        // a hand-built payload decodes the same as one inside a file
+        assert_eq!(list(decode_dump_payload(&dump(RDB_TYPE_LIST_ZIPLIST, &string(&ziplist(&["z"])))).unwrap()), ["z"]);
+
+        let mut corrupted = payload.clone();
+        corrupted[1] ^= 0xFF;
+        assert!(matches!(decode_dump_payload(&corrupted), Err(RdbError::ChecksumMismatch { .. })));
+
+        let mut trailing = string(b"v");
+        trailing.push(0);
+        assert!(matches!(decode_dump_payload(&dump(RDB_TYPE_STRING, &trailing)), Err(RdbError::Corrupt { .. })));
+
+        let mut too_new = vec![RDB_TYPE_STRING];
+        too_new.extend(string(b"v"));
+        too_new.extend_from_slice(&(RDB_MAX_VERSION as u16 + 1).to_le_bytes());
+        let crc = crc64(0, &too_new);
+        too_new.extend_from_slice(&crc.to_le_bytes());
+        assert!(matches!(decode_dump_payload(&too_new), Err(RdbError::UnsupportedVersion(_))));
+
+        assert!(decode_dump_payload(&[0; 5]).is_err());
+    }
+
+    #[test]
+    fn binary_keys_members_and_fields_load() {
+        let mut body = vec![RDB_TYPE_SET];
+        body.extend(string(b"\xe9"));
+        body.push(1);
+        body.extend(string(b"\xff\x00"));
+        let mut lp = ListpackWriter::new();
+        for item in [&b"\xfe"[..], b"\x80v"] {
+            lp.push_str(item);
+        }
+        body.push(RDB_TYPE_HASH_LISTPACK);
+        body.extend(string(b"h\xc3"));
+        body.extend(string(&lp.finish()));
+
+        let file = rdb_file(&body);
+        let events: Vec<RdbEvent> = RdbDecoder::new(&file[..]).unwrap().collect::<Result<_, _>>().unwrap();
+        match &events[0] {
+            RdbEvent::Entry { key, value: Value::Set(members), .. } => {
+                assert_eq!(key, b"\xe9");
+                assert!(members.contains_key(&b"\xff\x00"[..]));
+            }
+            other => panic!("expected a set, got {:?}", other),
+        }
+        match &events[1] {
+            RdbEvent::Entry { key, value: Value::Hash(fields), .. } => {
+                assert_eq!(key, b"h\xc3");
+                assert_eq!(fields.get(&b"\xfe"[..]).map(Vec::as_slice), Some(&b"\x80v"[..]));
+            }
+            other => panic!("expected a hash, got {:?}", other),
+        }
+
+        let mut zset = SortedSet::default();
+        zset.insert(b"\xe9".to_vec(), 1.0);
+        let payload = encode_dump_payload(&Value::ZSet(zset)).unwrap();
+        match decode_dump_payload(&payload).unwrap() {
+            Value::ZSet(zset) => assert_eq!(zset.score(b"\xe9"), Some(1.0)),
+            other => panic!("expected a sorted set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn huge_declared_lengths_fail_without_allocating_them() {
+        let huge = {
+            let mut len = vec![0x81];
+            len.extend_from_slice(&(1u64 << 62).to_be_bytes());
+            len
+        };
+        // a string claiming 2^62 bytes
+        let mut body = huge.clone();
+        body.extend_from_slice(b"short");
+        assert!(matches!(decode_dump_payload(&dump(RDB_TYPE_STRING, &body)), Err(RdbError::UnexpectedEof)));
+
+        // a list claiming 2^62 elements
+        let mut body = huge.clone();
+        body.extend(string(b"only"));
+        assert!(decode_dump_payload(&dump(RDB_TYPE_LIST, &body)).is_err());
+
+        // LZF data claiming to expand to 2^62 bytes
+        let mut body = vec![0xC3, 0x02];
+        body.extend_from_slice(&huge);
+        body.extend_from_slice(&[0x01, b'a', b'b']);
+        assert!(decode_dump_payload(&dump(RDB_TYPE_STRING, &body)).is_err());
+    }
+
+}
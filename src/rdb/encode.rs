@@ -0,0 +1,449 @@
+use crate::rdb::crc64::{crc64, Crc64Writer};
+use crate::rdb::listpack::ListpackWriter;
+use crate::rdb::{
+    Db, StreamEntry, Value, QUICKLIST_NODE_CONTAINER_PACKED, RDB_OPCODE_AUX, RDB_OPCODE_EOF,
+    RDB_OPCODE_EXPIRETIME_MS, RDB_OPCODE_RESIZEDB, RDB_OPCODE_SELECTDB, RDB_TYPE_HASH,
+    RDB_TYPE_LIST_QUICKLIST_2, RDB_TYPE_SET, RDB_TYPE_STREAM_LISTPACKS, RDB_TYPE_STRING, RDB_TYPE_ZSET_2,
+};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version written in file headers and DUMP payloads.
+pub const RDB_VERSION: u16 = 11;
+
+/// Redis release this server presents itself as: the `redis-ver` aux field
+/// of its RDB files and HELLO's `version`. 7.2 is the last release writing
+/// `RDB_VERSION`.
+pub const REDIS_VERSION: &str = "7.2.0";
+
+/// Entries per stream listpack node, as `stream-node-max-entries` defaults to.
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// Bytes per list quicklist node, as `list-max-listpack-size -2` allows.
+const LIST_MAX_LISTPACK_BYTES: usize = 8 * 1024;
+
+/// Stream listpack entry flags.
+pub(crate) const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+pub(crate) const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+/// Serializes a point-in-time copy of every database into a complete RDB file.
+pub fn encode_rdb_snapshot(dbs: &[Db]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_rdb_snapshot(&mut out, dbs)?;
+    Ok(out)
+}
+
+/// Streams an RDB file into `out`, finishing with its CRC64 checksum.
+/// Each non-empty database gets its own SELECTDB section.
+pub fn write_rdb_snapshot<W: Write>(out: &mut W, dbs: &[Db]) -> io::Result<()> {
+    let mut out = Crc64Writer::new(out);
+    let out = &mut out;
+    let now = SystemTime::now();
+
+    write!(out, "REDIS{:04}", RDB_VERSION)?;
+    write_aux(out, "redis-ver", REDIS_VERSION)?;
+    write_aux(out, "redis-bits", "64")?;
+    let ctime = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    write_aux(out, "ctime", &ctime.to_string())?;
+    write_aux(out, "aof-base", "0")?;
+
+    for (index, db) in dbs.iter().enumerate() {
+        let live: Vec<_> = db
+            .iter()
+            .filter(|(_, (_, expiry))| expiry.is_none_or(|t| t > now))
+            .collect();
+        if live.is_empty() {
+            continue;
+        }
+        println!("[rdb::encode] Writing DB {} with {} live key(s)", index, live.len());
+
+        out.write_all(&[RDB_OPCODE_SELECTDB])?;
+        write_size(out, index as u64)?;
+
+        let expires = live.iter().filter(|(_, (_, expiry))| expiry.is_some()).count();
+        out.write_all(&[RDB_OPCODE_RESIZEDB])?;
+        write_size(out, live.len() as u64)?;
+        write_size(out, expires as u64)?;
+
+        for (key, (value, expiry)) in live {
+            if let Some(t) = expiry {
+                let ms = t.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+                out.write_all(&[RDB_OPCODE_EXPIRETIME_MS])?;
+                out.write_all(&ms.to_le_bytes())?;
+            }
+            write_value(out, key, value)?;
+        }
+    }
+
+    out.write_all(&[RDB_OPCODE_EOF])?;
+    let crc = out.crc();
+    out.write_all(&crc.to_le_bytes())?;
+    out.flush()
+}
+
+fn write_aux<W: Write>(out: &mut W, key: &str, val: &str) -> io::Result<()> {
+    out.write_all(&[RDB_OPCODE_AUX])?;
+    write_string(out, key.as_bytes())?;
+    write_string(out, val.as_bytes())
+}
+
+fn write_value<W: Write>(out: &mut W, key: &[u8], value: &Value) -> io::Result<()> {
+    out.write_all(&[object_type(value)])?;
+    write_string(out, key)?;
+    write_object(out, value)
+}
+
+/// DUMP payload: the value's RDB type byte and encoding, then the RDB version
+/// (u16, little-endian) and a CRC64 of everything before the checksum.
+pub fn encode_dump_payload(value: &Value) -> io::Result<Vec<u8>> {
+    let mut payload = vec![object_type(value)];
+    write_object(&mut payload, value)?;
+    payload.extend_from_slice(&RDB_VERSION.to_le_bytes());
+    let crc = crc64(0, &payload);
+    payload.extend_from_slice(&crc.to_le_bytes());
+    Ok(payload)
+}
+
+fn object_type(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => RDB_TYPE_STRING,
+        Value::List(_) => RDB_TYPE_LIST_QUICKLIST_2,
+        Value::Set(_) => RDB_TYPE_SET,
+        Value::ZSet(_) => RDB_TYPE_ZSET_2,
+        Value::Hash(_) => RDB_TYPE_HASH,
+        Value::Stream(_) => RDB_TYPE_STREAM_LISTPACKS,
+    }
+}
+
+fn write_object<W: Write>(out: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::String(s) => write_string(out, s),
+        Value::List(items) => write_list(out, items),
+        Value::Set(members) => {
+            write_size(out, members.len() as u64)?;
+            for member in members.keys() {
+                write_string(out, member)?;
+            }
+            Ok(())
+        }
+        Value::ZSet(zset) => {
+            write_size(out, zset.len() as u64)?;
+            for (member, score) in zset.iter() {
+                write_string(out, member)?;
+                out.write_all(&score.to_le_bytes())?;
+            }
+            Ok(())
+        }
+        Value::Hash(hash) => {
+            write_size(out, hash.len() as u64)?;
+            for (field, val) in hash {
+                write_string(out, field)?;
+                write_string(out, val)?;
+            }
+            Ok(())
+        }
+        Value::Stream(entries) => write_stream(out, entries),
+    }
+}
+
+/// Lists are a quicklist: packed listpack nodes of up to
+/// `LIST_MAX_LISTPACK_BYTES` each (an element too big for that gets a node of
+/// its own).
+fn write_list<W: Write>(out: &mut W, items: &VecDeque<Vec<u8>>) -> io::Result<()> {
+    let mut nodes = Vec::new();
+    let mut lp = ListpackWriter::new();
+    for item in items {
+        if !lp.is_empty() && lp.size() + item.len() > LIST_MAX_LISTPACK_BYTES {
+            nodes.push(std::mem::take(&mut lp).finish());
+        }
+        lp.push_str(item);
+    }
+    if !lp.is_empty() {
+        nodes.push(lp.finish());
+    }
+
+    write_size(out, nodes.len() as u64)?;
+    for node in &nodes {
+        write_size(out, QUICKLIST_NODE_CONTAINER_PACKED)?;
+        write_string(out, node)?;
+    }
+    Ok(())
+}
+
+/// Streams are a radix tree of listpack nodes keyed by each node's master ID.
+fn write_stream<W: Write>(out: &mut W, entries: &[StreamEntry]) -> io::Result<()> {
+    let nodes: Vec<&[StreamEntry]> = entries.chunks(STREAM_NODE_MAX_ENTRIES).collect();
+    write_size(out, nodes.len() as u64)?;
+
+    for node in &nodes {
+        let (master_ms, master_seq) = node[0].id_parts();
+        let mut master_key = Vec::with_capacity(16);
+        master_key.extend_from_slice(&master_ms.to_be_bytes());
+        master_key.extend_from_slice(&master_seq.to_be_bytes());
+        write_string(out, &master_key)?;
+
+        let master_fields: Vec<&[u8]> = node[0].fields.iter().map(|(f, _)| f.as_slice()).collect();
+
+        let mut lp = ListpackWriter::new();
+        lp.push_int(node.len() as i64); // live entries
+        lp.push_int(0); // deleted entries
+        lp.push_int(master_fields.len() as i64);
+        for field in &master_fields {
+            lp.push_str(field);
+        }
+        lp.push_int(0); // master entry terminator
+
+        for entry in node.iter() {
+            let (ms, seq) = entry.id_parts();
+            let same_fields = entry.fields.len() == master_fields.len()
+                && entry.fields.iter().zip(&master_fields).all(|((f, _), m)| f == m);
+
+            lp.push_int(if same_fields { STREAM_ITEM_FLAG_SAMEFIELDS } else { 0 });
+            lp.push_int(ms.wrapping_sub(master_ms) as i64);
+            lp.push_int(seq.wrapping_sub(master_seq) as i64);
+            if same_fields {
+                for (_, v) in &entry.fields {
+                    lp.push_str(v);
+                }
+                lp.push_int(entry.fields.len() as i64 + 3);
+            } else {
+                lp.push_int(entry.fields.len() as i64);
+                for (f, v) in &entry.fields {
+                    lp.push_str(f);
+                    lp.push_str(v);
+                }
+                lp.push_int(entry.fields.len() as i64 * 2 + 4);
+            }
+        }
+
+        write_string(out, &lp.finish())?;
+    }
+
+    let (last_ms, last_seq) = entries.last().map(StreamEntry::id_parts).unwrap_or((0, 0));
+    write_size(out, entries.len() as u64)?;
+    write_size(out, last_ms)?;
+    write_size(out, last_seq)?;
+    write_size(out, 0) // consumer groups
+}
+
+fn write_size<W: Write>(out: &mut W, n: u64) -> io::Result<()> {
+    if n < 1 << 6 {
+        out.write_all(&[n as u8])
+    } else if n < 1 << 14 {
+        out.write_all(&[0x40 | (n >> 8) as u8, n as u8])
+    } else if n <= u32::MAX as u64 {
+        out.write_all(&[0x80])?;
+        out.write_all(&(n as u32).to_be_bytes())
+    } else {
+        out.write_all(&[0x81])?;
+        out.write_all(&n.to_be_bytes())
+    }
+}
+
+fn write_string<W: Write>(out: &mut W, s: &[u8]) -> io::Result<()> {
+    write_size(out, s.len() as u64)?;
+    out.write_all(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdb::crc64::crc64;
+    use crate::rdb::{load_databases, SortedSet};
+    use std::collections::{HashMap, HashSet};
+    use std::time::Duration;
+
+    /// Writes `value` under "k" in DB 0 and loads it back.
+    fn round_trip(value: Value) -> Value {
+        let mut db = Db::new();
+        db.insert(b"k".to_vec(), (value, None));
+        let rdb = encode_rdb_snapshot(&[db]).unwrap();
+        let mut dbs = load_databases(&rdb[..], 1).unwrap();
+        dbs[0].remove(&b"k"[..]).expect("key lost in round trip").0
+    }
+
+    fn stream_entry(id: &str, fields: &[(&str, &str)]) -> StreamEntry {
+        StreamEntry {
+            id: id.to_string(),
+            fields: fields.iter().map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec())).collect(),
+        }
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        for s in ["", "hello", "12345", &"a".repeat(20_000)] {
+            match round_trip(Value::String(s.into())) {
+                Value::String(back) => assert_eq!(back, s.as_bytes()),
+                other => panic!("expected a string, got {:?}", other),
+            }
+        }
+        // bytes that aren't UTF-8 come back unchanged
+        match round_trip(Value::String(vec![0xFF, 0x00, 0xC3])) {
+            Value::String(back) => assert_eq!(back, [0xFF, 0x00, 0xC3]),
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let items: VecDeque<Vec<u8>> = (0..500).map(|i| format!("item-{}", i).into_bytes()).chain([Vec::new()]).collect();
+        match round_trip(Value::List(items.clone())) {
+            Value::List(back) => assert_eq!(back, items),
+            other => panic!("expected a list, got {:?}", other),
+        }
+    }
+
+    /// The listpacks of a quicklist-2 list as `write_list` lays it out.
+    fn quicklist_nodes(items: &VecDeque<Vec<u8>>) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        write_list(&mut out, items).unwrap();
+        let mut rest = &out[..];
+        let read_size = |rest: &mut &[u8]| -> usize {
+            // sizes here are always under 2^14: 1 or 2 bytes
+            let (size, used) = match rest[0] >> 6 {
+                0 => ((rest[0] & 0x3F) as usize, 1),
+                1 => ((((rest[0] & 0x3F) as usize) << 8) | rest[1] as usize, 2),
+                _ => panic!("unexpected length encoding {:#x}", rest[0]),
+            };
+            *rest = &rest[used..];
+            size
+        };
+        let count = read_size(&mut rest);
+        (0..count)
+            .map(|_| {
+                assert_eq!(read_size(&mut rest) as u64, QUICKLIST_NODE_CONTAINER_PACKED);
+                let len = read_size(&mut rest);
+                let node = rest[..len].to_vec();
+                rest = &rest[len..];
+                node
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_are_split_into_bounded_listpack_nodes() {
+        let items: VecDeque<Vec<u8>> = (0..2_000).map(|i| format!("element-{:05}", i).into_bytes()).collect();
+        let nodes = quicklist_nodes(&items);
+        assert!(nodes.len() > 1, "{} node(s)", nodes.len());
+        assert!(nodes.iter().all(|node| node.len() <= LIST_MAX_LISTPACK_BYTES));
+        // each listpack records its own total size
+        assert!(nodes.iter().all(|node| u32::from_le_bytes(node[..4].try_into().unwrap()) as usize == node.len()));
+
+        // an element too big for a node gets one of its own
+        let items: VecDeque<Vec<u8>> = [b"a".to_vec(), b"b".repeat(10_000), b"c".to_vec()].into();
+        match round_trip(Value::List(items.clone())) {
+            Value::List(back) => assert_eq!(back, items),
+            other => panic!("expected a list, got {:?}", other),
+        }
+        assert_eq!(quicklist_nodes(&[b"a".to_vec()].into()).len(), 1);
+        assert_eq!(object_type(&Value::List(items)), RDB_TYPE_LIST_QUICKLIST_2);
+    }
+
+    #[test]
+    fn sets_and_hashes_round_trip() {
+        let members: HashSet<Vec<u8>> = (0..300).map(|i| format!("m{}", i).into_bytes()).collect();
+        match round_trip(Value::Set(members.iter().map(|m| (m.clone(), ())).collect())) {
+            Value::Set(back) => assert_eq!(back.into_iter().map(|(m, ())| m).collect::<HashSet<_>>(), members),
+            other => panic!("expected a set, got {:?}", other),
+        }
+
+        let hash: HashMap<Vec<u8>, Vec<u8>> =
+            (0..300).map(|i| (format!("f{}", i).into_bytes(), format!("v{}", i * 7).into_bytes())).collect();
+        match round_trip(Value::Hash(hash.clone().into_iter().collect())) {
+            Value::Hash(back) => assert_eq!(back.into_iter().collect::<HashMap<_, _>>(), hash),
+            other => panic!("expected a hash, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sorted_sets_round_trip_with_exact_scores() {
+        let mut zset = SortedSet::default();
+        for (member, score) in [("a", 1.5), ("b", -0.0), ("c", f64::INFINITY), ("d", f64::NEG_INFINITY), ("e", 1e-300)] {
+            zset.insert(member.as_bytes().to_vec(), score);
+        }
+
+        match round_trip(Value::ZSet(zset.clone())) {
+            Value::ZSet(back) => {
+                let expected: Vec<_> = zset.iter().map(|(m, s)| (m.to_vec(), s.to_bits())).collect();
+                let got: Vec<_> = back.iter().map(|(m, s)| (m.to_vec(), s.to_bits())).collect();
+                assert_eq!(got, expected);
+            }
+            other => panic!("expected a sorted set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn streams_round_trip_across_nodes_and_field_layouts() {
+        let mut entries: Vec<StreamEntry> = (0..250)
+            .map(|i| stream_entry(&format!("{}-{}", 1000 + i / 3, i % 3), &[("temp", &i.to_string()), ("unit", "C")]))
+            .collect();
+        // differing fields from the node's master entry
+        entries.push(stream_entry("2000-0", &[("other", "field")]));
+        entries.push(stream_entry("2000-1", &[("unit", "C"), ("temp", "swapped")]));
+
+        match round_trip(Value::Stream(entries.clone())) {
+            Value::Stream(back) => {
+                assert_eq!(back.len(), entries.len());
+                for (got, want) in back.iter().zip(&entries) {
+                    assert_eq!(got.id, want.id);
+                    assert_eq!(got.fields, want.fields);
+                }
+            }
+            other => panic!("expected a stream, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn snapshot_keeps_databases_and_expiries_and_drops_expired_keys() {
+        let in_an_hour = SystemTime::now() + Duration::from_secs(3600);
+        let ms = in_an_hour.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let in_an_hour = UNIX_EPOCH + Duration::from_millis(ms);
+
+        let mut dbs = vec![Db::new(), Db::new(), Db::new()];
+        dbs[0].insert(b"plain".to_vec(), (Value::String(b"0".to_vec()), None));
+        dbs[2].insert(b"volatile".to_vec(), (Value::String(b"2".to_vec()), Some(in_an_hour)));
+        dbs[2].insert(b"gone".to_vec(), (Value::String(b"x".to_vec()), Some(UNIX_EPOCH + Duration::from_secs(1))));
+
+        let rdb = encode_rdb_snapshot(&dbs).unwrap();
+        assert!(rdb.starts_with(b"REDIS0011"));
+        let loaded = load_databases(&rdb[..], 3).unwrap();
+
+        assert_eq!(loaded[0].len(), 1);
+        assert_eq!(loaded[0].get(&b"plain"[..]).unwrap().1, None);
+        assert!(loaded[1].is_empty());
+        assert_eq!(loaded[2].len(), 1);
+        assert_eq!(loaded[2].get(&b"volatile"[..]).unwrap().1, Some(in_an_hour));
+    }
+
+    #[test]
+    fn empty_snapshot_loads_as_empty() {
+        let rdb = encode_rdb_snapshot(&[Db::new(), Db::new()]).unwrap();
+        assert!(load_databases(&rdb[..], 2).unwrap().iter().all(|db| db.is_empty()));
+    }
+
+    #[test]
+    fn snapshot_ends_with_its_checksum() {
+        let mut db = Db::new();
+        db.insert(b"k".to_vec(), (Value::String(b"v".to_vec()), None));
+        let rdb = encode_rdb_snapshot(&[db]).unwrap();
+
+        let (body, footer) = rdb.split_at(rdb.len() - 8);
+        assert_eq!(body.last(), Some(&RDB_OPCODE_EOF));
+        assert_eq!(u64::from_le_bytes(footer.try_into().unwrap()), crc64(0, body));
+    }
+
+    #[test]
+    fn sizes_use_the_smallest_length_encoding() {
+        let encoded = |n: u64| {
+            let mut out = Vec::new();
+            write_size(&mut out, n).unwrap();
+            out
+        };
+        assert_eq!(encoded(63), [63]);
+        assert_eq!(encoded(64), [0x40, 64]);
+        assert_eq!(encoded(16383), [0x7F, 0xFF]);
+        assert_eq!(encoded(16384), [0x80, 0, 0, 0x40, 0]);
+        assert_eq!(encoded(u32::MAX as u64 + 1), [0x81, 0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fmt, io};
+
+/// Why an RDB stream could not be decoded.
+#[derive(Debug)]
+pub enum RdbError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The input ended in the middle of a record.
+    UnexpectedEof,
+    /// The stream doesn't start with `REDIS`.
+    BadMagic,
+    UnsupportedVersion(u32),
+    UnknownType(u8),
+    /// Recognized, but a format we can neither load nor skip.
+    Unsupported(&'static str),
+    DbIndexOutOfRange { index: usize, databases: usize },
+    ChecksumMismatch { stored: u64, computed: u64 },
+    /// A structurally invalid record; `what` names the encoding being decoded.
+    Corrupt { what: &'static str, reason: String },
+}
+
+impl RdbError {
+    pub(crate) fn corrupt(what: &'static str, reason: impl Into<String>) -> Self {
+        RdbError::Corrupt { what, reason: reason.into() }
+    }
+}
+
+impl fmt::Display for RdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RdbError::Io(e) => write!(f, "I/O error: {}", e),
+            RdbError::UnexpectedEof => write!(f, "Unexpected end of RDB data"),
+            RdbError::BadMagic => write!(f, "Wrong signature trying to load DB"),
+            RdbError::UnsupportedVersion(v) => write!(f, "Can't handle RDB format version {}", v),
+            RdbError::UnknownType(t) => write!(f, "Unknown RDB object type 0x{:X}", t),
+            RdbError::Unsupported(what) => write!(f, "Unsupported RDB content: {}", what),
+            RdbError::DbIndexOutOfRange { index, databases } => write!(
+                f,
+                "DB index {} out of range: server is configured with {} databases",
+                index, databases
+            ),
+            RdbError::ChecksumMismatch { stored, computed } => write!(
+                f,
+                "RDB checksum mismatch: stored {:016x}, computed {:016x}",
+                stored, computed
+            ),
+            RdbError::Corrupt { what, reason } => write!(f, "Malformed {}: {}", what, reason),
+        }
+    }
+}
+
+impl std::error::Error for RdbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RdbError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RdbError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            RdbError::UnexpectedEof
+        } else {
+            RdbError::Io(e)
+        }
+    }
+}
+
+impl From<RdbError> for io::Error {
+    fn from(e: RdbError) -> Self {
+        match e {
+            RdbError::Io(e) => e,
+            RdbError::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, e.to_string()),
+            other => io::Error::new(io::ErrorKind::InvalidData, other.to_string()),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::rdb::error::RdbError;
+
+/// One element of a listpack: either a small integer or a byte string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListpackEntry {
+    Int(i64),
+    Str(Vec<u8>),
+}
+
+impl ListpackEntry {
+    /// Integer value, parsing string-encoded integers the way `lpGetInteger` does.
+    pub fn as_int(&self) -> Result<i64, RdbError> {
+        match self {
+            ListpackEntry::Int(n) => Ok(*n),
+            ListpackEntry::Str(s) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| RdbError::corrupt("listpack", "entry is not an integer")),
+        }
+    }
+
+    /// Byte value; integers are rendered in decimal.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            ListpackEntry::Int(n) => n.to_string().into_bytes(),
+            ListpackEntry::Str(s) => s,
+        }
+    }
+
+    /// String value; integers are rendered in decimal.
+    pub fn into_string(self) -> Result<String, RdbError> {
+        match self {
+            ListpackEntry::Int(n) => Ok(n.to_string()),
+            ListpackEntry::Str(s) => {
+                String::from_utf8(s).map_err(|_| RdbError::corrupt("listpack", "invalid UTF-8"))
+            }
+        }
+    }
+}
+
+/// Builds a listpack blob (`<total-bytes><num-elements><entry>...<0xFF>`).
+#[derive(Default)]
+pub struct ListpackWriter {
+    body: Vec<u8>,
+    len: usize,
+}
+
+impl ListpackWriter {
+    pub fn new() -> Self {
+        Self { body: Vec::new(), len: 0 }
+    }
+
+    pub fn push_int(&mut self, n: i64) {
+        let start = self.body.len();
+        if (0..=127).contains(&n) {
+            self.body.push(n as u8);
+        } else if (-4096..4096).contains(&n) {
+            let v = (n as u16) & 0x1FFF;
+            self.body.push(0xC0 | (v >> 8) as u8);
+            self.body.push(v as u8);
+        } else if i16::try_from(n).is_ok() {
+            self.body.push(0xF1);
+            self.body.extend_from_slice(&(n as i16).to_le_bytes());
+        } else if (-(1 << 23)..(1 << 23)).contains(&n) {
+            self.body.push(0xF2);
+            self.body.extend_from_slice(&(n as i32).to_le_bytes()[..3]);
+        } else if i32::try_from(n).is_ok() {
+            self.body.push(0xF3);
+            self.body.extend_from_slice(&(n as i32).to_le_bytes());
+        } else {
+            self.body.push(0xF4);
+            self.body.extend_from_slice(&n.to_le_bytes());
+        }
+        self.finish_entry(start);
+    }
+
+    /// Appends a string, storing it as an integer when it round-trips exactly.
+    pub fn push_str(&mut self, s: &[u8]) {
+        if let Some(n) = std::str::from_utf8(s).ok().and_then(|t| t.parse::<i64>().ok()) {
+            if n.to_string().as_bytes() == s {
+                self.push_int(n);
+                return;
+            }
+        }
+
+        let start = self.body.len();
+        if s.len() < 64 {
+            self.body.push(0x80 | s.len() as u8);
+        } else if s.len() < 4096 {
+            self.body.push(0xE0 | (s.len() >> 8) as u8);
+            self.body.push(s.len() as u8);
+        } else {
+            self.body.push(0xF0);
+            self.body.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        }
+        self.body.extend_from_slice(s);
+        self.finish_entry(start);
+    }
+
+    /// Entries pushed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size in bytes of the listpack `finish` would return now.
+    pub fn size(&self) -> usize {
+        6 + self.body.len() + 1
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let total = self.size();
+        let count = if self.len < u16::MAX as usize { self.len as u16 } else { u16::MAX };
+        let mut out = Vec::with_capacity(total);
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&self.body);
+        out.push(0xFF);
+        out
+    }
+
+    fn finish_entry(&mut self, start: usize) {
+        let entry_len = self.body.len() - start;
+        self.body.extend_from_slice(&encode_backlen(entry_len));
+        self.len += 1;
+    }
+}
+
+/// Entry length written after each element so listpacks can be walked backwards.
+fn encode_backlen(len: usize) -> Vec<u8> {
+    let size = backlen_size(len);
+    let mut out = vec![0u8; size];
+    for (i, byte) in out.iter_mut().enumerate() {
+        let shift = 7 * (size - 1 - i);
+        *byte = ((len >> shift) & 0x7F) as u8;
+        if i > 0 {
+            *byte |= 0x80;
+        }
+    }
+    out
+}
+
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+/// Decodes every element of a listpack blob.
+pub fn decode_listpack(bytes: &[u8]) -> Result<Vec<ListpackEntry>, RdbError> {
+    let bad = |msg: &str| RdbError::corrupt("listpack", msg);
+
+    if bytes.len() < 7 {
+        return Err(bad("too short"));
+    }
+    let total = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    if total != bytes.len() {
+        return Err(bad("total length mismatch"));
+    }
+
+    let mut entries = Vec::new();
+    let mut pos = 6;
+    loop {
+        let b0 = *bytes.get(pos).ok_or_else(|| bad("missing terminator"))?;
+        if b0 == 0xFF {
+            break;
+        }
+
+        let take = |from: usize, n: usize| -> Result<&[u8], RdbError> {
+            bytes.get(from..from + n).ok_or_else(|| bad("truncated entry"))
+        };
+
+        let (entry, encoded_len) = if b0 & 0x80 == 0 {
+            (ListpackEntry::Int((b0 & 0x7F) as i64), 1)
+        } else if b0 & 0xC0 == 0x80 {
+            let len = (b0 & 0x3F) as usize;
+            (ListpackEntry::Str(take(pos + 1, len)?.to_vec()), 1 + len)
+        } else if b0 & 0xE0 == 0xC0 {
+            let raw = (((b0 & 0x1F) as u16) << 8) | take(pos + 1, 1)?[0] as u16;
+            // sign-extend the 13-bit value
+            let n = ((raw << 3) as i16 >> 3) as i64;
+            (ListpackEntry::Int(n), 2)
+        } else if b0 & 0xF0 == 0xE0 {
+            let len = (((b0 & 0x0F) as usize) << 8) | take(pos + 1, 1)?[0] as usize;
+            (ListpackEntry::Str(take(pos + 2, len)?.to_vec()), 2 + len)
+        } else {
+            match b0 {
+                0xF0 => {
+                    let l = take(pos + 1, 4)?;
+                    let len = u32::from_le_bytes([l[0], l[1], l[2], l[3]]) as usize;
+                    (ListpackEntry::Str(take(pos + 5, len)?.to_vec()), 5 + len)
+                }
+                0xF1 => {
+                    let d = take(pos + 1, 2)?;
+                    (ListpackEntry::Int(i16::from_le_bytes([d[0], d[1]]) as i64), 3)
+                }
+                0xF2 => {
+                    let d = take(pos + 1, 3)?;
+                    let n = (i32::from_le_bytes([0, d[0], d[1], d[2]]) >> 8) as i64;
+                    (ListpackEntry::Int(n), 4)
+                }
+                0xF3 => {
+                    let d = take(pos + 1, 4)?;
+                    (ListpackEntry::Int(i32::from_le_bytes([d[0], d[1], d[2], d[3]]) as i64), 5)
+                }
+                0xF4 => {
+                    let d = take(pos + 1, 8)?;
+                    let mut arr = [0u8; 8];
+                    arr.copy_from_slice(d);
+                    (ListpackEntry::Int(i64::from_le_bytes(arr)), 9)
+                }
+                other => return Err(bad(&format!("unknown encoding byte 0x{:X}", other))),
+            }
+        };
+
+        pos += encoded_len + backlen_size(encoded_len);
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_round_trip_through_every_width() {
+        let values = [
+            0,
+            127,
+            128,
+            -1,
+            -4096,
+            4095,
+            4096,
+            i16::MIN as i64,
+            i16::MAX as i64,
+            -(1 << 23),
+            (1 << 23) - 1,
+            1 << 23,
+            i32::MIN as i64,
+            i32::MAX as i64,
+            i32::MAX as i64 + 1,
+            i64::MIN,
+            i64::MAX,
+        ];
+        let mut lp = ListpackWriter::new();
+        for &n in &values {
+            lp.push_int(n);
+        }
+
+        let entries = decode_listpack(&lp.finish()).unwrap();
+        let decoded: Vec<i64> = entries.iter().map(|e| e.as_int().unwrap()).collect();
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn strings_round_trip_through_every_length_class() {
+        let values: Vec<Vec<u8>> = [0, 1, 63, 64, 4095, 4096, 70_000].iter().map(|&n| vec![b'x'; n]).collect();
+        let mut lp = ListpackWriter::new();
+        for v in &values {
+            lp.push_str(v);
+        }
+
+        let entries = decode_listpack(&lp.finish()).unwrap();
+        let expected: Vec<ListpackEntry> = values.into_iter().map(ListpackEntry::Str).collect();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn push_str_stores_canonical_integers_as_integers() {
+        let mut lp = ListpackWriter::new();
+        lp.push_str(b"12345");
+        lp.push_str(b"-7");
+        lp.push_str(b"007");
+        lp.push_str(b"+1");
+        lp.push_str(b"1.5");
+
+        let entries = decode_listpack(&lp.finish()).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ListpackEntry::Int(12345),
+                ListpackEntry::Int(-7),
+                ListpackEntry::Str(b"007".to_vec()),
+                ListpackEntry::Str(b"+1".to_vec()),
+                ListpackEntry::Str(b"1.5".to_vec()),
+            ]
+        );
+        assert_eq!(entries[2].clone().into_string().unwrap(), "007");
+        assert_eq!(entries[0].clone().into_string().unwrap(), "12345");
+    }
+
+    #[test]
+    fn size_matches_finished_length() {
+        assert_eq!(ListpackWriter::new().size(), ListpackWriter::new().finish().len());
+        let mut lp = ListpackWriter::new();
+        for i in 0..300 {
+            lp.push_str(format!("item-{}", i).as_bytes());
+            lp.push_int(i * 1000);
+        }
+        assert_eq!(lp.len(), 600);
+        let size = lp.size();
+        assert_eq!(lp.finish().len(), size);
+    }
+
+    #[test]
+    fn rejects_malformed_listpacks() {
+        let mut lp = ListpackWriter::new();
+        lp.push_str(b"hello");
+        let good = lp.finish();
+
+        assert!(decode_listpack(&good[..3]).is_err());
+
+        let mut wrong_total = good.clone();
+        wrong_total[0] += 1;
+        assert!(decode_listpack(&wrong_total).is_err());
+
+        let mut no_terminator = good.clone();
+        no_terminator.pop();
+        no_terminator[0] -= 1;
+        assert!(decode_listpack(&no_terminator).is_err());
+
+        let mut truncated_entry = good[..good.len() - 4].to_vec();
+        truncated_entry[0] = truncated_entry.len() as u8;
+        assert!(decode_listpack(&truncated_entry).is_err());
+    }
+
+    #[test]
+    fn as_int_parses_string_entries() {
+        assert_eq!(ListpackEntry::Str(b"-42".to_vec()).as_int().unwrap(), -42);
+        assert!(ListpackEntry::Str(b"forty-two".to_vec()).as_int().is_err());
+        assert!(ListpackEntry::Str(vec![0xFF]).into_string().is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::rdb::error::RdbError;
+use crate::rdb::MAX_PREALLOC;
+
+/// Decompresses an LZF-compressed RDB string into exactly `expected_len` bytes.
+pub fn lzf_decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, RdbError> {
+    let bad = |msg: &str| RdbError::corrupt("LZF data", msg);
+
+    let mut out = Vec::with_capacity(expected_len.min(MAX_PREALLOC));
+    let mut ip = 0;
+
+    while ip < input.len() {
+        let ctrl = input[ip] as usize;
+        ip += 1;
+
+        if ctrl < 32 {
+            // literal run of ctrl + 1 bytes
+            let run = ctrl + 1;
+            let lit = input.get(ip..ip + run).ok_or_else(|| bad("literal overruns input"))?;
+            if out.len() + run > expected_len {
+                return Err(bad("decompressed length mismatch"));
+            }
+            out.extend_from_slice(lit);
+            ip += run;
+        } else {
+            // back reference
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(ip).ok_or_else(|| bad("truncated length"))? as usize;
+                ip += 1;
+            }
+            len += 2;
+
+            let low = *input.get(ip).ok_or_else(|| bad("truncated offset"))? as usize;
+            ip += 1;
+            let back = ((ctrl & 0x1F) << 8) + low + 1;
+            if back > out.len() {
+                return Err(bad("back reference before start"));
+            }
+            if out.len() + len > expected_len {
+                return Err(bad("decompressed length mismatch"));
+            }
+
+            // byte-by-byte: the source range may overlap what we are writing
+            let start = out.len() - back;
+            for i in 0..len {
+                let b = out[start + i];
+                out.push(b);
+            }
+        }
+    }
+
+    if out.len() != expected_len {
+        return Err(bad("decompressed length mismatch"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_runs() {
+        assert_eq!(lzf_decompress(&[0x04, b'h', b'e', b'l', b'l', b'o'], 5).unwrap(), b"hello");
+        assert_eq!(lzf_decompress(&[], 0).unwrap(), b"");
+    }
+
+    #[test]
+    fn back_references_copy_earlier_output() {
+        // "abc", then 6 bytes from 3 back
+        assert_eq!(lzf_decompress(&[0x02, b'a', b'b', b'c', 0x80, 0x02], 9).unwrap(), b"abcabcabc");
+    }
+
+    #[test]
+    fn back_references_may_overlap_what_they_write() {
+        // "a", then 9 bytes from 1 back: a long reference with its extra length byte
+        assert_eq!(lzf_decompress(&[0x00, b'a', 0xE0, 0x00, 0x00], 10).unwrap(), b"aaaaaaaaaa");
+        // 264 more bytes: extra length 255
+        let out = lzf_decompress(&[0x00, b'z', 0xE0, 0xFF, 0x00], 265).unwrap();
+        assert_eq!(out, vec![b'z'; 265]);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        // reference before any output
+        assert!(lzf_decompress(&[0x20, 0x00], 3).is_err());
+        // literal running past the input
+        assert!(lzf_decompress(&[0x05, b'a'], 6).is_err());
+        // truncated reference
+        assert!(lzf_decompress(&[0x00, b'a', 0xE0], 10).is_err());
+        assert!(lzf_decompress(&[0x00, b'a', 0x20], 4).is_err());
+        // output longer or shorter than announced
+        assert!(lzf_decompress(&[0x02, b'a', b'b', b'c'], 2).is_err());
+        assert!(lzf_decompress(&[0x02, b'a', b'b', b'c'], 4).is_err());
+        assert!(lzf_decompress(&[0x00, b'a', 0xE0, 0x00, 0x00], 5).is_err());
+    }
+}
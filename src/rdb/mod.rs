@@ -0,0 +1,354 @@
+pub mod crc64;
+pub mod decode;
+pub mod encode;
+pub mod error;
+pub mod listpack;
+pub mod lzf;
+pub mod ziplist;
+
+use crate::dict::Dict;
+use crate::rdb::decode::{RdbDecoder, RdbEvent};
+use crate::rdb::error::RdbError;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl StreamEntry {
+    /// `(ms, seq)` of this entry's `<ms>-<seq>` ID.
+    pub fn id_parts(&self) -> (u64, u64) {
+        let mut p = self.id.splitn(2, '-');
+        let ms = p.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+        let seq = p.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+        (ms, seq)
+    }
+}
+
+/// Score wrapper giving `f64` the total order sorted sets need.
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Members ordered by `(score, member)`, with O(1) score lookup.
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    scores: Dict<Vec<u8>, f64>,
+    ordered: BTreeSet<(Score, Vec<u8>)>,
+}
+
+impl SortedSet {
+    /// Adds or re-scores `member`; returns `true` if it was not present before.
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        let previous = self.scores.insert(member.clone(), score);
+        if let Some(old) = previous {
+            self.ordered.remove(&(Score(old), member.clone()));
+        }
+        self.ordered.insert((Score(score), member));
+        previous.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// One SCAN step over the members, as `Dict::scan`.
+    pub fn scan<F: FnMut(&[u8], f64)>(&self, cursor: u64, mut f: F) -> u64 {
+        self.scores.scan(cursor, |member, score| f(member, *score))
+    }
+
+    /// Members in ascending `(score, member)` order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> {
+        self.ordered.iter().map(|(score, member)| (member.as_slice(), score.0))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Any bytes, as SET received them.
+    String(Vec<u8>),
+    /// O(1) push and pop at both ends, O(1) indexing.
+    List(VecDeque<Vec<u8>>),
+    /// Members; a `Dict` so SSCAN can walk its buckets.
+    Set(Dict<Vec<u8>, ()>),
+    ZSet(SortedSet),
+    /// Field → value; a `Dict` so HSCAN can walk its buckets.
+    Hash(Dict<Vec<u8>, Vec<u8>>),
+    Stream(Vec<StreamEntry>),
+}
+
+impl Value {
+    /// Name reported by TYPE.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+            Value::Hash(_) => "hash",
+            Value::Stream(_) => "stream",
+        }
+    }
+}
+
+// RDB object type bytes
+pub(crate) const RDB_TYPE_STRING: u8 = 0;
+pub(crate) const RDB_TYPE_LIST: u8 = 1;
+pub(crate) const RDB_TYPE_SET: u8 = 2;
+pub(crate) const RDB_TYPE_ZSET: u8 = 3;
+pub(crate) const RDB_TYPE_HASH: u8 = 4;
+pub(crate) const RDB_TYPE_ZSET_2: u8 = 5;
+pub(crate) const RDB_TYPE_MODULE_PRE_GA: u8 = 6;
+pub(crate) const RDB_TYPE_MODULE_2: u8 = 7;
+pub(crate) const RDB_TYPE_HASH_ZIPMAP: u8 = 9;
+pub(crate) const RDB_TYPE_LIST_ZIPLIST: u8 = 10;
+pub(crate) const RDB_TYPE_SET_INTSET: u8 = 11;
+pub(crate) const RDB_TYPE_ZSET_ZIPLIST: u8 = 12;
+pub(crate) const RDB_TYPE_HASH_ZIPLIST: u8 = 13;
+pub(crate) const RDB_TYPE_LIST_QUICKLIST: u8 = 14;
+pub(crate) const RDB_TYPE_STREAM_LISTPACKS: u8 = 15;
+pub(crate) const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+pub(crate) const RDB_TYPE_ZSET_LISTPACK: u8 = 17;
+pub(crate) const RDB_TYPE_LIST_QUICKLIST_2: u8 = 18;
+
+/// Upper bound on what the decoder preallocates from a length field, in
+/// elements or bytes. Lengths come from the input (a RESTORE payload is
+/// client data), so anything bigger grows as the data actually arrives.
+pub(crate) const MAX_PREALLOC: usize = 64 * 1024;
+
+// quicklist2 node containers
+pub(crate) const QUICKLIST_NODE_CONTAINER_PLAIN: u64 = 1;
+pub(crate) const QUICKLIST_NODE_CONTAINER_PACKED: u64 = 2;
+pub(crate) const RDB_TYPE_STREAM_LISTPACKS_2: u8 = 19;
+pub(crate) const RDB_TYPE_SET_LISTPACK: u8 = 20;
+pub(crate) const RDB_TYPE_STREAM_LISTPACKS_3: u8 = 21;
+
+// RDB opcodes
+pub(crate) const RDB_OPCODE_SLOT_INFO: u8 = 0xF4;
+pub(crate) const RDB_OPCODE_FUNCTION_PRE_GA: u8 = 0xF6;
+pub(crate) const RDB_OPCODE_FUNCTION2: u8 = 0xF5;
+pub(crate) const RDB_OPCODE_MODULE_AUX: u8 = 0xF7;
+pub(crate) const RDB_OPCODE_IDLE: u8 = 0xF8;
+pub(crate) const RDB_OPCODE_FREQ: u8 = 0xF9;
+pub(crate) const RDB_OPCODE_AUX: u8 = 0xFA;
+pub(crate) const RDB_OPCODE_RESIZEDB: u8 = 0xFB;
+pub(crate) const RDB_OPCODE_EXPIRETIME_MS: u8 = 0xFC;
+pub(crate) const RDB_OPCODE_EXPIRETIME: u8 = 0xFD;
+pub(crate) const RDB_OPCODE_SELECTDB: u8 = 0xFE;
+pub(crate) const RDB_OPCODE_EOF: u8 = 0xFF;
+
+/// One logical database: key → (value, expiry).
+pub type Db = Dict<Vec<u8>, (Value, Option<SystemTime>)>;
+
+/// Loads every database in the file; the result always has `databases` entries.
+pub fn load_rdb_snapshot_from_path<P: AsRef<Path>>(path: P, databases: usize) -> Result<Vec<Db>, RdbError> {
+    println!("[rdb::load_rdb_snapshot] Loading snapshot from {:?}", path.as_ref());
+
+    let file = match File::open(&path) {
+        Ok(f) => {
+            println!("[rdb::load_rdb_snapshot] File opened successfully.");
+            f
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("[rdb::load_rdb_snapshot] Snapshot file not found. Returning empty store.");
+            return Ok(vec![Db::new(); databases]);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let dbs = match load_databases(file, databases) {
+        Err(RdbError::BadMagic) => {
+            println!("[rdb::load_rdb_snapshot] Invalid or missing RDB header.");
+            return Ok(vec![Db::new(); databases]);
+        }
+        other => other?,
+    };
+    println!(
+        "[rdb::load_rdb_snapshot] Finished reading {} entries.",
+        dbs.iter().map(|db| db.len()).sum::<usize>()
+    );
+
+    Ok(dbs)
+}
+
+/// Decodes an RDB stream into `databases` fresh databases, routing each key
+/// into the database selected by the most recent SELECTDB (DB 0 before any).
+pub fn load_databases<R: Read>(inner: R, databases: usize) -> Result<Vec<Db>, RdbError> {
+    let mut decoder = RdbDecoder::new(inner)?;
+    decode_databases(&mut decoder, databases)
+}
+
+/// Like `load_databases`, but leaves the decoder (and anything after the
+/// RDB payload) to the caller.
+pub fn decode_databases<R: Read>(decoder: &mut RdbDecoder<R>, databases: usize) -> Result<Vec<Db>, RdbError> {
+    println!("[rdb::load] RDB version {}", decoder.version());
+
+    let mut dbs = vec![Db::new(); databases];
+    let mut db_index = 0;
+
+    for event in &mut *decoder {
+        match event? {
+            RdbEvent::Aux { key, value } => println!("[rdb::load] Skipped metadata: {} = {}", key, value),
+            RdbEvent::Function { code } => {
+                println!("[rdb::load] Skipped function library ({} bytes)", code.len())
+            }
+            RdbEvent::ModuleAux { module_id } => {
+                println!("[rdb::load] Skipped aux data of module id {:#x}", module_id)
+            }
+            RdbEvent::SelectDb(index) => {
+                if index >= databases {
+                    return Err(RdbError::DbIndexOutOfRange { index, databases });
+                }
+                println!("[rdb::load] Selecting DB {}", index);
+                db_index = index;
+            }
+            RdbEvent::ResizeDb { keys, expires } => {
+                println!("[rdb::load] DB {} holds {} key(s), {} with expiry", db_index, keys, expires);
+                dbs[db_index].reserve(keys.min(MAX_PREALLOC));
+            }
+            RdbEvent::Entry { key, value, expiry, .. } => {
+                dbs[db_index].insert(key, (value, expiry));
+            }
+            RdbEvent::ModuleValue { key } => {
+                println!("[rdb::load] Skipped module value for key '{}'", String::from_utf8_lossy(&key))
+            }
+        }
+    }
+
+    match decoder.checksum() {
+        Some(crc) => println!("[rdb::load] Checksum OK ({:016x}).", crc),
+        None => println!("[rdb::load] No checksum in snapshot; skipped verification."),
+    }
+
+    Ok(dbs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdb::crc64::crc64;
+
+    /// A complete RDB file of `version` around `body`, with its checksum.
+    fn rdb(version: u32, body: &[u8]) -> Vec<u8> {
+        let mut out = format!("REDIS{:04}", version).into_bytes();
+        out.extend_from_slice(body);
+        out.push(RDB_OPCODE_EOF);
+        let crc = crc64(0, &out);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// A string key `key` holding `val`.
+    fn string_entry(key: &str, val: &str) -> Vec<u8> {
+        let mut out = vec![RDB_TYPE_STRING, key.len() as u8];
+        out.extend_from_slice(key.as_bytes());
+        out.push(val.len() as u8);
+        out.extend_from_slice(val.as_bytes());
+        out
+    }
+
+    fn string_value(db: &Db, key: &str) -> Option<String> {
+        match db.get(key.as_bytes()) {
+            Some((Value::String(s), _)) => Some(String::from_utf8_lossy(s).into_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn loads_every_supported_version() {
+        for version in 6..=12 {
+            let dbs = load_databases(&rdb(version, &string_entry("k", "v"))[..], 1).unwrap();
+            assert_eq!(string_value(&dbs[0], "k").as_deref(), Some("v"), "version {}", version);
+        }
+        for version in [5, 13] {
+            assert!(matches!(
+                load_databases(&rdb(version, &[])[..], 1),
+                Err(RdbError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+        assert!(matches!(load_databases(&b"RDBX00011\xFF"[..], 1), Err(RdbError::BadMagic)));
+    }
+
+    #[test]
+    fn selectdb_routes_keys_into_their_database() {
+        let mut body = string_entry("first", "0");
+        body.extend_from_slice(&[RDB_OPCODE_SELECTDB, 3, RDB_OPCODE_RESIZEDB, 2, 0]);
+        body.extend(string_entry("a", "3"));
+        body.extend(string_entry("b", "3"));
+        body.extend_from_slice(&[RDB_OPCODE_SELECTDB, 1]);
+        body.extend(string_entry("a", "1"));
+
+        let dbs = load_databases(&rdb(11, &body)[..], 4).unwrap();
+        assert_eq!(dbs.len(), 4);
+        assert_eq!(string_value(&dbs[0], "first").as_deref(), Some("0"));
+        assert_eq!(string_value(&dbs[1], "a").as_deref(), Some("1"));
+        assert!(dbs[2].is_empty());
+        assert_eq!(dbs[3].len(), 2);
+        assert_eq!(string_value(&dbs[3], "a").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn selectdb_past_the_configured_databases_is_an_error() {
+        let mut body = vec![RDB_OPCODE_SELECTDB, 16];
+        body.extend(string_entry("k", "v"));
+        assert!(matches!(
+            load_databases(&rdb(11, &body)[..], 16),
+            Err(RdbError::DbIndexOutOfRange { index: 16, databases: 16 })
+        ));
+        assert_eq!(load_databases(&rdb(11, &body)[..], 17).unwrap()[16].len(), 1);
+    }
+
+    #[test]
+    fn missing_or_foreign_files_load_as_empty() {
+        let dir = std::env::temp_dir().join(format!("rdb-load-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let dbs = load_rdb_snapshot_from_path(dir.join("missing.rdb"), 2).unwrap();
+        assert_eq!(dbs.len(), 2);
+        assert!(dbs.iter().all(|db| db.is_empty()));
+
+        let foreign = dir.join("foreign.rdb");
+        std::fs::write(&foreign, b"not an rdb file").unwrap();
+        assert!(load_rdb_snapshot_from_path(&foreign, 2).unwrap().iter().all(|db| db.is_empty()));
+
+        let good = dir.join("good.rdb");
+        std::fs::write(&good, rdb(9, &string_entry("k", "v"))).unwrap();
+        assert_eq!(load_rdb_snapshot_from_path(&good, 2).unwrap()[0].len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+use crate::rdb::error::RdbError;
+use crate::rdb::listpack::ListpackEntry;
+
+fn bad(msg: &str) -> RdbError {
+    RdbError::corrupt("compact encoding", msg)
+}
+
+fn take(bytes: &[u8], from: usize, n: usize) -> Result<&[u8], RdbError> {
+    bytes.get(from..from + n).ok_or_else(|| bad("truncated entry"))
+}
+
+/// `<zlbytes><zltail><zllen><entry>...<0xFF>`; entries are decoded like listpack entries.
+pub fn decode_ziplist(bytes: &[u8]) -> Result<Vec<ListpackEntry>, RdbError> {
+    if bytes.len() < 11 {
+        return Err(bad("ziplist too short"));
+    }
+
+    let mut entries = Vec::new();
+    let mut pos = 10;
+    loop {
+        let first = *bytes.get(pos).ok_or_else(|| bad("ziplist missing terminator"))?;
+        if first == 0xFF {
+            break;
+        }
+
+        // previous entry length: 1 byte, or 0xFE followed by 4 bytes
+        pos += if first == 0xFE { 5 } else { 1 };
+
+        let enc = *bytes.get(pos).ok_or_else(|| bad("ziplist entry missing encoding"))?;
+        let (entry, len) = match enc >> 6 {
+            0 => {
+                let n = (enc & 0x3F) as usize;
+                (ListpackEntry::Str(take(bytes, pos + 1, n)?.to_vec()), 1 + n)
+            }
+            1 => {
+                let n = (((enc & 0x3F) as usize) << 8) | take(bytes, pos + 1, 1)?[0] as usize;
+                (ListpackEntry::Str(take(bytes, pos + 2, n)?.to_vec()), 2 + n)
+            }
+            2 => {
+                let l = take(bytes, pos + 1, 4)?;
+                let n = u32::from_be_bytes([l[0], l[1], l[2], l[3]]) as usize;
+                (ListpackEntry::Str(take(bytes, pos + 5, n)?.to_vec()), 5 + n)
+            }
+            _ => match enc {
+                0xC0 => {
+                    let d = take(bytes, pos + 1, 2)?;
+                    (ListpackEntry::Int(i16::from_le_bytes([d[0], d[1]]) as i64), 3)
+                }
+                0xD0 => {
+                    let d = take(bytes, pos + 1, 4)?;
+                    (ListpackEntry::Int(i32::from_le_bytes([d[0], d[1], d[2], d[3]]) as i64), 5)
+                }
+                0xE0 => {
+                    let d = take(bytes, pos + 1, 8)?;
+                    (ListpackEntry::Int(i64::from_le_bytes(d.try_into().unwrap())), 9)
+                }
+                0xF0 => {
+                    let d = take(bytes, pos + 1, 3)?;
+                    let n = (i32::from_le_bytes([0, d[0], d[1], d[2]]) >> 8) as i64;
+                    (ListpackEntry::Int(n), 4)
+                }
+                0xFE => {
+                    let d = take(bytes, pos + 1, 1)?;
+                    (ListpackEntry::Int(d[0] as i8 as i64), 2)
+                }
+                0xF1..=0xFD => (ListpackEntry::Int((enc & 0x0F) as i64 - 1), 1),
+                other => return Err(bad(&format!("unknown ziplist encoding 0x{:X}", other))),
+            },
+        };
+
+        pos += len;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// `<encoding><length><contents>`: a sorted array of 2, 4 or 8 byte little-endian integers.
+pub fn decode_intset(bytes: &[u8]) -> Result<Vec<i64>, RdbError> {
+    let header = take(bytes, 0, 8)?;
+    let width = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return Err(bad(&format!("invalid intset encoding {width}")));
+    }
+
+    let body = take(bytes, 8, width * len)?;
+    Ok(body
+        .chunks(width)
+        .map(|c| match width {
+            2 => i16::from_le_bytes([c[0], c[1]]) as i64,
+            4 => i32::from_le_bytes([c[0], c[1], c[2], c[3]]) as i64,
+            _ => i64::from_le_bytes(c.try_into().unwrap()),
+        })
+        .collect())
+}
+
+/// A zipmap field and its value.
+pub type ZipmapPair = (Vec<u8>, Vec<u8>);
+
+/// Pre-2.6 hash encoding: `<zmlen><len>key<len><free>value<free bytes>...<0xFF>`.
+pub fn decode_zipmap(bytes: &[u8]) -> Result<Vec<ZipmapPair>, RdbError> {
+    let read_len = |pos: &mut usize| -> Result<Option<usize>, RdbError> {
+        let b = *bytes.get(*pos).ok_or_else(|| bad("zipmap missing terminator"))?;
+        match b {
+            0xFF => Ok(None),
+            0xFE => {
+                let d = take(bytes, *pos + 1, 4)?;
+                *pos += 5;
+                Ok(Some(u32::from_le_bytes([d[0], d[1], d[2], d[3]]) as usize))
+            }
+            n => {
+                *pos += 1;
+                Ok(Some(n as usize))
+            }
+        }
+    };
+
+    let mut pairs = Vec::new();
+    let mut pos = 1;
+    while let Some(klen) = read_len(&mut pos)? {
+        let key = take(bytes, pos, klen)?.to_vec();
+        pos += klen;
+
+        let vlen = read_len(&mut pos)?.ok_or_else(|| bad("zipmap key without value"))?;
+        let free = take(bytes, pos, 1)?[0] as usize;
+        pos += 1;
+        let val = take(bytes, pos, vlen)?.to_vec();
+        pos += vlen + free;
+
+        pairs.push((key, val));
+    }
+
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ziplist of entries given as `<encoding><data>`, each prefixed with
+    /// the previous entry's length.
+    fn ziplist(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut prev = 0usize;
+        for entry in entries {
+            let start = body.len();
+            if prev < 254 {
+                body.push(prev as u8);
+            } else {
+                body.push(0xFE);
+                body.extend_from_slice(&(prev as u32).to_le_bytes());
+            }
+            body.extend_from_slice(entry);
+            prev = body.len() - start;
+        }
+        let mut out = Vec::new();
+        out.extend_from_slice(&((10 + body.len() + 1) as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // tail offset, unused by the decoder
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&body);
+        out.push(0xFF);
+        out
+    }
+
+    fn with(head: &[u8], data: &[u8]) -> Vec<u8> {
+        [head, data].concat()
+    }
+
+    #[test]
+    fn ziplist_decodes_every_entry_encoding() {
+        let long = vec![b'y'; 300];
+        let huge = vec![b'z'; 20_000];
+        let entries = vec![
+            with(&[0x03], b"abc"),
+            with(&[0x40 | 0x01, 0x2C], &long),
+            with(&[0x80, 0x00, 0x00, 0x4E, 0x20], &huge),
+            with(&[0xC0], &(-2i16).to_le_bytes()),
+            with(&[0xD0], &70_000i32.to_le_bytes()),
+            with(&[0xE0], &i64::MIN.to_le_bytes()),
+            with(&[0xF0], &(-5_000_000i32).to_le_bytes()[..3]),
+            vec![0xFE, 0x80],
+            vec![0xF1],
+            vec![0xFD],
+        ];
+
+        assert_eq!(
+            decode_ziplist(&ziplist(&entries)).unwrap(),
+            vec![
+                ListpackEntry::Str(b"abc".to_vec()),
+                ListpackEntry::Str(long),
+                ListpackEntry::Str(huge),
+                ListpackEntry::Int(-2),
+                ListpackEntry::Int(70_000),
+                ListpackEntry::Int(i64::MIN),
+                ListpackEntry::Int(-5_000_000),
+                ListpackEntry::Int(-128),
+                ListpackEntry::Int(0),
+                ListpackEntry::Int(12),
+            ]
+        );
+    }
+
+    #[test]
+    fn ziplist_rejects_truncated_input() {
+        let zl = ziplist(&[with(&[0x05], b"hello")]);
+        assert!(decode_ziplist(&zl[..zl.len() - 1]).is_err());
+        assert!(decode_ziplist(&zl[..zl.len() - 3]).is_err());
+        assert!(decode_ziplist(&zl[..5]).is_err());
+        assert_eq!(decode_ziplist(&ziplist(&[])).unwrap(), vec![]);
+    }
+
+    fn intset(width: u32, values: &[i64]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        for &v in values {
+            out.extend_from_slice(&v.to_le_bytes()[..width as usize]);
+        }
+        out
+    }
+
+    #[test]
+    fn intset_decodes_every_width() {
+        assert_eq!(decode_intset(&intset(2, &[-3, 7, 300])).unwrap(), vec![-3, 7, 300]);
+        assert_eq!(decode_intset(&intset(4, &[-70_000, 70_000])).unwrap(), vec![-70_000, 70_000]);
+        assert_eq!(decode_intset(&intset(8, &[i64::MIN, i64::MAX])).unwrap(), vec![i64::MIN, i64::MAX]);
+    }
+
+    #[test]
+    fn intset_rejects_bad_headers() {
+        assert!(decode_intset(&intset(3, &[1])).is_err());
+        let set = intset(4, &[1, 2]);
+        assert!(decode_intset(&set[..set.len() - 1]).is_err());
+        assert!(decode_intset(&set[..6]).is_err());
+    }
+
+    #[test]
+    fn zipmap_decodes_pairs_skipping_free_bytes() {
+        let mut zm = vec![2];
+        zm.extend_from_slice(&[3, b'f', b'o', b'o', 3, 2, b'b', b'a', b'r', 0, 0]);
+        let key = vec![b'k'; 300];
+        zm.push(0xFE);
+        zm.extend_from_slice(&300u32.to_le_bytes());
+        zm.extend_from_slice(&key);
+        zm.extend_from_slice(&[1, 0, b'v']);
+        zm.push(0xFF);
+
+        assert_eq!(
+            decode_zipmap(&zm).unwrap(),
+            vec![(b"foo".to_vec(), b"bar".to_vec()), (key, b"v".to_vec())]
+        );
+    }
+
+    #[test]
+    fn zipmap_rejects_a_key_without_value() {
+        assert!(decode_zipmap(&[1, 1, b'k', 0xFF]).is_err());
+        assert!(decode_zipmap(&[1, 1, b'k']).is_err());
+    }
+}
@@ -1,128 +1,295 @@
-use crate::commands::dispatch_cmd;
+use crate::commands::queue_in_transaction;
+use crate::persistence::snapshot_databases;
+use crate::server::execute_and_propagate;
 use crate::config::ServerConfig;
-use crate::resp::{peek_resp_command_size, read_resp_array, write_resp_array};
+use crate::resp::{Arg, parse_command, write_resp_array, RespParser, MAX_LINE_LEN};
 use crate::Context;
 
-use crate::rdb::load_rdb_snapshot_from_stream;
-use std::io::{self, BufRead, BufReader, Write};
-use std::net::TcpStream;
-use std::thread;
-use std::time::Duration;
+use crate::rdb::load_databases;
+use bytes::{Buf, Bytes, BytesMut};
+use memchr::memchr;
+use std::io::{self, Read};
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 
-pub fn connect_and_sync_master(mut ctx: Context) -> io::Result<()> {
+/// Read buffer for the link to the master.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Chunks of the snapshot payload read off the socket but not yet decoded.
+const RDB_CHUNKS_IN_FLIGHT: usize = 8;
+
+/// The read half of the link to the master and whatever has arrived on it
+/// but hasn't been consumed yet.
+struct MasterReader {
+    reader: OwnedReadHalf,
+    buf: BytesMut,
+}
+
+impl MasterReader {
+    /// Reads more off the socket; `UnexpectedEof` if the master hung up.
+    async fn fill(&mut self) -> io::Result<()> {
+        self.buf.reserve(READ_BUFFER_SIZE);
+        if self.reader.read_buf(&mut self.buf).await? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "master closed the connection"));
+        }
+        Ok(())
+    }
+
+    /// One `\n`-terminated line, without its line ending.
+    async fn read_line(&mut self) -> io::Result<String> {
+        loop {
+            if let Some(pos) = memchr(b'\n', &self.buf) {
+                let line = self.buf.split_to(pos + 1);
+                return Ok(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            if self.buf.len() > MAX_LINE_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "line from master too long"));
+            }
+            self.fill().await?;
+        }
+    }
+}
+
+/// The snapshot payload as the decoder sees it: a blocking `Read` over the
+/// chunks the link task forwards, ending once it has sent all of them.
+struct PayloadReader {
+    chunks: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl Read for PayloadReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.current.len());
+        self.current.copy_to_slice(&mut out[..n]);
+        Ok(n)
+    }
+}
+
+pub async fn connect_and_sync_master(mut ctx: Context) -> io::Result<()> {
     println!("[replication::main] Beginning full replication process...");
 
-    let mut stream = connect_to_master(&ctx.cfg)?;
-    ctx.this_client = Some(stream.try_clone()?);
+    let stream = connect_to_master(&ctx.cfg).await?;
+    stream.set_nodelay(true)?;
+    ctx.peer = stream.peer_addr().ok();
     println!("[replication::main] Connected to master.");
 
-    let mut reader = BufReader::new(stream.try_clone()?);
-    println!("[replication::main] Cloned stream for reading.");
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = MasterReader { reader, buf: BytesMut::with_capacity(READ_BUFFER_SIZE) };
 
-    perform_handshake(&mut stream, &mut reader, &ctx.cfg)?;
-    println!("[replication::main] Handshake complete.");
+    let offset = perform_handshake(&mut writer, &mut reader, &ctx.cfg).await?;
+    ctx.master_repl_offset.store(offset, Ordering::Relaxed);
+    println!("[replication::main] Handshake complete; replicating from offset {}.", offset);
 
-    load_rdb_snapshot_from_stream(&mut reader, &mut ctx)?;
+    load_rdb_snapshot_from_stream(&mut reader, &mut ctx).await?;
     println!("[replication::main] RDB snapshot loaded.");
 
-    stream_command_loop(&mut reader, &mut stream, &mut ctx)?;
+    if let Some(aof) = &ctx.aof {
+        let _barrier = ctx.sync_lock.read().unwrap();
+        aof.reset(&snapshot_databases(&ctx))?;
+    }
+
+    stream_command_loop(&mut reader, &mut writer, &mut ctx).await?;
     println!("[replication::main] Command streaming loop exited.");
 
     Ok(())
 }
 
-fn connect_to_master(cfg: &ServerConfig) -> io::Result<TcpStream> {
+async fn connect_to_master(cfg: &ServerConfig) -> io::Result<TcpStream> {
     println!("[replication::connect] Connecting to {}:{}", cfg.master_host, cfg.master_port);
-    TcpStream::connect((&cfg.master_host[..], cfg.master_port))
+    TcpStream::connect((&cfg.master_host[..], cfg.master_port)).await
 }
 
-fn perform_handshake(
-    stream: &mut TcpStream,
-    reader: &mut BufReader<TcpStream>,
-    cfg: &ServerConfig,
-) -> io::Result<()> {
-    send_and_expect(stream, reader, &["PING"], "+PONG")?;
-    send_and_expect(stream, reader, &["REPLCONF", "listening-port", &cfg.port.to_string()], "+OK")?;
-    send_and_expect(stream, reader, &["REPLCONF", "capa", "psync2"], "+OK")?;
-    send_and_expect(stream, reader, &["PSYNC", "?", "-1"], "+FULLRESYNC")?;
-    Ok(())
+/// Returns the offset the master's stream starts at, from its FULLRESYNC.
+async fn perform_handshake(writer: &mut OwnedWriteHalf, reader: &mut MasterReader, cfg: &ServerConfig) -> io::Result<u64> {
+    send_and_expect(writer, reader, &["PING"], "+PONG").await?;
+    send_and_expect(writer, reader, &["REPLCONF", "listening-port", &cfg.port.to_string()], "+OK").await?;
+    send_and_expect(writer, reader, &["REPLCONF", "capa", "psync2"], "+OK").await?;
+    let full = send_and_expect(writer, reader, &["PSYNC", "?", "-1"], "+FULLRESYNC").await?;
+    // +FULLRESYNC <replid> <offset>
+    full.split_whitespace()
+        .nth(2)
+        .and_then(|offset| offset.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Bad FULLRESYNC reply: {}", full)))
 }
 
-fn send_and_expect(
-    writer: &mut TcpStream,
-    reader: &mut BufReader<TcpStream>,
+async fn send_and_expect(
+    writer: &mut OwnedWriteHalf,
+    reader: &mut MasterReader,
     cmd: &[&str],
     expected_prefix: &str,
-) -> io::Result<()> {
-    write_resp_array(writer, cmd)?;
-    writer.flush()?;
+) -> io::Result<String> {
+    let mut frame = Vec::new();
+    write_resp_array(&mut frame, cmd)?;
+    writer.write_all(&frame).await?;
 
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    println!("[replication::handshake] Sent {:?}, received: {}", cmd, line.trim());
+    let line = reader.read_line().await?;
+    println!("[replication::handshake] Sent {:?}, received: {}", cmd, line);
 
     if !line.starts_with(expected_prefix) {
         return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Expected {}", expected_prefix)));
     }
 
-    Ok(())
+    Ok(line)
 }
 
-pub fn stream_command_loop(
-    reader: &mut BufReader<TcpStream>,
-    writer: &mut TcpStream,
-    ctx: &mut Context,
-) -> io::Result<()> {
+async fn stream_command_loop(reader: &mut MasterReader, writer: &mut OwnedWriteHalf, ctx: &mut Context) -> io::Result<()> {
     println!("[replication::stream] Entered command loop.");
 
+    let mut parser = RespParser::default();
     loop {
-        let cmd_size = match peek_resp_command_size(reader) {
-            Ok(0) => {
-                thread::sleep(Duration::from_millis(1));
-                continue;
-            }
-            Ok(n) => n,
-            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
-                println!("[replication::stream] Master disconnected (EOF).");
-                break;
-            }
+        let (args, cmd_size) = match parse_command(&mut parser, &mut reader.buf) {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => match reader.fill().await {
+                Ok(()) => continue,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    println!("[replication::stream] Master disconnected (EOF).");
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("[replication::stream] Error reading command: {}", e);
+                    break;
+                }
+            },
             Err(e) => {
-                eprintln!("[replication::stream] Peek error: {}", e);
+                eprintln!("[replication::stream] Error reading command: {}", e);
                 break;
             }
         };
+        if args.is_empty() {
+            println!("[replication::stream] Empty command.");
+            continue;
+        }
 
-        let maybe_args = read_resp_array(reader);
-        match maybe_args {
-            Err(e) => {
-                eprintln!("[replication::stream] Error reading command: {}", e);
-                break;
-            }
-            Ok(None) => {
-                println!("[replication::stream] No more commands.");
-                break;
-            }
-            Ok(Some(args)) => {
-                if args.is_empty() {
-                    println!("[replication::stream] Empty command.");
-                    continue;
-                }
+        println!("[replication::stream] Dispatching command: {:?}", args);
+        let reply = apply_command(&args, ctx)?;
+        if !reply.is_empty() {
+            writer.write_all(&reply).await?;
+        }
 
-                let cmd = args[0].to_uppercase();
-                println!("[replication::stream] Dispatching command: {:?}", args);
+        let offset = ctx.master_repl_offset.fetch_add(cmd_size as u64, Ordering::Relaxed) + cmd_size as u64;
+        println!("[replication::stream] Updated master_repl_offset: {}", offset);
+    }
 
-                dispatch_cmd(&cmd, writer, &args, ctx)?;
-                writer.flush()?;
+    println!("[replication::stream] Exiting command loop.");
+    Ok(())
+}
 
-                ctx.master_repl_offset += cmd_size;
-                println!(
-                    "[replication::stream] Updated master_repl_offset: {}",
-                    ctx.master_repl_offset
-                );
-            }
+/// Runs one command from the master; returns what the master is sent back
+/// (only GETACK gets an answer).
+fn apply_command(args: &[Arg], ctx: &mut Context) -> io::Result<Vec<u8>> {
+    let cmd = args[0].to_uppercase();
+    if queue_in_transaction(&cmd, args, ctx) {
+        return Ok(Vec::new());
+    }
+
+    // same barrier clients take, so an AOF rewrite sees each
+    // write either in its snapshot or in the new incremental file
+    let mut reply = Vec::new();
+    execute_and_propagate(&cmd, &mut reply, args, ctx)?;
+    Ok(reply)
+}
+
+/// Full-resync payload (`$<len>\r\n<rdb>`), decoded as it comes off the
+/// socket.
+async fn load_rdb_snapshot_from_stream(reader: &mut MasterReader, ctx: &mut Context) -> io::Result<()> {
+    let rdb_header = reader.read_line().await?;
+    println!("[replication::rdb] RDB header: {}", rdb_header);
+
+    if !rdb_header.starts_with('$') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected '$' prefix for RDB header, got: '{}'", rdb_header),
+        ));
+    }
+
+    let rdb_len: usize = rdb_header[1..].trim().parse().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "Invalid RDB length in header")
+    })?;
+
+    // Decode on a blocking thread while the payload arrives, instead of
+    // buffering all of it first. Exactly `rdb_len` bytes are forwarded:
+    // whatever follows them is the command stream. If the decoder stops
+    // early the rest is still consumed, and its result says what went wrong.
+    let (chunks, pending) = mpsc::channel(RDB_CHUNKS_IN_FLIGHT);
+    let databases = ctx.dbs.len();
+    let decoding = tokio::task::spawn_blocking(move || {
+        load_databases(PayloadReader { chunks: pending, current: Bytes::new() }, databases)
+    });
+
+    let mut remaining = rdb_len;
+    while remaining > 0 {
+        if reader.buf.is_empty() {
+            reader.fill().await?;
         }
+        let chunk = reader.buf.split_to(remaining.min(reader.buf.len())).freeze();
+        remaining -= chunk.len();
+        let _ = chunks.send(chunk).await;
     }
+    drop(chunks);
+
+    let parsed = decoding.await.map_err(io::Error::other)??;
+    println!("[replication::rdb] Snapshot read ({} bytes).", rdb_len);
+
+    for (store, db) in ctx.dbs.iter().zip(parsed) {
+        store.replace(db);
+    }
+    println!("[replication::rdb] Snapshot loaded into store successfully.");
 
-    println!("[replication::stream] Exiting command loop.");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{client_pair, test_context, test_runtime};
+    use crate::rdb::encode::encode_rdb_snapshot;
+    use crate::rdb::Value;
+    use std::io::Write;
+    use std::thread;
+
+    #[test]
+    fn snapshot_is_decoded_and_the_stream_after_it_kept() {
+        let source = test_context();
+        let big = "v".repeat(3 * READ_BUFFER_SIZE);
+        source.dbs[0].lock(b"k").insert(b"k".to_vec(), (Value::String(big.clone().into_bytes()), None));
+        source.dbs[2].lock(b"other").insert(b"other".to_vec(), (Value::String("x".into()), None));
+        let rdb = encode_rdb_snapshot(&source.dbs.iter().map(|db| db.lock_all().to_db()).collect::<Vec<_>>()).unwrap();
+
+        let (server, mut master) = client_pair();
+        let (reader, _writer) = server.into_split();
+        let mut reader = MasterReader { reader, buf: BytesMut::new() };
+        // sent in pieces, as a large payload arrives
+        let sender = thread::spawn(move || {
+            let mut stream = format!("${}\r\n", rdb.len()).into_bytes();
+            stream.extend_from_slice(&rdb);
+            stream.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+            for piece in stream.chunks(1000) {
+                master.write_all(piece).unwrap();
+            }
+            master
+        });
+
+        let mut ctx = test_context();
+        test_runtime().block_on(load_rdb_snapshot_from_stream(&mut reader, &mut ctx)).unwrap();
+        let _master = sender.join().unwrap();
+        assert!(matches!(ctx.dbs[0].lock(b"k").get(&b"k"[..]), Some((Value::String(v), None)) if *v == big.as_bytes()));
+        assert!(ctx.dbs[2].lock(b"other").contains_key(&b"other"[..]));
+
+        // the command stream after the payload is left for the link to read
+        test_runtime().block_on(async {
+            while reader.buf.len() < 14 {
+                reader.fill().await.unwrap();
+            }
+        });
+        assert_eq!(&reader.buf[..], b"*1\r\n$4\r\nPING\r\n");
+    }
+}
@@ -0,0 +1,110 @@
+use bytes::Bytes;
+use std::fmt;
+use std::ops::Deref;
+
+/// One command argument, as the client sent it.
+///
+/// Keys and values (a SET value, a RESTORE payload, what gets propagated)
+/// are read through `as_bytes`, which are exactly the bytes received, UTF-8
+/// or not. Names, numbers and options are read through its text view
+/// (`Arg` derefs to `str`); an argument that isn't UTF-8 reads there as
+/// U+FFFD, which no name, number or option matches.
+///
+/// The bytes are a `Bytes`, so an argument parsed from a frame shares the
+/// frame's buffer instead of copying out of it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Arg {
+    bytes: Bytes,
+    utf8: bool,
+}
+
+impl Arg {
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        let utf8 = std::str::from_utf8(&bytes).is_ok();
+        Self { bytes, utf8 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes.into()
+    }
+
+    pub fn as_str(&self) -> &str {
+        if self.utf8 {
+            // checked when the argument was made
+            std::str::from_utf8(&self.bytes).unwrap_or("\u{FFFD}")
+        } else {
+            "\u{FFFD}"
+        }
+    }
+}
+
+impl Deref for Arg {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<Bytes> for Arg {
+    fn from(bytes: Bytes) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<String> for Arg {
+    fn from(text: String) -> Self {
+        Self { bytes: text.into(), utf8: true }
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+impl From<Vec<u8>> for Arg {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Arg {
+    fn from(bytes: &[u8]) -> Self {
+        Self::from_bytes(bytes.to_vec())
+    }
+}
+
+impl PartialEq<str> for Arg {
+    fn eq(&self, other: &str) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for Arg {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl fmt::Debug for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.utf8 {
+            fmt::Debug::fmt(self.as_str(), f)
+        } else {
+            write!(f, "\"{}\"", self.bytes.escape_ascii())
+        }
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.bytes))
+    }
+}
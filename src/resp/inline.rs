@@ -0,0 +1,142 @@
+use crate::resp::ProtocolError;
+
+/// Longest inline command accepted, newline included, as in Redis.
+pub const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// Splits an inline command (`SET "a key" 'it''s'`) into its arguments, the
+/// way `redis-cli` and Redis' `sdssplitargs` do:
+///
+/// - arguments are separated by whitespace;
+/// - `"..."` may contain `\n`, `\r`, `\t`, `\b`, `\a`, `\xHH` and `\<char>`;
+/// - `'...'` is literal apart from `\'`;
+/// - a closing quote must be followed by whitespace or the end of the line.
+///
+/// A blank line gives no arguments.
+pub fn split_inline_args(line: &[u8]) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    let mut args = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            let Some(&c) = line.get(i) else {
+                if quote.is_some() {
+                    return Err(ProtocolError::UnbalancedQuotes);
+                }
+                break;
+            };
+
+            match quote {
+                Some(b'"') => {
+                    if c == b'\\' && i + 3 < line.len() && line[i + 1] == b'x' {
+                        if let Some(byte) = hex_byte(line[i + 2], line[i + 3]) {
+                            arg.push(byte);
+                            i += 4;
+                            continue;
+                        }
+                    }
+                    if c == b'\\' && i + 1 < line.len() {
+                        i += 1;
+                        arg.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    } else if c == b'"' {
+                        closing_quote(line, i)?;
+                        quote = None;
+                    } else {
+                        arg.push(c);
+                    }
+                }
+                Some(_) => {
+                    if c == b'\\' && line.get(i + 1) == Some(&b'\'') {
+                        i += 1;
+                        arg.push(b'\'');
+                    } else if c == b'\'' {
+                        closing_quote(line, i)?;
+                        quote = None;
+                    } else {
+                        arg.push(c);
+                    }
+                }
+                None => match c {
+                    b' ' | b'\n' | b'\r' | b'\t' | 0 => break,
+                    b'"' | b'\'' => quote = Some(c),
+                    _ => arg.push(c),
+                },
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
+/// The quote at `i` must end its argument.
+fn closing_quote(line: &[u8], i: usize) -> Result<(), ProtocolError> {
+    match line.get(i + 1) {
+        Some(next) if !next.is_ascii_whitespace() => Err(ProtocolError::UnbalancedQuotes),
+        _ => Ok(()),
+    }
+}
+
+fn hex_byte(hi: u8, lo: u8) -> Option<u8> {
+    let digit = |c: u8| (c as char).to_digit(16);
+    Some((digit(hi)? * 16 + digit(lo)?) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> Result<Vec<String>, ProtocolError> {
+        split_inline_args(line.as_bytes())
+            .map(|args| args.into_iter().map(|a| String::from_utf8(a).unwrap()).collect())
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(split("SET key value").unwrap(), ["SET", "key", "value"]);
+        assert_eq!(split("  PING \t ").unwrap(), ["PING"]);
+        assert_eq!(split("").unwrap(), Vec::<String>::new());
+        assert_eq!(split(" \t ").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn double_quotes_take_escapes() {
+        assert_eq!(split(r#"SET "a key" "two words""#).unwrap(), ["SET", "a key", "two words"]);
+        assert_eq!(split(r#"ECHO "a\nb\tc\"d\\e""#).unwrap(), ["ECHO", "a\nb\tc\"d\\e"]);
+        assert_eq!(split(r#"ECHO "\x41\x7a""#).unwrap(), ["ECHO", "Az"]);
+        assert_eq!(split(r#"ECHO """#).unwrap(), ["ECHO", ""]);
+        // not a hex escape: the `x` is kept
+        assert_eq!(split(r#"ECHO "\xZZ""#).unwrap(), ["ECHO", "xZZ"]);
+        assert_eq!(split_inline_args(br#""\x00\xff""#).unwrap(), [vec![0x00, 0xff]]);
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(split(r"ECHO 'a\nb'").unwrap(), ["ECHO", r"a\nb"]);
+        assert_eq!(split(r"ECHO 'it\'s'").unwrap(), ["ECHO", "it's"]);
+        assert_eq!(split(r#"ECHO '"quoted"'"#).unwrap(), ["ECHO", "\"quoted\""]);
+    }
+
+    #[test]
+    fn quotes_must_be_balanced_and_end_their_argument() {
+        assert_eq!(split(r#"SET "open"#), Err(ProtocolError::UnbalancedQuotes));
+        assert_eq!(split("SET 'open"), Err(ProtocolError::UnbalancedQuotes));
+        assert_eq!(split(r#"SET "a"b"#), Err(ProtocolError::UnbalancedQuotes));
+        assert_eq!(split("SET 'a'b"), Err(ProtocolError::UnbalancedQuotes));
+        assert_eq!(split(r#"SET "a" b"#).unwrap(), ["SET", "a", "b"]);
+    }
+}
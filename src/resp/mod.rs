@@ -0,0 +1,468 @@
+mod arg;
+mod inline;
+mod parser;
+mod value;
+
+pub use arg::Arg;
+pub use inline::{split_inline_args, MAX_INLINE_LEN};
+pub use parser::{ProtocolError, RespParser, DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN, MAX_LINE_LEN};
+pub use value::RespValue;
+
+use bytes::BytesMut;
+use memchr::memchr;
+use std::io::{self, BufRead, Write};
+
+/// Takes the next client command off the front of `buf`: a RESP Array of
+/// Bulk Strings or an inline command line. Returns it with its size on the
+/// wire, which the replication stream counts towards its offset; `Ok(None)`
+/// (with `buf` untouched) until all of it has arrived. A blank line gives an
+/// empty command.
+pub fn parse_command(
+    parser: &mut RespParser,
+    buf: &mut BytesMut,
+) -> Result<Option<(Vec<Arg>, usize)>, ProtocolError> {
+    let parsed = match buf.first() {
+        None => return Ok(None),
+        Some(b'*') => match parser.parse_sized(buf)? {
+            Some((frame, size)) => Some((command_args(frame)?, size)),
+            None => None,
+        },
+        Some(_) => parse_inline(buf)?,
+    };
+    if let Some((args, _)) = &parsed {
+        println!("[resp::parse_command] Parsed command: {:?}", args);
+    }
+    Ok(parsed)
+}
+
+/// One newline-terminated inline command; a trailing `\r` is dropped.
+fn parse_inline(buf: &mut BytesMut) -> Result<Option<(Vec<Arg>, usize)>, ProtocolError> {
+    let newline = match memchr(b'\n', buf) {
+        Some(pos) if pos < MAX_INLINE_LEN => pos,
+        None if buf.len() < MAX_INLINE_LEN => return Ok(None),
+        _ => return Err(ProtocolError::InlineTooLong),
+    };
+
+    let line = buf.split_to(newline + 1);
+    let text = &line[..newline];
+    let text = text.strip_suffix(b"\r").unwrap_or(text);
+    let args = split_inline_args(text)?.into_iter().map(Arg::from_bytes).collect();
+    Ok(Some((args, line.len())))
+}
+
+/// Read one command from an append-only file, where only RESP arrays are
+/// valid. Returns `Ok(None)` on EOF.
+pub fn read_multibulk_command<R: BufRead>(
+    reader: &mut R,
+    parser: &mut RespParser,
+) -> io::Result<Option<Vec<Arg>>> {
+    let parsed = read_multibulk(reader, parser);
+    match &parsed {
+        Ok(Some(args)) => println!("[resp::read_multibulk_command] Parsed command: {:?}", args),
+        Ok(None) => println!("[resp::read_multibulk_command] EOF reached"),
+        Err(e) => eprintln!("[resp::read_multibulk_command] Malformed command: {}", e),
+    }
+    parsed
+}
+
+/// `read_multibulk_command` without the logging or configured limits, for
+/// offline tools that walk whole files.
+pub fn parse_resp_array<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<Arg>>> {
+    read_multibulk(reader, &mut RespParser::default())
+}
+
+fn read_multibulk<R: BufRead>(reader: &mut R, parser: &mut RespParser) -> io::Result<Option<Vec<Arg>>> {
+    match reader.fill_buf()?.first() {
+        None => return Ok(None),
+        Some(b'*') => {}
+        Some(&other) => return Err(ProtocolError::ExpectedArray(other).into()),
+    }
+    match read_resp_frame(reader, parser)? {
+        Some((frame, _)) => Ok(Some(command_args(frame)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the next frame off `reader`, consuming exactly its bytes, and
+/// returns it with its size on the wire. `Ok(None)` on EOF before a frame
+/// starts; `UnexpectedEof` if it ends partway through one.
+pub fn read_resp_frame<R: BufRead>(
+    reader: &mut R,
+    parser: &mut RespParser,
+) -> io::Result<Option<(RespValue, usize)>> {
+    let mut buf = BytesMut::new();
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "EOF in the middle of a RESP frame"));
+        }
+        let before = buf.len();
+        buf.extend_from_slice(available);
+        let taken = available.len();
+
+        match parser.frame_len(&buf)? {
+            Some(len) => {
+                // leave whatever follows the frame in the reader for next time
+                reader.consume(len - before);
+                buf.truncate(len);
+                return Ok(parser.parse_sized(&mut buf)?);
+            }
+            None => reader.consume(taken),
+        }
+    }
+}
+
+/// The arguments of a command frame, which must be an array of bulk strings.
+pub fn command_args(frame: RespValue) -> Result<Vec<Arg>, ProtocolError> {
+    match frame {
+        RespValue::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                RespValue::BulkString(data) => Ok(Arg::from(data)),
+                other => Err(ProtocolError::ExpectedBulk(other.type_byte())),
+            })
+            .collect(),
+        RespValue::NullArray => Ok(Vec::new()),
+        other => Err(ProtocolError::UnexpectedByte(other.type_byte())),
+    }
+}
+
+/// *<N>\r\n then N bulk‐strings
+pub fn write_resp_array(out: &mut dyn Write, items: &[&str]) -> io::Result<()> {
+    let items: Vec<RespValue> = items.iter().map(|item| RespValue::bulk(item)).collect();
+    out.write_all(&RespValue::Array(items).to_bytes())
+}
+
+/// *<N>\r\n then N bulk strings holding any bytes, e.g. a command's arguments
+pub fn write_resp_bytes_array(out: &mut dyn Write, items: &[&[u8]]) -> io::Result<()> {
+    let items: Vec<RespValue> = items.iter().map(|item| RespValue::bulk_bytes(item)).collect();
+    out.write_all(&RespValue::Array(items).to_bytes())
+}
+
+/// +<string>\r\n
+pub fn write_simple_resp_string(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    out.write_all(&encode_simple_resp_string(s))
+}
+
+/// -ERR <msg>\r\n
+pub fn write_resp_error(out: &mut dyn Write, msg: &str) -> io::Result<()> {
+    out.write_all(&encode_resp_error(msg))
+}
+
+/// $<len>\r\n<data>\r\n
+pub fn write_bulk_resp_string(out: &mut dyn Write, data: &str) -> io::Result<()> {
+    out.write_all(&encode_bulk_resp_string(data))
+}
+
+/// $<len>\r\n<data>\r\n
+pub fn encode_bulk_resp_string(s: &str) -> Vec<u8> {
+    RespValue::bulk(s).to_bytes()
+}
+
+/// `$<len>\r\n<bytes>\r\n` for binary data.
+pub fn encode_bulk_resp_bytes(data: &[u8]) -> Vec<u8> {
+    RespValue::bulk_bytes(data).to_bytes()
+}
+
+/// *<N>\r\n then the already-encoded elements
+pub fn encode_resp_array(chunks: &[Vec<u8>]) -> Vec<u8> {
+    encode_aggregate(b'*', chunks)
+}
+
+/// -ERR <msg>\r\n
+pub fn encode_resp_error(msg: &str) -> Vec<u8> {
+    RespValue::Error(format!("ERR {}", msg)).to_bytes()
+}
+
+/// -<CODE> <msg>\r\n, for errors with their own code (WRONGPASS, CROSSSLOT...)
+pub fn encode_resp_error_code(code: &str, msg: &str) -> Vec<u8> {
+    RespValue::Error(format!("{} {}", code, msg)).to_bytes()
+}
+
+/// +<string>\r\n
+pub fn encode_simple_resp_string(s: &str) -> Vec<u8> {
+    RespValue::SimpleString(s.to_string()).to_bytes()
+}
+
+/// Encodes an integer in RESP format: `:<number>\r\n`
+pub fn encode_int(n: i64) -> Vec<u8> {
+    RespValue::Integer(n).to_bytes()
+}
+
+/// `*<N>` (or another aggregate type) followed by already-encoded elements.
+fn encode_aggregate(type_byte: u8, chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut result = Vec::new();
+    value::encode_header(&mut result, type_byte, chunks.len());
+    for c in chunks {
+        result.extend_from_slice(c);
+    }
+    result
+}
+
+// --- RESP3 types, sent to clients that negotiated protocol 3 via HELLO ---
+
+/// `%<N>\r\n` then N key/value pairs
+pub fn encode_resp_map(pairs: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut result = Vec::new();
+    value::encode_header(&mut result, b'%', pairs.len());
+    for (k, v) in pairs {
+        result.extend_from_slice(k);
+        result.extend_from_slice(v);
+    }
+    result
+}
+
+/// `~<N>\r\n` then N elements
+pub fn encode_resp_set(chunks: &[Vec<u8>]) -> Vec<u8> {
+    encode_aggregate(b'~', chunks)
+}
+
+/// `><N>\r\n` then N elements: out-of-band data such as pub/sub messages
+pub fn encode_resp_push(chunks: &[Vec<u8>]) -> Vec<u8> {
+    encode_aggregate(b'>', chunks)
+}
+
+/// `,<double>\r\n`, with `inf`, `-inf` and `nan` for the special values
+pub fn encode_double(d: f64) -> Vec<u8> {
+    RespValue::Double(d).to_bytes()
+}
+
+/// `#t\r\n` or `#f\r\n`
+pub fn encode_boolean(b: bool) -> Vec<u8> {
+    RespValue::Boolean(b).to_bytes()
+}
+
+/// `_\r\n`
+pub fn encode_null() -> Vec<u8> {
+    RespValue::Null.to_bytes()
+}
+
+/// `(<digits>\r\n` for integers beyond 64 bits
+pub fn encode_big_number(digits: &str) -> Vec<u8> {
+    RespValue::BigNumber(digits.to_string()).to_bytes()
+}
+
+/// `=<len>\r\n<fmt>:<text>\r\n`, where `format` is three characters (`txt`, `mkd`)
+pub fn encode_verbatim_string(format: &str, text: &str) -> Vec<u8> {
+    RespValue::VerbatimString { format: format.to_string(), text: text.to_string().into() }.to_bytes()
+}
+
+// --- Replies that depend on the client's protocol (2 or 3) ---
+
+/// A map in RESP3; a flat [k1, v1, k2, v2 ...] array in RESP2.
+pub fn encode_map_reply(protocol: u8, pairs: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    if protocol >= 3 {
+        return encode_resp_map(pairs);
+    }
+    let flat: Vec<Vec<u8>> = pairs.iter().flat_map(|(k, v)| [k.clone(), v.clone()]).collect();
+    encode_resp_array(&flat)
+}
+
+/// A push frame in RESP3; a plain array in RESP2.
+pub fn encode_push_reply(protocol: u8, chunks: &[Vec<u8>]) -> Vec<u8> {
+    if protocol >= 3 { encode_resp_push(chunks) } else { encode_resp_array(chunks) }
+}
+
+/// A double in RESP3; its bulk-string form in RESP2.
+pub fn encode_double_reply(protocol: u8, d: f64) -> Vec<u8> {
+    if protocol >= 3 { encode_double(d) } else { encode_bulk_resp_string(&d.to_string()) }
+}
+
+/// RESP3 null, or RESP2's null bulk string.
+pub fn encode_null_reply(protocol: u8) -> Vec<u8> {
+    if protocol >= 3 { encode_null() } else { RespValue::NullBulkString.to_bytes() }
+}
+
+/// A verbatim text string in RESP3; a bulk string in RESP2.
+pub fn encode_text_reply(protocol: u8, text: &str) -> Vec<u8> {
+    if protocol >= 3 { encode_verbatim_string("txt", text) } else { encode_bulk_resp_string(text) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor, Read};
+
+    #[test]
+    fn encodes_resp2_replies() {
+        assert_eq!(encode_simple_resp_string("OK"), b"+OK\r\n");
+        assert_eq!(encode_resp_error("unknown command"), b"-ERR unknown command\r\n");
+        assert_eq!(encode_resp_error_code("WRONGTYPE", "wrong kind"), b"-WRONGTYPE wrong kind\r\n");
+        assert_eq!(encode_int(-42), b":-42\r\n");
+        assert_eq!(encode_bulk_resp_string(""), b"$0\r\n\r\n");
+        assert_eq!(encode_bulk_resp_bytes(b"\x00\r\n"), b"$3\r\n\x00\r\n\r\n");
+        assert_eq!(
+            encode_resp_array(&[encode_int(1), encode_bulk_resp_string("a")]),
+            b"*2\r\n:1\r\n$1\r\na\r\n"
+        );
+        assert_eq!(encode_resp_array(&[]), b"*0\r\n");
+
+        let mut out = Vec::new();
+        write_resp_array(&mut out, &["SET", "k", "v"]).unwrap();
+        assert_eq!(out, b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n");
+    }
+
+    #[test]
+    fn encodes_resp3_types() {
+        let pairs = [(encode_bulk_resp_string("a"), encode_int(1)), (encode_bulk_resp_string("b"), encode_null())];
+        assert_eq!(encode_resp_map(&pairs), b"%2\r\n$1\r\na\r\n:1\r\n$1\r\nb\r\n_\r\n");
+        assert_eq!(encode_resp_set(&[encode_int(7)]), b"~1\r\n:7\r\n");
+        assert_eq!(encode_resp_push(&[encode_bulk_resp_string("message")]), b">1\r\n$7\r\nmessage\r\n");
+        assert_eq!(encode_double(1.5), b",1.5\r\n");
+        assert_eq!(encode_double(f64::INFINITY), b",inf\r\n");
+        assert_eq!(encode_double(f64::NEG_INFINITY), b",-inf\r\n");
+        assert_eq!(encode_double(f64::NAN), b",nan\r\n");
+        assert_eq!(encode_boolean(true), b"#t\r\n");
+        assert_eq!(encode_boolean(false), b"#f\r\n");
+        assert_eq!(encode_null(), b"_\r\n");
+        assert_eq!(encode_big_number("123456789012345678901234567890"), b"(123456789012345678901234567890\r\n");
+        assert_eq!(encode_verbatim_string("txt", "hello"), b"=9\r\ntxt:hello\r\n");
+    }
+
+    #[test]
+    fn protocol_aware_replies_fall_back_to_resp2() {
+        let pairs = [(encode_bulk_resp_string("k"), encode_bulk_resp_string("v"))];
+        assert_eq!(encode_map_reply(3, &pairs), b"%1\r\n$1\r\nk\r\n$1\r\nv\r\n");
+        assert_eq!(encode_map_reply(2, &pairs), b"*2\r\n$1\r\nk\r\n$1\r\nv\r\n");
+        assert_eq!(encode_map_reply(2, &[]), b"*0\r\n");
+
+        let chunks = [encode_bulk_resp_string("message")];
+        assert_eq!(encode_push_reply(3, &chunks), b">1\r\n$7\r\nmessage\r\n");
+        assert_eq!(encode_push_reply(2, &chunks), b"*1\r\n$7\r\nmessage\r\n");
+
+        assert_eq!(encode_double_reply(3, 3.0), b",3\r\n");
+        assert_eq!(encode_double_reply(2, 3.0), b"$1\r\n3\r\n");
+        assert_eq!(encode_double_reply(2, 0.5), b"$3\r\n0.5\r\n");
+        assert_eq!(encode_double_reply(2, f64::INFINITY), b"$3\r\ninf\r\n");
+
+        assert_eq!(encode_null_reply(3), b"_\r\n");
+        assert_eq!(encode_null_reply(2), b"$-1\r\n");
+
+        assert_eq!(encode_text_reply(3, "a\nb"), b"=7\r\ntxt:a\nb\r\n");
+        assert_eq!(encode_text_reply(2, "a\nb"), b"$3\r\na\nb\r\n");
+    }
+
+    #[test]
+    fn resp3_types_parse_back() {
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&encode_double(-2.25));
+        buf.extend_from_slice(&encode_boolean(true));
+        buf.extend_from_slice(&encode_verbatim_string("mkd", "# hi"));
+
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::Double(-2.25)));
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::Boolean(true)));
+        assert_eq!(
+            parser.parse(&mut buf).unwrap(),
+            Some(RespValue::VerbatimString { format: "mkd".to_string(), text: "# hi".to_string().into() })
+        );
+        assert!(buf.is_empty());
+    }
+
+    fn args(command: &[&str]) -> Vec<Arg> {
+        command.iter().map(|&a| a.into()).collect()
+    }
+
+    #[test]
+    fn parse_command_takes_arrays_of_bulk_strings() {
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::from(&b"*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n*1\r\n$4\r\nPI"[..]);
+
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), Some((args(&["ECHO", "hi"]), 22)));
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), None);
+        buf.extend_from_slice(b"NG\r\n");
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), Some((args(&["PING"]), 14)));
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn parse_command_takes_inline_lines() {
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::from(&b"SET k \"a b\"\r\nPING\n\r\nGET"[..]);
+
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), Some((args(&["SET", "k", "a b"]), 13)));
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), Some((args(&["PING"]), 5)));
+        // a blank line is an empty command
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), Some((Vec::new(), 2)));
+        assert_eq!(parse_command(&mut parser, &mut buf).unwrap(), None);
+        assert_eq!(&buf[..], b"GET");
+
+        let mut unbalanced = BytesMut::from(&b"SET \"k\n"[..]);
+        assert_eq!(parse_command(&mut parser, &mut unbalanced), Err(ProtocolError::UnbalancedQuotes));
+
+        let mut endless = BytesMut::from(&vec![b'a'; MAX_INLINE_LEN][..]);
+        assert_eq!(parse_command(&mut parser, &mut endless), Err(ProtocolError::InlineTooLong));
+
+        // append-only files only hold arrays
+        let mut inline = Cursor::new(b"PING\r\n".to_vec());
+        assert!(read_multibulk_command(&mut inline, &mut parser).is_err());
+    }
+
+    #[test]
+    fn non_utf8_args_keep_their_bytes() {
+        let mut reader = Cursor::new(b"*2\r\n$3\r\nSET\r\n$2\r\n\xff\x00\r\n".to_vec());
+        let args = parse_resp_array(&mut reader).unwrap().unwrap();
+        assert_eq!(args[1].as_bytes(), b"\xff\x00");
+        assert_eq!(&*args[1], "\u{FFFD}");
+
+        let latin1 = Arg::from(b"\xe9".to_vec());
+        let utf8 = Arg::from("é");
+        assert_ne!(latin1, utf8);
+        assert_eq!(utf8.as_bytes(), b"\xc3\xa9");
+    }
+
+    #[test]
+    fn bulk_args_share_the_read_buffer() {
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$5\r\nmykey\r\n"[..]);
+        let start = buf.as_ptr() as usize;
+        let (args, _) = parse_command(&mut RespParser::default(), &mut buf).unwrap().unwrap();
+        assert_eq!(args[1].as_bytes().as_ptr() as usize, start + 17);
+    }
+
+    #[test]
+    fn command_args_must_be_bulk_strings() {
+        assert_eq!(
+            command_args(RespValue::Array(vec![RespValue::bulk("GET"), RespValue::Integer(1)])),
+            Err(ProtocolError::ExpectedBulk(b':'))
+        );
+        assert_eq!(command_args(RespValue::Integer(1)), Err(ProtocolError::UnexpectedByte(b':')));
+        assert_eq!(command_args(RespValue::NullArray), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn read_resp_frame_consumes_exactly_one_frame() {
+        // a tiny buffer, so frames straddle many reads
+        let wire = b"*1\r\n$4\r\nPING\r\n+OK\r\nleft over".to_vec();
+        let mut reader = BufReader::with_capacity(3, Cursor::new(wire));
+        let mut parser = RespParser::default();
+
+        let (frame, size) = read_resp_frame(&mut reader, &mut parser).unwrap().unwrap();
+        assert_eq!(frame, RespValue::Array(vec![RespValue::bulk("PING")]));
+        assert_eq!(size, 14);
+        let (frame, size) = read_resp_frame(&mut reader, &mut parser).unwrap().unwrap();
+        assert_eq!(frame, RespValue::SimpleString("OK".to_string()));
+        assert_eq!(size, 5);
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "left over");
+    }
+
+    #[test]
+    fn read_multibulk_command_reports_eof_and_truncation() {
+        let mut parser = RespParser::default();
+        let mut empty = Cursor::new(Vec::new());
+        assert_eq!(read_multibulk_command(&mut empty, &mut parser).unwrap(), None);
+
+        let mut cut = Cursor::new(b"*2\r\n$3\r\nGET\r\n$1\r".to_vec());
+        let err = read_multibulk_command(&mut cut, &mut parser).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut not_array = Cursor::new(b"*1\r\n:1\r\n".to_vec());
+        let err = parse_resp_array(&mut not_array).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::resp::RespValue;
+use bytes::{Bytes, BytesMut};
+use memchr::memchr;
+use std::fmt;
+use std::io;
+
+/// Default `proto-max-bulk-len`: the largest bulk string a peer may send.
+pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// Most elements one aggregate may announce, as in Redis.
+pub const DEFAULT_MAX_MULTIBULK_LEN: usize = i32::MAX as usize;
+/// Longest header or simple line accepted before giving up on the peer.
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+/// Deepest nesting of aggregates accepted; keeps decoding off the stack's edge.
+const MAX_DEPTH: usize = 128;
+/// Elements reserved up front for an aggregate, however many it announces:
+/// the rest is only allocated as the elements actually arrive.
+const MAX_PREALLOC: usize = 1024;
+
+/// Malformed or oversized input. The connection can't be trusted to be in
+/// sync after one, so the server reports it and hangs up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    UnexpectedByte(u8),
+    /// A command array element that isn't a bulk string.
+    ExpectedBulk(u8),
+    /// Something other than a command array where only arrays may appear.
+    ExpectedArray(u8),
+    InvalidBulkLength,
+    InvalidMultibulkLength,
+    BulkTooLong,
+    LineTooLong,
+    TooDeep,
+    MissingCrlf,
+    InvalidLine,
+    InlineTooLong,
+    UnbalancedQuotes,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::UnexpectedByte(b) => write!(f, "Protocol error: unexpected type byte '{}'", *b as char),
+            ProtocolError::ExpectedBulk(b) => write!(f, "Protocol error: expected '$', got '{}'", *b as char),
+            ProtocolError::ExpectedArray(b) => write!(f, "Protocol error: expected '*', got '{}'", *b as char),
+            ProtocolError::InvalidBulkLength => f.write_str("Protocol error: invalid bulk length"),
+            ProtocolError::InvalidMultibulkLength => f.write_str("Protocol error: invalid multibulk length"),
+            ProtocolError::BulkTooLong => f.write_str("Protocol error: bulk length exceeds proto-max-bulk-len"),
+            ProtocolError::LineTooLong => f.write_str("Protocol error: too big request line"),
+            ProtocolError::TooDeep => f.write_str("Protocol error: too deeply nested aggregate"),
+            ProtocolError::MissingCrlf => f.write_str("Protocol error: bulk string not terminated by CRLF"),
+            ProtocolError::InvalidLine => f.write_str("Protocol error: invalid value line"),
+            ProtocolError::InlineTooLong => f.write_str("Protocol error: too big inline request"),
+            ProtocolError::UnbalancedQuotes => f.write_str("Protocol error: unbalanced quotes in request"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for io::Error {
+    fn from(e: ProtocolError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Incremental RESP parser over a `BytesMut` the caller keeps filling.
+///
+/// A frame is first scanned for completeness, picking up where the previous
+/// call stopped, so a frame trickling in over many reads is walked once. Only
+/// a complete frame is split off the buffer and decoded; its bulk strings
+/// are slices of it rather than copies.
+#[derive(Debug)]
+pub struct RespParser {
+    max_bulk_len: usize,
+    max_multibulk_len: usize,
+    /// Bytes at the front of the buffer already known to hold whole elements
+    /// of the frame being scanned.
+    scanned: usize,
+    /// Elements still owed to each open aggregate, innermost last.
+    pending: Vec<usize>,
+}
+
+impl Default for RespParser {
+    fn default() -> Self {
+        RespParser::new(DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN)
+    }
+}
+
+impl RespParser {
+    pub fn new(max_bulk_len: usize, max_multibulk_len: usize) -> RespParser {
+        RespParser { max_bulk_len, max_multibulk_len, scanned: 0, pending: Vec::new() }
+    }
+
+    /// Takes the next complete frame off the front of `buf`; `Ok(None)`
+    /// (with `buf` untouched) until all of it has arrived.
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<RespValue>, ProtocolError> {
+        Ok(self.parse_sized(buf)?.map(|(value, _)| value))
+    }
+
+    /// `parse`, also returning how many bytes the frame took on the wire.
+    pub fn parse_sized(&mut self, buf: &mut BytesMut) -> Result<Option<(RespValue, usize)>, ProtocolError> {
+        let Some(len) = self.frame_len(buf)? else {
+            return Ok(None);
+        };
+        let frame = buf.split_to(len).freeze();
+        let (value, _) = decode(&frame, 0)?;
+        Ok(Some((value, len)))
+    }
+
+    /// Length of the complete frame at the front of `buf`, or `None` if it
+    /// is still partial. Must be called with the same buffer (only ever
+    /// appended to) until it returns `Some` or an error.
+    pub fn frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, ProtocolError> {
+        let result = self.scan(buf);
+        if !matches!(result, Ok(None)) {
+            self.scanned = 0;
+            self.pending.clear();
+        }
+        result
+    }
+
+    fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>, ProtocolError> {
+        loop {
+            let start = self.scanned;
+            let Some(line_end) = find_crlf(buf, start)? else {
+                return Ok(None);
+            };
+            let type_byte = buf[start];
+            if line_end == start {
+                return Err(ProtocolError::UnexpectedByte(type_byte));
+            }
+            let header = &buf[start + 1..line_end];
+            let mut next = line_end + 2;
+
+            let children = match type_byte {
+                b'$' | b'!' | b'=' => {
+                    if let Some(len) = self.blob_len(header)? {
+                        next += len + 2;
+                        if buf.len() < next {
+                            return Ok(None);
+                        }
+                        if &buf[next - 2..next] != b"\r\n" {
+                            return Err(ProtocolError::MissingCrlf);
+                        }
+                    }
+                    0
+                }
+                b'*' | b'~' | b'>' => self.aggregate_len(header)?,
+                b'%' | b'|' => self.aggregate_len(header)?.saturating_mul(2),
+                b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(' => 0,
+                other => return Err(ProtocolError::UnexpectedByte(other)),
+            };
+            self.scanned = next;
+
+            // this element fills one slot of its parent; its own children come next
+            if let Some(owed) = self.pending.last_mut() {
+                *owed -= 1;
+            }
+            if children > 0 {
+                if self.pending.len() >= MAX_DEPTH {
+                    return Err(ProtocolError::TooDeep);
+                }
+                self.pending.push(children);
+            }
+            while self.pending.last() == Some(&0) {
+                self.pending.pop();
+            }
+            if self.pending.is_empty() {
+                return Ok(Some(self.scanned));
+            }
+        }
+    }
+
+    /// Payload length of a blob header; `None` for the `-1` null.
+    fn blob_len(&self, header: &[u8]) -> Result<Option<usize>, ProtocolError> {
+        match parse_int(header).ok_or(ProtocolError::InvalidBulkLength)? {
+            -1 => Ok(None),
+            n if n < 0 => Err(ProtocolError::InvalidBulkLength),
+            n if n as u64 > self.max_bulk_len as u64 => Err(ProtocolError::BulkTooLong),
+            n => Ok(Some(n as usize)),
+        }
+    }
+
+    /// Element count of an aggregate header; 0 for empty and null ones.
+    fn aggregate_len(&self, header: &[u8]) -> Result<usize, ProtocolError> {
+        match parse_int(header).ok_or(ProtocolError::InvalidMultibulkLength)? {
+            -1 => Ok(0),
+            n if n < 0 || n as u64 > self.max_multibulk_len as u64 => Err(ProtocolError::InvalidMultibulkLength),
+            n => Ok(n as usize),
+        }
+    }
+}
+
+/// Index of the `\r` ending the line that starts at `start`.
+fn find_crlf(buf: &[u8], start: usize) -> Result<Option<usize>, ProtocolError> {
+    let rest = &buf[start.min(buf.len())..];
+    match memchr(b'\n', rest) {
+        Some(0) => Err(ProtocolError::InvalidLine),
+        Some(pos) if rest[pos - 1] == b'\r' => Ok(Some(start + pos - 1)),
+        Some(_) => Err(ProtocolError::InvalidLine),
+        None if rest.len() > MAX_LINE_LEN => Err(ProtocolError::LineTooLong),
+        None => Ok(None),
+    }
+}
+
+fn parse_int(digits: &[u8]) -> Option<i64> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn line_str(line: &[u8]) -> Result<String, ProtocolError> {
+    String::from_utf8(line.to_vec()).map_err(|_| ProtocolError::InvalidLine)
+}
+
+/// Decodes the value at `pos` of a frame `scan` found complete; returns it
+/// with the position just past it.
+fn decode(frame: &Bytes, pos: usize) -> Result<(RespValue, usize), ProtocolError> {
+    let line_end = find_crlf(frame, pos)?.ok_or(ProtocolError::InvalidLine)?;
+    let line = &frame[pos + 1..line_end];
+    let body = line_end + 2;
+
+    let blob = |len: i64| frame.slice(body..body + len as usize);
+    let value = match frame[pos] {
+        b'+' => RespValue::SimpleString(line_str(line)?),
+        b'-' => RespValue::Error(line_str(line)?),
+        b':' => RespValue::Integer(parse_int(line).ok_or(ProtocolError::InvalidLine)?),
+        b'_' => RespValue::Null,
+        b'#' => match line {
+            b"t" => RespValue::Boolean(true),
+            b"f" => RespValue::Boolean(false),
+            _ => return Err(ProtocolError::InvalidLine),
+        },
+        b',' => RespValue::Double(parse_double(line).ok_or(ProtocolError::InvalidLine)?),
+        b'(' => RespValue::BigNumber(line_str(line)?),
+        b'$' | b'!' | b'=' => {
+            let len = parse_int(line).ok_or(ProtocolError::InvalidBulkLength)?;
+            if len < 0 {
+                return Ok((RespValue::NullBulkString, body));
+            }
+            let data = blob(len);
+            let value = match frame[pos] {
+                b'$' => RespValue::BulkString(data),
+                b'!' => RespValue::BulkError(data),
+                _ => {
+                    if data.len() < 4 || data[3] != b':' {
+                        return Err(ProtocolError::InvalidLine);
+                    }
+                    let format = line_str(&data[..3])?;
+                    RespValue::VerbatimString { format, text: data.slice(4..) }
+                }
+            };
+            return Ok((value, body + len as usize + 2));
+        }
+        b'*' | b'~' | b'>' => {
+            let count = parse_int(line).ok_or(ProtocolError::InvalidMultibulkLength)?;
+            if count < 0 {
+                return Ok((RespValue::NullArray, body));
+            }
+            let mut items = Vec::with_capacity((count as usize).min(MAX_PREALLOC));
+            let mut next = body;
+            for _ in 0..count {
+                let (item, after) = decode(frame, next)?;
+                items.push(item);
+                next = after;
+            }
+            let value = match frame[pos] {
+                b'*' => RespValue::Array(items),
+                b'~' => RespValue::Set(items),
+                _ => RespValue::Push(items),
+            };
+            return Ok((value, next));
+        }
+        b'%' | b'|' => {
+            let count = parse_int(line).ok_or(ProtocolError::InvalidMultibulkLength)?;
+            let mut pairs = Vec::with_capacity((count.max(0) as usize).min(MAX_PREALLOC));
+            let mut next = body;
+            for _ in 0..count {
+                let (key, after_key) = decode(frame, next)?;
+                let (value, after_value) = decode(frame, after_key)?;
+                pairs.push((key, value));
+                next = after_value;
+            }
+            let value = if frame[pos] == b'%' { RespValue::Map(pairs) } else { RespValue::Attribute(pairs) };
+            return Ok((value, next));
+        }
+        other => return Err(ProtocolError::UnexpectedByte(other)),
+    };
+    Ok((value, body))
+}
+
+fn parse_double(line: &[u8]) -> Option<f64> {
+    match line {
+        b"inf" => Some(f64::INFINITY),
+        b"-inf" => Some(f64::NEG_INFINITY),
+        b"nan" => Some(f64::NAN),
+        _ => std::str::from_utf8(line).ok()?.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(bytes: &[u8]) -> Result<Option<RespValue>, ProtocolError> {
+        RespParser::default().parse(&mut BytesMut::from(bytes))
+    }
+
+    fn every_type() -> Vec<RespValue> {
+        vec![
+            RespValue::SimpleString("OK".to_string()),
+            RespValue::Error("ERR bad".to_string()),
+            RespValue::Integer(-42),
+            RespValue::bulk("hello"),
+            RespValue::bulk_bytes(b"bin\r\n\0ary"),
+            RespValue::bulk(""),
+            RespValue::NullBulkString,
+            RespValue::NullArray,
+            RespValue::Null,
+            RespValue::Boolean(true),
+            RespValue::Boolean(false),
+            RespValue::Double(3.25),
+            RespValue::Double(f64::NEG_INFINITY),
+            RespValue::BigNumber("3492890328409238509324850943850943825024385".to_string()),
+            RespValue::BulkError(Bytes::from_static(b"SYNTAX line\r\nbreak")),
+            RespValue::VerbatimString { format: "txt".to_string(), text: Bytes::from_static(b"Some string") },
+            RespValue::Map(vec![(RespValue::bulk("k"), RespValue::Integer(1))]),
+            RespValue::Attribute(vec![(RespValue::SimpleString("ttl".to_string()), RespValue::Integer(3))]),
+            RespValue::Set(vec![RespValue::bulk("a"), RespValue::bulk("b")]),
+            RespValue::Push(vec![RespValue::bulk("message"), RespValue::Array(vec![])]),
+            RespValue::Array(vec![
+                RespValue::Integer(1),
+                RespValue::Array(vec![RespValue::Null, RespValue::Map(vec![])]),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn every_type_round_trips() {
+        for value in every_type() {
+            let wire = value.to_bytes();
+            let mut buf = BytesMut::from(&wire[..]);
+            let (parsed, size) = RespParser::default().parse_sized(&mut buf).unwrap().unwrap();
+            assert_eq!(parsed, value);
+            assert_eq!(size, wire.len());
+            assert!(buf.is_empty());
+        }
+
+        match parse_all(b",nan\r\n") {
+            Ok(Some(RespValue::Double(d))) => assert!(d.is_nan()),
+            other => panic!("expected NaN, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn partial_frames_wait_for_the_rest() {
+        let wire = RespValue::Array(every_type()).to_bytes();
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::new();
+
+        for (i, &byte) in wire.iter().enumerate() {
+            buf.extend_from_slice(&[byte]);
+            let parsed = parser.parse(&mut buf).unwrap();
+            if i + 1 < wire.len() {
+                assert_eq!(parsed, None, "complete after {} of {} bytes", i + 1, wire.len());
+                assert_eq!(buf.len(), i + 1);
+            } else {
+                assert_eq!(parsed, Some(RespValue::Array(every_type())));
+            }
+        }
+    }
+
+    #[test]
+    fn pipelined_frames_come_off_one_at_a_time() {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"+first\r\n:2\r\n*1\r\n$5\r\nthird\r\n$6\r\nfou");
+        let mut parser = RespParser::default();
+
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::SimpleString("first".to_string())));
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::Integer(2)));
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::Array(vec![RespValue::bulk("third")])));
+        assert_eq!(parser.parse(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], b"$6\r\nfou");
+
+        buf.extend_from_slice(b"rth\r\n");
+        assert_eq!(parser.parse(&mut buf).unwrap(), Some(RespValue::bulk("fourth")));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn bulk_strings_share_the_frame_buffer() {
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n"[..]);
+        let base = buf.as_ptr() as usize;
+        let Some(RespValue::Array(items)) = RespParser::default().parse(&mut buf).unwrap() else {
+            panic!("expected an array");
+        };
+        let RespValue::BulkString(bar) = &items[1] else {
+            panic!("expected a bulk string");
+        };
+        assert_eq!(bar.as_ptr() as usize, base + 17);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert_eq!(parse_all(b"?what\r\n"), Err(ProtocolError::UnexpectedByte(b'?')));
+        assert_eq!(parse_all(b"\r\n"), Err(ProtocolError::UnexpectedByte(b'\r')));
+        assert_eq!(parse_all(b"+OK\n"), Err(ProtocolError::InvalidLine));
+        assert_eq!(parse_all(b"$3\r\nfoobar\r\n"), Err(ProtocolError::MissingCrlf));
+        assert_eq!(parse_all(b"$-2\r\n"), Err(ProtocolError::InvalidBulkLength));
+        assert_eq!(parse_all(b"$x\r\n"), Err(ProtocolError::InvalidBulkLength));
+        assert_eq!(parse_all(b"*-2\r\n"), Err(ProtocolError::InvalidMultibulkLength));
+        assert_eq!(parse_all(b"*1x\r\n"), Err(ProtocolError::InvalidMultibulkLength));
+        assert_eq!(parse_all(b"#x\r\n"), Err(ProtocolError::InvalidLine));
+        assert_eq!(parse_all(b"=3\r\ntxt\r\n"), Err(ProtocolError::InvalidLine));
+    }
+
+    #[test]
+    fn limits_are_enforced_before_the_data_arrives() {
+        let mut parser = RespParser::new(10, 100);
+        assert_eq!(parser.parse(&mut BytesMut::from(&b"$11\r\n"[..])), Err(ProtocolError::BulkTooLong));
+        assert_eq!(parser.parse(&mut BytesMut::from(&b"*101\r\n"[..])), Err(ProtocolError::InvalidMultibulkLength));
+        assert_eq!(
+            parser.parse(&mut BytesMut::from(&b"$10\r\n0123456789\r\n"[..])),
+            Ok(Some(RespValue::bulk("0123456789")))
+        );
+
+        let long_line = vec![b'+'; MAX_LINE_LEN + 2];
+        assert_eq!(parse_all(&long_line), Err(ProtocolError::LineTooLong));
+
+        let deep = b"*1\r\n".repeat(MAX_DEPTH + 1);
+        assert_eq!(parse_all(&deep), Err(ProtocolError::TooDeep));
+        let mut just_deep_enough = b"*1\r\n".repeat(MAX_DEPTH);
+        just_deep_enough.extend_from_slice(b":1\r\n");
+        assert!(parse_all(&just_deep_enough).unwrap().is_some());
+    }
+
+    #[test]
+    fn parser_starts_over_after_an_error() {
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::from(&b"*2\r\n:1\r\n"[..]);
+        assert_eq!(parser.parse(&mut buf), Ok(None));
+        assert!(parser.frame_len(b"*2\r\n:1\r\n?\r\n").is_err());
+        assert_eq!(parser.parse(&mut BytesMut::from(&b":7\r\n"[..])), Ok(Some(RespValue::Integer(7))));
+    }
+}
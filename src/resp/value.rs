@@ -0,0 +1,156 @@
+use bytes::Bytes;
+
+/// One RESP2/RESP3 value, as read off the wire or about to be written.
+///
+/// Bulk payloads are `Bytes`, so values parsed from a frame share its
+/// buffer instead of copying out of it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RespValue {
+    /// `+OK`
+    SimpleString(String),
+    /// `-ERR message`: the first word is the error code
+    Error(String),
+    /// `:42`
+    Integer(i64),
+    /// `$3\r\nfoo`
+    BulkString(Bytes),
+    /// `$-1`, RESP2's nil
+    NullBulkString,
+    /// `*N` followed by N values
+    Array(Vec<RespValue>),
+    /// `*-1`, RESP2's nil array
+    NullArray,
+    /// `_`, RESP3's nil
+    Null,
+    /// `#t` / `#f`
+    Boolean(bool),
+    /// `,3.14`, with `inf`, `-inf` and `nan`
+    Double(f64),
+    /// `(` followed by an integer of any size, kept as its digits
+    BigNumber(String),
+    /// `!N`: an error that may contain any byte, newlines included
+    BulkError(Bytes),
+    /// `=N\r\ntxt:...`: text with a three-letter format hint
+    VerbatimString { format: String, text: Bytes },
+    /// `%N` followed by N key/value pairs
+    Map(Vec<(RespValue, RespValue)>),
+    /// `|N` pairs describing the value that follows them
+    Attribute(Vec<(RespValue, RespValue)>),
+    /// `~N` followed by N values
+    Set(Vec<RespValue>),
+    /// `>N`: out-of-band data such as pub/sub messages
+    Push(Vec<RespValue>),
+}
+
+impl RespValue {
+    pub fn bulk(s: &str) -> RespValue {
+        RespValue::BulkString(Bytes::copy_from_slice(s.as_bytes()))
+    }
+
+    pub fn bulk_bytes(data: &[u8]) -> RespValue {
+        RespValue::BulkString(Bytes::copy_from_slice(data))
+    }
+
+    /// The byte that introduces the value on the wire.
+    pub fn type_byte(&self) -> u8 {
+        match self {
+            RespValue::SimpleString(_) => b'+',
+            RespValue::Error(_) => b'-',
+            RespValue::Integer(_) => b':',
+            RespValue::BulkString(_) | RespValue::NullBulkString => b'$',
+            RespValue::Array(_) | RespValue::NullArray => b'*',
+            RespValue::Null => b'_',
+            RespValue::Boolean(_) => b'#',
+            RespValue::Double(_) => b',',
+            RespValue::BigNumber(_) => b'(',
+            RespValue::BulkError(_) => b'!',
+            RespValue::VerbatimString { .. } => b'=',
+            RespValue::Map(_) => b'%',
+            RespValue::Attribute(_) => b'|',
+            RespValue::Set(_) => b'~',
+            RespValue::Push(_) => b'>',
+        }
+    }
+
+    /// The value's wire encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    /// Appends the value's wire encoding to `out`. Every reply the server
+    /// sends is produced here, either directly or through the `encode_*`
+    /// helpers that wrap it.
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            RespValue::SimpleString(s) => encode_line(out, b'+', s),
+            RespValue::Error(msg) => encode_line(out, b'-', msg),
+            RespValue::Integer(n) => encode_line(out, b':', &n.to_string()),
+            RespValue::BulkString(data) => encode_blob(out, b'$', data),
+            RespValue::NullBulkString => out.extend_from_slice(b"$-1\r\n"),
+            RespValue::Array(items) => encode_items(out, b'*', items),
+            RespValue::NullArray => out.extend_from_slice(b"*-1\r\n"),
+            RespValue::Null => out.extend_from_slice(b"_\r\n"),
+            RespValue::Boolean(b) => encode_line(out, b'#', if *b { "t" } else { "f" }),
+            RespValue::Double(d) => encode_line(out, b',', &format_double(*d)),
+            RespValue::BigNumber(digits) => encode_line(out, b'(', digits),
+            RespValue::BulkError(data) => encode_blob(out, b'!', data),
+            RespValue::VerbatimString { format, text } => {
+                encode_header(out, b'=', format.len() + 1 + text.len());
+                out.extend_from_slice(format.as_bytes());
+                out.push(b':');
+                out.extend_from_slice(text);
+                out.extend_from_slice(b"\r\n");
+            }
+            RespValue::Map(pairs) => encode_pairs(out, b'%', pairs),
+            RespValue::Attribute(pairs) => encode_pairs(out, b'|', pairs),
+            RespValue::Set(items) => encode_items(out, b'~', items),
+            RespValue::Push(items) => encode_items(out, b'>', items),
+        }
+    }
+}
+
+/// `<type><len>\r\n`, the header of every blob and aggregate.
+pub(crate) fn encode_header(out: &mut Vec<u8>, type_byte: u8, len: usize) {
+    out.push(type_byte);
+    out.extend_from_slice(len.to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn encode_line(out: &mut Vec<u8>, type_byte: u8, line: &str) {
+    out.push(type_byte);
+    out.extend_from_slice(line.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn encode_blob(out: &mut Vec<u8>, type_byte: u8, data: &[u8]) {
+    encode_header(out, type_byte, data.len());
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\r\n");
+}
+
+fn encode_items(out: &mut Vec<u8>, type_byte: u8, items: &[RespValue]) {
+    encode_header(out, type_byte, items.len());
+    for item in items {
+        item.encode_into(out);
+    }
+}
+
+fn encode_pairs(out: &mut Vec<u8>, type_byte: u8, pairs: &[(RespValue, RespValue)]) {
+    encode_header(out, type_byte, pairs.len());
+    for (key, value) in pairs {
+        key.encode_into(out);
+        value.encode_into(out);
+    }
+}
+
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        d.to_string()
+    }
+}
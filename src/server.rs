@@ -1,48 +1,180 @@
-use crate::commands::{dispatch_cmd, is_write_cmd};
-use crate::resp::{read_resp_array, write_resp_array, write_simple_resp_string, write_resp_error};
+use crate::commands::pubsub::unsubscribe_all;
+use crate::commands::{dispatch_blocking_cmd, dispatch_cmd, is_blocking_cmd, is_known_cmd};
+use crate::config::OutputBufferLimit;
+use crate::outbox::Outbox;
+use crate::persistence::now_unix_millis;
+use crate::resp::{
+    Arg,
+    parse_command, write_resp_array, write_resp_bytes_array, write_resp_error, write_simple_resp_string, RespParser, DEFAULT_MAX_MULTIBULK_LEN,
+};
 use crate::role::Role;
 use crate::Context;
 
+use bytes::BytesMut;
 use std::{
-    io::{self, BufReader, Write},
-    net::TcpStream,
-    thread,
+    io,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
 };
+use tokio::io::AsyncReadExt;
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::TcpStream;
+
+/// CLIENT ID / HELLO `id` of the next connection.
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
 
-pub fn serve_client_connection(stream: TcpStream, mut ctx: Context) -> io::Result<()> {
+pub async fn serve_client_connection(stream: TcpStream, mut ctx: Context) -> io::Result<()> {
     let peer = stream
         .peer_addr()
-        .unwrap_or_else(|_| "[unknown]".parse().unwrap());
-    println!("[handle_client] New client connected: {:?}", peer);
+        .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+    ctx.client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+    ctx.peer = Some(peer);
+    println!("[handle_client] New client {} connected: {:?}", ctx.client_id, peer);
+
+    stream.set_nodelay(true)?;
+    let (reader, writer) = stream.into_split();
+    let outbox = Outbox::start(writer, OutputBufferLimit::UNLIMITED, ctx.protocol);
+    ctx.outbox = Some(outbox.clone());
+
+    let result = tokio::select! {
+        result = serve_commands(reader, &mut ctx, peer) => result,
+        // kicked off for breaking its output buffer limit
+        _ = outbox.wait_closed() => Ok(()),
+    };
+
+    // however the connection ended, stop delivering messages to it
+    let dropped = unsubscribe_all(&mut ctx);
+    if ctx.replicas.lock().unwrap().remove(&peer).is_some() {
+        println!("[handle_client] Replica {:?} went away", peer);
+    }
+    outbox.finish();
+    if dropped > 0 {
+        println!("[handle_client] Dropped {} subscription(s) of {:?}", dropped, peer);
+    }
+    result
+}
+
+/// Buffered replies are queued once they reach this many bytes, even if
+/// more pipelined commands are waiting.
+const REPLY_FLUSH_THRESHOLD: usize = 16 * 1024;
+/// Read buffer per connection: one read takes in this much of a pipeline.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
 
-    ctx.this_client = Some(stream.try_clone()?);
-    let mut reader = BufReader::new(stream.try_clone()?);
-    let mut writer = stream;
+/// Queues `bytes` for the client. Replies collect in `replies` and go to
+/// the outbox together, see `flush_replies`.
+fn send(ctx: &Context, replies: &mut Vec<u8>, bytes: &[u8]) -> io::Result<()> {
+    replies.extend_from_slice(bytes);
+    if replies.len() >= REPLY_FLUSH_THRESHOLD {
+        flush_replies(ctx, replies)?;
+    }
+    Ok(())
+}
+
+/// Hands everything buffered by `send` to the outbox as one frame, which
+/// its writer sends with a single write.
+fn flush_replies(ctx: &Context, replies: &mut Vec<u8>) -> io::Result<()> {
+    if replies.is_empty() {
+        return Ok(());
+    }
+    let batch = std::mem::take(replies);
+    match &ctx.outbox {
+        Some(outbox) if !outbox.push(batch) => Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            "client output buffer closed",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Commands that may wait a while or queue output on the outbox themselves:
+/// buffered replies go out before they run, so the client isn't kept
+/// waiting for them and nothing overtakes them.
+fn flushes_first(cmd: &str) -> bool {
+    matches!(
+        cmd,
+        "BLPOP" | "XREAD" | "WAIT" | "PSYNC" | "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE"
+    )
+}
 
-    while let Some(args) = read_resp_array(&mut reader)? {
+/// Why `cmd` can't be queued in a MULTI, if it can't: unknown commands, and
+/// those that can't run under EXEC's exclusive hold of the sync barrier —
+/// PSYNC takes the barrier itself and SHUTDOWN saves through it. Refusing
+/// one fails the transaction, so EXEC answers EXECABORT.
+fn refused_in_transaction(cmd: &str) -> Option<&'static str> {
+    match cmd {
+        "PSYNC" | "SHUTDOWN" => Some("Command not allowed inside a transaction"),
+        _ if !is_known_cmd(cmd) => Some("unknown command"),
+        _ => None,
+    }
+}
+
+async fn serve_commands(mut reader: OwnedReadHalf, ctx: &mut Context, peer: SocketAddr) -> io::Result<()> {
+    let mut parser = RespParser::new(ctx.cfg.proto_max_bulk_len as usize, DEFAULT_MAX_MULTIBULK_LEN);
+    let mut buf = BytesMut::with_capacity(READ_BUFFER_SIZE);
+    let mut replies = Vec::new();
+
+    let result = serve_pipeline(&mut reader, &mut buf, &mut replies, &mut parser, ctx, peer).await;
+    // whatever was answered before the connection ended still goes out
+    let flushed = flush_replies(ctx, &mut replies);
+    result.and(flushed)
+}
+
+async fn serve_pipeline(
+    reader: &mut OwnedReadHalf,
+    buf: &mut BytesMut,
+    replies: &mut Vec<u8>,
+    parser: &mut RespParser,
+    ctx: &mut Context,
+    peer: SocketAddr,
+) -> io::Result<()> {
+    loop {
+        let args = match parse_command(parser, buf) {
+            Ok(Some((args, _))) => args,
+            Ok(None) => {
+                // the read batch is done: answer it before waiting for more
+                flush_replies(ctx, replies)?;
+                buf.reserve(READ_BUFFER_SIZE);
+                if reader.read_buf(buf).await? == 0 {
+                    break;
+                }
+                continue;
+            }
+            Err(e) => {
+                // the stream can't be resynced after a bad frame: say why, then hang up
+                eprintln!("[handle_client] {} from {:?}; closing the connection", e, peer);
+                let mut err = Vec::new();
+                write_resp_error(&mut err, &e.to_string())?;
+                send(ctx, replies, &err)?;
+                break;
+            }
+        };
         if args.is_empty() {
             continue;
         }
         let cmd = args[0].to_uppercase();
 
         // --- Subscribed‐mode guard + special PING ---
-        if !ctx.subscribed_channels.is_empty() {
+        // (RESP3 clients get messages as push frames, so they may run anything)
+        if ctx.in_subscribed_mode() && ctx.protocol < 3 {
             // PING in subscribed mode returns ["pong", ""]
             if cmd == "PING" {
-                write_resp_array(&mut writer, &["pong", ""])?;
-                writer.flush()?;
+                let mut pong = Vec::new();
+                write_resp_array(&mut pong, &["pong", ""])?;
+                send(ctx, replies, &pong)?;
                 continue;
             }
             // otherwise only these are allowed
             let allowed = matches!(
                 cmd.as_str(),
-                "SUBSCRIBE" | "UNSUBSCRIBE" | "PSUBSCRIBE" | "PUNSUBSCRIBE" | "QUIT"
+                "SUBSCRIBE" | "UNSUBSCRIBE" | "PSUBSCRIBE" | "PUNSUBSCRIBE" | "SSUBSCRIBE" | "SUNSUBSCRIBE"
+                    | "RESET" | "QUIT"
             );
             if !allowed {
                 // don't prefix with "ERR " here—write_resp_error will do that
                 let msg = format!("Can't execute '{}' in subscribed mode", cmd.to_lowercase());
-                write_resp_error(&mut writer, &msg)?;
-                writer.flush()?;
+                let mut err = Vec::new();
+                write_resp_error(&mut err, &msg)?;
+                send(ctx, replies, &err)?;
                 continue;
             }
         }
@@ -52,81 +184,518 @@ pub fn serve_client_connection(stream: TcpStream, mut ctx: Context) -> io::Resul
             && cmd != "MULTI"
             && cmd != "EXEC"
             && cmd != "DISCARD"
+            && cmd != "RESET"
         {
+            if let Some(msg) = refused_in_transaction(&cmd) {
+                println!("[handle_client] Refused '{}' in transaction", cmd);
+                ctx.transaction_failed = true;
+                let mut err = Vec::new();
+                write_resp_error(&mut err, msg)?;
+                send(ctx, replies, &err)?;
+                continue;
+            }
             println!("[handle_client] Queued '{}' in transaction", cmd);
             ctx.queued.push((cmd.clone(), args.clone()));
-            write_simple_resp_string(&mut writer, "QUEUED")?;
-            writer.flush()?;
+            let mut queued = Vec::new();
+            write_simple_resp_string(&mut queued, "QUEUED")?;
+            send(ctx, replies, &queued)?;
             continue;
         }
 
-        // — Master: bump offset & propagate writes —
-        if !ctx.in_transaction
-            && ctx.cfg.role == Role::Master
-            && is_write_cmd(&cmd)
-        {
-            ctx.master_repl_offset += 1;
-            println!(
-                "[handle_client] master_repl_offset now {} after '{}'",
-                ctx.master_repl_offset, cmd
-            );
+        // — Execute locally & reply to client —
+        if flushes_first(&cmd) {
+            flush_replies(ctx, replies)?;
+        }
+        println!("[handle_client] Dispatching '{}' for {:?}", cmd, peer);
+        let mut reply = Vec::new();
+        if is_blocking_cmd(&cmd) {
+            // they wait without holding the barrier; BLPOP takes it (and
+            // propagates its pop as an LPOP) only while it pops, PSYNC
+            // exclusively while it copies the dataset, SHUTDOWN shared while
+            // it saves
+            dispatch_blocking_cmd(&cmd, &mut reply, &args, ctx).await?;
+        } else {
+            execute_and_propagate(&cmd, &mut reply, &args, ctx)?;
+        }
+        send(ctx, replies, &reply)?;
+
+        // — on PSYNC, this connection becomes the replica's link —
+        if ctx.cfg.role == Role::Master && cmd.eq_ignore_ascii_case("PSYNC") {
+            println!("[handle_client] PSYNC complete, serving the replication link");
+            flush_replies(ctx, replies)?;
+            return serve_replica_link(reader, buf, parser, ctx, peer).await;
+        }
+    }
+
+    println!("[handle_client] Client {:?} disconnected.", peer);
+    Ok(())
+}
 
-            let items: Vec<&str> = args.iter().map(String::as_str).collect();
-            let mut reps = ctx.replicas.lock().unwrap();
-            let mut to_remove = Vec::new();
-            for (&addr, (rs, _)) in reps.iter_mut() {
-                if let Err(e) = write_resp_array(rs, &items).and_then(|_| rs.flush()) {
-                    eprintln!("[propagate] to {} failed: {}; removing", addr, e);
-                    to_remove.push(addr);
-                } else {
-                    println!("[propagate] Write propagated to replica {}", addr);
+/// Runs a command that doesn't block; a write propagates itself once it has
+/// changed something (`record_write`).
+pub(crate) fn execute_and_propagate(cmd: &str, reply: &mut Vec<u8>, args: &[Arg], ctx: &mut Context) -> io::Result<()> {
+    // The sync barrier is held shared across execute + propagate, so a full
+    // resync or an AOF rewrite sees each write either in its snapshot or in
+    // what follows it (the replica backlog, the new incremental AOF), never
+    // both. EXEC holds it exclusively: no other command runs until its queue
+    // is done, so a transaction is applied and observed as one step.
+    // PSYNC takes it exclusively itself, and MIGRATE takes it shared around
+    // its reads and deletes but not its round trip to the target.
+    let sync_lock = ctx.sync_lock.clone();
+    let _exclusive = (cmd == "EXEC").then(|| sync_lock.write().unwrap());
+    let _shared = (!matches!(cmd, "EXEC" | "PSYNC" | "MIGRATE")).then(|| sync_lock.read().unwrap());
+
+    dispatch_cmd(cmd, reply, args, ctx)
+}
+
+/// What a replica sends after PSYNC: only its REPLCONF ACKs matter, and
+/// nothing is replied (the outbox now carries the replication stream).
+async fn serve_replica_link(
+    reader: &mut OwnedReadHalf,
+    buf: &mut BytesMut,
+    parser: &mut RespParser,
+    ctx: &mut Context,
+    peer: SocketAddr,
+) -> io::Result<()> {
+    loop {
+        let args = match parse_command(parser, buf) {
+            Ok(Some((args, _))) => args,
+            Ok(None) => {
+                buf.reserve(READ_BUFFER_SIZE);
+                if reader.read_buf(buf).await? == 0 {
+                    break;
                 }
+                continue;
             }
-            for addr in to_remove {
-                reps.remove(&addr);
+            Err(e) => {
+                eprintln!("[replication_reader] {} from {:?}", e, peer);
+                break;
             }
-        }
-
-        // — Execute locally & reply to client —
-        println!("[handle_client] Dispatching '{}' for {:?}", cmd, peer);
-        dispatch_cmd(&cmd, &mut writer, &args, &mut ctx)?;
-        writer.flush()?;
+        };
 
-        // — on PSYNC, hand off replication link —
-        if ctx.cfg.role == Role::Master && cmd.eq_ignore_ascii_case("PSYNC") {
-            println!("[handle_client] PSYNC complete, handing off replication link");
-
-            let repl_stream = reader.into_inner();
-            let ctx_for_reader = ctx.clone();
-
-            thread::spawn(move || {
-                let mut buf = BufReader::new(repl_stream);
-                while let Ok(Some(args)) = read_resp_array(&mut buf) {
-                    if args.len() == 3
-                        && args[0].eq_ignore_ascii_case("REPLCONF")
-                        && args[1].eq_ignore_ascii_case("ACK")
-                    {
-                        if let Ok(peer_addr) = buf.get_ref().peer_addr() {
-                            if let Ok(offset) = args[2].parse::<usize>() {
-                                let mut reps = ctx_for_reader.replicas.lock().unwrap();
-                                if let Some((_, last_ack)) = reps.get_mut(&peer_addr) {
-                                    *last_ack = offset;
-                                    println!(
-                                        "[replication_reader] {} ACKed offset {}",
-                                        peer_addr, offset
-                                    );
-                                }
-                            }
-                        }
-                    }
+        if args.len() == 3 && args[0].eq_ignore_ascii_case("REPLCONF") && args[1].eq_ignore_ascii_case("ACK") {
+            if let Ok(offset) = args[2].parse::<u64>() {
+                let mut reps = ctx.replicas.lock().unwrap();
+                if let Some(link) = reps.get_mut(&peer) {
+                    link.ack_offset = offset;
+                    println!("[replication_reader] {} ACKed offset {}", peer, offset);
+                    ctx.replica_acked.notify_waiters();
                 }
-                println!("[replication_reader] replication link closed");
-            });
+            }
+        }
+    }
+    println!("[replication_reader] replication link closed");
+    Ok(())
+}
 
-            return Ok(());
+/// Feeds one effective write to the AOF and, on a master, to every replica,
+/// as a single RESP frame. Relative expiries are pinned to absolute times so
+/// a replay later still expires the key at the same moment.
+pub fn propagate_write(ctx: &mut Context, args: &[Arg]) -> io::Result<()> {
+    let mut items: Vec<&[u8]> = args.iter().map(Arg::as_bytes).collect();
+    let pxat;
+    if items.len() == 5 && items[0].eq_ignore_ascii_case(b"SET") && items[3].eq_ignore_ascii_case(b"PX") {
+        if let Ok(ms) = args[4].parse::<u64>() {
+            pxat = (now_unix_millis() + ms).to_string();
+            items[3] = b"PXAT";
+            items[4] = pxat.as_bytes();
+        }
+    }
+    let absttl;
+    if items.len() >= 4
+        && items[0].eq_ignore_ascii_case(b"RESTORE")
+        && !items[4..].iter().any(|opt| opt.eq_ignore_ascii_case(b"ABSTTL"))
+    {
+        if let Ok(ms) = args[2].parse::<u64>() {
+            if ms > 0 {
+                absttl = (now_unix_millis() + ms).to_string();
+                items[2] = absttl.as_bytes();
+                items.push(b"ABSTTL");
+            }
         }
     }
 
-    println!("[handle_client] Client {:?} disconnected.", peer);
+    let mut frame = Vec::new();
+    write_resp_bytes_array(&mut frame, &items)?;
+
+    if let Some(aof) = &ctx.aof {
+        aof.append(ctx.db_index, &frame)?;
+    }
+
+    if ctx.cfg.role != Role::Master {
+        return Ok(());
+    }
+
+    // every replica is sent the same stream (a new one makes them all
+    // SELECT again), so the offset counts what each of them gets
+    let mut reps = ctx.replicas.lock().unwrap();
+    let mut out = Vec::with_capacity(frame.len());
+    if reps.values().any(|link| link.selected_db != Some(ctx.db_index)) {
+        write_resp_array(&mut out, &["SELECT", &ctx.db_index.to_string()])?;
+    }
+    out.extend_from_slice(&frame);
+    let offset = ctx.master_repl_offset.fetch_add(out.len() as u64, Ordering::Relaxed) + out.len() as u64;
+    println!("[propagate] master_repl_offset now {} after '{}'", offset, args[0]);
+
+    let mut to_remove = Vec::new();
+    for (&addr, link) in reps.iter_mut() {
+        link.selected_db = Some(ctx.db_index);
+        if let Some(backlog) = link.backlog.as_mut() {
+            backlog.push(out.clone());
+            println!("[propagate] Buffered write for syncing replica {}", addr);
+            continue;
+        }
+        if !link.outbox.push(out.clone()) {
+            eprintln!("[propagate] replica {} is gone; removing", addr);
+            to_remove.push(addr);
+        } else {
+            println!("[propagate] Write propagated to replica {}", addr);
+        }
+    }
+    for addr in to_remove {
+        reps.remove(&addr);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{assert_nothing_pushed, assert_pushed, attach_replica, test_context, test_runtime};
+    use crate::rdb::encode::encode_dump_payload;
+    use crate::rdb::Value;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A client connected to a connection served with `ctx`.
+    fn connect(ctx: &Context) -> std::net::TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        server.set_nonblocking(true).unwrap();
+
+        let ctx = ctx.clone();
+        test_runtime().spawn(async move {
+            let server = TcpStream::from_std(server).unwrap();
+            serve_client_connection(server, ctx).await
+        });
+        client
+    }
+
+    #[test]
+    fn answers_a_pipeline_in_order() {
+        let ctx = test_context();
+        let mut client = connect(&ctx);
+        client.write_all(b"PING\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\nGET k\r\nECHO \"a b\"\r\n").unwrap();
+        assert_pushed(&mut client, b"+PONG\r\n+OK\r\n$1\r\nv\r\n$3\r\na b\r\n");
+        assert_nothing_pushed(&mut client);
+    }
+
+    #[test]
+    fn large_pipelines_are_answered_in_full() {
+        let ctx = test_context();
+        let mut client = connect(&ctx);
+        let value = "x".repeat(1000);
+        // replies well past the flush threshold, sent while more input waits
+        let command = format!("ECHO {}\r\n", value);
+        let reply = format!("${}\r\n{}\r\n", value.len(), value);
+        let writer = {
+            let mut client = client.try_clone().unwrap();
+            thread::spawn(move || client.write_all(command.repeat(100).as_bytes()).unwrap())
+        };
+        assert_pushed(&mut client, reply.repeat(100).as_bytes());
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn commands_after_blpop_wait_until_it_is_served() {
+        let ctx = test_context();
+        let mut waiting = connect(&ctx);
+        waiting.write_all(b"PING\r\nBLPOP jobs 0\r\nPING\r\n").unwrap();
+        // what came before BLPOP is answered right away
+        assert_pushed(&mut waiting, b"+PONG\r\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut waiting);
+
+        let mut pusher = connect(&ctx);
+        pusher.write_all(b"RPUSH jobs a b\r\n").unwrap();
+        // both elements were pushed before the waiter took one
+        assert_pushed(&mut pusher, b":2\r\n");
+        assert_pushed(&mut waiting, b"*2\r\n$4\r\njobs\r\n$1\r\na\r\n+PONG\r\n");
+
+        pusher.write_all(b"LRANGE jobs 0 -1\r\n").unwrap();
+        assert_pushed(&mut pusher, b"*1\r\n$1\r\nb\r\n");
+    }
+
+    #[test]
+    fn one_push_serves_several_waiters_and_timeouts_reply_nil() {
+        let ctx = test_context();
+        let mut first = connect(&ctx);
+        first.write_all(b"BLPOP q 0\r\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let mut second = connect(&ctx);
+        second.write_all(b"BLPOP q 0\r\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        let mut pusher = connect(&ctx);
+        pusher.write_all(b"RPUSH q 1 2 3\r\n").unwrap();
+        assert_pushed(&mut pusher, b":3\r\n");
+        assert_pushed(&mut first, b"*2\r\n$1\r\nq\r\n$1\r\n1\r\n");
+        assert_pushed(&mut second, b"*2\r\n$1\r\nq\r\n$1\r\n2\r\n");
+
+        // the waiters took all but one; a push that hands out everything
+        // leaves no empty list behind
+        pusher.write_all(b"LPOP q\r\n").unwrap();
+        assert_pushed(&mut pusher, b"$1\r\n3\r\n");
+        first.write_all(b"BLPOP q 0\r\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        pusher.write_all(b"RPUSH q 4\r\nTYPE q\r\n").unwrap();
+        assert_pushed(&mut pusher, b":1\r\n+none\r\n");
+        assert_pushed(&mut first, b"*2\r\n$1\r\nq\r\n$1\r\n4\r\n");
+
+        let mut late = connect(&ctx);
+        late.write_all(b"BLPOP empty 0.05\r\nPING\r\n").unwrap();
+        assert_pushed(&mut late, b"$-1\r\n+PONG\r\n");
+        assert!(ctx.blocking.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn blpop_in_multi_does_not_block() {
+        let ctx = test_context();
+        let mut client = connect(&ctx);
+        client.write_all(b"MULTI\r\nBLPOP none 0\r\nEXEC\r\n").unwrap();
+        assert_pushed(&mut client, b"+OK\r\n+QUEUED\r\n*1\r\n$-1\r\n");
+    }
+
+    #[test]
+    fn subscribers_get_messages_published_on_other_connections() {
+        let ctx = test_context();
+        let mut subscriber = connect(&ctx);
+        subscriber.write_all(b"SUBSCRIBE news\r\n").unwrap();
+        assert_pushed(&mut subscriber, b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n");
+
+        let mut publisher = connect(&ctx);
+        publisher.write_all(b"PUBLISH news hi\r\n").unwrap();
+        assert_pushed(&mut publisher, b":1\r\n");
+        assert_pushed(&mut subscriber, b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n");
+
+        // once the subscriber hangs up, it is no longer counted
+        drop(subscriber);
+        thread::sleep(Duration::from_millis(100));
+        publisher.write_all(b"PUBLISH news again\r\n").unwrap();
+        assert_pushed(&mut publisher, b":0\r\n");
+    }
+
+    #[test]
+    fn xread_block_waits_on_a_timer() {
+        let ctx = test_context();
+        let mut client = connect(&ctx);
+        client.write_all(b"XREAD BLOCK 50 STREAMS s $\r\nPING\r\n").unwrap();
+        assert_pushed(&mut client, b"$-1\r\n+PONG\r\n");
+    }
+
+    #[test]
+    fn pops_by_blpop_and_push_handoffs_are_propagated() {
+        let ctx = test_context();
+        let mut replica = attach_replica(&ctx);
+        let mut waiting = connect(&ctx);
+        waiting.write_all(b"BLPOP q 0\r\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // the element handed to the waiter is popped on the replica too
+        let mut pusher = connect(&ctx);
+        pusher.write_all(b"RPUSH q a b\r\n").unwrap();
+        assert_pushed(&mut pusher, b":2\r\n");
+        assert_pushed(&mut waiting, b"*2\r\n$1\r\nq\r\n$1\r\na\r\n");
+        assert_pushed(&mut replica, b"*4\r\n$5\r\nRPUSH\r\n$1\r\nq\r\n$1\r\na\r\n$1\r\nb\r\n");
+        assert_pushed(&mut replica, b"*2\r\n$4\r\nLPOP\r\n$1\r\nq\r\n");
+
+        // and so is one BLPOP takes without waiting
+        waiting.write_all(b"BLPOP q 0\r\n").unwrap();
+        assert_pushed(&mut waiting, b"*2\r\n$1\r\nq\r\n$1\r\nb\r\n");
+        assert_pushed(&mut replica, b"*2\r\n$4\r\nLPOP\r\n$1\r\nq\r\n");
+        assert_nothing_pushed(&mut replica);
+    }
+
+
+    #[test]
+    fn refused_commands_abort_the_transaction() {
+        let ctx = test_context();
+        let mut client = connect(&ctx);
+        client.write_all(b"MULTI\r\nSET k v\r\nPSYNC ? -1\r\nNOSUCHCMD\r\nEXEC\r\nGET k\r\n").unwrap();
+        assert_pushed(
+            &mut client,
+            b"+OK\r\n+QUEUED\r\n-ERR Command not allowed inside a transaction\r\n-ERR unknown command\r\n\
+              -EXECABORT Transaction discarded because of previous errors.\r\n$-1\r\n",
+        );
+
+        // the next transaction starts clean
+        client.write_all(b"MULTI\r\nMULTI\r\nSET k v\r\nEXEC\r\n").unwrap();
+        assert_pushed(&mut client, b"+OK\r\n-ERR MULTI calls can not be nested\r\n+QUEUED\r\n*1\r\n+OK\r\n");
+    }
+
+
+    #[test]
+    fn pushes_only_serve_waiters_in_the_same_database() {
+        let ctx = test_context();
+        let mut waiting = connect(&ctx);
+        waiting.write_all(b"SELECT 1\r\nBLPOP q 0\r\n").unwrap();
+        assert_pushed(&mut waiting, b"+OK\r\n");
+        thread::sleep(Duration::from_millis(50));
+
+        let mut pusher = connect(&ctx);
+        pusher.write_all(b"RPUSH q a\r\n").unwrap();
+        assert_pushed(&mut pusher, b":1\r\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut waiting);
+
+        pusher.write_all(b"SELECT 1\r\nRPUSH q b\r\n").unwrap();
+        assert_pushed(&mut pusher, b"+OK\r\n:1\r\n");
+        assert_pushed(&mut waiting, b"*2\r\n$1\r\nq\r\n$1\r\nb\r\n");
+    }
+
+
+    #[test]
+    fn xread_block_is_woken_by_xadd() {
+        let ctx = test_context();
+        let mut reader = connect(&ctx);
+        reader.write_all(b"XREAD BLOCK 0 STREAMS s $\r\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut reader);
+
+        let mut writer = connect(&ctx);
+        writer.write_all(b"XADD s 1-1 f v\r\n").unwrap();
+        assert_pushed(&mut writer, b"$3\r\n1-1\r\n");
+        assert_pushed(&mut reader, b"*1\r\n*2\r\n$1\r\ns\r\n*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n");
+    }
+
+    #[test]
+    fn wait_returns_once_a_replica_acks() {
+        let ctx = test_context();
+        let mut replica = attach_replica(&ctx);
+        let mut client = connect(&ctx);
+        // timeout 0 waits for as long as it takes
+        client.write_all(b"SET k v\r\nWAIT 1 0\r\n").unwrap();
+        assert_pushed(&mut client, b"+OK\r\n");
+        assert_pushed(&mut replica, b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n");
+        assert_pushed(&mut replica, b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut client);
+
+        // the offset is in bytes: the SET frame's 27, not counting the GETACK
+        let ack = |offset| {
+            for link in ctx.replicas.lock().unwrap().values_mut() {
+                link.ack_offset = offset;
+            }
+            ctx.replica_acked.notify_waiters();
+        };
+        ack(26);
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut client);
+        ack(27);
+        assert_pushed(&mut client, b":1\r\n");
+        assert_eq!(ctx.master_repl_offset.load(Ordering::Relaxed), 27 + 37);
+    }
+
+    #[test]
+    fn only_writes_that_change_something_are_propagated() {
+        let ctx = test_context();
+        let mut replica = attach_replica(&ctx);
+        let mut client = connect(&ctx);
+        ctx.dbs[1].lock(b"k").insert(b"k".to_vec(), (Value::String(b"there".to_vec()), None));
+
+        client.write_all(b"RPUSH list a\r\nSET k v\r\n").unwrap();
+        assert_pushed(&mut client, b":1\r\n+OK\r\n");
+        assert_pushed(&mut replica, b"*3\r\n$5\r\nRPUSH\r\n$4\r\nlist\r\n$1\r\na\r\n");
+        assert_pushed(&mut replica, b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n");
+
+        // refused, or with nothing to change
+        client
+            .write_all(b"INCR list\r\nLPOP missing\r\nMOVE k 1\r\nSET k v PX soon\r\nDEL missing\r\n")
+            .unwrap();
+        assert_pushed(
+            &mut client,
+            b"-ERR value is not an integer or out of range\r\n$-1\r\n:0\r\n-ERR PX must be integer\r\n:0\r\n",
+        );
+        let payload = encode_dump_payload(&Value::String(b"x".to_vec())).unwrap();
+        let mut restore = Vec::new();
+        write_resp_bytes_array(&mut restore, &[b"RESTORE", b"k", b"0", &payload]).unwrap();
+        client.write_all(&restore).unwrap();
+        assert_pushed(&mut client, b"-BUSYKEY Target key name already exists.\r\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut replica);
+    }
+
+    #[test]
+    fn exec_propagates_its_writes_as_one_transaction() {
+        let ctx = test_context();
+        let mut replica = attach_replica(&ctx);
+        let mut client = connect(&ctx);
+
+        client.write_all(b"MULTI\r\nSET a 1\r\nLPOP missing\r\nINCR a\r\nEXEC\r\n").unwrap();
+        assert_pushed(&mut client, b"+OK\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n*3\r\n+OK\r\n$-1\r\n:2\r\n");
+        assert_pushed(&mut replica, b"*1\r\n$5\r\nMULTI\r\n");
+        assert_pushed(&mut replica, b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n");
+        assert_pushed(&mut replica, b"*2\r\n$4\r\nINCR\r\n$1\r\na\r\n");
+        assert_pushed(&mut replica, b"*1\r\n$4\r\nEXEC\r\n");
+
+        // a transaction that changed nothing sends nothing
+        client.write_all(b"MULTI\r\nGET a\r\nLPOP missing\r\nEXEC\r\n").unwrap();
+        assert_pushed(&mut client, b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*2\r\n$1\r\n2\r\n$-1\r\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_nothing_pushed(&mut replica);
+    }
+
+    #[test]
+    fn psync_sends_a_snapshot_then_streams_writes() {
+        use std::io::{BufRead, BufReader, Read};
+
+        let ctx = test_context();
+        let mut writer = connect(&ctx);
+        writer.write_all(b"SET before 1\r\n").unwrap();
+        assert_pushed(&mut writer, b"+OK\r\n");
+
+        let mut replica = connect(&ctx);
+        replica.write_all(b"PSYNC ? -1\r\n").unwrap();
+        let mut reader = BufReader::new(replica.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        // the offset counts the bytes of the SET already propagated
+        assert_eq!(line, format!("+FULLRESYNC {} 32\r\n", ctx.cfg.master_replid));
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        let len: usize = line.trim_end().strip_prefix('$').unwrap().parse().unwrap();
+        let mut rdb = vec![0; len];
+        reader.read_exact(&mut rdb).unwrap();
+        assert!(rdb.starts_with(b"REDIS"));
+        assert!(rdb.windows(6).any(|w| w == b"before"));
+
+        writer.write_all(b"SET after 2\r\n").unwrap();
+        assert_pushed(&mut writer, b"+OK\r\n");
+        let mut streamed = vec![0; 54];
+        reader.read_exact(&mut streamed).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&streamed),
+            "*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*3\r\n$3\r\nSET\r\n$5\r\nafter\r\n$1\r\n2\r\n"
+        );
+    }
+
+    #[test]
+    fn a_failed_shutdown_save_is_reported_and_the_server_keeps_running() {
+        use crate::config::ServerConfig;
+        use std::sync::Arc;
+
+        let cfg = ServerConfig::for_tests(std::path::Path::new("/nonexistent/dir"));
+        let databases = cfg.databases;
+        let ctx = Context::new(Arc::new(cfg), vec![Default::default(); databases]);
+        let mut client = connect(&ctx);
+        client.write_all(b"SHUTDOWN bogus\r\nSHUTDOWN SAVE\r\nPING\r\n").unwrap();
+        assert_pushed(
+            &mut client,
+            b"-ERR usage: SHUTDOWN [NOSAVE|SAVE]\r\n-ERR Errors trying to SHUTDOWN. Check logs.\r\n+PONG\r\n",
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Cluster key slots: CRC16 (XMODEM) of the key, or of its `{hash tag}`
+//! when it has a non-empty one, modulo 16384.
+
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+/// Slot `key` (or sharded pub/sub channel) maps to.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let hashed = match key.iter().position(|&b| b == b'{') {
+        Some(open) => match key[open + 1..].iter().position(|&b| b == b'}') {
+            Some(len) if len > 0 => &key[open + 1..open + 1 + len],
+            _ => key,
+        },
+        None => key,
+    };
+    crc16(hashed) % CLUSTER_SLOTS
+}
+
+/// CRC16-CCITT as Redis Cluster uses it: polynomial 0x1021, initial value 0.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_cluster_spec() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(key_hash_slot(b"bar"), 5061);
+        assert_eq!(key_hash_slot(b""), 0);
+    }
+
+    #[test]
+    fn hashes_only_a_non_empty_tag() {
+        let tagged = key_hash_slot(b"user1000");
+        assert_eq!(key_hash_slot(b"{user1000}.following"), tagged);
+        assert_eq!(key_hash_slot(b"x{user1000}{other}"), tagged);
+        // an empty or unclosed tag hashes the whole key
+        assert_ne!(key_hash_slot(b"{}user1000"), key_hash_slot(b"{}other"));
+        assert_eq!(key_hash_slot(b"{user1000"), crc16(b"{user1000") % CLUSTER_SLOTS);
+    }
+}
@@ -0,0 +1,311 @@
+//! The keyspace of one logical database, split into shards that are locked
+//! independently so commands on different keys don't contend.
+//!
+//! A key always lives in the shard its hash slot maps to, so keys sharing a
+//! `{hash tag}` share a shard. Anything that holds more than one shard lock
+//! takes them in ascending shard order, and across databases in ascending
+//! database index order, so no two lockers can deadlock:
+//!
+//! * single-key commands lock their key's shard (`Store::lock`);
+//! * multi-key commands lock the shards of all their keys (`Store::lock_keys`);
+//! * point-in-time views (RDB save, a full resync, KEYS, FLUSHDB, SWAPDB)
+//!   hold every shard at once (`Store::lock_all`);
+//! * SCAN walks one shard at a time; each shard keeps `Dict::scan`'s
+//!   guarantee and a key never changes shard, so a key present for the whole
+//!   iteration is still returned at least once.
+//!
+//! Transactions are isolated above this level: EXEC runs with the server's
+//! sync barrier held exclusively.
+
+use crate::rdb::{Db, Value};
+use crate::slot::key_hash_slot;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Shards per database. A power of two, at most the number of hash slots.
+pub const STORE_SHARDS: usize = 16;
+
+/// Bits of a SCAN cursor that hold the shard index.
+const SHARD_BITS: u32 = STORE_SHARDS.trailing_zeros();
+
+pub struct Store {
+    shards: Box<[Mutex<Db>]>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self { shards: (0..STORE_SHARDS).map(|_| Mutex::new(Db::new())).collect() }
+    }
+}
+
+impl Store {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store holding the keys of `db`.
+    pub fn from_db(db: Db) -> Self {
+        let store = Self::new();
+        store.replace(db);
+        store
+    }
+
+    /// Index of the shard `key` lives in.
+    pub fn shard_index(key: &[u8]) -> usize {
+        key_hash_slot(key) as usize & (STORE_SHARDS - 1)
+    }
+
+    /// Locks the shard holding `key`.
+    pub fn lock(&self, key: &[u8]) -> MutexGuard<'_, Db> {
+        self.shards[Self::shard_index(key)].lock().unwrap()
+    }
+
+    /// Locks the shards holding `keys`, each once, in ascending order.
+    pub fn lock_keys<'k, I: IntoIterator<Item = &'k [u8]>>(&self, keys: I) -> Shards<'_> {
+        let mut wanted = [false; STORE_SHARDS];
+        for key in keys {
+            wanted[Self::shard_index(key)] = true;
+        }
+        self.lock_where(|index| wanted[index])
+    }
+
+    /// Locks every shard, in ascending order: a consistent view of the
+    /// whole database.
+    pub fn lock_all(&self) -> Shards<'_> {
+        self.lock_where(|_| true)
+    }
+
+    fn lock_where<F: Fn(usize) -> bool>(&self, wanted: F) -> Shards<'_> {
+        let guards = self
+            .shards
+            .iter()
+            .enumerate()
+            .map(|(index, shard)| wanted(index).then(|| shard.lock().unwrap()))
+            .collect();
+        Shards { guards }
+    }
+
+    /// Replaces the contents with the keys of `db`.
+    pub fn replace(&self, db: Db) {
+        let mut shards = self.lock_all();
+        shards.take();
+        let mut split: Vec<Db> = (0..STORE_SHARDS).map(|_| Db::new()).collect();
+        for (key, entry) in db {
+            split[Self::shard_index(&key)].insert(key, entry);
+        }
+        for (guard, part) in shards.guards.iter_mut().zip(split) {
+            **guard.as_mut().unwrap() = part;
+        }
+    }
+
+    /// One SCAN step over the whole store: calls `f` on one bucket of the
+    /// shard `cursor` points into and returns the next cursor, 0 once every
+    /// shard has been walked. The shard index sits in the cursor's low bits,
+    /// the shard's own `Dict` cursor above them.
+    pub fn scan<F: FnMut(&Vec<u8>, &(Value, Option<SystemTime>))>(&self, cursor: u64, f: F) -> u64 {
+        let shard = (cursor as usize) & (STORE_SHARDS - 1);
+        let inner = self.shards[shard].lock().unwrap().scan(cursor >> SHARD_BITS, f);
+        match (inner, shard + 1) {
+            (0, next) if next == STORE_SHARDS => 0,
+            (0, next) => next as u64,
+            (inner, _) => (inner << SHARD_BITS) | shard as u64,
+        }
+    }
+}
+
+/// Shards of one `Store` locked together; see `Store::lock_keys` and
+/// `Store::lock_all`. Released when dropped.
+pub struct Shards<'a> {
+    /// By shard index; `None` for the shards that weren't asked for.
+    guards: Vec<Option<MutexGuard<'a, Db>>>,
+}
+
+impl Shards<'_> {
+    /// The shard holding `key`, which must be one of the locked ones.
+    pub fn shard(&self, key: &[u8]) -> &Db {
+        self.guards[Store::shard_index(key)].as_ref().expect("shard of key not locked")
+    }
+
+    pub fn shard_mut(&mut self, key: &[u8]) -> &mut Db {
+        self.guards[Store::shard_index(key)].as_mut().expect("shard of key not locked")
+    }
+
+    fn locked(&self) -> impl Iterator<Item = &Db> {
+        self.guards.iter().flatten().map(|guard| &**guard)
+    }
+
+    pub fn len(&self) -> usize {
+        self.locked().map(Db::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locked().all(Db::is_empty)
+    }
+
+    /// Entries of the locked shards, shard by shard.
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &(Value, Option<SystemTime>))> {
+        self.locked().flat_map(|db| db.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &(Value, Option<SystemTime>)> {
+        self.iter().map(|(_, entry)| entry)
+    }
+
+    /// A copy of the locked shards as a single `Db`.
+    pub fn to_db(&self) -> Db {
+        let mut db = Db::new();
+        db.reserve(self.len());
+        for (key, entry) in self.iter() {
+            db.insert(key.clone(), entry.clone());
+        }
+        db
+    }
+
+    /// Empties the locked shards, returning their old contents.
+    pub fn take(&mut self) -> Vec<Db> {
+        self.guards.iter_mut().flatten().map(|guard| std::mem::take(&mut **guard)).collect()
+    }
+
+    /// Exchanges the contents of the shards locked in both.
+    pub fn swap(&mut self, other: &mut Shards<'_>) {
+        for (a, b) in self.guards.iter_mut().zip(other.guards.iter_mut()) {
+            if let (Some(a), Some(b)) = (a, b) {
+                std::mem::swap(&mut **a, &mut **b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn string(s: &str) -> (Value, Option<SystemTime>) {
+        (Value::String(s.into()), None)
+    }
+
+    /// Keys that all live in different shards, one per shard.
+    fn one_key_per_shard() -> Vec<Vec<u8>> {
+        let mut keys: Vec<Option<Vec<u8>>> = vec![None; STORE_SHARDS];
+        for i in 0.. {
+            let key = format!("key:{}", i).into_bytes();
+            let slot = &mut keys[Store::shard_index(&key)];
+            if slot.is_none() {
+                *slot = Some(key);
+                if keys.iter().all(Option::is_some) {
+                    break;
+                }
+            }
+        }
+        keys.into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn keys_sharing_a_hash_tag_share_a_shard() {
+        assert_eq!(Store::shard_index(b"{user:1}.name"), Store::shard_index(b"{user:1}.email"));
+        assert_eq!(Store::shard_index(b"{user:1}.name"), Store::shard_index(b"user:1"));
+
+        let db: Db = one_key_per_shard().into_iter().map(|k| (k.clone(), string("v"))).collect();
+        let store = Store::from_db(db);
+        assert_eq!(store.lock_all().len(), STORE_SHARDS);
+        for key in one_key_per_shard() {
+            let shard = store.lock(&key);
+            assert_eq!(shard.len(), 1);
+            assert!(shard.get(&key).is_some());
+        }
+    }
+
+    #[test]
+    fn lock_keys_holds_the_shards_of_its_keys() {
+        let keys = one_key_per_shard();
+        let store = Store::new();
+        let mut shards = store.lock_keys([keys[3].as_slice(), keys[1].as_slice(), keys[3].as_slice()]);
+        shards.shard_mut(&keys[1]).insert(keys[1].clone(), string("one"));
+        shards.shard_mut(&keys[3]).insert(keys[3].clone(), string("three"));
+        assert_eq!(shards.len(), 2);
+        drop(shards);
+
+        // the other shards were free all along, and now all of them are
+        assert!(store.lock(&keys[2]).is_empty());
+        assert_eq!(store.lock(&keys[3]).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "shard of key not locked")]
+    fn shards_not_asked_for_are_not_handed_out() {
+        let keys = one_key_per_shard();
+        let store = Store::new();
+        let shards = store.lock_keys([keys[0].as_slice()]);
+        shards.shard(&keys[1]);
+    }
+
+    #[test]
+    fn crossing_multi_key_lockers_do_not_deadlock() {
+        let keys = Arc::new(one_key_per_shard());
+        let store = Arc::new(Store::new());
+        let workers: Vec<_> = (0..8)
+            .map(|worker| {
+                let (keys, store) = (keys.clone(), store.clone());
+                thread::spawn(move || {
+                    for round in 0..500 {
+                        // each worker asks for its keys in a different order
+                        let a = &keys[(worker + round) % STORE_SHARDS];
+                        let b = &keys[(worker * 7 + round) % STORE_SHARDS];
+                        if round % 10 == 0 {
+                            store.lock_all().len();
+                        } else {
+                            let mut shards = store.lock_keys([b.as_slice(), a.as_slice()]);
+                            shards.shard_mut(a).insert(a.clone(), string("v"));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert!(store.lock_all().len() <= STORE_SHARDS);
+    }
+
+    #[test]
+    fn scan_walks_every_shard() {
+        let db: Db = (0..500).map(|i| (format!("k{}", i).into_bytes(), string("v"))).collect();
+        let store = Store::from_db(db);
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut shards_seen = HashSet::new();
+        loop {
+            shards_seen.insert(cursor as usize & (STORE_SHARDS - 1));
+            cursor = store.scan(cursor, |key, _| {
+                seen.insert(key.clone());
+            });
+            if cursor == 0 {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 500);
+        assert_eq!(shards_seen.len(), STORE_SHARDS);
+    }
+
+    #[test]
+    fn whole_stores_swap_take_and_copy() {
+        let first = Store::from_db([(b"a".to_vec(), string("1"))].into_iter().collect());
+        let second = [(b"b".to_vec(), string("2")), (b"c".to_vec(), string("3"))];
+        let second = Store::from_db(second.into_iter().collect());
+
+        first.lock_all().swap(&mut second.lock_all());
+        assert_eq!(first.lock_all().len(), 2);
+        assert!(second.lock(b"a").get(&b"a"[..]).is_some());
+
+        let copy = first.lock_all().to_db();
+        assert_eq!(copy.len(), 2);
+        let taken: usize = first.lock_all().take().iter().map(Db::len).sum();
+        assert_eq!(taken, 2);
+        assert!(first.lock_all().is_empty());
+        assert!(copy.get(&b"b"[..]).is_some() && copy.get(&b"c"[..]).is_some());
+    }
+}